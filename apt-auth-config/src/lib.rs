@@ -1,7 +1,19 @@
+//! Parse APT `auth.conf` and `auth.conf.d` files.
+//!
+//! Besides `login` and `password`, an entry may set `token` to send a bearer
+//! token, or `helper` to get the credential from an external command, see
+//! [`CredentialHelper`]:
+//!
+//! ```text
+//! machine artifacts.example.com/debian token "s3cr3t"
+//! machine private.example.com helper "/usr/local/bin/get-token"
+//! ```
+
 use std::{
-    fs::read_dir,
+    fs::{self, read_dir},
     io,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 pub use netrc::Authenticator;
@@ -9,8 +21,13 @@ use netrc::Netrc;
 use thiserror::Error;
 use url::Url;
 
+mod provider;
 pub mod reqwuest;
 
+pub use provider::{
+    AuthProvider, BearerToken, Credential, CredentialError, CredentialHelper, SharedProvider,
+};
+
 #[derive(Debug, Error)]
 pub enum AuthConfigError {
     #[error("Failed to read dir: {path}")]
@@ -140,7 +157,7 @@ impl From<&str> for AuthUrl {
 }
 
 #[derive(Debug, Clone, Default)]
-pub struct AuthConfig(pub Vec<(AuthUrl, Authenticator)>);
+pub struct AuthConfig(pub Vec<(AuthUrl, SharedProvider)>);
 
 impl AuthConfig {
     pub fn system(sysroot: impl AsRef<Path>) -> Result<Self, AuthConfigError> {
@@ -153,11 +170,7 @@ impl AuthConfig {
 
         let auth_conf = base_path.join("auth.conf");
         if auth_conf.exists() {
-            let config = Netrc::from_file(&auth_conf).map_err(|e| AuthConfigError::OpenFile {
-                path: auth_conf.clone(),
-                err: io::Error::other(e),
-            })?;
-            v.push(config);
+            v.extend(read_auth_file(&auth_conf)?);
         }
 
         let auth_conf_d = base_path.join("auth.conf.d");
@@ -175,32 +188,22 @@ impl AuthConfig {
                     continue;
                 }
 
-                let config = Netrc::from_file(&path).map_err(|e| AuthConfigError::OpenFile {
-                    path: path.clone(),
-                    err: io::Error::other(e),
-                })?;
-                v.push(config);
+                v.extend(read_auth_file(&path)?);
             }
         }
 
-        let v = v
-            .into_iter()
-            .flat_map(|x| x.hosts)
-            .map(|(k, v)| (AuthUrl::from(k.as_str()), v))
-            .collect::<Vec<_>>();
-
         Ok(Self(v))
     }
 
-    pub fn find(&self, url: &Url) -> Option<&Authenticator> {
+    pub fn find(&self, url: &Url) -> Option<&dyn AuthProvider> {
         self.0
             .iter()
             .filter_map(|(config_url, auth)| config_url.match_score(url).map(|score| (score, auth)))
             .max_by_key(|(score, _)| *score)
-            .map(|(_, auth)| auth)
+            .map(|(_, auth)| auth.as_ref())
     }
 
-    pub fn find_str(&self, url: &str) -> Option<&Authenticator> {
+    pub fn find_str(&self, url: &str) -> Option<&dyn AuthProvider> {
         if let Ok(parsed_url) = Url::parse(url) {
             return self.find(&parsed_url);
         }
@@ -215,6 +218,33 @@ impl AuthConfig {
                     .map(|score| (score, auth))
             })
             .max_by_key(|(score, _)| *score)
-            .map(|(_, auth)| auth)
+            .map(|(_, auth)| auth.as_ref())
     }
 }
+
+fn read_auth_file(path: &Path) -> Result<Vec<(AuthUrl, SharedProvider)>, AuthConfigError> {
+    let open_err = |err| AuthConfigError::OpenFile {
+        path: path.to_path_buf(),
+        err,
+    };
+
+    let s = fs::read(path).map_err(open_err)?;
+    let (rest, providers) = provider::extract_providers(&String::from_utf8_lossy(&s));
+
+    let netrc = rest.parse::<Netrc>().map_err(|e| {
+        open_err(io::Error::other(netrc::Error::Parsing {
+            parser: e,
+            filename: path.display().to_string(),
+        }))
+    })?;
+
+    let basic = netrc
+        .hosts
+        .into_iter()
+        .map(|(k, v)| (k, Arc::new(v) as SharedProvider));
+
+    Ok(basic
+        .chain(providers)
+        .map(|(k, v)| (AuthUrl::from(k.as_str()), v))
+        .collect())
+}
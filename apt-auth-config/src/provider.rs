@@ -0,0 +1,454 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    ops::Range,
+    process::{Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use netrc::Authenticator;
+use spdlog::debug;
+use thiserror::Error;
+use url::Url;
+
+/// Credentials from the helper are refreshed this long before they expire
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum CredentialError {
+    #[error("Failed to run credential helper: {command}")]
+    RunHelper {
+        command: String,
+        #[source]
+        err: io::Error,
+    },
+    #[error("Credential helper {command} exited with {status}")]
+    HelperFailed { command: String, status: ExitStatus },
+    #[error("Credential helper {command} did not print a credential")]
+    NoCredential { command: String },
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credential {
+    Basic { login: String, password: String },
+    Bearer(String),
+}
+
+impl fmt::Debug for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credential::Basic { login, .. } => f
+                .debug_struct("Basic")
+                .field("login", login)
+                .finish_non_exhaustive(),
+            Credential::Bearer(_) => f.write_str("Bearer"),
+        }
+    }
+}
+
+/// Provide the credential for requests matching an `auth.conf` entry
+pub trait AuthProvider: fmt::Debug + Send + Sync {
+    fn credential(&self, url: &Url) -> Result<Credential, CredentialError>;
+}
+
+pub type SharedProvider = Arc<dyn AuthProvider>;
+
+impl AuthProvider for Authenticator {
+    fn credential(&self, _url: &Url) -> Result<Credential, CredentialError> {
+        Ok(Credential::Basic {
+            login: self.login.clone(),
+            password: self.password.clone(),
+        })
+    }
+}
+
+/// A static bearer token, from `token` of an `auth.conf` entry
+pub struct BearerToken(pub String);
+
+impl fmt::Debug for BearerToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BearerToken")
+    }
+}
+
+impl AuthProvider for BearerToken {
+    fn credential(&self, _url: &Url) -> Result<Credential, CredentialError> {
+        Ok(Credential::Bearer(self.0.clone()))
+    }
+}
+
+/// An external command printing the credential, from `helper` of an `auth.conf` entry
+///
+/// Like git credential helpers, the command is run by `sh` and reads
+/// `protocol=`, `host=` and `path=` lines of the request on stdin, then prints
+/// `username=` and `password=`, or `token=` for a bearer token. The credential
+/// is reused for all requests matching the entry until `password_expiry_utc=`
+/// (seconds since the Unix epoch), or for the whole process if it is unset.
+#[derive(Debug)]
+pub struct CredentialHelper {
+    command: String,
+    cache: Mutex<Option<(Credential, Option<SystemTime>)>>,
+}
+
+impl CredentialHelper {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            cache: Mutex::new(None),
+        }
+    }
+
+    fn run(&self, url: &Url) -> Result<(Credential, Option<SystemTime>), CredentialError> {
+        let run_err = |err| CredentialError::RunHelper {
+            command: self.command.clone(),
+            err,
+        };
+
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(run_err)?;
+
+        let input = format!(
+            "protocol={}\nhost={}\npath={}\n\n",
+            url.scheme(),
+            url.host_str().unwrap_or_default(),
+            url.path().trim_start_matches('/')
+        );
+
+        // 辅助程序可能不读取输入就退出
+        if let Some(mut stdin) = child.stdin.take()
+            && let Err(e) = stdin.write_all(input.as_bytes())
+            && e.kind() != io::ErrorKind::BrokenPipe
+        {
+            return Err(run_err(e));
+        }
+
+        let output = child.wait_with_output().map_err(run_err)?;
+
+        if !output.status.success() {
+            return Err(CredentialError::HelperFailed {
+                command: self.command.clone(),
+                status: output.status,
+            });
+        }
+
+        parse_helper_output(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+            CredentialError::NoCredential {
+                command: self.command.clone(),
+            }
+        })
+    }
+}
+
+impl AuthProvider for CredentialHelper {
+    fn credential(&self, url: &Url) -> Result<Credential, CredentialError> {
+        // 持有锁运行辅助程序，避免并发请求重复获取凭据
+        let mut cache = self.cache.lock().unwrap();
+
+        if let Some((credential, expiry)) = &*cache
+            && expiry.is_none_or(|t| SystemTime::now() + EXPIRY_MARGIN < t)
+        {
+            return Ok(credential.clone());
+        }
+
+        let (credential, expiry) = self.run(url)?;
+        debug!(
+            "Got credential from helper {}, expires at {expiry:?}",
+            self.command
+        );
+
+        *cache = Some((credential.clone(), expiry));
+
+        Ok(credential)
+    }
+}
+
+fn parse_helper_output(output: &str) -> Option<(Credential, Option<SystemTime>)> {
+    let mut login = None;
+    let mut password = None;
+    let mut token = None;
+    let mut expiry = None;
+
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        match key.trim() {
+            "username" => login = Some(value.to_string()),
+            "password" => password = Some(value.to_string()),
+            "token" => token = Some(value.to_string()),
+            "password_expiry_utc" => {
+                expiry = value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+            }
+            _ => {}
+        }
+    }
+
+    let credential = match (token, password) {
+        (Some(token), _) => Credential::Bearer(token),
+        (None, Some(password)) => Credential::Basic {
+            login: login.unwrap_or_default(),
+            password,
+        },
+        (None, None) => return None,
+    };
+
+    Some((credential, expiry))
+}
+
+/// Split the entries using `token` or `helper`, which the netrc parser does not know,
+/// out of an `auth.conf` file
+///
+/// Returns the file with these entries blanked out, and the machine and provider of each entry.
+pub(crate) fn extract_providers(s: &str) -> (String, Vec<(String, SharedProvider)>) {
+    let tokens = tokenize(s);
+    let mut rest = s.to_string();
+    let mut providers: Vec<(String, SharedProvider)> = vec![];
+    let mut i = 0;
+
+    while i < tokens.len() {
+        if tokens[i].0 != "machine" {
+            i += 1;
+            continue;
+        }
+
+        let start = tokens[i].1.start;
+        let machine = tokens
+            .get(i + 1)
+            .map(|(t, _)| t.clone())
+            .unwrap_or_default();
+        let mut provider: Option<SharedProvider> = None;
+        let mut end = i + 2;
+
+        while end < tokens.len() && !matches!(tokens[end].0.as_str(), "machine" | "default") {
+            let value = tokens.get(end + 1).map(|(t, _)| t.clone());
+
+            match tokens[end].0.as_str() {
+                "token" => provider = value.map(|v| Arc::new(BearerToken(v)) as _),
+                "helper" => provider = value.map(|v| Arc::new(CredentialHelper::new(v)) as _),
+                _ => {}
+            }
+
+            end += 2;
+        }
+
+        let end = end.min(tokens.len());
+
+        if let Some(provider) = provider
+            && !machine.is_empty()
+        {
+            // 保留换行，使 netrc 解析错误的行号不变
+            let range = start..tokens[end - 1].1.end;
+            let blank = s[range.clone()]
+                .bytes()
+                .map(|b| if b == b'\n' { '\n' } else { ' ' })
+                .collect::<String>();
+            rest.replace_range(range, &blank);
+            providers.push((machine, provider));
+        }
+
+        i = end;
+    }
+
+    (rest, providers)
+}
+
+/// Split a netrc file into tokens and their byte ranges, like the netrc parser
+fn tokenize(s: &str) -> Vec<(String, Range<usize>)> {
+    let mut tokens = vec![];
+    let mut chars = s.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        // 注释直到行尾
+        if c == '#' {
+            for (_, c) in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+            continue;
+        }
+
+        // macdef 的内容直到空行，不是 token
+        if s[start..].starts_with("macdef") && s[start + 6..].starts_with(char::is_whitespace) {
+            let body_end = s[start..]
+                .find("\n\n")
+                .map(|pos| start + pos)
+                .unwrap_or(s.len());
+            while chars.next_if(|(i, _)| *i < body_end).is_some() {}
+            continue;
+        }
+
+        let mut token = String::new();
+        let mut end = s.len();
+        let quoted = c == '"';
+
+        if !quoted {
+            let c = if c == '\\' {
+                chars.next().map(|(_, c)| c).unwrap_or(' ')
+            } else {
+                c
+            };
+            token.push(c);
+        }
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' if quoted => {
+                    end = i + 1;
+                    break;
+                }
+                c if !quoted && c.is_whitespace() => {
+                    end = i;
+                    break;
+                }
+                '\\' => token.push(chars.next().map(|(_, c)| c).unwrap_or(' ')),
+                c => token.push(c),
+            }
+        }
+
+        tokens.push((token, start..end));
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(s: &str) -> Vec<String> {
+        tokenize(s).into_iter().map(|(t, _)| t).collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let s = "machine a.example.com login \"foo bar\" password p\\ w # comment\n";
+        let tokens = tokenize(s);
+
+        assert_eq!(
+            tokens.iter().map(|(t, _)| t.as_str()).collect::<Vec<_>>(),
+            vec![
+                "machine",
+                "a.example.com",
+                "login",
+                "foo bar",
+                "password",
+                "p w"
+            ]
+        );
+        assert_eq!(&s[tokens[3].1.clone()], "\"foo bar\"");
+        assert_eq!(&s[tokens[5].1.clone()], "p\\ w");
+    }
+
+    #[test]
+    fn test_tokenize_quoting() {
+        // 引号内的转义字符和空白
+        assert_eq!(
+            tokens(r#"helper "a \"b\" c""#),
+            vec!["helper", r#"a "b" c"#]
+        );
+        assert_eq!(tokens("token \"\""), vec!["token", ""]);
+        // 未闭合的引号直到文件末尾
+        assert_eq!(tokens("token \"a b\nc"), vec!["token", "a b\nc"]);
+        // 引号只在 token 开头有特殊含义
+        assert_eq!(tokens("a\"b c"), vec!["a\"b", "c"]);
+        // 末尾的反斜杠
+        assert_eq!(tokens("a\\"), vec!["a "]);
+        assert_eq!(tokens("# only comment"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_tokenize_macdef() {
+        assert_eq!(
+            tokens("macdef init\ncd /pub\nbin\n\nmachine a login b"),
+            vec!["machine", "a", "login", "b"]
+        );
+        assert_eq!(tokens("macdefx y"), vec!["macdefx", "y"]);
+    }
+
+    #[test]
+    fn test_extract_providers() {
+        let s = "\
+machine a.example.com login foo password bar
+machine b.example.com/debian
+  token \"s3 cr3t\"
+machine c.example.com helper \"get-token --host c\"
+default login anonymous password x
+";
+        let (rest, providers) = extract_providers(s);
+
+        // 含 token 或 helper 的条目被替换为等长的空白，其他行不变
+        for (i, (line, orig)) in rest.lines().zip(s.lines()).enumerate() {
+            if (1..4).contains(&i) {
+                assert_eq!(line, " ".repeat(orig.len()));
+            } else {
+                assert_eq!(line, orig);
+            }
+        }
+
+        assert_eq!(rest.lines().count(), s.lines().count());
+
+        let providers = providers
+            .iter()
+            .map(|(machine, provider)| (machine.as_str(), format!("{provider:?}")))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            providers,
+            vec![
+                ("b.example.com/debian", "BearerToken".to_string()),
+                (
+                    "c.example.com",
+                    format!("{:?}", CredentialHelper::new("get-token --host c"))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_providers_without_machine() {
+        let s = "machine token abc\nmachine\n";
+        let (rest, providers) = extract_providers(s);
+
+        // "token" 被当作主机名，该条目不含 token 或 helper
+        assert_eq!(rest, s);
+        assert!(providers.is_empty());
+    }
+
+    #[test]
+    fn test_parse_helper_output() {
+        assert_eq!(
+            parse_helper_output("username=foo\npassword=a=b\n"),
+            Some((
+                Credential::Basic {
+                    login: "foo".to_string(),
+                    password: "a=b".to_string()
+                },
+                None
+            ))
+        );
+        assert_eq!(
+            parse_helper_output("password=x\ntoken=t\npassword_expiry_utc=10\n"),
+            Some((
+                Credential::Bearer("t".to_string()),
+                Some(UNIX_EPOCH + Duration::from_secs(10))
+            ))
+        );
+        assert_eq!(parse_helper_output("username=foo\n"), None);
+    }
+}
@@ -1,7 +1,11 @@
-use reqwest_middleware::RequestInitialiser;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use http::{Extensions, HeaderValue, header::AUTHORIZATION};
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next};
+use spdlog::warn;
 use std::sync::Arc;
 
-use crate::AuthConfig;
+use crate::{AuthConfig, Credential};
 
 pub struct AuthMiddleware {
     config: Arc<AuthConfig>,
@@ -15,24 +19,50 @@ impl AuthMiddleware {
     }
 }
 
-impl RequestInitialiser for AuthMiddleware {
-    fn init(&self, req: reqwest_middleware::RequestBuilder) -> reqwest_middleware::RequestBuilder {
-        let url = req
-            .try_clone()
-            .unwrap()
-            .build()
-            .ok()
-            .map(|r| r.url().clone());
-
-        if let Some(url) = url
-            && let Some(auth) = self.config.find(&url)
-        {
-            let login = &auth.login;
-            let passwd = &auth.password;
-
-            req.basic_auth(login, Some(passwd))
-        } else {
-            req
+#[async_trait::async_trait]
+impl Middleware for AuthMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        if self.config.find(req.url()).is_none() {
+            return next.run(req, extensions).await;
         }
+
+        // 凭据辅助程序是阻塞运行的外部命令，不能在异步任务中直接运行
+        let config = self.config.clone();
+        let url = req.url().clone();
+        let credential = tokio::task::spawn_blocking(move || {
+            config.find(&url).map(|auth| auth.credential(&url))
+        })
+        .await
+        .map_err(reqwest_middleware::Error::middleware)?;
+
+        let header = match credential {
+            Some(Ok(Credential::Basic { login, password })) => {
+                HeaderValue::try_from(basic_auth(&login, &password))
+            }
+            Some(Ok(Credential::Bearer(token))) => HeaderValue::try_from(format!("Bearer {token}")),
+            Some(Err(e)) => {
+                warn!(
+                    "Failed to get credential for {}: {e}",
+                    req.url().host_str().unwrap_or_default()
+                );
+                return Err(reqwest_middleware::Error::middleware(e));
+            }
+            None => return next.run(req, extensions).await,
+        };
+
+        let mut header = header.map_err(reqwest_middleware::Error::middleware)?;
+        header.set_sensitive(true);
+        req.headers_mut().insert(AUTHORIZATION, header);
+
+        next.run(req, extensions).await
     }
 }
+
+fn basic_auth(login: &str, password: &str) -> String {
+    format!("Basic {}", STANDARD.encode(format!("{login}:{password}")))
+}
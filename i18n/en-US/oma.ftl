@@ -4,9 +4,16 @@ execute-cmd-fail = Failed to execute `{ $cmd }': { $e }.
 # history
 history-tips-1 = oma has successfully applied changes to your system.
 history-tips-2 = If you would like to undo these changes, please use the { $cmd } command.
+history-rollback-no-snapshot = No system snapshot was recorded for history entry { $id }.
+history-rollback-confirm = Restore { $backend } snapshot { $snapshot } taken before history entry { $id }?
+history-rollback-success = System snapshot { $snapshot } has been restored.
+history-rollback-reboot = Please reboot your system to boot into the restored snapshot.
+failed-to-take-snapshot = Failed to take system snapshot.
+failed-to-rollback-snapshot = Failed to restore system snapshot.
 # verify
 fail-load-certs-from-file = Failed to load repository signature from { $path }.
 cert-file-is-bad = Repository signature at { $path } is invalid.
+no-signing-key = No usable unencrypted signing key in { $path }.
 # topics
 can-not-find-specified-topic = Cannot find the specified topic repository: { $topic }.
 do-not-edit-topic-sources-list = # Generated by oma. DO NOT EDIT!
@@ -38,6 +45,9 @@ apt-error = `apt' returned an error.
 invalid-pattern = Invalid pattern: { $p }
 additional-version = { $len } additional version(s) available. Please use the `-a' switch to list all available version(s).
 could-not-find-pkg-from-keyword = Could not find any package for keyword { $c }.
+search-query-empty-value = Missing value for `{ $key }' in the search query.
+search-query-invalid-size = Invalid size `{ $value }' in the search query, expected a size like `>100M'.
+search-query-invalid-status = Invalid package status `{ $value }' in the search query, expected `installed', `upgradable' or `available'.
 no-need-to-remove = Package { $name } is not installed. No need to remove.
 packages-can-be-upgrade = { $len } package(s) can be upgraded.
 packages-can-be-upgrade-has-manual-held = { $len } package(s) can be upgraded ({ $held_count } were marked for version hold).
@@ -106,6 +116,7 @@ invalid-filename = Invalid file name: { $name }.
 checksum-mismatch-retry = Checksum verification failed for { $c } (retried { $retry } times) ...
 timeout-retry = Download timeout failed for { $c } (retried { $retry } times) ...
 can-not-get-source-next-url = Retrying with the next available mirror ...
+download-throttled = (throttled)
 checksum-mismatch = Checksum verification failed for file { $filename }.
 # db
 invalid-url = Invalid URL { $url }.
@@ -118,6 +129,8 @@ inrelease-checksum-can-not-parse = InRelease file is invalid: Failed to parse fi
 inrelease-parse-unsupported-file-type = BUG: InRelease parser has encountered an unsupported file format. Please report this issue at https://github.com/AOSC-Dev/oma.
 can-not-parse-sources-list = Failed to parse the sources.list file { $path }.
 unsupported-protocol = oma does not support the protocol: { $url }.
+failed-to-read-mirror-list = Failed to read mirror list.
+no-mirror-available = No usable mirror in mirror list: { $url }.
 refreshing-repo-metadata = Refreshing local database ...
 not-found = Failed to download InRelease from { $url }: Remote file not found (404).
 inrelease-syntax-error = InRelease file { $path } is invalid.
@@ -144,6 +157,53 @@ download-file-failed-with-reason = Failed to download file { $filename }, Reason
 download-failed-no-name = Failed to download required file(s)!
 need-more-size = Insufficient storage space: { $a } is available, but { $n } is needed.
 successfully-download-to-path = Successfully downloaded { $len } package(s) to path: { $path }.
+bundle-created = Bundle with { $len } package(s) has been written to { $path }.
+bundle-failed-to-sign = Failed to sign the bundle manifest.
+bundle-bad-signature = Failed to verify the signature of the bundle manifest.
+bundle-bad-format = Invalid bundle: { $path }.
+bundle-arch-mismatch = The bundle is built for { $bundle }, but this system is { $arch }.
+bundle-checksum-mismatch = Checksum mismatch for { $name } in the bundle.
+repo-created = Repository with { $len } package(s) has been written to { $path }.
+repo-updated = { $len } package(s) have been added to repository { $path }.
+repo-replaced = Replaced existing entry of { $path } in the repository index.
+repo-unsigned = No signing key specified, the repository metadata is not signed. Use `[trusted=yes]' in sources.list to use this repository.
+repo-failed-to-sign = Failed to sign the repository metadata.
+repo-bad-deb = Invalid package archive: { $path }.
+repo-bad-index = Invalid repository index: { $path }.
+repo-not-exist = { $path } is not a repository created by `oma repo create'.
+manifest-bad-format = Invalid manifest: { $path }.
+manifest-bad-version = Invalid version requirement for { $name }: { $version }.
+manifest-exported = Manifest with { $len } package(s) has been written to { $path }.
+pin-added = Pinned { $name } with priority { $priority }.
+pin-removed = Removed pin for { $name }.
+pin-not-found = { $name } is not pinned by oma.
+pin-list-empty = No package is pinned by oma.
+pin-package = Package
+pin-target = Pin
+pin-priority = Priority
+pin-invalid-package = Invalid package name: { $name }.
+pin-invalid-target = Invalid pin: { $pin }.
+build-dep-resolving = Resolving build dependencies of { $name } { $version } ...
+build-dep-no-source-index = Unable to find source package { $name }, please make sure deb-src entries are enabled in your sources.
+unattended-on-battery = The device is running on battery power, skipping unattended upgrade.
+unattended-nothing-to-do = No updates allowed by the unattended upgrade policy.
+unattended-confirm = Apply { $count } updates allowed by the unattended upgrade policy?
+unattended-skipped = Keeping back { $name }: { $reason }.
+unattended-would-remove = Unattended upgrade would remove { $pkgs }, aborting.
+unattended-kept-upgraded = Unattended upgrade would still upgrade { $pkgs } kept back by the policy, aborting.
+unattended-reason-denied = denied by policy
+unattended-reason-origin = not from an allowed origin
+unattended-reason-not-security = not a security update
+unattended-reason-conffile = has locally modified configuration files
+unattended-reason-dependency = depends on a kept back update
+build-dep-unsatisfiable = Build dependency { $dep } cannot be satisfied.
+build-dep-bad-control = Failed to parse { $path }.
+build-dep-no-source = No source package stanza found in { $path }.
+daemon-started = oma daemon is running as { $name } on the system bus.
+daemon-busy = Another operation is in progress, please try again later.
+daemon-not-authorized = Not authorized to perform { $action }.
+serve-cache-started = Serving package cache { $path } on { $addr }.
+serve-cache-announce-failed = Failed to announce package cache via Avahi: { $e }
 oma-may =
     oma may { $a }, { $b }, { $c }, { $d }, or { $e } packages in order
     to fulfill your requested changes.
@@ -155,6 +215,11 @@ illegal-topic-entry = Topic entry contains illegal character(s): { $name }.
 failed-to-get-rg-process-info = Failed to get process status for `rg'.
 failed-to-calculate-available-space = Failed to calculate available storage space.
 failed-to-create-http-client = Failed to create an HTTP client.
+failed-to-create-http-client-for = Failed to create an HTTP client for { $host }.
+tls-read-file-failed = Failed to read TLS certificate or key file { $path }.
+tls-invalid-ca-info = Invalid CA certificates in { $path }.
+tls-invalid-client-cert = Invalid TLS client certificate or private key in { $path }.
+tls-handshake-failed = Failed to establish a secure connection to { $host }. Please check its certificate, or the CaInfo, SslCert and SslKey settings of this server in APT configuration.
 failed-to-connect-history-database = Failed to connect to the history database.
 failed-to-execute-query-stmt = Failed to query the history database.
 failed-to-parse-history-object = Failed to parse an object in the history database.
@@ -253,6 +318,30 @@ tum-2 =
     following changes to your system components. Details of the component changes
     for this operation are as follows:
 security = security update(s)
+advisories-pending = pending
+advisories-fixed = fixed
+advisories-none = No advisories affect installed packages.
+advisories-summary = { $total } advisory(ies) affect installed packages, { $pending } of them pending.
+conffiles-modified = modified
+conffiles-missing = missing
+conffiles-unmodified = unmodified
+conffiles-none = No modified configuration files.
+conffiles-not-found = { $path } is not a configuration file of any installed package.
+conffiles-no-cached-deb = Package archive of { $name } ({ $version }) is not in the local package cache, run `oma install --reinstall --download-only { $name }' to download it.
+conffiles-diff-packaged = { $path } (packaged)
+conffiles-no-diff = The local version is identical to the packaged version.
+conffiles-diff-failed = Failed to compare configuration files.
+conffiles-merge-failed = Failed to merge configuration files.
+conffiles-resolved = Resolved { $path }.
+verify-not-installed = Package { $name } is not installed.
+verify-no-archive = Package archive of { $name } ({ $version }) is not available in the local package cache, only verifying against the dpkg database.
+verify-archive-mismatch = Checksum of { $path } does not match the repository, ignoring it.
+verify-bad-archive = Failed to read md5sums from { $path }.
+verify-modified = modified
+verify-missing = missing
+verify-replaced = replaced
+verify-ok = All files of { $count } package(s) are intact.
+verify-broken = { $count } package(s) have modified or missing files.
 verify-error = An error occurred while verifying the signature for { $p }.
 sources-list-empty = Mirror source configuration is empty.
 failed-refresh = Failed to refresh repository metadata.
@@ -271,6 +360,7 @@ broken-pipe-err = Broken pipe
 send-request-timeout = Send network request timeout
 download-timeout = Download file timeout
 checksum-mismatch-download-err = Checksum mismatch
+unexpected-range-download-err = Server did not return the requested range
 suggest = The following packages are recommended to enhance the functionality of the packages you have installed:
 tum-name = Name
 tum-notes = Notes
@@ -286,6 +376,7 @@ failed-set-upgrade-mode-tips = This issue is an internal error in the APT backen
 failed-lock-apt-tips = Check for other related processes, such as the oma or apt commands in another terminal, or a graphical package manager, and wait for them to complete their operations.
 path-not-exist = Path { $path } does not exist.
 not-allow-oma-pending-piped = oma cannot run with piped output in interactive mode.
+json-events-prompt-unanswered = Prompt "{ $prompt }" cannot be answered while emitting JSON events, use { $hint } to answer it in advance.
 space-warn = You seem to be running out of space. Consider using { $cmd } to purge the package cache, which will free up { $size } in storage space; Please also consider cleaning up your rarely used software or personal data.
 space-warn-with-zero = You seem to be running out of space, Please consider cleaning up your rarely used software or personal data.
 wrong-thread-count = The specified number of threads { $count } is not allowed (min: 1, max: 255).
@@ -297,6 +388,12 @@ psa-without-root-tips = To launch the disk usage analyzer with administrative fe
 psa-total = Total
 mirrors-setting-empty = No mirror specified, refusing to save configuration.
 loading-tree = Analyzing dependency tree
+tree-no-path = No dependency path from { $from } to { $to }.
+tree-removal-broken = Packages depending on the removed package(s):
+tree-removal-orphaned = Packages no longer needed:
+tree-removal-nothing = No other package would be removed.
+tree-cycle = Circular dependency: { $pkgs }
+tree-no-cycles = No circular dependency found.
 battery-check-disabled = Power status monitoring has been disabled. System failure or data loss may occur, should an accidental power loss take place while oma is performing system changes.
 session-check-disabled = Session lock has been disabled. System failure or data loss may occur if oma was interrupted by desktop power management, scheduled tasks, etc. as it performs system changes.
 topics-held-tips = The topic(s) you have enabled affects { $count } packages which were marked for version hold, these packages are not upgraded.
@@ -318,6 +415,41 @@ clap-about = User-friendly and fool-proof package manager for APT repositories
 clap-install-help = Install package(s) from the repository
 clap-upgrade-help = Upgrade packages installed on the system
 clap-download-help = Download package(s) from the repository
+clap-bundle-help = Create or apply an offline package bundle
+clap-bundle-create-help = Create an offline bundle with package(s) and all their dependencies
+clap-bundle-create-packages-help = Package(s) to include in the bundle
+clap-bundle-output-help = Path to write the bundle to
+clap-bundle-sign-key-help = OpenPGP secret key file used to sign the bundle manifest
+clap-bundle-apply-help = Install package(s) from an offline bundle
+clap-bundle-path-help = Path to the bundle
+clap-bundle-keyring-help = OpenPGP certificate file used to verify the bundle manifest (default: trusted keys of the system)
+clap-repo-help = Create or update a local APT repository
+clap-repo-create-help = Create a flat repository from package archive(s) in a directory
+clap-repo-add-deb-help = Add package archive(s) to a repository created by `oma repo create'
+clap-repo-dir-help = Repository directory
+clap-repo-debs-help = Package archive(s) to add
+clap-repo-sign-key-help = OpenPGP secret key file used to sign the repository metadata
+clap-repo-origin-help = Value of the `Origin' field in the Release file
+clap-apply-help = Apply a declarative package manifest to the system
+clap-apply-manifest-help = Path to the manifest
+clap-export-help = Export manually installed package(s) as a declarative package manifest
+clap-export-output-help = Write the manifest to a file instead of standard output
+clap-export-with-version-help = Require the currently installed version of each package
+clap-pin-help = Manage APT pinning preferences
+clap-pin-add-help = Pin a package to a version, origin or release
+clap-pin-remove-help = Remove pin(s) created by oma
+clap-pin-list-help = List pins created by oma
+clap-pin-package-help = Package(s) to pin
+clap-pin-target-help = Version, origin:<HOST> or release:<FILTER> to pin
+clap-pin-target-long-help = What to pin the package to, one of:
+    <VERSION>: a version, may use `*' as wildcard (e.g. 1.2*)
+    origin:<HOST>: packages from the specified host (e.g. origin:repo.aosc.io)
+    release:<FILTER>: packages from the specified release (e.g. release:a=stable)
+clap-pin-priority-help = Pin priority, see apt_preferences(5)
+clap-build-dep-help = Install build dependencies of a source package
+clap-build-dep-target-help = Source package name, source directory or path to debian/control
+clap-build-dep-build-profiles-help = Build profiles to activate, separated by commas (e.g., nocheck,nodoc)
+clap-upgrade-unattended-help = Apply updates allowed by the unattended upgrade policy in /etc/oma.toml without prompting
 clap-remove-help = Remove package(s)
 clap-refresh-help = Refresh repository metadata
 clap-show-help = Show information on the specified package(s)
@@ -333,10 +465,33 @@ clap-rdepends-help = List reverse dependency(ies) for the specified package(s)
 clap-clean-help = Clear local package cache
 clap-history-help = Show a history/log of package changes in the system
 clap-undo-help = Undo a change to packages on the system
+clap-history-rollback-help = Restore the system snapshot taken before a history entry
+clap-history-rollback-id-help = History entry ID to roll back
+clap-undo-id-help = History entry ID to undo (select interactively if omitted)
+clap-daemon-help = Run oma as a D-Bus system service
+clap-serve-cache-help = Share the package cache with other machines in the LAN
+clap-serve-cache-listen-help = Address to listen on
+clap-serve-cache-no-announce-help = Do not announce the package cache via Avahi
 clap-tui-help = Interactive terminal user interface
 clap-version-help = Print version
 clap-topics-help = Manage topics (testing repositories)
 clap-mirror-help = Manage package repository mirrors
+clap-advisories-help = List security advisories affecting installed packages
+clap-advisories-pending-help = Only list advisories not yet applied to this system
+clap-advisories-all-help = Also list non-security updates
+clap-conffiles-help = Manage modified configuration files
+clap-conffiles-list-help = List configuration files which differ from the packaged version
+clap-conffiles-all-help = Also list unmodified configuration files
+clap-conffiles-diff-help = Show differences between the packaged and the local version
+clap-conffiles-path-help = Path to the configuration file
+clap-conffiles-resolve-help = Keep the local version, take the packaged version or merge both
+clap-conffiles-keep-help = Keep the local version and remove leftover files
+clap-conffiles-take-help = Replace the local version with the packaged version (the local version is saved as .dpkg-old)
+clap-conffiles-merge-help = Merge the packaged version into the local version interactively
+clap-verify-help = Verify installed files of packages against the dpkg database
+clap-verify-packages-help = Package(s) to verify, verify all installed packages if not specified
+clap-verify-archive-help = Also verify against md5sums from the cached package archives
+clap-verify-reinstall-help = Reinstall packages with modified or missing files
 clap-size-analyzer-help = Analyze storage usage
 clap-tree-help = Visualize dependency tree for the specified package
 clap-why-help = Visualize why the specified package was installed
@@ -371,6 +526,7 @@ clap-no-install-recommends-help = Do not install recommended package(s)
 clap-no-install-suggests-help = Do not install suggested package(s)
 clap-no-pager-help = Print output directly to stdout (output stream) directly without a pager
 clap-no-progress-help = Do not display progress bar
+clap-json-events-help = Print newline-delimited JSON events to stdout instead of progress bars and prompts
 clap-no-refresh-help = Do not refresh repository metadata
 clap-no-refresh-topics-help = Do not refresh topics metadata
 clap-no-remove-help = Do not allow removal of packages during upgrade (like `apt upgrade')
@@ -392,7 +548,7 @@ clap-depends-packages-help = Package(s) to query dependency(ies) for
 clap-download-packages-help = Package(s) to download
 clap-download-path-help = Path where package(s) should be downloaded to
 clap-install-packages-help = Package(s) to install
-clap-list-packages-help = Package(s) to list
+clap-list-packages-help = Package(s) to list, and filter(s) like `section:devel' or `installed-size:>100M'
 clap-list-all-help = List all available version(s) of the specified package(s)
 clap-list-installed-help = List only installed package(s)
 clap-list-upgradable-help = List only package(s) with update(s) available
@@ -416,7 +572,7 @@ clap-pick-package-help = Package to pick specific version for
 clap-rdepends-packages-help = Package(s) to query reverse dependency(ies) for
 clap-remove-packages-help = Package(s) to remove
 clap-purge-packages-help = Package(s) to remove and purge configurations
-clap-search-pattern-help = Keyword(s)/pattern(s) to search
+clap-search-pattern-help = Keyword(s)/pattern(s) to search, and filter(s) like `section:devel' or `installed-size:>100M'
 clap-show-all-help = Show information on all available version(s) of the specified package(s)
 clap-show-packages-help = Package(s) to show
 clap-size-analyzer-details-help = Only display packages size details
@@ -429,6 +585,10 @@ clap-topics-yes-long-help = Note that this parameter requires on the `--opt-out'
 clap-tree-packages-help = Package(s) to display a dependency tree for
 clap-tree-reverse-help = Visualize the reverse dependency(ies) for the specified package
 clap-tree-depth-help = Maximum query level for dependencies (default: 5, { $memory_warn })
+clap-tree-format-help = Output format: tree, or export the dependency graph as dot, graphml or json
+clap-tree-path-help = Show the shortest dependency path to the specified package
+clap-tree-removal-help = Show packages that would be removed along with the specified package(s)
+clap-tree-cycles-help = List circular dependencies among the dependency(ies)
 clap-memory-warn = RAM REQUIREMENT INCREASES EXPONENTIALLY!
 clap-help = Print help
 clap-mark-hold = Lock package version(s), this will prevent the specified package(s) from being updated or downgraded
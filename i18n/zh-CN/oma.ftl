@@ -4,9 +4,16 @@ execute-cmd-fail = 无法执行 `{ $cmd }' 命令：{ $e }。
 # history
 history-tips-1 = oma 已成功应用对系统的更改。
 history-tips-2 = 如需撤销本次操作，请使用 { $cmd } 命令。
+history-rollback-no-snapshot = 历史记录 { $id } 没有对应的系统快照。
+history-rollback-confirm = 是否恢复历史记录 { $id } 执行前创建的 { $backend } 快照 { $snapshot }？
+history-rollback-success = 已恢复系统快照 { $snapshot }。
+history-rollback-reboot = 请重启系统以进入恢复后的快照。
+failed-to-take-snapshot = 无法创建系统快照。
+failed-to-rollback-snapshot = 无法恢复系统快照。
 # verify
 fail-load-certs-from-file = 无法从 { $path } 载入软件源签名。
 cert-file-is-bad = 位于 { $path } 的软件源签名无效。
+no-signing-key = { $path } 中没有可用的未加密签名密钥。
 # topics
 can-not-find-specified-topic = 找不到测试源：{ $topic }。
 do-not-edit-topic-sources-list = # 本文件使用 oma 生成，请勿编辑！
@@ -38,6 +45,9 @@ apt-error = `apt' 返回了错误。
 invalid-pattern = 非法的表达式：{ $p }
 additional-version = 另有 { $len } 个可用版本。请使用 `-a' 列出所有可用版本。
 could-not-find-pkg-from-keyword = 无法找到匹配关键字 { $c } 的软件包。
+search-query-empty-value = 搜索条件 `{ $key }' 缺少值。
+search-query-invalid-size = 搜索条件中的大小 `{ $value }' 无效，应形如 `>100M'。
+search-query-invalid-status = 搜索条件中的软件包状态 `{ $value }' 无效，应为 `installed'、`upgradable' 或 `available'。
 no-need-to-remove = 软件包 { $name } 尚未安装，因此无需卸载。
 packages-can-be-upgrade = 有 { $len } 个可升级的软件包。
 packages-can-be-upgrade-has-manual-held = 有 { $len } 个可升级的软件包（其中 { $held_count } 个被标记为版本锁定）。
@@ -103,6 +113,7 @@ invalid-filename = 文件名 { $name } 无效。
 checksum-mismatch-retry = 文件 { $c } 完整性验证失败（已重试 { $retry } 次）...
 timeout-retry = 文件 { $c } 下载超时（已重试 { $retry } 次）...
 can-not-get-source-next-url = 将使用下一个镜像源重试 ...
+download-throttled = （已限速）
 checksum-mismatch = 文件 { $filename } 完整性验证失败。
 # db
 invalid-url = URL { $url } 无效。
@@ -115,6 +126,8 @@ inrelease-checksum-can-not-parse = InRelease 文件无效：无法解析文件
 inrelease-parse-unsupported-file-type = BUG：解析器不支持该 InRelease 文件的格式，请于 https://github.com/AOSC-Dev/oma 报告问题。
 can-not-parse-sources-list = 无法解析 sources.list 文件 { $path }。
 unsupported-protocol = oma 不支持协议：{ $url }。
+failed-to-read-mirror-list = 无法读取镜像列表。
+no-mirror-available = 镜像列表中没有可用的镜像：{ $url }。
 refreshing-repo-metadata = 正在刷新本机软件包数据库 ...
 not-found = 无法从 { $url } 下载 InRelease 文件：找不到远端文件 (HTTP 404)。
 inrelease-syntax-error = 位于 { $path } 的 InRelease 文件解析失败。
@@ -141,6 +154,53 @@ download-file-failed-with-reason = 下载文件 { $filename } 失败，原因：
 download-failed-no-name = 下载文件失败！
 need-more-size = 存储空间不足：{ $a } 可用，但需要 { $n }。
 successfully-download-to-path = 已下载 { $len } 个软件包到该路径：{ $path }。
+bundle-created = 已将包含 { $len } 个软件包的软件包集合写入 { $path }。
+bundle-failed-to-sign = 无法签名软件包集合清单。
+bundle-bad-signature = 无法验证软件包集合清单的签名。
+bundle-bad-format = 无效的软件包集合：{ $path }。
+bundle-arch-mismatch = 该软件包集合适用于 { $bundle }，但本系统架构为 { $arch }。
+bundle-checksum-mismatch = 软件包集合中 { $name } 的校验和不匹配。
+repo-created = 已将包含 { $len } 个软件包的软件源写入 { $path }。
+repo-updated = 已将 { $len } 个软件包添加至软件源 { $path }。
+repo-replaced = 已替换软件源索引中 { $path } 的现有条目。
+repo-unsigned = 未指定签名密钥，软件源元数据未经签名。请在 sources.list 中使用 `[trusted=yes]' 以使用该软件源。
+repo-failed-to-sign = 无法签名软件源元数据。
+repo-bad-deb = 无效的软件包文件：{ $path }。
+repo-bad-index = 无效的软件源索引：{ $path }。
+repo-not-exist = { $path } 不是由 `oma repo create' 创建的软件源。
+manifest-bad-format = 无效的软件包清单：{ $path }。
+manifest-bad-version = { $name } 的版本要求无效：{ $version }。
+manifest-exported = 包含 { $len } 个软件包的清单已写入 { $path }。
+pin-added = 已将 { $name } 的 pin 优先级设为 { $priority }。
+pin-removed = 已移除 { $name } 的 pin。
+pin-not-found = { $name } 未被 oma pin。
+pin-list-empty = 没有被 oma pin 的软件包。
+pin-package = 软件包
+pin-target = Pin
+pin-priority = 优先级
+pin-invalid-package = 无效的软件包名：{ $name }。
+pin-invalid-target = 无效的 pin：{ $pin }。
+build-dep-resolving = 正在解析 { $name } { $version } 的构建依赖 ...
+build-dep-no-source-index = 无法找到源码包 { $name }，请确认软件源配置中已启用 deb-src 条目。
+unattended-on-battery = 设备正在使用电池供电，跳过无人值守升级。
+unattended-nothing-to-do = 没有符合无人值守升级策略的更新。
+unattended-confirm = 是否应用 { $count } 个符合无人值守升级策略的更新？
+unattended-skipped = 保留 { $name }：{ $reason }。
+unattended-would-remove = 无人值守升级将会删除 { $pkgs }，已中止。
+unattended-kept-upgraded = 无人值守升级仍会升级策略要求保留的 { $pkgs }，已中止。
+unattended-reason-denied = 策略禁止升级
+unattended-reason-origin = 不来自允许的软件源
+unattended-reason-not-security = 不是安全更新
+unattended-reason-conffile = 配置文件已被本地修改
+unattended-reason-dependency = 依赖被保留的更新
+build-dep-unsatisfiable = 无法满足构建依赖 { $dep }。
+build-dep-bad-control = 无法解析 { $path }。
+build-dep-no-source = { $path } 中没有源码包信息段。
+daemon-started = oma 守护进程已在系统总线上以 { $name } 运行。
+daemon-busy = 另一项操作正在进行中，请稍后再试。
+daemon-not-authorized = 无权执行 { $action }。
+serve-cache-started = 正在 { $addr } 上共享软件包缓存 { $path }。
+serve-cache-announce-failed = 无法通过 Avahi 广播软件包缓存：{ $e }
 oma-may = 为应用您指定的更改，oma 可能 { $a }、{ $b }、{ $c }、{ $d } 或 { $e } 软件包。
 failed-to-read-decode-inrelease = 无法读取解密后的 InRelease 文件。
 failed-to-operate-path = 无法在路径 { $p } 中执行文件操作。
@@ -150,6 +210,11 @@ illegal-topic-entry = 测试源配置条目包含无效字符：{ $name }。
 failed-to-get-rg-process-info = 无法获取 `rg' 的进程状态。
 failed-to-calculate-available-space = 无法计算可用存储空间。
 failed-to-create-http-client = 无法创建 HTTP 客户端。
+failed-to-create-http-client-for = 无法为 { $host } 创建 HTTP 客户端。
+tls-read-file-failed = 无法读取 TLS 证书或私钥文件 { $path }。
+tls-invalid-ca-info = { $path } 中的 CA 证书无效。
+tls-invalid-client-cert = { $path } 中的 TLS 客户端证书或私钥无效。
+tls-handshake-failed = 无法与 { $host } 建立安全连接。请检查服务器证书，或 APT 配置中该服务器的 CaInfo、SslCert 及 SslKey 设置。
 failed-to-connect-history-database = 无法连接到历史数据库。
 failed-to-execute-query-stmt = 无法在历史数据库中执行查询命令。
 failed-to-parse-history-object = 无法解析历史数据库中的对象。
@@ -244,6 +309,30 @@ tum-1 = oma 找到了 { $updates } 个可用系统更新：
 tum-1-with-security = oma 找到了 { $updates } 个关键系统更新（含 { $security } 个 { $security_str })：
 tum-2 = 根据您指定的操作，oma 需要对系统组件执行若干变更，变更详情如下：
 security = 安全更新
+advisories-pending = 待更新
+advisories-fixed = 已修复
+advisories-none = 没有影响已安装软件包的公告。
+advisories-summary = 共有 { $total } 个公告涉及已安装的软件包，其中 { $pending } 个尚未修复。
+conffiles-modified = 已修改
+conffiles-missing = 已缺失
+conffiles-unmodified = 未修改
+conffiles-none = 没有被修改的配置文件。
+conffiles-not-found = { $path } 不是任何已安装软件包的配置文件。
+conffiles-no-cached-deb = 本地软件包缓存中没有 { $name }（{ $version }）的软件包文件，请运行 `oma install --reinstall --download-only { $name }' 下载。
+conffiles-diff-packaged = { $path }（软件包版本）
+conffiles-no-diff = 本地版本与软件包中的版本相同。
+conffiles-diff-failed = 无法比较配置文件。
+conffiles-merge-failed = 无法合并配置文件。
+conffiles-resolved = 已处理 { $path }。
+verify-not-installed = 软件包 { $name } 未安装。
+verify-no-archive = 本地软件包缓存中没有 { $name }（{ $version }）的软件包文件，仅使用 dpkg 数据库进行校验。
+verify-archive-mismatch = { $path } 的校验和与软件源不符，已忽略。
+verify-bad-archive = 无法从 { $path } 读取 md5sums。
+verify-modified = 已修改
+verify-missing = 已缺失
+verify-replaced = 已被替换
+verify-ok = { $count } 个软件包的文件均完好。
+verify-broken = { $count } 个软件包的文件被修改或缺失。
 verify-error = 在验证 { $p } 的签名时遇到错误。
 sources-list-empty = 软件源配置为空。
 failed-refresh = 无法刷新软件源数据。
@@ -262,6 +351,7 @@ broken-pipe-err = 管道传送错误
 send-request-timeout = 发送网络请求超时
 download-timeout = 下载文件超时
 checksum-mismatch-download-err = 校验文件失败
+unexpected-range-download-err = 服务器未返回所请求的数据范围
 suggest = 建议安装如下软件包，可增强您安装的软件包的功能：
 tum-name = 名称
 tum-notes = 备注
@@ -277,6 +367,7 @@ failed-set-upgrade-mode-tips = 该问题属于 APT 后端的内部错误，为
 failed-lock-apt-tips = 请检查是否有其他相关进程，如另一个终端中的 oma 或 apt 命令，抑或图形化的软件包管理器等，并耐心等待其完成操作。
 path-not-exist = 路径 { $path } 不存在。
 not-allow-oma-pending-piped = 不允许搭配管道使用 oma 的交互模式界面。
+json-events-prompt-unanswered = 输出 JSON 事件时无法回答提示“{ $prompt }”，请使用 { $hint } 预先作答。
 space-warn = 检测到您的存储空间告急，您可以使用 { $cmd } 清除软件安装包缓存，可释放 { $size } 存储空间；也请考虑删除不必要的软件包或个人数据。
 space-warn-with-zero = 检测到您的存储空间告急，请考虑删除不必要的软件包或个人数据。
 wrong-thread-count = 您指定的线程数 { $count } 不合法（最小：1，最大：255）。
@@ -288,6 +379,12 @@ psa-without-root-tips = 若要启动磁盘分析器的管理界面，请以管
 psa-total = 总占用
 mirrors-setting-empty = 未配置任何软件源，拒绝保存配置。
 loading-tree = 正在分析依赖树
+tree-no-path = 不存在从 { $from } 到 { $to } 的依赖路径。
+tree-removal-broken = 依赖于待删除软件包的软件包：
+tree-removal-orphaned = 不再需要的软件包：
+tree-removal-nothing = 不会删除其他软件包。
+tree-cycle = 循环依赖：{ $pkgs }
+tree-no-cycles = 未发现循环依赖。
 battery-check-disabled = 电源监测功能已被禁用，oma 在执行系统更改时如发生意外断电等情况，可能导致系统故障和数据丢失。
 session-check-disabled = 会话监测功能已被禁用，oma 在执行任务时可能会被桌面电源管理、计划任务等打断，导致系统故障和数据丢失。
 topics-held-tips = 您开启的测试源涉及 { $count } 个被标记为版本锁定的软件包，本次操作未更新这些软件包。
@@ -309,6 +406,41 @@ clap-about = 易用、防呆的 APT 软件包管理器
 clap-install-help = 从仓库中安装软件包
 clap-upgrade-help = 升级系统中安装的软件包
 clap-download-help = 从软件仓库下载软件包
+clap-bundle-help = 创建或应用离线软件包集合
+clap-bundle-create-help = 创建包含指定软件包及其所有依赖的离线软件包集合
+clap-bundle-create-packages-help = 要加入软件包集合的软件包
+clap-bundle-output-help = 软件包集合的输出路径
+clap-bundle-sign-key-help = 用于签名软件包集合清单的 OpenPGP 私钥文件
+clap-bundle-apply-help = 从离线软件包集合安装软件包
+clap-bundle-path-help = 软件包集合路径
+clap-bundle-keyring-help = 用于验证软件包集合清单的 OpenPGP 证书文件（默认：系统信任的密钥）
+clap-repo-help = 创建或更新本地 APT 软件源
+clap-repo-create-help = 使用目录中的软件包文件创建平铺软件源
+clap-repo-add-deb-help = 向 `oma repo create' 创建的软件源添加软件包文件
+clap-repo-dir-help = 软件源目录
+clap-repo-debs-help = 要添加的软件包文件
+clap-repo-sign-key-help = 用于签名软件源元数据的 OpenPGP 私钥文件
+clap-repo-origin-help = Release 文件中 `Origin' 字段的值
+clap-apply-help = 将声明式软件包清单应用到系统
+clap-apply-manifest-help = 软件包清单路径
+clap-export-help = 将手动安装的软件包导出为声明式软件包清单
+clap-export-output-help = 将清单写入文件而非标准输出
+clap-export-with-version-help = 要求各软件包使用当前安装的版本
+clap-pin-help = 管理 APT pin 偏好设置
+clap-pin-add-help = 将软件包 pin 到指定版本、来源或发行版
+clap-pin-remove-help = 移除由 oma 创建的 pin
+clap-pin-list-help = 列出由 oma 创建的 pin
+clap-pin-package-help = 要 pin 的软件包
+clap-pin-target-help = 要 pin 的版本、origin:<主机> 或 release:<筛选条件>
+clap-pin-target-long-help = 软件包要 pin 到的目标，可以是：
+    <版本>：版本号，可使用 `*' 作为通配符（如 1.2*）
+    origin:<主机>：来自指定主机的软件包（如 origin:repo.aosc.io）
+    release:<筛选条件>：来自指定发行版的软件包（如 release:a=stable）
+clap-pin-priority-help = pin 优先级，详见 apt_preferences(5)
+clap-build-dep-help = 安装源码包的构建依赖
+clap-build-dep-target-help = 源码包名、源码目录或 debian/control 文件路径
+clap-build-dep-build-profiles-help = 要启用的构建配置（build profiles），以逗号分隔（如 nocheck,nodoc）
+clap-upgrade-unattended-help = 按照 /etc/oma.toml 中的无人值守升级策略应用更新，不进行任何询问
 clap-remove-help = 删除软件包
 clap-refresh-help = 刷新软件仓库元数据
 clap-show-help = 显示指定软件包的信息
@@ -324,10 +456,33 @@ clap-rdepends-help = 查询指定软件包的逆向依赖关系
 clap-clean-help = 清除本地软件包缓存
 clap-history-help = 显示系统软件包更改的历史
 clap-undo-help = 撤销系统软件包更改
+clap-history-rollback-help = 恢复历史记录执行前创建的系统快照
+clap-history-rollback-id-help = 要回滚的历史记录 ID
+clap-undo-id-help = 要撤销的历史记录 ID（若未指定则交互式选择）
+clap-daemon-help = 以 D-Bus 系统服务模式运行 oma
+clap-serve-cache-help = 与局域网中的其他机器共享软件包缓存
+clap-serve-cache-listen-help = 监听地址
+clap-serve-cache-no-announce-help = 不通过 Avahi 广播软件包缓存
 clap-tui-help = 交互性终端用户界面 (TUI)
 clap-version-help = 显示小熊猫包管理 (oma) 的版本号
 clap-topics-help = 加入或退出测试主题（测试源）
 clap-mirror-help = 开启或关闭镜像源
+clap-advisories-help = 列出影响已安装软件包的安全公告
+clap-advisories-pending-help = 仅列出尚未在本系统上修复的公告
+clap-advisories-all-help = 同时列出非安全更新
+clap-conffiles-help = 管理被修改的配置文件
+clap-conffiles-list-help = 列出与软件包中版本不同的配置文件
+clap-conffiles-all-help = 同时列出未被修改的配置文件
+clap-conffiles-diff-help = 显示软件包版本与本地版本的差异
+clap-conffiles-path-help = 配置文件路径
+clap-conffiles-resolve-help = 保留本地版本、使用软件包版本或合并两者
+clap-conffiles-keep-help = 保留本地版本并删除遗留文件
+clap-conffiles-take-help = 使用软件包版本替换本地版本（本地版本将保存为 .dpkg-old）
+clap-conffiles-merge-help = 交互式地将软件包版本合并到本地版本
+clap-verify-help = 根据 dpkg 数据库校验已安装软件包的文件
+clap-verify-packages-help = 要校验的软件包，未指定时校验所有已安装的软件包
+clap-verify-archive-help = 同时使用本地缓存的软件包文件中的 md5sums 进行校验
+clap-verify-reinstall-help = 重新安装文件被修改或缺失的软件包
 clap-size-analyzer-help = 软件包磁盘占用分析器
 clap-tree-help = 显示指定软件包的依赖关系树
 clap-why-help = 通过树形可视化呈现指定软件包被安装的原因
@@ -361,6 +516,7 @@ clap-no-install-recommends-help = 不要安装推荐依赖
 clap-no-install-suggests-help = 不要安装建议依赖
 clap-no-pager-help = 将输出直接打印到标准输出流 (stdout) 而不使用查看器 (pager)
 clap-no-progress-help = 不要显示进度条
+clap-json-events-help = 向标准输出打印以换行分隔的 JSON 事件，而非进度条及交互提示
 clap-no-refresh-help = 不要刷新软件仓库元数据
 clap-no-refresh-topics-help = 不要刷新测试主题元数据
 clap-no-remove-help = 在更新时不允许删除软件包（类似 `apt upgrade'）
@@ -381,7 +537,7 @@ clap-depends-packages-help = 要查询依赖关系的软件包
 clap-download-packages-help = 要下载的软件包
 clap-download-path-help = 软件包的下载路径
 clap-install-packages-help = 要安装的软件包
-clap-list-packages-help = 要列出的软件包
+clap-list-packages-help = 要列出的软件包，及形如 `section:devel' 或 `installed-size:>100M' 的过滤条件
 clap-list-all-help = 列出指定软件包的所有可用版本
 clap-list-installed-help = 仅列出已安装的软件包
 clap-list-upgradable-help = 仅列出有可用更新的软件包
@@ -405,7 +561,7 @@ clap-pick-package-help = 要选择并安装特定版本的软件包
 clap-rdepends-packages-help = 要查询逆向依赖关系的软件包
 clap-remove-packages-help = 要移除的软件包
 clap-purge-packages-help = 要移除并清除配置文件的软件包
-clap-search-pattern-help = 要搜索的关键字/模式
+clap-search-pattern-help = 要搜索的关键字/模式，及形如 `section:devel' 或 `installed-size:>100M' 的过滤条件
 clap-show-all-help = 显示指定软件包的所有版本的信息
 clap-show-packages-help = 要显示的软件包
 clap-size-analyzer-details-help = 仅显示软件包大小信息
@@ -418,6 +574,10 @@ clap-topics-yes-long-help = 注意：该参数依赖 `--opt-out' 或 `--opt-in'
 clap-tree-packages-help = 要显示依赖关系树的软件包
 clap-tree-reverse-help = 显示指定软件包的逆向依赖树
 clap-tree-depth-help = 最大依赖查询深度（默认为 5 层，{ $memory_warn }）
+clap-tree-format-help = 输出格式：树形视图（tree），或将依赖图导出为 dot、graphml 或 json
+clap-tree-path-help = 显示到指定软件包的最短依赖路径
+clap-tree-removal-help = 显示删除指定软件包时会一并删除的软件包
+clap-tree-cycles-help = 列出依赖中的循环依赖
 clap-memory-warn = 查询的内存需求随深度指数性增长！
 clap-why-packages-help = 要显示逆向依赖关系树的软件包
 clap-help = 显示帮助信息
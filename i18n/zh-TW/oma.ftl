@@ -4,9 +4,16 @@ execute-cmd-fail = 無法執行 `{ $cmd }' 指令：{ $e }。
 # history
 history-tips-1 = oma 已成功套用對系統的變更。
 history-tips-2 = 如需取消本次操作，請使用 { $cmd } 指令。
+history-rollback-no-snapshot = 歷史記錄 { $id } 沒有對應的系統快照。
+history-rollback-confirm = 是否還原歷史記錄 { $id } 執行前建立的 { $backend } 快照 { $snapshot }？
+history-rollback-success = 已還原系統快照 { $snapshot }。
+history-rollback-reboot = 請重新啟動系統以進入還原後的快照。
+failed-to-take-snapshot = 無法建立系統快照。
+failed-to-rollback-snapshot = 無法還原系統快照。
 # verify
 fail-load-certs-from-file = 無法從 { $path } 載入軟體庫簽章。
 cert-file-is-bad = 位於 { $path } 的軟體庫簽章無效。
+no-signing-key = { $path } 中沒有可用的未加密簽署金鑰。
 # topics
 can-not-find-specified-topic = 找不到測試庫：{ $topic }。
 do-not-edit-topic-sources-list = # 本檔案使用 oma 產生，請勿編輯！
@@ -38,6 +45,9 @@ apt-error = `apt' 回傳了錯誤。
 invalid-pattern = 表達式格式有誤：{ $p }
 additional-version = 另有 { $len } 個可用版本。請使用 `-a' 列出所有可用版本。
 could-not-find-pkg-from-keyword = 無法找到符合關鍵字 { $c } 的軟體套件。
+search-query-empty-value = 搜尋條件 `{ $key }' 缺少值。
+search-query-invalid-size = 搜尋條件中的大小 `{ $value }' 無效，應形如 `>100M'。
+search-query-invalid-status = 搜尋條件中的軟體套件狀態 `{ $value }' 無效，應為 `installed'、`upgradable' 或 `available'。
 no-need-to-remove = 軟體套件 { $name } 尚未安裝，因此無需解除安裝。
 packages-can-be-upgrade = 有 { $len } 個可升級的軟體套件。
 packages-can-be-removed = 有 { $len } 個可移除的軟體套件。
@@ -97,6 +107,7 @@ invalid-filename = 檔案名 { $name } 無效。
 checksum-mismatch-retry = 檔案 { $c } 完整性驗證失敗（已重試 { $retry } 次）...
 timeout-retry = 檔案 { $c } 下載超時（已重試 { $retry } 次）...
 can-not-get-source-next-url = 將使用下一個鏡像源重試 ...
+download-throttled = （已限速）
 checksum-mismatch = 檔案 { $filename } 完整性驗證失敗。
 # db
 invalid-url = URL { $url } 無效。
@@ -109,6 +120,8 @@ inrelease-checksum-can-not-parse = InRelease 檔案無效：無法解析檔案 {
 inrelease-parse-unsupported-file-type = BUG：解析器不支援該 InRelease 檔案的格式，請於 https://github.com/AOSC-Dev/oma 報告問題。
 can-not-parse-sources-list = 無法解析 sources.list 檔案 { $path }。
 unsupported-protocol = oma 不支援協定：{ $url }。
+failed-to-read-mirror-list = 無法讀取鏡像列表。
+no-mirror-available = 鏡像列表中沒有可用的鏡像：{ $url }。
 refreshing-repo-metadata = 正在重新整理本機軟體套件資料庫 ...
 not-found = 無法從 { $url } 下載 InRelease 檔案：找不到遠端檔案 (HTTP 404)。
 inrelease-syntax-error = 位於 { $path } 的 InRelease 檔案解析失敗。
@@ -133,6 +146,53 @@ download-failed = 下載 { $filename } 檔案失敗！
 download-failed-no-name = 下載檔案失敗！
 need-more-size = 儲存空間不足：{ $a } 可用，但需要 { $n }。
 successfully-download-to-path = 已下載 { $len } 個軟體套件到該路徑：{ $path }。
+bundle-created = 已將包含 { $len } 個軟體套件的軟體套件集合寫入 { $path }。
+bundle-failed-to-sign = 無法簽署軟體套件集合清單。
+bundle-bad-signature = 無法驗證軟體套件集合清單的簽章。
+bundle-bad-format = 無效的軟體套件集合：{ $path }。
+bundle-arch-mismatch = 此軟體套件集合適用於 { $bundle }，但本系統架構為 { $arch }。
+bundle-checksum-mismatch = 軟體套件集合中 { $name } 的校驗和不符。
+repo-created = 已將包含 { $len } 個軟體套件的軟體源寫入 { $path }。
+repo-updated = 已將 { $len } 個軟體套件新增至軟體源 { $path }。
+repo-replaced = 已取代軟體源索引中 { $path } 的現有條目。
+repo-unsigned = 未指定簽署金鑰，軟體源中繼資料未經簽署。請在 sources.list 中使用 `[trusted=yes]' 以使用此軟體源。
+repo-failed-to-sign = 無法簽署軟體源中繼資料。
+repo-bad-deb = 無效的軟體套件檔案：{ $path }。
+repo-bad-index = 無效的軟體源索引：{ $path }。
+repo-not-exist = { $path } 不是由 `oma repo create' 建立的軟體源。
+manifest-bad-format = 無效的軟體套件清單：{ $path }。
+manifest-bad-version = { $name } 的版本要求無效：{ $version }。
+manifest-exported = 包含 { $len } 個軟體套件的清單已寫入 { $path }。
+pin-added = 已將 { $name } 的 pin 優先級設為 { $priority }。
+pin-removed = 已移除 { $name } 的 pin。
+pin-not-found = { $name } 未被 oma pin。
+pin-list-empty = 沒有被 oma pin 的軟體套件。
+pin-package = 軟體套件
+pin-target = Pin
+pin-priority = 優先級
+pin-invalid-package = 無效的軟體套件名稱：{ $name }。
+pin-invalid-target = 無效的 pin：{ $pin }。
+build-dep-resolving = 正在解析 { $name } { $version } 的建置相依 ...
+build-dep-no-source-index = 無法找到原始碼包 { $name }，請確認軟體源設定中已啟用 deb-src 條目。
+unattended-on-battery = 裝置正在使用電池供電，跳過無人值守升級。
+unattended-nothing-to-do = 沒有符合無人值守升級策略的更新。
+unattended-confirm = 是否套用 { $count } 個符合無人值守升級策略的更新？
+unattended-skipped = 保留 { $name }：{ $reason }。
+unattended-would-remove = 無人值守升級將會移除 { $pkgs }，已中止。
+unattended-kept-upgraded = 無人值守升級仍會升級策略要求保留的 { $pkgs }，已中止。
+unattended-reason-denied = 策略禁止升級
+unattended-reason-origin = 不來自允許的軟體源
+unattended-reason-not-security = 不是安全性更新
+unattended-reason-conffile = 設定檔已被本機修改
+unattended-reason-dependency = 相依於被保留的更新
+build-dep-unsatisfiable = 無法滿足建置相依 { $dep }。
+build-dep-bad-control = 無法解析 { $path }。
+build-dep-no-source = { $path } 中沒有原始碼包資訊段。
+daemon-started = oma 守護程序已在系統匯流排上以 { $name } 執行。
+daemon-busy = 另一項操作正在進行中，請稍後再試。
+daemon-not-authorized = 無權執行 { $action }。
+serve-cache-started = 正在 { $addr } 上分享軟體包快取 { $path }。
+serve-cache-announce-failed = 無法透過 Avahi 廣播軟體包快取：{ $e }
 oma-may = 為套用您指定的變更，oma 可能 { $a }、{ $b }、{ $c }、{ $d } 或 { $e } 軟體套件。
 failed-to-read-decode-inrelease = 無法讀取解密後的 InRelease 檔案。
 failed-to-operate-path = 無法在路徑 { $p } 中執行檔案操作。
@@ -142,6 +202,11 @@ illegal-topic-entry = 測試庫設定項包含無效字元：{ $name }。
 failed-to-get-rg-process-info = 無法取得 `rg' 的行程狀態。
 failed-to-calculate-available-space = 無法計算可用儲存空間。
 failed-to-create-http-client = 無法建立 HTTP 用戶端。
+failed-to-create-http-client-for = 無法為 { $host } 建立 HTTP 用戶端。
+tls-read-file-failed = 無法讀取 TLS 憑證或私鑰檔案 { $path }。
+tls-invalid-ca-info = { $path } 中的 CA 憑證無效。
+tls-invalid-client-cert = { $path } 中的 TLS 用戶端憑證或私鑰無效。
+tls-handshake-failed = 無法與 { $host } 建立安全連線。請檢查伺服器憑證，或 APT 設定中該伺服器的 CaInfo、SslCert 及 SslKey 設定。
 failed-to-connect-history-database = 無法連接到歷史資料庫。
 failed-to-execute-query-stmt = 無法在歷史資料庫中執行查詢指令。
 failed-to-parse-history-object = 無法解析歷史資料庫中的物件。
@@ -237,6 +302,30 @@ tum-1 = 您的系統有 { $updates } 個可用的更新：
 tum-1-with-security = 您的系統有 { $updates } 個重要更新（含 { $security } 個標記為 { $security_str } 的安全更新）：
 tum-2 = 根據您指定的操作，oma 需要對系統組件進行若干變更，詳情如下：
 security = 安全更新
+advisories-pending = 待更新
+advisories-fixed = 已修復
+advisories-none = 沒有影響已安裝套件的公告。
+advisories-summary = 共有 { $total } 個公告涉及已安裝的套件，其中 { $pending } 個尚未修復。
+conffiles-modified = 已修改
+conffiles-missing = 已缺失
+conffiles-unmodified = 未修改
+conffiles-none = 沒有被修改的設定檔。
+conffiles-not-found = { $path } 不是任何已安裝套件的設定檔。
+conffiles-no-cached-deb = 本機套件快取中沒有 { $name }（{ $version }）的套件檔案，請執行 `oma install --reinstall --download-only { $name }' 下載。
+conffiles-diff-packaged = { $path }（套件版本）
+conffiles-no-diff = 本機版本與套件中的版本相同。
+conffiles-diff-failed = 無法比較設定檔。
+conffiles-merge-failed = 無法合併設定檔。
+conffiles-resolved = 已處理 { $path }。
+verify-not-installed = 套件 { $name } 未安裝。
+verify-no-archive = 本機套件快取中沒有 { $name }（{ $version }）的套件檔案，僅使用 dpkg 資料庫進行驗證。
+verify-archive-mismatch = { $path } 的校驗和與軟體庫不符，已忽略。
+verify-bad-archive = 無法從 { $path } 讀取 md5sums。
+verify-modified = 已修改
+verify-missing = 已缺失
+verify-replaced = 已被取代
+verify-ok = { $count } 個套件的檔案均完好。
+verify-broken = { $count } 個套件的檔案被修改或缺失。
 verify-error = 在驗證 { $p } 的簽章時遇到錯誤。
 unsupported-sources-list = APT 軟體庫設定檔 { $p } 不受支援：僅支援單列 ({ $list }) 及 DEB822 ({ $sources }) 格式的設定檔。
 broken-pipe-err = 管線傳送錯誤
@@ -261,6 +350,7 @@ failed-set-upgrade-mode-tips = 此問題屬於 APT 後端的內部錯誤，為
 failed-lock-apt-tips = 請檢查是否有其他相關行程，如另一個終端機中的 oma 或 apt 指令，抑或圖形化的軟體套件管理器等，並耐心等待其完成操作。
 path-not-exist = 路徑 { $path } 不存在。
 not-allow-oma-pending-piped = 不允許搭配管線使用 oma 的交互模式介面。
+json-events-prompt-unanswered = 輸出 JSON 事件時無法回答提示「{ $prompt }」，請使用 { $hint } 預先作答。
 space-warn = 檢測到您的儲存空間不足，您可以使用 { $cmd } 清除軟體安裝檔快取，可釋放 { $size } 儲存空間；也請考慮刪除不必要的軟體套件或個人資料。
 space-warn-with-zero = 檢測到您的儲存空間不足，請考慮刪除不必要的軟體套件或個人資料。
 sources-list-empty = 鏡像源設定為空。
@@ -272,6 +362,7 @@ failed-create-cache-tips = 造成此問題的直接原因是本機軟體套件
 send-request-timeout = 發送網路請求超時
 download-timeout = 下載檔案超時
 checksum-mismatch-download-err = 檔案驗證失敗
+unexpected-range-download-err = 伺服器未傳回所請求的資料範圍
 suggest = 建議安裝如下軟體套件，可增強您安裝的軟體套件的功能：
 download-package-failed-with-reason = 下載軟體套件 { $filename } 失敗，原因：{ $reason }。
 create-symlink-err = 無法建立符號連結
@@ -284,6 +375,12 @@ psa-autoremove = 清理未使用的軟體套件
 psa-without-root-tips = 若要啟動磁碟分析器的管理介面，請以管理者權限執行 `oma size-analyzer`。
 psa-total = 總佔用
 loading-tree = 正在分析依賴樹
+tree-no-path = 不存在從 { $from } 到 { $to } 的依賴路徑。
+tree-removal-broken = 依賴於待移除軟體套件的軟體套件：
+tree-removal-orphaned = 不再需要的軟體套件：
+tree-removal-nothing = 不會移除其他軟體套件。
+tree-cycle = 循環依賴：{ $pkgs }
+tree-no-cycles = 未發現循環依賴。
 mirrors-setting-empty = 未指定任何鏡像源，不儲存設定。
 packages-can-be-upgrade-has-manual-held = 有 { $len } 個可升級的軟體套件（其中 { $held_count } 個被標記為版本鎖定）
 upgrade-manual-held-tips = { $count } package(s) were marked as manual and were not upgraded.
@@ -305,6 +402,41 @@ clap-usage = 用法
 clap-about = 易用、防呆的 APT 軟體套件管理器
 clap-upgrade-help = 升級系統中安裝的軟體套件
 clap-download-help = 從軟體庫下載軟體套件
+clap-bundle-help = 建立或套用離線軟體套件集合
+clap-bundle-create-help = 建立包含指定軟體套件及其所有相依的離線軟體套件集合
+clap-bundle-create-packages-help = 要加入軟體套件集合的軟體套件
+clap-bundle-output-help = 軟體套件集合的輸出路徑
+clap-bundle-sign-key-help = 用於簽署軟體套件集合清單的 OpenPGP 私鑰檔案
+clap-bundle-apply-help = 從離線軟體套件集合安裝軟體套件
+clap-bundle-path-help = 軟體套件集合路徑
+clap-bundle-keyring-help = 用於驗證軟體套件集合清單的 OpenPGP 憑證檔案（預設：系統信任的金鑰）
+clap-repo-help = 建立或更新本機 APT 軟體源
+clap-repo-create-help = 使用目錄中的軟體套件檔案建立平鋪軟體源
+clap-repo-add-deb-help = 向 `oma repo create' 建立的軟體源新增軟體套件檔案
+clap-repo-dir-help = 軟體源目錄
+clap-repo-debs-help = 要新增的軟體套件檔案
+clap-repo-sign-key-help = 用於簽署軟體源中繼資料的 OpenPGP 私鑰檔案
+clap-repo-origin-help = Release 檔案中 `Origin' 欄位的值
+clap-apply-help = 將宣告式軟體套件清單套用到系統
+clap-apply-manifest-help = 軟體套件清單路徑
+clap-export-help = 將手動安裝的軟體套件匯出為宣告式軟體套件清單
+clap-export-output-help = 將清單寫入檔案而非標準輸出
+clap-export-with-version-help = 要求各軟體套件使用目前安裝的版本
+clap-pin-help = 管理 APT pin 偏好設定
+clap-pin-add-help = 將軟體套件 pin 到指定版本、來源或發行版
+clap-pin-remove-help = 移除由 oma 建立的 pin
+clap-pin-list-help = 列出由 oma 建立的 pin
+clap-pin-package-help = 要 pin 的軟體套件
+clap-pin-target-help = 要 pin 的版本、origin:<主機> 或 release:<篩選條件>
+clap-pin-target-long-help = 軟體套件要 pin 到的目標，可以是：
+    <版本>：版本號，可使用 `*' 作為萬用字元（如 1.2*）
+    origin:<主機>：來自指定主機的軟體套件（如 origin:repo.aosc.io）
+    release:<篩選條件>：來自指定發行版的軟體套件（如 release:a=stable）
+clap-pin-priority-help = pin 優先級，詳見 apt_preferences(5)
+clap-build-dep-help = 安裝原始碼包的建置相依
+clap-build-dep-target-help = 原始碼包名、原始碼目錄或 debian/control 檔案路徑
+clap-build-dep-build-profiles-help = 要啟用的建置設定（build profiles），以逗號分隔（如 nocheck,nodoc）
+clap-upgrade-unattended-help = 依照 /etc/oma.toml 中的無人值守升級策略套用更新，不進行任何詢問
 clap-remove-help = 移除軟體套件
 clap-refresh-help = 重新整理軟體庫後設資料
 clap-show-help = 顯示指定軟體套件的資訊
@@ -318,9 +450,32 @@ clap-rdepends-help = 查詢指定軟體套件的逆向依賴關係
 clap-clean-help = 清理本機軟體套件快取
 clap-history-help = 顯示系統軟體套件變更歷史
 clap-undo-help = 取消系統軟體套件變更
+clap-history-rollback-help = 還原歷史記錄執行前建立的系統快照
+clap-history-rollback-id-help = 要回滾的歷史記錄 ID
+clap-undo-id-help = 要取消的歷史記錄 ID（若未指定則互動式選擇）
+clap-daemon-help = 以 D-Bus 系統服務模式執行 oma
+clap-serve-cache-help = 與區域網路中的其他機器分享軟體包快取
+clap-serve-cache-listen-help = 監聽位址
+clap-serve-cache-no-announce-help = 不透過 Avahi 廣播軟體包快取
 clap-tui-help = 互動式終端使用者介面
 clap-version-help = 顯示版本號
 clap-mirror-help = 管理軟體庫鏡像源
+clap-advisories-help = 列出影響已安裝套件的安全性公告
+clap-advisories-pending-help = 僅列出尚未在本系統上修復的公告
+clap-advisories-all-help = 同時列出非安全性更新
+clap-conffiles-help = 管理被修改的設定檔
+clap-conffiles-list-help = 列出與套件中版本不同的設定檔
+clap-conffiles-all-help = 同時列出未被修改的設定檔
+clap-conffiles-diff-help = 顯示套件版本與本機版本的差異
+clap-conffiles-path-help = 設定檔路徑
+clap-conffiles-resolve-help = 保留本機版本、使用套件版本或合併兩者
+clap-conffiles-keep-help = 保留本機版本並刪除遺留檔案
+clap-conffiles-take-help = 使用套件版本取代本機版本（本機版本將儲存為 .dpkg-old）
+clap-conffiles-merge-help = 互動式地將套件版本合併至本機版本
+clap-verify-help = 依據 dpkg 資料庫驗證已安裝套件的檔案
+clap-verify-packages-help = 要驗證的套件，未指定時驗證所有已安裝的套件
+clap-verify-archive-help = 同時使用本機快取的套件檔案中的 md5sums 進行驗證
+clap-verify-reinstall-help = 重新安裝檔案被修改或缺失的套件
 clap-size-analyzer-help = 分析軟體套件磁碟佔用
 clap-tree-help = 顯示指定軟體套件的依賴樹
 clap-apt-options-help = 指定 APT 選項
@@ -347,6 +502,7 @@ clap-no-install-recommends-help = 不要安裝推薦依賴
 clap-no-install-suggests-help = 不要安裝建議依賴
 clap-no-pager-help = 將輸出直接寫入標準輸出 (stdout) 而不使用分頁器 (pager)
 clap-no-progress-help = 不要顯示進度列
+clap-json-events-help = 向標準輸出列印以換行分隔的 JSON 事件，而非進度列及互動提示
 clap-no-refresh-topics-help = 不要重新整理測試主題後設資料
 clap-no-remove-help = 不允許在更新時移除軟體套件（類似 `apt upgrade'）
 clap-reinstall-help = 重新安裝指定的軟體套件
@@ -363,7 +519,7 @@ clap-depends-packages-help = 要查詢依賴關係的軟體套件
 clap-download-packages-help = 要下載的軟體套件
 clap-download-path-help = 軟體套件的下載路徑
 clap-install-packages-help = 要安裝的軟體套件
-clap-list-packages-help = 要列出的軟體套件
+clap-list-packages-help = 要列出的軟體套件，及形如 `section:devel' 或 `installed-size:>100M' 的過濾條件
 clap-list-installed-help = 僅列出已安裝的軟體套件
 clap-list-upgradable-help = 僅列出有可用更新的軟體套件
 clap-list-manually-installed-help = 僅列出標記為手動安裝的軟體套件
@@ -383,7 +539,7 @@ clap-pick-package-help = 要選擇並安裝指定版本的軟體套件
 clap-rdepends-packages-help = 要查詢逆向依賴關係的軟體套件
 clap-remove-packages-help = 要移除的軟體套件
 clap-purge-packages-help = 要移除並清理設定檔的軟體套件
-clap-search-pattern-help = 要搜尋的關鍵字/表達式
+clap-search-pattern-help = 要搜尋的關鍵字/表達式，及形如 `section:devel' 或 `installed-size:>100M' 的過濾條件
 clap-show-packages-help = 要顯示的軟體套件
 clap-size-analyzer-details-help = 僅顯示軟體套件大小資訊
 clap-topics-all-help = 顯示所有測試主題（包括當前處於草稿狀態的測試主體）
@@ -394,6 +550,10 @@ clap-topics-yes-long-help = 注意：該參數依賴 `--opt-out` 或 `--opt-in`
 clap-tree-packages-help = 要顯示依賴樹的軟體套件
 clap-tree-reverse-help = 顯示指定軟體套件的逆向依賴樹
 clap-tree-depth-help = 最大依賴查詢深度（預設為 5 層，{ $memory_warn }）
+clap-tree-format-help = 輸出格式：樹狀檢視（tree），或將依賴圖匯出為 dot、graphml 或 json
+clap-tree-path-help = 顯示到指定軟體套件的最短依賴路徑
+clap-tree-removal-help = 顯示移除指定軟體套件時會一併移除的軟體套件
+clap-tree-cycles-help = 列出依賴中的循環依賴
 clap-memory-warn = 查詢的記憶體需求隨深度指數性增加！
 clap-help = 顯示幫助資訊
 clap-mark-hold = 鎖定軟體套件版本，這會防止指定軟體套件被更新或降級
@@ -36,6 +36,8 @@ pub struct PackageEntry {
     pub provides: Option<String>,
     pub section: Option<String>,
     pub priority: Option<String>,
+    /// Comma separated tags, like debtags
+    pub tag: Option<String>,
     pub homepage: Option<String>,
     #[deb822(field = "Multi-Arch")]
     pub multi_arch: Option<String>,
@@ -191,6 +193,7 @@ Status: deinstall ok config-files
                 provides: None,
                 section: None,
                 priority: None,
+                tag: None,
                 homepage: None,
                 multi_arch: None,
                 filename: None,
@@ -215,6 +218,7 @@ Status: deinstall ok config-files
                 provides: None,
                 section: None,
                 priority: None,
+                tag: None,
                 homepage: None,
                 multi_arch: None,
                 filename: None,
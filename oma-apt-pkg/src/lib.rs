@@ -4,6 +4,7 @@ mod dep;
 mod dpkg;
 mod dpkg_state;
 pub mod error;
+pub mod query;
 pub mod search;
 
 pub use apt_db::*;
@@ -12,4 +13,5 @@ pub use dep::*;
 pub use dpkg::*;
 pub use dpkg_state::*;
 pub use error::*;
+pub use query::*;
 pub use search::*;
@@ -0,0 +1,371 @@
+//! A small query language to filter packages by their fields.
+//!
+//! A query is a list of terms separated by whitespace. Terms like `key:value`
+//! are filters, the other terms are keywords for the relevance search:
+//!
+//! ```text
+//! section:devel maintainer:*aosc* installed-size:>100M arch:arm64 status:upgradable rust
+//! ```
+//!
+//! * `section`, `maintainer`, `tag`, `arch` and `priority` take glob patterns,
+//!   matched case-insensitively.
+//! * `installed-size` takes a size with an optional `>`, `>=`, `<`, `<=` or `=`
+//!   operator, and an optional `K`, `M` or `G` suffix (powers of 1024).
+//! * `status` takes `installed`, `upgradable` or `available`.
+//!
+//! A value may list alternatives separated by commas, e.g. `arch:arm64,all`.
+//! A package must match all filters.
+
+use std::str::FromStr;
+
+use glob_match::glob_match;
+
+use crate::{PackageEntry, search::PackageStatus};
+
+#[derive(Debug, thiserror::Error)]
+pub enum QueryError {
+    #[error("Missing value for {0}")]
+    EmptyValue(String),
+    #[error("Invalid size: {0}")]
+    InvalidSize(String),
+    #[error("Invalid package status: {0}")]
+    InvalidStatus(String),
+}
+
+/// Comparison of `installed-size`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeOp {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+impl SizeOp {
+    fn test(self, size: u64, bound: u64) -> bool {
+        match self {
+            SizeOp::Lt => size < bound,
+            SizeOp::Le => size <= bound,
+            SizeOp::Eq => size == bound,
+            SizeOp::Ge => size >= bound,
+            SizeOp::Gt => size > bound,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusFilter {
+    /// Installed, including the upgradable ones
+    Installed,
+    Upgradable,
+    /// Not installed
+    Available,
+}
+
+impl StatusFilter {
+    fn test(self, status: PackageStatus) -> bool {
+        match self {
+            StatusFilter::Installed => status != PackageStatus::Avail,
+            StatusFilter::Upgradable => status == PackageStatus::Upgrade,
+            StatusFilter::Available => status == PackageStatus::Avail,
+        }
+    }
+}
+
+/// A `key:value` term of a query, matching if any of its alternatives matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    Section(Vec<String>),
+    Maintainer(Vec<String>),
+    Tag(Vec<String>),
+    Arch(Vec<String>),
+    Priority(Vec<String>),
+    /// Installed size in bytes
+    InstalledSize(Vec<(SizeOp, u64)>),
+    Status(Vec<StatusFilter>),
+}
+
+impl Filter {
+    /// Parse a `key:value` term, `None` if it is not a filter
+    fn parse(term: &str) -> Option<Result<Self, QueryError>> {
+        let (key, value) = term.split_once(':')?;
+        let key = key.to_ascii_lowercase();
+
+        let values = value
+            .split(',')
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>();
+
+        let globs = || values.iter().map(|v| v.to_lowercase()).collect();
+
+        let filter = match key.as_str() {
+            "section" => Ok(Filter::Section(globs())),
+            "maintainer" => Ok(Filter::Maintainer(globs())),
+            "tag" => Ok(Filter::Tag(globs())),
+            "arch" | "architecture" => Ok(Filter::Arch(globs())),
+            "priority" => Ok(Filter::Priority(globs())),
+            "installed-size" => values
+                .iter()
+                .map(|v| parse_size(v))
+                .collect::<Result<_, _>>()
+                .map(Filter::InstalledSize),
+            "status" => values
+                .iter()
+                .map(|v| parse_status(v))
+                .collect::<Result<_, _>>()
+                .map(Filter::Status),
+            // 诸如 `foo:amd64` 的包名
+            _ => return None,
+        };
+
+        if values.is_empty() {
+            return Some(Err(QueryError::EmptyValue(key)));
+        }
+
+        Some(filter)
+    }
+
+    pub fn matches(&self, pkg: &PackageFields) -> bool {
+        let glob = |patterns: &[String], value: Option<&str>| {
+            let value = value.unwrap_or_default().to_lowercase();
+            patterns.iter().any(|p| glob_match(p, &value))
+        };
+
+        match self {
+            // 也匹配 `non-free/devel` 等带有分区前缀的栏目
+            Filter::Section(p) => {
+                glob(p, pkg.section)
+                    || glob(p, pkg.section.and_then(|s| s.rsplit_once('/')).map(|s| s.1))
+            }
+            Filter::Maintainer(p) => glob(p, pkg.maintainer),
+            Filter::Tag(p) => pkg
+                .tag
+                .unwrap_or_default()
+                .split(',')
+                .any(|tag| glob(p, Some(tag.trim()))),
+            Filter::Arch(p) => glob(p, pkg.architecture),
+            Filter::Priority(p) => glob(p, pkg.priority),
+            Filter::InstalledSize(bounds) => pkg
+                .installed_size
+                .is_some_and(|size| bounds.iter().any(|(op, bound)| op.test(size, *bound))),
+            Filter::Status(s) => s.iter().any(|s| s.test(pkg.status)),
+        }
+    }
+}
+
+fn parse_size(value: &str) -> Result<(SizeOp, u64), QueryError> {
+    let err = || QueryError::InvalidSize(value.to_string());
+
+    let (op, rest) = [
+        (">=", SizeOp::Ge),
+        ("<=", SizeOp::Le),
+        (">", SizeOp::Gt),
+        ("<", SizeOp::Lt),
+        ("=", SizeOp::Eq),
+    ]
+    .into_iter()
+    .find_map(|(prefix, op)| value.strip_prefix(prefix).map(|rest| (op, rest)))
+    .unwrap_or((SizeOp::Eq, value));
+
+    let rest = rest
+        .trim_end_matches(['B', 'b'])
+        .trim_end_matches(['i', 'I']);
+
+    let (num, unit) = match rest.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&rest[..i], c.to_ascii_uppercase()),
+        _ => (rest, ' '),
+    };
+
+    let multiplier: u64 = match unit {
+        ' ' => 1,
+        'K' => 1 << 10,
+        'M' => 1 << 20,
+        'G' => 1 << 30,
+        _ => return Err(err()),
+    };
+
+    let num = num.parse::<f64>().map_err(|_| err())?;
+
+    if !num.is_finite() || num < 0.0 {
+        return Err(err());
+    }
+
+    Ok((op, (num * multiplier as f64) as u64))
+}
+
+fn parse_status(value: &str) -> Result<StatusFilter, QueryError> {
+    match value.to_ascii_lowercase().as_str() {
+        "installed" => Ok(StatusFilter::Installed),
+        "upgradable" | "upgrade" => Ok(StatusFilter::Upgradable),
+        "available" | "avail" => Ok(StatusFilter::Available),
+        _ => Err(QueryError::InvalidStatus(value.to_string())),
+    }
+}
+
+/// Fields of a package version to evaluate filters against
+#[derive(Debug, Clone, Copy)]
+pub struct PackageFields<'a> {
+    pub section: Option<&'a str>,
+    pub maintainer: Option<&'a str>,
+    /// Comma separated tags, like the `Tag` field
+    pub tag: Option<&'a str>,
+    pub architecture: Option<&'a str>,
+    pub priority: Option<&'a str>,
+    /// Installed size in bytes
+    pub installed_size: Option<u64>,
+    pub status: PackageStatus,
+}
+
+impl PackageEntry {
+    /// Fields of this entry to evaluate filters against
+    pub fn query_fields(&self, status: PackageStatus) -> PackageFields<'_> {
+        PackageFields {
+            section: self.section.as_deref(),
+            maintainer: self.maintainer.as_deref(),
+            tag: self.tag.as_deref(),
+            architecture: self.architecture.as_deref(),
+            priority: self.priority.as_deref(),
+            // Installed-Size 的单位是 KiB
+            installed_size: self.installed_size.map(|s| s * 1024),
+            status,
+        }
+    }
+}
+
+/// A parsed query, see the [module documentation](self)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    /// Terms for the relevance search
+    pub keywords: Vec<String>,
+    pub filters: Vec<Filter>,
+}
+
+impl Query {
+    /// Parse a query from terms, e.g. command line arguments
+    pub fn from_terms<S: AsRef<str>>(
+        terms: impl IntoIterator<Item = S>,
+    ) -> Result<Self, QueryError> {
+        let mut query = Query::default();
+
+        for term in terms {
+            let term = term.as_ref();
+
+            match Filter::parse(term) {
+                Some(filter) => query.filters.push(filter?),
+                None => query.keywords.push(term.to_string()),
+            }
+        }
+
+        Ok(query)
+    }
+
+    pub fn has_filters(&self) -> bool {
+        !self.filters.is_empty()
+    }
+
+    /// Keywords for the relevance search, separated by spaces
+    pub fn text(&self) -> String {
+        self.keywords.join(" ")
+    }
+
+    /// Whether the package matches all filters
+    pub fn matches(&self, pkg: &PackageFields) -> bool {
+        self.filters.iter().all(|f| f.matches(pkg))
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_terms(s.split_whitespace())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(status: PackageStatus) -> PackageFields<'static> {
+        PackageFields {
+            section: Some("devel"),
+            maintainer: Some("AOSC Maintainers <maintainers@aosc.io>"),
+            tag: Some("devel::lang:rust, role::program"),
+            architecture: Some("arm64"),
+            priority: Some("optional"),
+            installed_size: Some(200 * 1024 * 1024),
+            status,
+        }
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = "section:devel maintainer:*aosc* installed-size:>100M rust libc6:amd64"
+            .parse::<Query>()
+            .unwrap();
+
+        assert_eq!(query.keywords, vec!["rust", "libc6:amd64"]);
+        assert_eq!(
+            query.filters,
+            vec![
+                Filter::Section(vec!["devel".into()]),
+                Filter::Maintainer(vec!["*aosc*".into()]),
+                Filter::InstalledSize(vec![(SizeOp::Gt, 100 * 1024 * 1024)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024").unwrap(), (SizeOp::Eq, 1024));
+        assert_eq!(parse_size("<=1.5K").unwrap(), (SizeOp::Le, 1536));
+        assert_eq!(parse_size(">=2GiB").unwrap(), (SizeOp::Ge, 2 << 30));
+        assert_eq!(parse_size("<10mb").unwrap(), (SizeOp::Lt, 10 << 20));
+        assert!(parse_size(">").is_err());
+        assert!(parse_size("10T").is_err());
+        assert!(parse_size("-1").is_err());
+    }
+
+    #[test]
+    fn test_invalid_query() {
+        assert!(matches!(
+            "status:foo".parse::<Query>(),
+            Err(QueryError::InvalidStatus(_))
+        ));
+        assert!(matches!(
+            "section:".parse::<Query>(),
+            Err(QueryError::EmptyValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_matches() {
+        let pkg = fields(PackageStatus::Upgrade);
+        let matches = |s: &str| s.parse::<Query>().unwrap().matches(&pkg);
+
+        assert!(matches("section:devel maintainer:*aosc* arch:arm64,all"));
+        assert!(matches("SECTION:Devel tag:role::* priority:opt*"));
+        assert!(matches("installed-size:>100M status:installed"));
+        assert!(matches("status:upgradable rust"));
+        assert!(!matches("status:available"));
+        assert!(!matches("installed-size:<100M"));
+        assert!(!matches("arch:amd64,all"));
+        assert!(!matches("section:devel tag:implemented-in::*"));
+    }
+
+    #[test]
+    fn test_matches_section_with_component() {
+        let pkg = PackageFields {
+            section: Some("non-free/devel"),
+            ..fields(PackageStatus::Avail)
+        };
+
+        assert!(Query::from_terms(["section:devel"]).unwrap().matches(&pkg));
+        assert!(
+            Query::from_terms(["section:non-free/*"])
+                .unwrap()
+                .matches(&pkg)
+        );
+    }
+}
@@ -18,7 +18,7 @@ use serde::{Deserialize, Serialize};
 use spdlog::debug;
 use wincode::{SchemaRead, SchemaWrite};
 
-use crate::{AptDb, DpkgState, parse_dep_list};
+use crate::{AptDb, DpkgState, PackageEntry, Query, QueryError, parse_dep_list};
 
 type IndexSet<T> = indexmap::IndexSet<T, RandomState>;
 type IndexMap<K, V> = indexmap::IndexMap<K, V, RandomState>;
@@ -111,6 +111,8 @@ pub enum OmaSearchError {
     FailedGetCandidate(String),
     #[error("Null pointer in apt cache")]
     PtrIsNone,
+    #[error("Invalid search query")]
+    InvalidQuery(#[from] QueryError),
 }
 
 pub type OmaSearchResult<T> = Result<T, OmaSearchError>;
@@ -700,6 +702,105 @@ impl OmaSearch for TextSearch<'_> {
     }
 }
 
+/// Search with a [`Query`]: its keywords are searched by another searcher, and
+/// the results are filtered by the fields of the packages.
+pub struct QuerySearch<'a> {
+    apt_db: &'a AptDb,
+    dpkg: &'a DpkgState,
+    inner: &'a dyn OmaSearch,
+}
+
+impl<'a> QuerySearch<'a> {
+    pub fn new(apt_db: &'a AptDb, dpkg: &'a DpkgState, inner: &'a dyn OmaSearch) -> Self {
+        Self {
+            apt_db,
+            dpkg,
+            inner,
+        }
+    }
+
+    pub fn search_query(&self, query: &Query) -> OmaSearchResult<Vec<SearchResult>> {
+        if !query.has_filters() {
+            return self.inner.search(&query.text());
+        }
+
+        // 同名的包可能有多个版本或架构，任意一个匹配即可
+        let mut matched: IndexMap<&str, &PackageEntry> = IndexMap::with_hasher(RandomState::new());
+
+        for entry in &self.apt_db.entries {
+            let name = entry.package.as_str();
+
+            if name.ends_with("-dbg")
+                || !query.matches(&entry.query_fields(package_status(self.dpkg, entry)))
+            {
+                continue;
+            }
+
+            let newer = matched.get(name).is_none_or(|existing| {
+                is_upgradable(entry.version.as_ref(), existing.version.as_ref())
+            });
+
+            if newer {
+                matched.insert(name, entry);
+            }
+        }
+
+        if !query.keywords.is_empty() {
+            let mut results = self.inner.search(&query.text())?;
+            results.retain(|r| matched.contains_key(r.name.as_str()));
+
+            return Ok(results);
+        }
+
+        let mut results = matched
+            .into_values()
+            .map(|entry| {
+                let name = &entry.package;
+                let status = package_status(self.dpkg, entry);
+                let (old_version, new_version) =
+                    extract_versions(status, &self.dpkg.installed_versions, name, &entry.version);
+
+                SearchResult {
+                    name: name.clone(),
+                    desc: entry
+                        .description
+                        .as_deref()
+                        .map(|d| d.lines().next().unwrap_or(d).to_string())
+                        .unwrap_or_else(|| "No description".to_string()),
+                    old_version,
+                    new_version,
+                    full_match: false,
+                    dbg_package: self.apt_db.has_package(&format!("{name}-dbg")),
+                    status,
+                    is_base: entry.section.as_deref().is_some_and(|s| s == "Bases"),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        sort_and_promote(&mut results);
+
+        Ok(results)
+    }
+}
+
+impl OmaSearch for QuerySearch<'_> {
+    fn search(&self, query: &str) -> OmaSearchResult<Vec<SearchResult>> {
+        self.search_query(&query.parse()?)
+    }
+}
+
+fn package_status(dpkg: &DpkgState, entry: &PackageEntry) -> PackageStatus {
+    let name = entry.package.as_str();
+
+    if !dpkg.is_installed(name) {
+        PackageStatus::Avail
+    } else if is_upgradable(entry.version.as_ref(), dpkg.installed_versions.get(name)) {
+        PackageStatus::Upgrade
+    } else {
+        PackageStatus::Installed
+    }
+}
+
 /// Sort results by status (Upgrade > Installed > Avail)
 /// and make full-match entries to the front.
 fn sort_and_promote(results: &mut [SearchResult]) {
@@ -818,6 +919,7 @@ mod tests {
                 provides: None,
                 section: None,
                 priority: None,
+                tag: None,
                 homepage: None,
                 multi_arch: None,
                 filename: None,
@@ -842,6 +944,7 @@ mod tests {
                 provides: None,
                 section: None,
                 priority: None,
+                tag: None,
                 homepage: None,
                 multi_arch: None,
                 filename: None,
@@ -853,4 +956,72 @@ mod tests {
         assert!(db.has_package("foo"));
         assert!(db.has_package("foo-dbg"));
     }
+
+    #[test]
+    fn test_query_search() {
+        use crate::apt_lists::PackageEntry;
+
+        let entry = |package: &str, version: &str, section: &str| PackageEntry {
+            package: package.into(),
+            version: Some(version.into()),
+            architecture: Some("amd64".into()),
+            description: Some(format!("{package} package")),
+            description_md5: None,
+            maintainer: None,
+            installed_size: None,
+            depends: None,
+            pre_depends: None,
+            recommends: None,
+            suggests: None,
+            breaks: None,
+            conflicts: None,
+            replaces: None,
+            provides: None,
+            section: Some(section.into()),
+            priority: None,
+            tag: None,
+            homepage: None,
+            multi_arch: None,
+            filename: None,
+            size: None,
+            sha256: None,
+        };
+
+        let db = AptDb::from_entries(vec![
+            entry("rustc", "1.81", "devel"),
+            entry("rust-analyzer", "1.0", "devel"),
+            entry("rusty", "1.0", "games"),
+        ]);
+
+        let dpkg = DpkgState {
+            installed: HashSet::from(["rustc".to_string()]),
+            installed_versions: HashMap::from([("rustc".to_string(), "1.80".to_string())]),
+        };
+
+        let text = TextSearch::new(&db, &dpkg);
+        let searcher = QuerySearch::new(&db, &dpkg, &text);
+
+        let names = |query: &str| {
+            searcher
+                .search(query)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names("rust section:devel"), vec!["rustc", "rust-analyzer"]);
+        assert_eq!(names("section:games"), vec!["rusty"]);
+        assert_eq!(names("status:upgradable"), vec!["rustc"]);
+
+        let res = searcher.search("section:devel status:installed").unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].old_version.as_deref(), Some("1.80"));
+        assert_eq!(res[0].new_version, "1.81");
+
+        assert!(matches!(
+            searcher.search("installed-size:big"),
+            Err(OmaSearchError::InvalidQuery(_))
+        ));
+    }
 }
@@ -1,12 +1,15 @@
 use crate::{
     CompressType, DownloadSource, Event, SingleDownloadErrorHelper, checksum::ChecksumValidator,
-    send_request,
+    local_path, metalink, send_request, throttle::Throttle,
 };
 use std::{
     io::{self, SeekFrom},
     path::Path,
     pin::Pin,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
     task::{Context, Poll},
     time::Duration,
 };
@@ -17,9 +20,13 @@ use async_compression::futures::bufread::{
 use bon::bon;
 use futures::{AsyncBufRead, AsyncRead, TryStreamExt, io::BufReader};
 use headers::{ContentLength, ContentRange, HeaderMapExt};
-use reqwest::{Method, StatusCode, header::RANGE};
+use reqwest::{
+    Method, StatusCode,
+    header::{IF_RANGE, RANGE},
+};
 use reqwest_middleware::ClientWithMiddleware;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
 use snafu::{ResultExt, Snafu};
 use tokio::{
     fs::{self, File},
@@ -32,6 +39,12 @@ use tokio_util::compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
 
 use crate::{DownloadEntry, DownloadSourceType};
 
+mod resume;
+mod segment;
+
+use resume::{CHECKPOINT_INTERVAL, ResumeState, state_path};
+use segment::MIRROR_BLACKLIST;
+
 const READ_FILE_BUFSIZE: usize = 65536;
 const DOWNLOAD_BUFSIZE: usize = 8192;
 
@@ -45,11 +58,14 @@ pub enum BuilderError {
 
 pub(crate) struct SingleDownloader {
     client: ClientWithMiddleware,
+    redirector_client: Option<ClientWithMiddleware>,
     pub entry: DownloadEntry,
     total: usize,
     retry_times: usize,
     download_list_index: usize,
     timeout: Duration,
+    throttle: Arc<Throttle>,
+    segment_threshold: u64,
 }
 
 pub enum DownloadResult {
@@ -97,6 +113,8 @@ pub enum SingleDownloadError {
     ChecksumMismatch,
     #[snafu(display("semaphore acquire error"))]
     AcquireError,
+    #[snafu(display("Server did not return the requested range"))]
+    UnexpectedRange,
 }
 
 impl Serialize for SingleDownloadError {
@@ -144,6 +162,7 @@ impl Serialize for SingleDownloadError {
             Self::DownloadTimeout => SingleDownloadErrorHelper::DownloadTimeout,
             Self::ChecksumMismatch => SingleDownloadErrorHelper::ChecksumMismatch,
             Self::AcquireError => SingleDownloadErrorHelper::AcquireError,
+            Self::UnexpectedRange => SingleDownloadErrorHelper::UnexpectedRange,
         };
 
         helper.serialize(serializer)
@@ -201,6 +220,7 @@ impl<'de> Deserialize<'de> for SingleDownloadError {
             SingleDownloadErrorHelper::DownloadTimeout => Self::DownloadTimeout,
             SingleDownloadErrorHelper::ChecksumMismatch => Self::ChecksumMismatch,
             SingleDownloadErrorHelper::AcquireError => Self::AcquireError,
+            SingleDownloadErrorHelper::UnexpectedRange => Self::UnexpectedRange,
         };
 
         Ok(error)
@@ -212,11 +232,14 @@ impl SingleDownloader {
     #[builder]
     pub(crate) fn new(
         client: ClientWithMiddleware,
+        redirector_client: Option<ClientWithMiddleware>,
         entry: DownloadEntry,
         total: usize,
         retry_times: usize,
         download_list_index: usize,
         timeout: Duration,
+        throttle: Arc<Throttle>,
+        segment_threshold: u64,
     ) -> Result<SingleDownloader, BuilderError> {
         if entry.source.is_empty() {
             return Err(BuilderError::EmptySource {
@@ -226,15 +249,18 @@ impl SingleDownloader {
 
         Ok(Self {
             client,
+            redirector_client,
             entry,
             total,
             retry_times,
             download_list_index,
             timeout,
+            throttle,
+            segment_threshold,
         })
     }
 
-    pub(crate) async fn try_download<F, Fut>(self, callback: &F) -> DownloadResult
+    pub(crate) async fn try_download<F, Fut>(mut self, callback: &F) -> DownloadResult
     where
         F: Fn(Event) -> Fut + Send + Sync + 'static,
         Fut: std::future::Future<Output = ()> + Send + 'static,
@@ -282,71 +308,65 @@ impl SingleDownloader {
             }
         }
 
+        self.resolve_redirectors().await;
+
         let mut sources = self.entry.source.clone();
-        assert!(!sources.is_empty());
 
-        sources.sort_unstable_by(|a, b| b.source_type.cmp(&a.source_type));
+        if sources.is_empty() {
+            debug!("{} has no download source", self.entry.filename);
+            return DownloadResult::Failed {
+                file_name: self.entry.filename.to_string(),
+            };
+        }
+
+        // 同类下载源（如镜像列表展开后的各个镜像）需保持原有顺序
+        sources.sort_by(|a, b| b.source_type.cmp(&a.source_type));
+
+        // 不再使用本次运行中提供过错误数据的镜像源，除非已没有其他下载源
+        if sources.iter().any(|s| !MIRROR_BLACKLIST.contains(&s.url)) {
+            sources.retain(|s| !MIRROR_BLACKLIST.contains(&s.url));
+        }
+
+        if let Some(mirrors) = self.segment_mirrors(&sources) {
+            match self.segmented_download(&mirrors, callback).await {
+                Ok(()) => return self.download_done(&mirrors[0].url, true, callback).await,
+                Err(e) => {
+                    debug!(
+                        "Segmented download of {} failed, falling back: {e}",
+                        self.entry.filename
+                    );
+                    tokio::fs::remove_file(self.entry.dir.join(&*self.entry.filename))
+                        .await
+                        .ok();
+                }
+            }
+        }
 
         for (index, c) in sources.iter().enumerate() {
             let download_res = match &c.source_type {
                 DownloadSourceType::Http => self.try_http_download(c, callback).await,
+                // 局域网内的其他机器不一定缓存了该软件包，因此只尝试一次
+                DownloadSourceType::Peer => {
+                    self.http_download(self.entry.allow_resume, c, callback)
+                        .await
+                }
                 DownloadSourceType::Local(as_symlink) => {
                     self.download_local(c, *as_symlink, callback).await
                 }
+                DownloadSourceType::Metalink => {
+                    unreachable!("mirror redirectors are resolved before downloading")
+                }
             };
 
             match download_res {
-                Ok(wrote) => {
-                    if let Some(ref final_dir) = self.entry.final_dir {
-                        let current_path = self.entry.dir.join(&*self.entry.filename);
-                        let target_path = final_dir.join(&*self.entry.filename);
-
-                        if !final_dir.is_dir()
-                            && let Err(e) = tokio::fs::create_dir_all(final_dir).await
-                        {
-                            callback(Event::Failed {
-                                file_name: final_dir.to_string_lossy().to_string(),
-                                error: SingleDownloadError::Create { source: e },
-                            })
-                            .await;
-                            return DownloadResult::Failed {
-                                file_name: self.entry.filename.to_string(),
-                            };
-                        }
-
-                        if current_path.is_file() {
-                            trace!(
-                                "Moving completed file from {} to {}",
-                                current_path.display(),
-                                target_path.display()
-                            );
-                            if let Err(e) = tokio::fs::rename(&current_path, &target_path).await {
-                                callback(Event::Failed {
-                                    file_name: self.entry.filename.clone(),
-                                    error: SingleDownloadError::Write { source: e },
-                                })
-                                .await;
-                                return DownloadResult::Failed {
-                                    file_name: self.entry.filename.to_string(),
-                                };
-                            }
-                        }
+                Ok(wrote) => return self.download_done(&c.url, wrote, callback).await,
+                Err(e) => {
+                    if c.source_type == DownloadSourceType::Http
+                        && matches!(e, SingleDownloadError::ChecksumMismatch)
+                    {
+                        MIRROR_BLACKLIST.strike(&c.url);
                     }
 
-                    callback(Event::DownloadDone {
-                        index: self.download_list_index,
-                        msg: msg.into(),
-                    })
-                    .await;
-
-                    return DownloadResult::Success(SuccessSummary {
-                        file_name: self.entry.filename.to_string(),
-                        url: c.url.clone(),
-                        index: self.download_list_index,
-                        wrote,
-                    });
-                }
-                Err(e) => {
                     if index == sources.len() - 1 {
                         callback(Event::Failed {
                             file_name: self.entry.filename.clone(),
@@ -357,6 +377,12 @@ impl SingleDownloader {
                             file_name: self.entry.filename.to_string(),
                         };
                     }
+
+                    if c.source_type == DownloadSourceType::Peer {
+                        debug!("Failed to download {} from peer: {e}", c.url);
+                        continue;
+                    }
+
                     callback(Event::NextUrl {
                         index: self.download_list_index,
                         file_name: self.entry.filename.to_string(),
@@ -370,6 +396,116 @@ impl SingleDownloader {
         unreachable!()
     }
 
+    /// Replace mirror redirectors with the mirrors they list
+    ///
+    /// The checksum and size told by the redirector are used if the entry has none.
+    async fn resolve_redirectors(&mut self) {
+        if !self
+            .entry
+            .source
+            .iter()
+            .any(|s| s.source_type == DownloadSourceType::Metalink)
+        {
+            return;
+        }
+
+        let mut sources = vec![];
+
+        for source in std::mem::take(&mut self.entry.source) {
+            if source.source_type != DownloadSourceType::Metalink {
+                sources.push(source);
+                continue;
+            }
+
+            let client = self.redirector_client.as_ref().unwrap_or(&self.client);
+
+            match metalink::resolve(client, &source.url).await {
+                Ok(resolved) if !resolved.sources.is_empty() => {
+                    if self.entry.hash.is_none() {
+                        self.entry.hash = resolved.hash;
+                    }
+
+                    if self.entry.size.is_none() {
+                        self.entry.size = resolved.size;
+                    }
+
+                    sources.extend(resolved.sources);
+                }
+                res => {
+                    if let Err(e) = res {
+                        debug!("Failed to resolve mirror redirector {}: {e}", source.url);
+                    }
+
+                    // 直接从重定向服务下载，由其选择镜像源
+                    sources.push(DownloadSource {
+                        url: metalink::redirector_url(&source.url).to_string(),
+                        source_type: DownloadSourceType::Http,
+                    });
+                }
+            }
+        }
+
+        self.entry.source = sources;
+    }
+
+    /// Move the downloaded file to its final directory and report the result
+    async fn download_done<F, Fut>(&self, url: &str, wrote: bool, callback: &F) -> DownloadResult
+    where
+        F: Fn(Event) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        ResumeState::remove(&state_path(&self.entry.dir.join(&*self.entry.filename))).await;
+
+        if let Some(ref final_dir) = self.entry.final_dir {
+            let current_path = self.entry.dir.join(&*self.entry.filename);
+            let target_path = final_dir.join(&*self.entry.filename);
+
+            if !final_dir.is_dir()
+                && let Err(e) = tokio::fs::create_dir_all(final_dir).await
+            {
+                callback(Event::Failed {
+                    file_name: final_dir.to_string_lossy().to_string(),
+                    error: SingleDownloadError::Create { source: e },
+                })
+                .await;
+                return DownloadResult::Failed {
+                    file_name: self.entry.filename.to_string(),
+                };
+            }
+
+            if current_path.is_file() {
+                trace!(
+                    "Moving completed file from {} to {}",
+                    current_path.display(),
+                    target_path.display()
+                );
+                if let Err(e) = tokio::fs::rename(&current_path, &target_path).await {
+                    callback(Event::Failed {
+                        file_name: self.entry.filename.clone(),
+                        error: SingleDownloadError::Write { source: e },
+                    })
+                    .await;
+                    return DownloadResult::Failed {
+                        file_name: self.entry.filename.to_string(),
+                    };
+                }
+            }
+        }
+
+        callback(Event::DownloadDone {
+            index: self.download_list_index,
+            msg: self.download_message().into(),
+        })
+        .await;
+
+        DownloadResult::Success(SuccessSummary {
+            file_name: self.entry.filename.to_string(),
+            url: url.to_string(),
+            index: self.download_list_index,
+            wrote,
+        })
+    }
+
     /// Download file with retry (http)
     async fn try_http_download<F, Fut>(
         &self,
@@ -438,7 +574,11 @@ impl SingleDownloader {
         F: Fn(Event) -> Fut + Send + Sync + 'static,
         Fut: std::future::Future<Output = ()> + Send + 'static,
     {
+        // 按实际请求的主机限制并发连接数
+        let _connection = self.throttle.connect(&source.url).await;
+
         let file = self.entry.dir.join(&*self.entry.filename);
+        let state_file = state_path(&file);
         let file_exist = file.exists();
         let file_size = file.metadata().ok().map(|x| x.len()).unwrap_or(0);
 
@@ -463,12 +603,15 @@ impl SingleDownloader {
             .map(|hash| hash.get_validator())
             .unwrap_or(ChecksumValidator::None);
 
+        // 上次运行中断时保存的续传状态，以及已写入部分的 SHA256 计算状态
+        let mut resume = ResumeState::new(&source.url);
+        let mut prefix_hasher = Sha256::new();
+
         if file_exist && !is_symlink {
             trace!(
                 "File {} already exists, verifying checksum ...",
                 self.entry.filename
             );
-            downloaded_size = file_size;
 
             if let Some(hash) = &self.entry.hash {
                 trace!("Hash {} exists for the existing file.", hash);
@@ -483,6 +626,7 @@ impl SingleDownloader {
 
                 if finish {
                     trace!("checksum of {} matches, cache hit!", self.entry.filename);
+                    ResumeState::remove(&state_file).await;
                     callback(Event::ProgressDone(self.download_list_index)).await;
                     return Ok(false);
                 }
@@ -494,9 +638,8 @@ impl SingleDownloader {
                 old_downloaded_size = read;
             }
 
-            if self.entry.file_type != CompressType::None {
-                downloaded_size = 0;
-            }
+            (downloaded_size, resume, prefix_hasher) =
+                self.resume_point(&file, source, allow_resume).await;
         }
 
         callback(Event::NewProgressSpinner {
@@ -529,6 +672,10 @@ impl SingleDownloader {
         };
         let mut total_size: Option<u64> = None;
         let mut first_request = true;
+        let mut throttled = false;
+        let mut last_checkpoint = downloaded_size;
+        // 压缩文件写入的是解压后的内容，无法与 HTTP 范围请求对应，不保存续传状态
+        let resumable = allow_resume && self.entry.file_type == CompressType::None;
         'download: while if let Some(total_size) = total_size {
             downloaded_size < total_size
         } else {
@@ -549,6 +696,11 @@ impl SingleDownloader {
                 // assume reqwest's automatic decompression is disabled
                 debug!("sending partial request ...");
                 req = req.header(RANGE, format!("bytes={downloaded_size}-"));
+
+                // 服务器上的文件发生变化时会返回完整内容，从头开始下载
+                if let Some(validator) = resume.if_range() {
+                    req = req.header(IF_RANGE, validator);
+                }
             } else {
                 debug!("sending complete request ...");
             }
@@ -638,6 +790,15 @@ impl SingleDownloader {
             debug!("response body is at {downloaded_size}/{total_size:?}");
             let is_complete = resp.status() == StatusCode::OK;
 
+            if downloaded_size == 0 {
+                // 从头下载时原有的续传状态已失效
+                ResumeState::remove(&state_file).await;
+                resume.update_validators(resp_headers);
+                prefix_hasher = Sha256::new();
+                last_checkpoint = 0;
+            }
+            resume.total_size = total_size;
+
             // seek to expected location
             if downloaded_size != old_downloaded_size {
                 assert!(downloaded_size == 0 || self.entry.file_type == CompressType::None);
@@ -742,10 +903,20 @@ impl SingleDownloader {
                 let buf_size = match timeout(self.timeout, reader.read(&mut buf[..])).await {
                     Ok(Ok(size)) => size,
                     Ok(Err(e)) => {
+                        if resumable {
+                            resume
+                                .checkpoint(&state_file, &mut dest, downloaded_size, &prefix_hasher)
+                                .await;
+                        }
                         callback(Event::ProgressDone(self.download_list_index)).await;
                         return Err(SingleDownloadError::BrokenPipe { source: e });
                     }
                     Err(_) => {
+                        if resumable {
+                            resume
+                                .checkpoint(&state_file, &mut dest, downloaded_size, &prefix_hasher)
+                                .await;
+                        }
                         callback(Event::ProgressDone(self.download_list_index)).await;
                         return Err(SingleDownloadError::DownloadTimeout);
                     }
@@ -762,12 +933,33 @@ impl SingleDownloader {
                 let http_size = stream_counter.swap(0, Ordering::AcqRel);
                 let http_size: u64 = http_size.try_into().unwrap();
                 downloaded_size += http_size;
+
+                if resumable {
+                    prefix_hasher.update(&buf[..buf_size]);
+
+                    if downloaded_size - last_checkpoint >= CHECKPOINT_INTERVAL {
+                        last_checkpoint = downloaded_size;
+                        resume
+                            .checkpoint(&state_file, &mut dest, downloaded_size, &prefix_hasher)
+                            .await;
+                    }
+                }
                 callback(Event::ProgressInc {
                     index: self.download_list_index,
                     size: http_size,
                 })
                 .await;
                 callback(Event::GlobalProgressAdd(http_size)).await;
+
+                let is_throttled = self.throttle.consume(&source.url, http_size).await;
+                if is_throttled != throttled {
+                    throttled = is_throttled;
+                    callback(Event::Throttled {
+                        index: self.download_list_index,
+                        throttled,
+                    })
+                    .await;
+                }
             }
 
             debug!("downloaded {} bytes", downloaded_size - old_downloaded_size);
@@ -793,6 +985,7 @@ impl SingleDownloader {
             callback(Event::ProgressDone(self.download_list_index)).await;
 
             // truncate file, avoid attempts to reuse it in retries
+            ResumeState::remove(&state_file).await;
             if let Err(e) = dest.set_len(0).await {
                 callback(Event::ProgressDone(self.download_list_index)).await;
                 return Err(SingleDownloadError::Write { source: e });
@@ -830,7 +1023,12 @@ impl SingleDownloader {
     {
         debug!("{:?}", self.entry);
 
-        let url = source.url.strip_prefix("file:").unwrap();
+        let url = local_path(&source.url).ok_or_else(|| SingleDownloadError::Open {
+            source: io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unsupported local URL: {}", source.url),
+            ),
+        })?;
 
         let url_path = Path::new(url);
 
@@ -0,0 +1,308 @@
+//! Sidecar files recording how to safely resume partial downloads in later runs.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use reqwest::header::{ETAG, HeaderMap, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use spdlog::debug;
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+use super::{READ_FILE_BUFSIZE, SingleDownloader};
+use crate::{CompressType, DownloadSource};
+
+const STATE_SUFFIX: &str = ".oma-resume";
+
+/// Save the state after writing this many bytes
+pub(super) const CHECKPOINT_INTERVAL: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct ResumeState {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub total_size: Option<u64>,
+    /// Length of the partial file known to be written
+    pub downloaded: u64,
+    /// SHA256 of the first `downloaded` bytes of the partial file
+    pub sha256: String,
+}
+
+pub(super) fn state_path(file: &Path) -> PathBuf {
+    let mut path = file.as_os_str().to_owned();
+    path.push(STATE_SUFFIX);
+
+    PathBuf::from(path)
+}
+
+impl ResumeState {
+    pub(super) fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn load(path: &Path) -> Option<Self> {
+        let s = fs::read(path).await.ok()?;
+
+        serde_json::from_slice(&s)
+            .inspect_err(|e| debug!("Invalid resume state {}: {e}", path.display()))
+            .ok()
+    }
+
+    async fn save(&self, path: &Path) -> io::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        fs::write(&tmp, serde_json::to_vec(self)?).await?;
+        fs::rename(&tmp, path).await
+    }
+
+    pub(super) async fn remove(path: &Path) {
+        if let Err(e) = fs::remove_file(path).await
+            && e.kind() != io::ErrorKind::NotFound
+        {
+            debug!("Failed to remove {}: {e}", path.display());
+        }
+    }
+
+    /// Validator to send with `If-Range`, weak ETags can not be used for range requests
+    pub(super) fn if_range(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+
+    pub(super) fn update_validators(&mut self, headers: &HeaderMap) {
+        let get = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+
+        self.etag = get(ETAG);
+        self.last_modified = get(LAST_MODIFIED);
+    }
+
+    /// Record that the first `downloaded` bytes of `dest`, hashed by `hasher`, are on disk
+    pub(super) async fn checkpoint(
+        &mut self,
+        path: &Path,
+        dest: &mut File,
+        downloaded: u64,
+        hasher: &Sha256,
+    ) {
+        // 无法确认服务器上的文件未发生变化时不记录
+        if self.if_range().is_none() {
+            return;
+        }
+
+        if let Err(e) = async {
+            dest.flush().await?;
+            dest.sync_data().await
+        }
+        .await
+        {
+            debug!("Failed to sync partial file: {e}");
+            return;
+        }
+
+        self.downloaded = downloaded;
+        self.sha256 = faster_hex::hex_string(&hasher.clone().finalize());
+
+        if let Err(e) = self.save(path).await {
+            debug!("Failed to save resume state {}: {e}", path.display());
+        }
+    }
+
+    /// Hash the first `downloaded` bytes of `file`, `None` if they do not match the state
+    async fn verify(&self, file: &Path) -> Option<Sha256> {
+        let (read, hasher) = hash_prefix(file, self.downloaded).await?;
+        let sha256 = faster_hex::hex_string(&hasher.clone().finalize());
+
+        (read == self.downloaded && sha256 == self.sha256).then_some(hasher)
+    }
+
+    /// Drop the data of `file` written after the state was saved
+    async fn truncate(&self, file: &Path) -> io::Result<()> {
+        fs::OpenOptions::new()
+            .write(true)
+            .open(file)
+            .await?
+            .set_len(self.downloaded)
+            .await
+    }
+}
+
+/// Hash at most the first `len` bytes of `file`, returning the number of bytes read
+async fn hash_prefix(file: &Path, len: u64) -> Option<(u64, Sha256)> {
+    let f = File::open(file).await.ok()?;
+    let mut reader = f.take(len);
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; READ_FILE_BUFSIZE];
+    let mut read = 0;
+
+    loop {
+        let n = reader.read(&mut buf).await.ok()?;
+
+        if n == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..n]);
+        read += n as u64;
+    }
+
+    Some((read, hasher))
+}
+
+impl SingleDownloader {
+    /// Find where the partial `file` can be safely resumed from
+    ///
+    /// The partial file is truncated to the returned offset, along with the
+    /// state to update and the hash state of the bytes before the offset.
+    pub(super) async fn resume_point(
+        &self,
+        file: &Path,
+        source: &DownloadSource,
+        allow_resume: bool,
+    ) -> (u64, ResumeState, Sha256) {
+        let fresh = || (0, ResumeState::new(&source.url), Sha256::new());
+
+        if !allow_resume || self.entry.file_type != CompressType::None {
+            return fresh();
+        }
+
+        let path = state_path(file);
+
+        let Some(state) = ResumeState::load(&path).await else {
+            // 没有续传状态时（如旧版本留下的文件）按已有文件的长度续传
+            debug!(
+                "No resume state for {}, resuming from its length",
+                file.display()
+            );
+
+            return match hash_prefix(file, u64::MAX).await {
+                Some((len, hasher)) => (len, ResumeState::new(&source.url), hasher),
+                None => fresh(),
+            };
+        };
+
+        // 换用其他镜像源时 ETag 等信息不再适用
+        if state.url != source.url || state.if_range().is_none() {
+            debug!("Resume state of {} is not usable", file.display());
+            ResumeState::remove(&path).await;
+            return fresh();
+        }
+
+        let Some(hasher) = state.verify(file).await else {
+            debug!(
+                "Partial file {} does not match its resume state",
+                file.display()
+            );
+            ResumeState::remove(&path).await;
+            return fresh();
+        };
+
+        // 最后一次保存状态后写入的数据可能不完整
+        if let Err(e) = state.truncate(file).await {
+            debug!("Failed to truncate {}: {e}", file.display());
+            ResumeState::remove(&path).await;
+            return fresh();
+        }
+
+        (state.downloaded, state, hasher)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("oma-fetch-resume-{}-{name}", std::process::id()))
+    }
+
+    fn sha256_hex(data: &[u8]) -> String {
+        faster_hex::hex_string(&Sha256::digest(data))
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let file = temp_path("load");
+        let path = state_path(&file);
+
+        assert!(path.to_string_lossy().ends_with(".oma-resume"));
+        assert!(ResumeState::load(&path).await.is_none());
+
+        let mut state = ResumeState::new("https://example.com/a.deb");
+        state.etag = Some("\"abc\"".to_string());
+        state.downloaded = 3;
+        state.sha256 = sha256_hex(b"foo");
+        state.save(&path).await.unwrap();
+
+        let loaded = ResumeState::load(&path).await.unwrap();
+        assert_eq!(loaded.url, state.url);
+        assert_eq!(loaded.etag, state.etag);
+        assert_eq!(loaded.downloaded, 3);
+        assert_eq!(loaded.sha256, state.sha256);
+
+        fs::write(&path, b"not json").await.unwrap();
+        assert!(ResumeState::load(&path).await.is_none());
+
+        ResumeState::remove(&path).await;
+        assert!(!path.exists());
+        // 不存在的文件也可以移除
+        ResumeState::remove(&path).await;
+    }
+
+    #[tokio::test]
+    async fn test_verify_and_truncate() {
+        let file = temp_path("verify");
+        fs::write(&file, b"foobar").await.unwrap();
+
+        let mut state = ResumeState::new("https://example.com/a.deb");
+        state.downloaded = 3;
+        state.sha256 = sha256_hex(b"foo");
+
+        let hasher = state.verify(&file).await.unwrap();
+        assert_eq!(faster_hex::hex_string(&hasher.finalize()), state.sha256);
+
+        state.truncate(&file).await.unwrap();
+        assert_eq!(fs::read(&file).await.unwrap(), b"foo");
+
+        // 内容不一致
+        state.sha256 = sha256_hex(b"bar");
+        assert!(state.verify(&file).await.is_none());
+
+        // 文件比记录的长度短
+        state.downloaded = 6;
+        state.sha256 = sha256_hex(b"foobar");
+        assert!(state.verify(&file).await.is_none());
+
+        fs::remove_file(&file).await.unwrap();
+        assert!(state.verify(&file).await.is_none());
+    }
+
+    #[test]
+    fn test_if_range() {
+        let mut state = ResumeState::new("https://example.com/a.deb");
+        assert_eq!(state.if_range(), None);
+
+        state.last_modified = Some("Sat, 17 Oct 2026 00:00:00 GMT".to_string());
+        state.etag = Some("W/\"abc\"".to_string());
+        assert_eq!(state.if_range(), state.last_modified.as_deref());
+
+        state.etag = Some("\"abc\"".to_string());
+        assert_eq!(state.if_range(), Some("\"abc\""));
+    }
+}
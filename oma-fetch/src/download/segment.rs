@@ -0,0 +1,584 @@
+//! Segmented downloads of large files from multiple mirrors.
+
+use std::{
+    io::{self, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use ahash::AHashMap;
+use futures::{StreamExt, future::join_all};
+use headers::{ContentRange, HeaderMapExt};
+use reqwest::{Method, StatusCode, Url, header::RANGE};
+use sha2::{Digest, Sha256};
+use snafu::ResultExt;
+use spdlog::{debug, warn};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    time::timeout,
+};
+
+use super::{
+    CreateSnafu, FlushSnafu, OpenSnafu, READ_FILE_BUFSIZE, SeekSnafu, SingleDownloadError,
+    SingleDownloader, WriteSnafu,
+};
+use crate::{CompressType, DownloadSource, DownloadSourceType, Event, send_request};
+
+/// Files are split among at most this many mirrors
+const MAX_SEGMENTS: usize = 4;
+
+/// A mirror is blacklisted after this many downloads from it failed the checksum
+const MAX_STRIKES: u32 = 2;
+
+/// Mirrors which served data not matching the checksum, not used again in this process
+pub(crate) static MIRROR_BLACKLIST: LazyLock<Blacklist> = LazyLock::new(Blacklist::default);
+
+#[derive(Debug, Default)]
+pub(crate) struct Blacklist(Mutex<AHashMap<String, u32>>);
+
+impl Blacklist {
+    pub(crate) fn contains(&self, url: &str) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .get(&mirror_key(url))
+            .is_some_and(|strikes| *strikes >= MAX_STRIKES)
+    }
+
+    /// Blacklist a mirror proven to serve corrupted data
+    pub(crate) fn insert(&self, url: &str) {
+        let key = mirror_key(url);
+        warn!("Mirror {key} served corrupted data, it will not be used again");
+        self.0.lock().unwrap().insert(key, MAX_STRIKES);
+    }
+
+    /// Record a checksum failure of a download from a mirror
+    ///
+    /// The data may also be corrupted on the way or by the repository itself, so
+    /// the mirror is only blacklisted after repeated failures.
+    pub(crate) fn strike(&self, url: &str) {
+        let key = mirror_key(url);
+        let mut map = self.0.lock().unwrap();
+        let strikes = map.entry(key.clone()).or_default();
+        *strikes += 1;
+
+        if *strikes == MAX_STRIKES {
+            warn!("Mirror {key} served corrupted data repeatedly, it will not be used again");
+        } else {
+            debug!("Mirror {key} served data not matching the checksum");
+        }
+    }
+}
+
+fn mirror_key(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(url) => format!(
+            "{}://{}:{}",
+            url.scheme(),
+            url.host_str().unwrap_or_default(),
+            url.port_or_known_default().unwrap_or_default()
+        ),
+        Err(_) => url.to_string(),
+    }
+}
+
+/// The hash served by at least two mirrors, in the order of `votes`
+fn consensus(votes: &[(usize, Vec<u8>)]) -> Option<&[u8]> {
+    votes
+        .iter()
+        .find(|(_, hash)| votes.iter().filter(|(_, h)| h == hash).count() >= 2)
+        .map(|(_, hash)| hash.as_slice())
+}
+
+/// Split `size` bytes into `count` ranges of nearly equal length
+fn split(size: u64, count: usize) -> Vec<Range<u64>> {
+    let count = count as u64;
+    let len = size.div_ceil(count);
+
+    (0..count)
+        .map(|i| (i * len).min(size)..((i + 1) * len).min(size))
+        .filter(|range| !range.is_empty())
+        .collect()
+}
+
+impl SingleDownloader {
+    /// Mirrors to download this entry from in segments, `None` if the entry is not eligible
+    pub(super) fn segment_mirrors<'a>(
+        &self,
+        sources: &'a [DownloadSource],
+    ) -> Option<Vec<&'a DownloadSource>> {
+        let size = self.entry.size?;
+
+        if self.segment_threshold == 0
+            || size < self.segment_threshold
+            || self.entry.hash.is_none()
+            || self.entry.file_type != CompressType::None
+        {
+            return None;
+        }
+
+        // 已有下载了一部分的文件时交给断点续传处理
+        if self.entry.dir.join(&*self.entry.filename).exists() {
+            return None;
+        }
+
+        let mirrors = sources
+            .iter()
+            .filter(|s| s.source_type == DownloadSourceType::Http)
+            .take(MAX_SEGMENTS)
+            .collect::<Vec<_>>();
+
+        (mirrors.len() > 1).then_some(mirrors)
+    }
+
+    /// Download the entry in segments concurrently from `mirrors` and verify the result
+    pub(super) async fn segmented_download<F, Fut>(
+        &self,
+        mirrors: &[&DownloadSource],
+        callback: &F,
+    ) -> Result<(), SingleDownloadError>
+    where
+        F: Fn(Event) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let size = self.entry.size.unwrap_or(0);
+        let file = self.entry.dir.join(&*self.entry.filename);
+
+        File::create(&file)
+            .await
+            .context(CreateSnafu)?
+            .set_len(size)
+            .await
+            .context(WriteSnafu)?;
+
+        callback(Event::NewProgressBar {
+            index: self.download_list_index,
+            total: self.total,
+            msg: self.download_message(),
+            size,
+        })
+        .await;
+
+        let segments = split(size, mirrors.len());
+        let progress = AtomicU64::new(0);
+
+        let results = join_all(segments.iter().enumerate().map(|(i, range)| {
+            self.download_segment(mirrors, i, range.clone(), &file, &progress, callback)
+        }))
+        .await;
+
+        callback(Event::ProgressDone(self.download_list_index)).await;
+
+        let mut served_by = vec![];
+
+        for res in results {
+            match res {
+                Ok(mirror) => served_by.push(mirror),
+                Err(e) => {
+                    callback(Event::GlobalProgressSub(progress.load(Ordering::Acquire))).await;
+                    return Err(e);
+                }
+            }
+        }
+
+        if self.verify_file(&file).await? {
+            return Ok(());
+        }
+
+        debug!(
+            "checksum mismatch for segmented download of {}, looking for bad mirrors ...",
+            self.entry.filename
+        );
+
+        // 从其他镜像源将各个分段下载到单独的文件中比较，
+        // 只有当至少两个镜像源提供的数据一致时，才据此修复分段并将提供不同数据的镜像源列入黑名单
+        let mut suspects = vec![];
+        let mut undecided = vec![];
+        let mut scratch = vec![];
+
+        let res = async {
+            for (i, (range, mirror)) in segments.iter().zip(served_by).enumerate() {
+                let mut votes = vec![(mirror, hash_range(&file, range.clone()).await?)];
+                let mut copies: Vec<(usize, PathBuf)> = vec![(mirror, file.clone())];
+
+                for alternate in (1..mirrors.len()).map(|n| (mirror + n) % mirrors.len()) {
+                    if consensus(&votes).is_some() {
+                        break;
+                    }
+
+                    let path = self
+                        .entry
+                        .dir
+                        .join(format!("{}.segment-{i}-{alternate}", self.entry.filename));
+                    File::create(&path).await.context(CreateSnafu)?;
+                    scratch.push(path.clone());
+
+                    let mut hasher = Sha256::new();
+                    if let Err(e) = self
+                        .fetch_range(
+                            &mirrors[alternate].url,
+                            range.clone(),
+                            &path,
+                            Some(&mut hasher),
+                            None,
+                            callback,
+                        )
+                        .await
+                    {
+                        debug!(
+                            "Failed to download segment from {}: {e}",
+                            mirrors[alternate].url
+                        );
+                        continue;
+                    }
+
+                    votes.push((alternate, hasher.finalize().to_vec()));
+                    copies.push((alternate, path));
+                }
+
+                match consensus(&votes) {
+                    Some(agreed) => {
+                        if votes[0].1 != agreed {
+                            let (_, src) =
+                                &copies[votes.iter().position(|(_, h)| h == agreed).unwrap()];
+                            copy_range(src, &file, range.clone()).await?;
+                        }
+
+                        suspects.extend(votes.iter().filter(|(_, h)| h != agreed).map(|(m, _)| *m));
+                    }
+                    None if votes.len() > 1 => undecided.push((i, range.clone(), votes, copies)),
+                    None => {}
+                }
+            }
+
+            if self.verify_file(&file).await? {
+                return Ok(true);
+            }
+
+            // 镜像源数量不足以判断时，逐一尝试各个版本的分段，以最终校验和为准
+            for (i, range, votes, copies) in undecided {
+                let original = self
+                    .entry
+                    .dir
+                    .join(format!("{}.segment-{i}", self.entry.filename));
+                File::create(&original).await.context(CreateSnafu)?;
+                scratch.push(original.clone());
+                copy_range(&file, &original, range.clone()).await?;
+
+                for (n, (_, src)) in copies.iter().enumerate().skip(1) {
+                    copy_range(src, &file, range.clone()).await?;
+
+                    if self.verify_file(&file).await? {
+                        suspects.extend(
+                            votes
+                                .iter()
+                                .filter(|(_, h)| *h != votes[n].1)
+                                .map(|(m, _)| *m),
+                        );
+                        return Ok(true);
+                    }
+                }
+
+                copy_range(&original, &file, range).await?;
+            }
+
+            Ok(false)
+        }
+        .await;
+
+        for path in scratch {
+            tokio::fs::remove_file(path).await.ok();
+        }
+
+        if !res? {
+            callback(Event::GlobalProgressSub(progress.load(Ordering::Acquire))).await;
+            return Err(SingleDownloadError::ChecksumMismatch);
+        }
+
+        for mirror in suspects {
+            MIRROR_BLACKLIST.insert(&mirrors[mirror].url);
+        }
+
+        Ok(())
+    }
+
+    /// Download segment `index`, trying other mirrors if the preferred one fails
+    ///
+    /// Returns the index of the mirror which served the segment.
+    async fn download_segment<F, Fut>(
+        &self,
+        mirrors: &[&DownloadSource],
+        index: usize,
+        range: Range<u64>,
+        file: &Path,
+        progress: &AtomicU64,
+        callback: &F,
+    ) -> Result<usize, SingleDownloadError>
+    where
+        F: Fn(Event) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let mut last_err = None;
+
+        for i in 0..mirrors.len() {
+            let mirror = (index + i) % mirrors.len();
+            let attempt = AtomicU64::new(0);
+
+            match self
+                .fetch_range(
+                    &mirrors[mirror].url,
+                    range.clone(),
+                    file,
+                    None,
+                    Some(&attempt),
+                    callback,
+                )
+                .await
+            {
+                Ok(()) => {
+                    progress.fetch_add(attempt.load(Ordering::Acquire), Ordering::AcqRel);
+                    return Ok(mirror);
+                }
+                Err(e) => {
+                    debug!(
+                        "Failed to download segment {range:?} from {}: {e}",
+                        mirrors[mirror].url
+                    );
+                    callback(Event::GlobalProgressSub(attempt.load(Ordering::Acquire))).await;
+                    last_err = Some(e);
+                }
+            }
+        }
+
+        Err(last_err.expect("mirrors is not empty"))
+    }
+
+    /// Write `range` of the file at `url` to the same range of `file`
+    async fn fetch_range<F, Fut>(
+        &self,
+        url: &str,
+        range: Range<u64>,
+        file: &Path,
+        mut hasher: Option<&mut Sha256>,
+        progress: Option<&AtomicU64>,
+        callback: &F,
+    ) -> Result<(), SingleDownloadError>
+    where
+        F: Fn(Event) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let _connection = self.throttle.connect(url).await;
+
+        let req = self
+            .client
+            .request(Method::GET, url)
+            .header(RANGE, format!("bytes={}-{}", range.start, range.end - 1));
+
+        let resp = match timeout(self.timeout, send_request(req)).await {
+            Ok(Ok(resp)) => resp,
+            Ok(Err(e)) => return Err(SingleDownloadError::ReqwestMiddlewareError { source: e }),
+            Err(_) => return Err(SingleDownloadError::SendRequestTimeout),
+        };
+
+        let returned = resp
+            .headers()
+            .typed_get::<ContentRange>()
+            .and_then(|r| r.bytes_range());
+
+        if resp.status() != StatusCode::PARTIAL_CONTENT
+            || returned != Some((range.start, range.end - 1))
+        {
+            return Err(SingleDownloadError::UnexpectedRange);
+        }
+
+        let mut dest = OpenOptions::new()
+            .write(true)
+            .open(file)
+            .await
+            .context(OpenSnafu)?;
+
+        dest.seek(SeekFrom::Start(range.start))
+            .await
+            .context(SeekSnafu)?;
+
+        let mut stream = resp.bytes_stream();
+        let mut remaining = range.end - range.start;
+
+        while remaining > 0 {
+            let chunk = match timeout(self.timeout, stream.next()).await {
+                Ok(Some(Ok(chunk))) => chunk,
+                Ok(Some(Err(e))) => {
+                    return Err(SingleDownloadError::BrokenPipe {
+                        source: io::Error::other(e),
+                    });
+                }
+                Ok(None) => {
+                    return Err(SingleDownloadError::BrokenPipe {
+                        source: io::Error::from(io::ErrorKind::UnexpectedEof),
+                    });
+                }
+                Err(_) => return Err(SingleDownloadError::DownloadTimeout),
+            };
+
+            let len = chunk.len().min(remaining as usize);
+            let chunk = &chunk[..len];
+
+            dest.write_all(chunk).await.context(WriteSnafu)?;
+
+            if let Some(hasher) = hasher.as_mut() {
+                hasher.update(chunk);
+            }
+
+            remaining -= len as u64;
+
+            if let Some(progress) = progress {
+                progress.fetch_add(len as u64, Ordering::AcqRel);
+                callback(Event::ProgressInc {
+                    index: self.download_list_index,
+                    size: len as u64,
+                })
+                .await;
+                callback(Event::GlobalProgressAdd(len as u64)).await;
+            }
+
+            self.throttle.consume(url, len as u64).await;
+        }
+
+        dest.flush().await.context(WriteSnafu)?;
+
+        Ok(())
+    }
+
+    async fn verify_file(&self, file: &Path) -> Result<bool, SingleDownloadError> {
+        let Some(hash) = &self.entry.hash else {
+            return Ok(true);
+        };
+
+        let mut validator = hash.get_validator();
+        let mut f = File::open(file).await.context(OpenSnafu)?;
+        let mut buf = vec![0u8; READ_FILE_BUFSIZE];
+
+        loop {
+            let n = f.read(&mut buf).await.context(OpenSnafu)?;
+
+            if n == 0 {
+                break;
+            }
+
+            validator.update(&buf[..n]);
+        }
+
+        Ok(validator.finish())
+    }
+}
+
+/// Copy `range` of `src` into the same position of `dst`
+async fn copy_range(src: &Path, dst: &Path, range: Range<u64>) -> Result<(), SingleDownloadError> {
+    let mut src = File::open(src).await.context(OpenSnafu)?;
+    src.seek(SeekFrom::Start(range.start))
+        .await
+        .context(SeekSnafu)?;
+
+    let mut dst = OpenOptions::new()
+        .write(true)
+        .open(dst)
+        .await
+        .context(OpenSnafu)?;
+    dst.seek(SeekFrom::Start(range.start))
+        .await
+        .context(SeekSnafu)?;
+
+    tokio::io::copy(&mut src.take(range.end - range.start), &mut dst)
+        .await
+        .context(WriteSnafu)?;
+    dst.flush().await.context(FlushSnafu)?;
+
+    Ok(())
+}
+
+async fn hash_range(file: &Path, range: Range<u64>) -> Result<Vec<u8>, SingleDownloadError> {
+    let mut f = File::open(file).await.context(OpenSnafu)?;
+    f.seek(SeekFrom::Start(range.start))
+        .await
+        .context(SeekSnafu)?;
+
+    let mut reader = f.take(range.end - range.start);
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; READ_FILE_BUFSIZE];
+
+    loop {
+        let n = reader.read(&mut buf).await.context(OpenSnafu)?;
+
+        if n == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(hasher.finalize().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split(12, 4), vec![0..3, 3..6, 6..9, 9..12]);
+        assert_eq!(split(10, 4), vec![0..3, 3..6, 6..9, 9..10]);
+        assert_eq!(split(10, 1), vec![0..10]);
+        assert_eq!(split(2, 4), vec![0..1, 1..2]);
+        assert!(split(0, 4).is_empty());
+    }
+
+    #[test]
+    fn test_mirror_key() {
+        assert_eq!(
+            mirror_key("https://repo.aosc.io/debs/pool/a.deb"),
+            "https://repo.aosc.io:443"
+        );
+        assert_eq!(
+            mirror_key("http://mirror.example.com:8080/debs"),
+            "http://mirror.example.com:8080"
+        );
+        assert_eq!(mirror_key("not a url"), "not a url");
+    }
+
+    #[test]
+    fn test_consensus() {
+        let a = vec![1u8];
+        let b = vec![2u8];
+
+        assert_eq!(consensus(&[(0, a.clone())]), None);
+        assert_eq!(consensus(&[(0, a.clone()), (1, b.clone())]), None);
+        assert_eq!(
+            consensus(&[(0, a.clone()), (1, b.clone()), (2, b.clone())]),
+            Some(b.as_slice())
+        );
+        assert_eq!(
+            consensus(&[(0, a.clone()), (1, a.clone())]),
+            Some(a.as_slice())
+        );
+    }
+
+    #[test]
+    fn test_blacklist() {
+        let blacklist = Blacklist::default();
+
+        blacklist.strike("https://a.example.com/debs/a.deb");
+        assert!(!blacklist.contains("https://a.example.com/debs/b.deb"));
+
+        blacklist.strike("https://a.example.com/debs/b.deb");
+        assert!(blacklist.contains("https://a.example.com/debs/c.deb"));
+        assert!(!blacklist.contains("http://a.example.com/debs/c.deb"));
+
+        blacklist.insert("https://b.example.com/debs/a.deb");
+        assert!(blacklist.contains("https://b.example.com/"));
+    }
+}
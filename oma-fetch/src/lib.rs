@@ -9,10 +9,18 @@ use reqwest::{Method, Response, Url};
 use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
 use serde::{Deserialize, Serialize};
 use spdlog::debug;
+use throttle::{NetworkLimits, Throttle};
 use tokio::task::JoinSet;
 
 pub mod checksum;
 pub mod download;
+pub mod metalink;
+pub mod mirror_list;
+pub mod peer;
+pub mod proxy;
+pub mod throttle;
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+pub mod tls;
 pub use crate::download::SingleDownloadError;
 
 pub use reqwest;
@@ -24,6 +32,8 @@ pub struct DownloadEntry {
     dir: PathBuf,
     final_dir: Option<PathBuf>,
     hash: Option<Checksum>,
+    /// Expected file size, large files may be downloaded from multiple mirrors in segments
+    size: Option<u64>,
     allow_resume: bool,
     msg: Option<Cow<'static, str>>,
     #[builder(default)]
@@ -37,6 +47,7 @@ impl Debug for DownloadEntry {
             .field("filename", &self.filename)
             .field("dir", &self.dir)
             .field("hash", &self.hash.as_ref().map(|c| c.to_string()))
+            .field("size", &self.size)
             .field("allow_resume", &self.allow_resume)
             .field("msg", &self.msg)
             .field("file_type", &self.file_type)
@@ -77,7 +88,35 @@ pub struct DownloadSource {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum DownloadSourceType {
     Http,
+    /// A LAN peer serving its package cache, tried once before mirrors.
+    Peer,
     Local(bool),
+    /// A mirror redirector, resolved to mirrors and checksum before downloading.
+    Metalink,
+}
+
+impl DownloadSourceType {
+    /// Sources with higher rank are tried first.
+    fn rank(&self) -> u8 {
+        match self {
+            DownloadSourceType::Http | DownloadSourceType::Metalink => 0,
+            DownloadSourceType::Peer => 1,
+            DownloadSourceType::Local(_) => 2,
+        }
+    }
+
+    /// Get the source type of `url` by its scheme, `None` if the scheme is not supported.
+    ///
+    /// Files from `copy:` URLs are always copied, as the media may be removed afterwards.
+    pub fn from_url(url: &str, local_as_symlink: bool) -> Option<Self> {
+        match url.split_once(':')?.0 {
+            "http" | "https" => Some(DownloadSourceType::Http),
+            "file" => Some(DownloadSourceType::Local(local_as_symlink)),
+            "copy" => Some(DownloadSourceType::Local(false)),
+            "metalink+http" | "metalink+https" => Some(DownloadSourceType::Metalink),
+            _ => None,
+        }
+    }
 }
 
 impl PartialOrd for DownloadSourceType {
@@ -88,19 +127,35 @@ impl PartialOrd for DownloadSourceType {
 
 impl Ord for DownloadSourceType {
     fn cmp(&self, other: &Self) -> Ordering {
-        match self {
-            DownloadSourceType::Http => match other {
-                DownloadSourceType::Http => Ordering::Equal,
-                DownloadSourceType::Local { .. } => Ordering::Less,
-            },
-            DownloadSourceType::Local { .. } => match other {
-                DownloadSourceType::Http => Ordering::Greater,
-                DownloadSourceType::Local { .. } => Ordering::Equal,
-            },
-        }
+        self.rank().cmp(&other.rank())
     }
 }
 
+/// Get the path of a `file:` or `copy:` URL
+pub fn local_path(url: &str) -> Option<&str> {
+    url.strip_prefix("file:")
+        .or_else(|| url.strip_prefix("copy:"))
+}
+
+/// Resolve a `cdrom:[Label]/path` URL to a URL under the mount point of the media
+///
+/// `copy:` is used so that files are not linked to the removable media.
+pub fn resolve_cdrom_url(url: &str, mount_point: &str) -> Option<String> {
+    let rest = url.strip_prefix("cdrom:")?;
+
+    // 方括号内是光盘的卷标，其后才是光盘上的路径
+    let path = match rest.strip_prefix('[') {
+        Some(rest) => rest.split_once(']')?.1,
+        None => rest,
+    };
+
+    Some(format!(
+        "copy:{}/{}",
+        mount_point.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    ))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Event {
     ChecksumMismatch {
@@ -130,6 +185,11 @@ pub enum Event {
         index: usize,
         size: u64,
     },
+    /// A download started or stopped waiting for bandwidth limits
+    Throttled {
+        index: usize,
+        throttled: bool,
+    },
     NextUrl {
         index: usize,
         file_name: String,
@@ -164,11 +224,14 @@ pub(crate) enum SingleDownloadErrorHelper {
     DownloadTimeout,
     ChecksumMismatch,
     AcquireError,
+    UnexpectedRange,
 }
 
 #[derive(Builder)]
 pub struct DownloadManager {
     client: ClientWithMiddleware,
+    /// Same as `client` but not following redirects, used to query mirror redirectors
+    redirector_client: Option<ClientWithMiddleware>,
     download_list: Box<[DownloadEntry]>,
     #[builder(default = 4)]
     threads: usize,
@@ -178,6 +241,11 @@ pub struct DownloadManager {
     total_size: u64,
     #[builder(default = Duration::from_secs(15))]
     timeout: Duration,
+    #[builder(default)]
+    limits: NetworkLimits,
+    /// Files of at least this size are downloaded from multiple mirrors in segments, 0 to disable
+    #[builder(default = 16 * 1024 * 1024)]
+    segment_threshold: u64,
 }
 
 #[derive(Debug)]
@@ -207,6 +275,7 @@ impl DownloadManager {
         let len = self.download_list.len();
 
         let mut source_locks = AHashMap::new();
+        let throttle = Arc::new(Throttle::new(&self.limits, self.threads));
 
         for (i, c) in std::mem::take(&mut self.download_list)
             .into_iter()
@@ -229,11 +298,14 @@ impl DownloadManager {
 
             let single = SingleDownloader::builder()
                 .client(self.client.clone())
+                .maybe_redirector_client(self.redirector_client.clone())
                 .download_list_index(i)
                 .entry(c)
                 .total(len)
                 .retry_times(self.retry_times)
                 .timeout(self.timeout)
+                .throttle(throttle.clone())
+                .segment_threshold(self.segment_threshold)
                 .build()?;
 
             list.push((single, source_sem));
@@ -0,0 +1,391 @@
+//! Mirror redirectors serving Metalink (RFC 5854) documents or Metalink/HTTP (RFC 6249) headers.
+//!
+//! Sources with `metalink+http:` or `metalink+https:` URLs are resolved before downloading: the
+//! redirector is asked for a Metalink document, and if it does not serve one, the `Location`,
+//! `Link: rel=duplicate` and `Digest` headers of its redirect response are used instead.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use reqwest::{
+    Method, Response, Url,
+    header::{ACCEPT, CONTENT_TYPE, HeaderMap, LINK, LOCATION},
+};
+use reqwest_middleware::ClientWithMiddleware;
+use snafu::{ResultExt, Snafu};
+use spdlog::debug;
+
+use crate::{DownloadSource, DownloadSourceType, checksum::Checksum, send_request};
+
+const METALINK_NS: &str = "urn:ietf:params:xml:ns:metalink";
+const METALINK_CONTENT_TYPE: &str = "application/metalink4+xml";
+
+#[derive(Debug, Snafu)]
+pub enum MetalinkError {
+    #[snafu(display("Failed to query mirror redirector {url}"))]
+    Request {
+        url: String,
+        source: reqwest_middleware::Error,
+    },
+    #[snafu(display("Failed to read Metalink document from {url}"))]
+    Read { url: String, source: reqwest::Error },
+    #[snafu(display("Invalid Metalink document"))]
+    Parse { source: roxmltree::Error },
+    #[snafu(display("Metalink document does not describe {name}"))]
+    NoFile { name: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetalinkUrl {
+    pub url: String,
+    /// URLs with lower priority are tried first, unset ones are tried last
+    pub priority: Option<u32>,
+    /// ISO 3166-1 country code of the mirror
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetalinkFile {
+    pub name: String,
+    pub size: Option<u64>,
+    pub hashes: Vec<Checksum>,
+    /// Mirrors of this file, in order of preference
+    pub urls: Vec<MetalinkUrl>,
+}
+
+impl MetalinkFile {
+    /// The strongest checksum of this file
+    pub fn checksum(&self) -> Option<&Checksum> {
+        self.hashes.iter().max_by_key(|c| strength(c))
+    }
+
+    /// Download sources of this file, only HTTP(S) mirrors are supported
+    pub fn sources(&self) -> Vec<DownloadSource> {
+        self.urls
+            .iter()
+            .filter(|u| {
+                DownloadSourceType::from_url(&u.url, false) == Some(DownloadSourceType::Http)
+            })
+            .map(|u| DownloadSource {
+                url: u.url.clone(),
+                source_type: DownloadSourceType::Http,
+            })
+            .collect()
+    }
+}
+
+/// Parse the `<file>` elements of a Metalink 4 document
+pub fn parse(xml: &str) -> Result<Vec<MetalinkFile>, MetalinkError> {
+    let doc = roxmltree::Document::parse(xml).context(ParseSnafu)?;
+    let mut files = vec![];
+
+    for file in children(doc.root_element(), "file") {
+        let Some(name) = file.attribute("name") else {
+            continue;
+        };
+
+        let text = |name| children(file, name).next().and_then(|n| n.text());
+
+        let hashes = children(file, "hash")
+            .filter_map(|n| {
+                let hash = n.text()?.trim();
+
+                match n.attribute("type")? {
+                    "sha-512" => Checksum::from_sha512_str(hash).ok(),
+                    "sha-256" => Checksum::from_sha256_str(hash).ok(),
+                    "md5" => Checksum::from_md5_str(hash).ok(),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        let mut urls = children(file, "url")
+            .filter_map(|n| {
+                Some(MetalinkUrl {
+                    url: n.text()?.trim().to_string(),
+                    priority: n.attribute("priority").and_then(|p| p.parse().ok()),
+                    location: n.attribute("location").map(|l| l.to_string()),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // 优先级相同时保持文档中的顺序
+        urls.sort_by_key(|u| u.priority.unwrap_or(u32::MAX));
+
+        files.push(MetalinkFile {
+            name: name.to_string(),
+            size: text("size").and_then(|s| s.trim().parse().ok()),
+            hashes,
+            urls,
+        });
+    }
+
+    Ok(files)
+}
+
+fn children<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    node.children()
+        .filter(move |n| n.has_tag_name((METALINK_NS, name)))
+}
+
+/// Get the `rel=duplicate` URLs of the `Link` headers, in order of preference
+pub fn duplicates(headers: &HeaderMap) -> Vec<String> {
+    let mut links = vec![];
+
+    for value in headers.get_all(LINK).iter().filter_map(|v| v.to_str().ok()) {
+        // Link: <http://a/f>; rel=duplicate; pri=1, <http://b/f>; rel=duplicate; pri=2
+        for link in value.split('<').skip(1) {
+            let Some((url, params)) = link.split_once('>') else {
+                continue;
+            };
+
+            let mut duplicate = false;
+            let mut priority = None;
+
+            for param in params.trim_end_matches([',', ' ']).split(';') {
+                let Some((key, value)) = param.split_once('=') else {
+                    continue;
+                };
+
+                let value = value.trim().trim_matches('"');
+
+                match key.trim() {
+                    "rel" => duplicate = value.split_whitespace().any(|r| r == "duplicate"),
+                    "pri" => priority = value.parse::<u32>().ok(),
+                    _ => {}
+                }
+            }
+
+            if duplicate {
+                links.push((priority.unwrap_or(u32::MAX), url.trim().to_string()));
+            }
+        }
+    }
+
+    links.sort_by_key(|(priority, _)| *priority);
+
+    links.into_iter().map(|(_, url)| url).collect()
+}
+
+/// Get the strongest checksum in the `Digest` headers (RFC 3230)
+pub fn digest(headers: &HeaderMap) -> Option<Checksum> {
+    headers
+        .get_all("digest")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|d| {
+            let (algo, value) = d.trim().split_once('=')?;
+            let hash = STANDARD.decode(value).ok()?;
+
+            match (algo.to_ascii_lowercase().as_str(), hash.len()) {
+                ("sha-512", 64) => Some(Checksum::Sha512(hash)),
+                ("sha-256", 32) => Some(Checksum::Sha256(hash)),
+                ("md5", 16) => Some(Checksum::Md5(hash)),
+                _ => None,
+            }
+        })
+        .max_by_key(strength)
+}
+
+fn strength(checksum: &Checksum) -> u8 {
+    match checksum {
+        Checksum::Sha512(_) => 2,
+        Checksum::Sha256(_) => 1,
+        Checksum::Md5(_) => 0,
+    }
+}
+
+/// Strip the `metalink+` prefix of a redirector URL
+pub fn redirector_url(url: &str) -> &str {
+    url.strip_prefix("metalink+").unwrap_or(url)
+}
+
+/// Mirrors and checksum of a file, as told by a mirror redirector
+#[derive(Debug, Default)]
+pub struct Resolved {
+    pub sources: Vec<DownloadSource>,
+    pub hash: Option<Checksum>,
+    pub size: Option<u64>,
+}
+
+/// Ask the mirror redirector at `url` for the mirrors and checksum of the file
+///
+/// `client` should not follow redirects, otherwise only the mirror it was redirected to is known.
+pub async fn resolve(client: &ClientWithMiddleware, url: &str) -> Result<Resolved, MetalinkError> {
+    let url = redirector_url(url);
+    let accept = format!("{METALINK_CONTENT_TYPE}, */*;q=0.1");
+
+    let req = client.request(Method::HEAD, url).header(ACCEPT, &accept);
+
+    let resp = send_request(req).await.context(RequestSnafu { url })?;
+
+    let is_metalink = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with(METALINK_CONTENT_TYPE));
+
+    if is_metalink {
+        let req = client.request(Method::GET, url).header(ACCEPT, &accept);
+        let resp = send_request(req).await.context(RequestSnafu { url })?;
+
+        from_document(url, resp).await
+    } else {
+        Ok(from_headers(&resp))
+    }
+}
+
+async fn from_document(url: &str, resp: Response) -> Result<Resolved, MetalinkError> {
+    let xml = resp.text().await.context(ReadSnafu { url })?;
+    let files = parse(&xml)?;
+
+    let name = url.rsplit('/').next().unwrap_or(url);
+
+    // 文档只描述一个文件时不要求文件名一致，以兼容重定向后的文件名
+    let file = match files.iter().find(|f| f.name == name) {
+        Some(file) => file,
+        None if files.len() == 1 => &files[0],
+        None => {
+            return Err(MetalinkError::NoFile {
+                name: name.to_string(),
+            });
+        }
+    };
+
+    debug!(
+        "Metalink document of {url} lists {} mirrors",
+        file.urls.len()
+    );
+
+    Ok(Resolved {
+        sources: file.sources(),
+        hash: file.checksum().cloned(),
+        size: file.size,
+    })
+}
+
+fn from_headers(resp: &Response) -> Resolved {
+    let headers = resp.headers();
+
+    // 重定向服务选择的镜像优先；未重定向时由重定向服务直接提供文件
+    let (first, size) = if resp.status().is_redirection() {
+        (location(resp.url(), headers), None)
+    } else {
+        (Some(resp.url().to_string()), resp.content_length())
+    };
+
+    let mut urls = first.into_iter().collect::<Vec<_>>();
+
+    for url in duplicates(headers) {
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+
+    Resolved {
+        sources: urls
+            .into_iter()
+            .filter(|url| {
+                DownloadSourceType::from_url(url, false) == Some(DownloadSourceType::Http)
+            })
+            .map(|url| DownloadSource {
+                url,
+                source_type: DownloadSourceType::Http,
+            })
+            .collect(),
+        hash: digest(headers),
+        size,
+    }
+}
+
+/// Get the redirect target in the `Location` header, relative to `base`
+fn location(base: &Url, headers: &HeaderMap) -> Option<String> {
+    let location = headers.get(LOCATION)?.to_str().ok()?;
+
+    base.join(location).ok().map(|url| url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn header_map(values: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        for (name, value) in values {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+
+        headers
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let headers = header_map(&[
+            (
+                "link",
+                "<http://b.example.com/f.deb>; rel=duplicate; pri=2, \
+                 <http://a.example.com/f.deb>; rel=duplicate; pri=1",
+            ),
+            ("link", "<http://example.com/f.meta4>; rel=describedby"),
+            ("link", "<http://c.example.com/f.deb>; rel=\"duplicate\""),
+            ("link", "<http://d.example.com/f.deb>; rel=duplicate; pri=2"),
+        ]);
+
+        assert_eq!(
+            duplicates(&headers),
+            vec![
+                "http://a.example.com/f.deb",
+                "http://b.example.com/f.deb",
+                "http://d.example.com/f.deb",
+                "http://c.example.com/f.deb",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_digest() {
+        // sha-256 和 md5 均为空字符串的摘要
+        let headers = header_map(&[
+            ("digest", "MD5=1B2M2Y8AsgTpgAmY7PhCfg=="),
+            (
+                "digest",
+                "unknown=abc, SHA-256=47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=",
+            ),
+        ]);
+
+        assert_eq!(
+            digest(&headers),
+            Checksum::from_sha256_str(
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+            )
+            .ok()
+        );
+
+        // 长度与算法不符的摘要被忽略
+        let headers = header_map(&[("digest", "SHA-256=1B2M2Y8AsgTpgAmY7PhCfg==")]);
+        assert_eq!(digest(&headers), None);
+    }
+
+    #[test]
+    fn test_location() {
+        let base = Url::parse("https://mirrors.example.com/debs/pool/f.deb").unwrap();
+
+        assert_eq!(
+            location(
+                &base,
+                &header_map(&[("location", "https://a.example.com/f.deb")])
+            ),
+            Some("https://a.example.com/f.deb".to_string())
+        );
+        assert_eq!(
+            location(&base, &header_map(&[("location", "/mirror/f.deb")])),
+            Some("https://mirrors.example.com/mirror/f.deb".to_string())
+        );
+        assert_eq!(location(&base, &HeaderMap::new()), None);
+    }
+}
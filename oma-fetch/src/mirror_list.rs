@@ -0,0 +1,289 @@
+//! Mirror lists used by APT's `mirror` method (`mirror+file:`, `mirror+http:`, `mirror:` etc.).
+//!
+//! Each line of a mirror list is a mirror URL, optionally followed by tab-separated
+//! `key:value` metadata, e.g. `http://ftp.de.debian.org/debian/\tpriority:1\ttype:index`.
+
+use std::io;
+
+use reqwest::Method;
+use reqwest_middleware::ClientWithMiddleware;
+use snafu::{ResultExt, Snafu};
+
+use crate::{DownloadSource, DownloadSourceType, local_path, send_request_with_url_and_method};
+
+#[derive(Debug, Snafu)]
+pub enum MirrorListError {
+    #[snafu(display("Unsupported mirror list URL: {url}"))]
+    UnsupportedUrl { url: String },
+    #[snafu(display("Failed to read mirror list {path}"))]
+    ReadFile { path: String, source: io::Error },
+    #[snafu(display("Failed to download mirror list {url}"))]
+    Download {
+        url: String,
+        source: reqwest_middleware::Error,
+    },
+    #[snafu(display("Mirror list {url} contains no mirror"))]
+    Empty { url: String },
+}
+
+/// What a mirror may be used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MirrorType {
+    /// Repository metadata
+    Index,
+    /// Package archives
+    Deb,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirrorListEntry {
+    pub url: String,
+    /// Mirrors with lower priority are tried first, unset ones are tried last
+    pub priority: Option<u32>,
+    /// Only use this mirror for these architectures, empty for all
+    pub archs: Vec<String>,
+    /// Only use this mirror for these types, empty for all
+    pub types: Vec<MirrorType>,
+}
+
+impl MirrorListEntry {
+    fn matches(&self, arch: Option<&str>, kind: MirrorType) -> bool {
+        let arch_ok = match arch {
+            Some(arch) => self.archs.is_empty() || self.archs.iter().any(|a| a == arch),
+            None => true,
+        };
+
+        arch_ok && (self.types.is_empty() || self.types.contains(&kind))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MirrorList(pub Vec<MirrorListEntry>);
+
+impl MirrorList {
+    pub fn parse(s: &str) -> Self {
+        let mut entries = vec![];
+
+        for line in s.lines() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let Some(url) = fields.next() else {
+                continue;
+            };
+
+            let mut entry = MirrorListEntry {
+                url: url.to_string(),
+                priority: None,
+                archs: vec![],
+                types: vec![],
+            };
+
+            for field in fields {
+                let Some((key, value)) = field.split_once(':') else {
+                    continue;
+                };
+
+                match key {
+                    "priority" => entry.priority = value.parse().ok(),
+                    "arch" => entry.archs.extend(value.split(',').map(|a| a.to_string())),
+                    "type" => entry.types.extend(value.split(',').filter_map(|t| match t {
+                        "index" => Some(MirrorType::Index),
+                        "deb" => Some(MirrorType::Deb),
+                        _ => None,
+                    })),
+                    _ => {}
+                }
+            }
+
+            entries.push(entry);
+        }
+
+        // 优先级相同时保持镜像列表中的顺序
+        entries.sort_by_key(|e| e.priority.unwrap_or(u32::MAX));
+
+        Self(entries)
+    }
+
+    /// Base URLs of the mirrors usable for `arch` and `kind`, in order of preference
+    pub fn urls(&self, arch: Option<&str>, kind: MirrorType) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter(|e| e.matches(arch, kind))
+            .map(|e| e.url.as_str())
+            .collect()
+    }
+
+    /// Download sources of `path` (relative to the repository root) as ordered fallbacks
+    pub fn sources(
+        &self,
+        path: &str,
+        arch: Option<&str>,
+        kind: MirrorType,
+        local_as_symlink: bool,
+    ) -> Vec<DownloadSource> {
+        self.urls(arch, kind)
+            .into_iter()
+            .filter_map(|base| {
+                let url = format!(
+                    "{}/{}",
+                    base.trim_end_matches('/'),
+                    path.trim_start_matches('/')
+                );
+
+                let source_type = DownloadSourceType::from_url(&url, local_as_symlink)?;
+
+                Some(DownloadSource { url, source_type })
+            })
+            .collect()
+    }
+}
+
+/// Whether `url` uses the `mirror` method
+pub fn is_mirror_url(url: &str) -> bool {
+    url.starts_with("mirror:") || url.starts_with("mirror+")
+}
+
+/// Get the location of the mirror list from a `mirror` method repository URL
+///
+/// `mirror://host/list` is the same as `mirror+http://host/list`.
+pub fn mirror_list_location(url: &str) -> Option<String> {
+    let location = if let Some(rest) = url.strip_prefix("mirror+") {
+        rest.to_string()
+    } else {
+        format!("http:{}", url.strip_prefix("mirror:")?)
+    };
+
+    Some(location.trim_end_matches('/').to_string())
+}
+
+/// Read the mirror list of a `mirror` method repository URL
+pub async fn fetch_mirror_list(
+    client: &ClientWithMiddleware,
+    url: &str,
+) -> Result<MirrorList, MirrorListError> {
+    let location = mirror_list_location(url).ok_or_else(|| MirrorListError::UnsupportedUrl {
+        url: url.to_string(),
+    })?;
+
+    let s = if let Some(path) = local_path(&location) {
+        tokio::fs::read_to_string(path)
+            .await
+            .context(ReadFileSnafu { path })?
+    } else if DownloadSourceType::from_url(&location, false) == Some(DownloadSourceType::Http) {
+        send_request_with_url_and_method(&location, client, Method::GET)
+            .await
+            .context(DownloadSnafu { url: &location })?
+            .text()
+            .await
+            .map_err(reqwest_middleware::Error::from)
+            .context(DownloadSnafu { url: &location })?
+    } else {
+        return Err(MirrorListError::UnsupportedUrl { url: location });
+    };
+
+    let list = MirrorList::parse(&s);
+
+    if list.0.is_empty() {
+        return Err(MirrorListError::Empty { url: location });
+    }
+
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIST: &str = "\
+# comment
+http://b.example.com/debian/\tpriority:2\ttype:deb
+http://a.example.com/debian\tpriority:1\tarch:amd64,arm64
+file:/srv/mirror/debian\ttype:index
+
+http://c.example.com/debian/\tpriority:2\tfoo:bar
+";
+
+    #[test]
+    fn test_parse() {
+        let list = MirrorList::parse(LIST);
+
+        assert_eq!(
+            list.0,
+            vec![
+                MirrorListEntry {
+                    url: "http://a.example.com/debian".to_string(),
+                    priority: Some(1),
+                    archs: vec!["amd64".to_string(), "arm64".to_string()],
+                    types: vec![],
+                },
+                MirrorListEntry {
+                    url: "http://b.example.com/debian/".to_string(),
+                    priority: Some(2),
+                    archs: vec![],
+                    types: vec![MirrorType::Deb],
+                },
+                MirrorListEntry {
+                    url: "http://c.example.com/debian/".to_string(),
+                    priority: Some(2),
+                    archs: vec![],
+                    types: vec![],
+                },
+                MirrorListEntry {
+                    url: "file:/srv/mirror/debian".to_string(),
+                    priority: None,
+                    archs: vec![],
+                    types: vec![MirrorType::Index],
+                },
+            ]
+        );
+
+        assert!(MirrorList::parse("# nothing\n\n").0.is_empty());
+    }
+
+    #[test]
+    fn test_sources() {
+        let list = MirrorList::parse(LIST);
+
+        let urls = |arch, kind| {
+            list.sources("/pool/main/f/foo.deb", arch, kind, false)
+                .into_iter()
+                .map(|s| s.url)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            urls(Some("amd64"), MirrorType::Deb),
+            vec![
+                "http://a.example.com/debian/pool/main/f/foo.deb",
+                "http://b.example.com/debian/pool/main/f/foo.deb",
+                "http://c.example.com/debian/pool/main/f/foo.deb",
+            ]
+        );
+        assert_eq!(
+            urls(Some("riscv64"), MirrorType::Deb),
+            vec![
+                "http://b.example.com/debian/pool/main/f/foo.deb",
+                "http://c.example.com/debian/pool/main/f/foo.deb",
+            ]
+        );
+        assert_eq!(
+            urls(Some("riscv64"), MirrorType::Index),
+            vec![
+                "http://c.example.com/debian/pool/main/f/foo.deb",
+                "file:/srv/mirror/debian/pool/main/f/foo.deb",
+            ]
+        );
+
+        let only_arm64 = MirrorList::parse("http://a.example.com/debian\tarch:arm64\n");
+        assert!(
+            only_arm64
+                .sources("pool/foo.deb", Some("amd64"), MirrorType::Deb, false)
+                .is_empty()
+        );
+    }
+}
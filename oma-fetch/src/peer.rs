@@ -0,0 +1,98 @@
+//! Share downloaded packages between machines in the same LAN.
+//!
+//! Peers serve their package cache over HTTP (see `oma serve-cache`), packages are
+//! requested as `{peer}/{filename}` and always verified against the checksum from
+//! the repository index.
+
+use std::{collections::BTreeSet, time::Duration};
+
+use futures::future::join_all;
+use reqwest_middleware::ClientWithMiddleware;
+use spdlog::debug;
+use tokio::process::Command;
+
+/// DNS-SD service type announced by peers.
+pub const SERVICE_TYPE: &str = "_oma-cache._tcp";
+
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Get reachable peers from the configured list, and from the LAN via Avahi if `mdns` is set.
+pub async fn discover_peers(
+    client: &ClientWithMiddleware,
+    peers: &[String],
+    mdns: bool,
+) -> Vec<String> {
+    let mut candidates = peers
+        .iter()
+        .map(|p| p.trim_end_matches('/').to_string())
+        .collect::<BTreeSet<_>>();
+
+    if mdns {
+        match browse_avahi().await {
+            Ok(found) => candidates.extend(found),
+            Err(e) => debug!("Failed to browse {SERVICE_TYPE} services: {e}"),
+        }
+    }
+
+    // 不可达的机器会让每个软件包的下载都等待超时，因此先过滤掉
+    join_all(candidates.into_iter().map(|peer| async move {
+        let reachable = client
+            .head(format!("{peer}/"))
+            .timeout(PROBE_TIMEOUT)
+            .send()
+            .await
+            .is_ok_and(|resp| resp.status().is_success());
+
+        debug!("Peer {peer} reachable: {reachable}");
+
+        reachable.then_some(peer)
+    }))
+    .await
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+async fn browse_avahi() -> std::io::Result<Vec<String>> {
+    let output = Command::new("avahi-browse")
+        .args([
+            "--resolve",
+            "--terminate",
+            "--parsable",
+            "--no-db-lookup",
+            SERVICE_TYPE,
+        ])
+        .output()
+        .await?;
+
+    Ok(parse_avahi_browse(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse resolved services from `avahi-browse --parsable`, like:
+/// `=;eth0;IPv4;oma cache on foo;_oma-cache._tcp;local;foo.local;192.168.1.2;8765;`
+fn parse_avahi_browse(output: &str) -> Vec<String> {
+    let mut res = vec![];
+
+    for line in output.lines() {
+        let fields = line.split(';').collect::<Vec<_>>();
+
+        if fields.len() < 9 || fields[0] != "=" {
+            continue;
+        }
+
+        let (proto, address, port) = (fields[2], fields[7], fields[8]);
+
+        let peer = match proto {
+            "IPv4" => format!("http://{address}:{port}"),
+            // 链路本地地址需要指定网络接口，URL 中无法表示
+            "IPv6" if !address.starts_with("fe80") => format!("http://[{address}]:{port}"),
+            _ => continue,
+        };
+
+        if !res.contains(&peer) {
+            res.push(peer);
+        }
+    }
+
+    res
+}
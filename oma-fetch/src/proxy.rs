@@ -0,0 +1,276 @@
+//! Proxy selection following APT's `Acquire::*::Proxy` rules.
+//!
+//! For each request, the first of these settings applies:
+//!
+//! 1. The proxy of the host, like `Acquire::http::Proxy::<host>`
+//! 2. The output of the auto-detection script, like `Acquire::http::Proxy-Auto-Detect`, which
+//!    is run once for each URL scheme
+//! 3. The proxy of the URL scheme, like `Acquire::http::Proxy`
+//! 4. The `<scheme>_proxy` and `no_proxy` environment variables
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use ahash::AHashMap;
+use reqwest::{Proxy, Url};
+use spdlog::{debug, warn};
+
+/// How requests are sent to a host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProxySetting {
+    /// Connect to the host directly
+    Direct,
+    /// Connect through a proxy, e.g. `http://proxy:3128` or `socks5h://127.0.0.1:1080`
+    Proxy(String),
+}
+
+impl ProxySetting {
+    /// Parse an APT style proxy setting, `DIRECT` means no proxy
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+
+        if s.is_empty() {
+            None
+        } else if s.eq_ignore_ascii_case("DIRECT") {
+            Some(ProxySetting::Direct)
+        } else {
+            Some(ProxySetting::Proxy(s.to_string()))
+        }
+    }
+
+    fn into_url(self) -> Option<String> {
+        match self {
+            ProxySetting::Direct => None,
+            ProxySetting::Proxy(url) => Some(url),
+        }
+    }
+}
+
+type DetectedProxy = OnceLock<Option<ProxySetting>>;
+
+#[derive(Debug, Clone, Default)]
+pub struct ProxyConfig {
+    /// Proxy of each URL scheme, like `Acquire::http::Proxy`
+    pub schemes: AHashMap<String, ProxySetting>,
+    /// Proxy of each host, like `Acquire::http::Proxy::<host>`, keyed by scheme and host
+    pub hosts: AHashMap<(String, String), ProxySetting>,
+    /// Script printing the proxy for the URL given as its argument, keyed by scheme
+    pub auto_detect: AHashMap<String, PathBuf>,
+    /// Output of the auto-detection scripts, keyed by scheme
+    detected: Arc<Mutex<AHashMap<String, Arc<DetectedProxy>>>>,
+}
+
+impl ProxyConfig {
+    /// No proxy is configured, the environment variables are used as is
+    pub fn is_empty(&self) -> bool {
+        self.schemes.is_empty() && self.hosts.is_empty() && self.auto_detect.is_empty()
+    }
+
+    /// Get the proxy URL for `url`, `None` to connect directly
+    pub fn resolve(&self, url: &Url) -> Option<String> {
+        let scheme = url.scheme();
+        let host = url.host_str().unwrap_or_default();
+
+        if let Some(setting) = self.hosts.get(&(scheme.to_string(), host.to_string())) {
+            return setting.clone().into_url();
+        }
+
+        if let Some(script) = self.auto_detect.get(scheme)
+            && let Some(setting) = self.detect(scheme, script, url)
+        {
+            return setting.into_url();
+        }
+
+        if let Some(setting) = self.schemes.get(scheme) {
+            return setting.clone().into_url();
+        }
+
+        env_proxy(scheme, host)
+    }
+
+    /// Run the auto-detection script of `scheme` with the first URL requested, and reuse its output
+    fn detect(&self, scheme: &str, script: &Path, url: &Url) -> Option<ProxySetting> {
+        let cell = self
+            .detected
+            .lock()
+            .unwrap()
+            .entry(scheme.to_string())
+            .or_default()
+            .clone();
+
+        // 脚本运行期间只阻塞同一协议的请求
+        cell.get_or_init(|| auto_detect(script, url)).clone()
+    }
+
+    /// Create a proxy for the HTTP client, decisions are cached for each host
+    pub fn into_proxy(self) -> Proxy {
+        let cache: Mutex<AHashMap<String, Option<String>>> = Mutex::new(AHashMap::new());
+
+        Proxy::custom(move |url| {
+            let key = format!("{}://{}", url.scheme(), url.host_str().unwrap_or_default());
+
+            if let Some(proxy) = cache.lock().unwrap().get(&key) {
+                return proxy.clone();
+            }
+
+            let proxy = self.resolve(url);
+            debug!("Using proxy for {url}: {}", proxy.is_some());
+            cache.lock().unwrap().insert(key, proxy.clone());
+
+            proxy
+        })
+    }
+}
+
+/// Run the auto-detection script like APT, its first line of output is the proxy
+fn auto_detect(script: &Path, url: &Url) -> Option<ProxySetting> {
+    let output = match Command::new(script).arg(url.as_str()).output() {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            warn!(
+                "Proxy auto-detection script {} exited with {}",
+                script.display(),
+                output.status
+            );
+            return None;
+        }
+        Err(e) => {
+            warn!(
+                "Failed to run proxy auto-detection script {}: {e}",
+                script.display()
+            );
+            return None;
+        }
+    };
+
+    ProxySetting::parse(String::from_utf8_lossy(&output.stdout).lines().next()?)
+}
+
+fn env_proxy(scheme: &str, host: &str) -> Option<String> {
+    let var = |name: &str| {
+        env::var(name.to_lowercase())
+            .or_else(|_| env::var(name.to_uppercase()))
+            .ok()
+            .filter(|v| !v.is_empty())
+    };
+
+    let proxy = var(&format!("{scheme}_proxy")).or_else(|| var("all_proxy"))?;
+    let no_proxy = var("no_proxy").unwrap_or_default();
+
+    (!bypass_proxy(&no_proxy, host)).then_some(proxy)
+}
+
+/// Whether `host` matches the `no_proxy` list, domains also match their subdomains
+fn bypass_proxy(no_proxy: &str, host: &str) -> bool {
+    no_proxy.split(',').map(|s| s.trim()).any(|pattern| {
+        let domain = pattern.trim_start_matches('.');
+
+        pattern == "*"
+            || (!domain.is_empty()
+                && (host == domain
+                    || host
+                        .strip_suffix(domain)
+                        .is_some_and(|rest| rest.ends_with('.'))))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ProxySetting::parse(""), None);
+        assert_eq!(ProxySetting::parse("  \n"), None);
+        assert_eq!(ProxySetting::parse("DIRECT"), Some(ProxySetting::Direct));
+        assert_eq!(ProxySetting::parse(" direct "), Some(ProxySetting::Direct));
+        assert_eq!(
+            ProxySetting::parse(" http://proxy:3128 "),
+            Some(ProxySetting::Proxy("http://proxy:3128".to_string()))
+        );
+        assert_eq!(
+            ProxySetting::parse("socks5h://127.0.0.1:1080"),
+            Some(ProxySetting::Proxy("socks5h://127.0.0.1:1080".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_bypass_proxy() {
+        let no_proxy = "localhost, .internal.example.com,example.org,,";
+
+        assert!(bypass_proxy(no_proxy, "localhost"));
+        assert!(bypass_proxy(no_proxy, "internal.example.com"));
+        assert!(bypass_proxy(no_proxy, "repo.internal.example.com"));
+        assert!(bypass_proxy(no_proxy, "example.org"));
+        assert!(bypass_proxy(no_proxy, "mirror.example.org"));
+        assert!(!bypass_proxy(no_proxy, "badexample.org"));
+        assert!(!bypass_proxy(no_proxy, "example.com"));
+        assert!(!bypass_proxy(no_proxy, "example.org.evil.com"));
+        assert!(!bypass_proxy("", "example.org"));
+        assert!(!bypass_proxy(".", "example.org"));
+        assert!(bypass_proxy("*", "example.org"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut config = ProxyConfig::default();
+        config.schemes.insert(
+            "http".to_string(),
+            ProxySetting::Proxy("http://proxy:3128".to_string()),
+        );
+        config.hosts.insert(
+            ("http".to_string(), "local.example.com".to_string()),
+            ProxySetting::Direct,
+        );
+
+        let url = |s| Url::parse(s).unwrap();
+
+        assert_eq!(
+            config.resolve(&url("http://repo.example.com/debs")),
+            Some("http://proxy:3128".to_string())
+        );
+        assert_eq!(config.resolve(&url("http://local.example.com/debs")), None);
+    }
+
+    #[test]
+    fn test_auto_detect_cached() {
+        let dir = env::temp_dir().join(format!("oma-fetch-proxy-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let script = dir.join("detect");
+        let count = dir.join("count");
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho \"$1\" >> '{}'\necho DIRECT\necho ignored\n",
+                count.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = ProxyConfig::default();
+        config.schemes.insert(
+            "https".to_string(),
+            ProxySetting::Proxy("http://proxy:3128".to_string()),
+        );
+        config.auto_detect.insert("https".to_string(), script);
+
+        let url = |s| Url::parse(s).unwrap();
+
+        assert_eq!(config.resolve(&url("https://a.example.com/")), None);
+        assert_eq!(config.resolve(&url("https://b.example.com/")), None);
+        assert_eq!(
+            fs::read_to_string(&count).unwrap(),
+            "https://a.example.com/\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
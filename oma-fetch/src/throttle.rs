@@ -0,0 +1,234 @@
+//! Bandwidth limiting shared by concurrent downloads.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use ahash::AHashMap;
+use reqwest::Url;
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{Instant, sleep},
+};
+
+/// Idle limiters may save up to this much of their rate for later bursts
+const BURST: Duration = Duration::from_secs(1);
+
+/// Limits of network usage, `None` means unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NetworkLimits {
+    /// Total download rate of all connections, in bytes per second
+    pub max_rate: Option<u64>,
+    /// Total download rate of all connections to the same host, in bytes per second
+    pub max_rate_per_host: Option<u64>,
+    /// Concurrent connections to the same host, defaults to the download threads
+    pub max_connections_per_host: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: u64,
+    // 在此时刻之前发送的数据都已超出额度
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: u64) -> Self {
+        Self {
+            rate: rate.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Account `bytes` to this limiter, returns how long the caller should wait for
+    pub fn reserve(&self, bytes: u64) -> Duration {
+        self.reserve_at(bytes, Instant::now())
+    }
+
+    fn reserve_at(&self, bytes: u64, now: Instant) -> Duration {
+        let mut next = self.next.lock().unwrap();
+
+        let start = (*next).max(now.checked_sub(BURST).unwrap_or(now));
+        *next = start + Duration::from_secs_f64(bytes as f64 / self.rate as f64);
+
+        next.saturating_duration_since(now)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    global: Option<RateLimiter>,
+    per_host_rate: Option<u64>,
+    hosts: Mutex<AHashMap<String, RateLimiter>>,
+    connections_per_host: usize,
+    connections: Mutex<AHashMap<String, Arc<Semaphore>>>,
+}
+
+impl Throttle {
+    /// Create the limiters, `threads` is the default of concurrent connections per host
+    pub(crate) fn new(limits: &NetworkLimits, threads: usize) -> Self {
+        // 0 与未设置相同，表示不限制
+        let non_zero = |rate: Option<u64>| rate.filter(|rate| *rate != 0);
+
+        Self {
+            global: non_zero(limits.max_rate).map(RateLimiter::new),
+            per_host_rate: non_zero(limits.max_rate_per_host),
+            hosts: Mutex::new(AHashMap::new()),
+            connections_per_host: limits
+                .max_connections_per_host
+                .filter(|count| *count != 0)
+                .unwrap_or(threads)
+                .max(1),
+            connections: Mutex::new(AHashMap::new()),
+        }
+    }
+
+    /// Wait for a free connection to the host of `url`, held until the permit is dropped
+    pub(crate) async fn connect(&self, url: &str) -> OwnedSemaphorePermit {
+        let sem = self
+            .connections
+            .lock()
+            .unwrap()
+            .entry(host(url))
+            .or_insert_with(|| Arc::new(Semaphore::new(self.connections_per_host)))
+            .clone();
+
+        sem.acquire_owned()
+            .await
+            .expect("connection semaphores are never closed")
+    }
+
+    /// Account `bytes` downloaded from `url` and wait if any limit is exceeded
+    ///
+    /// Returns whether the download has been throttled.
+    pub(crate) async fn consume(&self, url: &str, bytes: u64) -> bool {
+        let mut wait = Duration::ZERO;
+
+        if let Some(global) = &self.global {
+            wait = wait.max(global.reserve(bytes));
+        }
+
+        if let Some(rate) = self.per_host_rate {
+            let host_wait = self
+                .hosts
+                .lock()
+                .unwrap()
+                .entry(host(url))
+                .or_insert_with(|| RateLimiter::new(rate))
+                .reserve(bytes);
+
+            wait = wait.max(host_wait);
+        }
+
+        if wait.is_zero() {
+            return false;
+        }
+
+        sleep(wait).await;
+
+        true
+    }
+}
+
+fn host(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    #[tokio::test]
+    async fn test_reserve_burst() {
+        let limiter = RateLimiter::new(100);
+        let t0 = *limiter.next.lock().unwrap();
+
+        // 空闲 5 秒后最多只能累积 1 秒的额度
+        let t = t0 + secs(5.0);
+        assert_eq!(limiter.reserve_at(100, t), Duration::ZERO);
+        assert_eq!(limiter.reserve_at(100, t), secs(1.0));
+        assert_eq!(limiter.reserve_at(50, t), secs(1.5));
+    }
+
+    #[tokio::test]
+    async fn test_reserve_refill() {
+        let limiter = RateLimiter::new(100);
+        let t0 = *limiter.next.lock().unwrap();
+
+        assert_eq!(limiter.reserve_at(200, t0), secs(2.0));
+        // 等待期间额度逐渐恢复
+        assert_eq!(limiter.reserve_at(100, t0 + secs(1.0)), secs(2.0));
+        assert_eq!(limiter.reserve_at(100, t0 + secs(4.0)), Duration::ZERO);
+        assert_eq!(limiter.reserve_at(0, t0 + secs(4.0)), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_zero_limit() {
+        // 0 字节每秒时按 1 字节每秒计算，不会除以 0
+        let limiter = RateLimiter::new(0);
+        let t0 = *limiter.next.lock().unwrap();
+        assert_eq!(limiter.reserve_at(2, t0), secs(2.0));
+
+        let throttle = Throttle::new(
+            &NetworkLimits {
+                max_rate: Some(0),
+                max_rate_per_host: Some(0),
+                max_connections_per_host: Some(0),
+            },
+            4,
+        );
+        assert!(throttle.global.is_none());
+        assert!(throttle.per_host_rate.is_none());
+        assert_eq!(throttle.connections_per_host, 4);
+        assert!(!throttle.consume("https://example.com/a", u64::MAX).await);
+    }
+
+    #[tokio::test]
+    async fn test_consume_per_host() {
+        let throttle = Throttle::new(
+            &NetworkLimits {
+                max_rate_per_host: Some(1_000_000),
+                ..Default::default()
+            },
+            4,
+        );
+
+        throttle.consume("https://a.example.com/a", 1).await;
+        throttle.consume("https://a.example.com/b", 1).await;
+        throttle.consume("https://b.example.com/a", 1).await;
+
+        // 每个主机使用单独的额度
+        let hosts = throttle.hosts.lock().unwrap();
+        assert_eq!(hosts.len(), 2);
+        assert!(hosts.contains_key("a.example.com"));
+        assert!(hosts.contains_key("b.example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_connect_per_host() {
+        let throttle = Throttle::new(
+            &NetworkLimits {
+                max_connections_per_host: Some(1),
+                ..Default::default()
+            },
+            4,
+        );
+
+        let a = throttle.connect("https://a.example.com/x").await;
+        // 其他主机的连接不受影响
+        let _b = throttle.connect("https://b.example.com/x").await;
+
+        let sem = throttle.connections.lock().unwrap()["a.example.com"].clone();
+        assert_eq!(sem.available_permits(), 0);
+        drop(a);
+        assert_eq!(sem.available_permits(), 1);
+    }
+}
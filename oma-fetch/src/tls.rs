@@ -0,0 +1,181 @@
+//! TLS settings following APT's `Acquire::https::<host>::CaInfo`, `SslCert`, `SslKey` and
+//! `Verify-Peer`.
+//!
+//! A reqwest client has only one TLS configuration, so requests to hosts with their own settings
+//! are sent through dedicated clients by [`HostTlsMiddleware`].
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use ahash::AHashMap;
+use http::Extensions;
+use reqwest::{Certificate, ClientBuilder, Identity, Request, Response};
+use reqwest_middleware::{Middleware, Next};
+use snafu::{ResultExt, Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum TlsError {
+    #[snafu(display("Failed to read {}", path.display()))]
+    ReadFile { path: PathBuf, source: io::Error },
+    #[snafu(display("Invalid CA certificates in {}", path.display()))]
+    InvalidCaInfo {
+        path: PathBuf,
+        source: reqwest::Error,
+    },
+    #[snafu(display("Invalid client certificate or key in {}", path.display()))]
+    InvalidClientCert {
+        path: PathBuf,
+        source: reqwest::Error,
+    },
+    #[snafu(display("Failed to create HTTP client for {host}"))]
+    BuildClient {
+        host: String,
+        source: reqwest::Error,
+    },
+}
+
+// 异步与阻塞的 ClientBuilder 方法相同但没有共同的 trait
+macro_rules! apply_tls {
+    ($settings:expr, $builder:expr) => {{
+        let settings = $settings;
+        let mut builder = $builder;
+
+        if let Some(path) = &settings.ca_info {
+            let certs =
+                Certificate::from_pem_bundle(&read(path)?).context(InvalidCaInfoSnafu { path })?;
+
+            builder = builder.tls_certs_only(certs);
+        }
+
+        if let Some(cert) = &settings.ssl_cert {
+            let key = settings.ssl_key.as_deref().unwrap_or(cert);
+            builder = builder.identity(identity(cert, key)?);
+        }
+
+        if settings.verify_peer == Some(false) {
+            builder = builder.tls_danger_accept_invalid_certs(true);
+        }
+
+        if settings.verify_host == Some(false) {
+            builder = builder.tls_danger_accept_invalid_hostnames(true);
+        }
+
+        Ok(builder)
+    }};
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsSettings {
+    /// PEM bundle of the only CAs to trust, like `CaInfo`
+    pub ca_info: Option<PathBuf>,
+    /// PEM client certificate, like `SslCert`
+    pub ssl_cert: Option<PathBuf>,
+    /// PEM private key of the client certificate, like `SslKey`, defaults to the certificate file
+    pub ssl_key: Option<PathBuf>,
+    /// Verify the certificate of the server, like `Verify-Peer`
+    pub verify_peer: Option<bool>,
+    /// Verify that the certificate matches the host name, like `Verify-Host`
+    pub verify_host: Option<bool>,
+}
+
+impl TlsSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Fill the unset settings with the ones of `other`
+    pub fn or(self, other: &TlsSettings) -> Self {
+        Self {
+            ca_info: self.ca_info.or_else(|| other.ca_info.clone()),
+            ssl_cert: self.ssl_cert.or_else(|| other.ssl_cert.clone()),
+            ssl_key: self.ssl_key.or_else(|| other.ssl_key.clone()),
+            verify_peer: self.verify_peer.or(other.verify_peer),
+            verify_host: self.verify_host.or(other.verify_host),
+        }
+    }
+
+    /// Apply these settings to a client
+    pub fn apply(&self, builder: ClientBuilder) -> Result<ClientBuilder, TlsError> {
+        apply_tls!(self, builder)
+    }
+
+    /// Apply these settings to a blocking client
+    #[cfg(feature = "blocking")]
+    pub fn apply_blocking(
+        &self,
+        builder: reqwest::blocking::ClientBuilder,
+    ) -> Result<reqwest::blocking::ClientBuilder, TlsError> {
+        apply_tls!(self, builder)
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, TlsError> {
+    fs::read(path).context(ReadFileSnafu { path })
+}
+
+#[cfg(feature = "rustls")]
+fn identity(cert: &Path, key: &Path) -> Result<Identity, TlsError> {
+    let mut pem = read(cert)?;
+
+    if key != cert {
+        pem.push(b'\n');
+        pem.extend(read(key)?);
+    }
+
+    Identity::from_pem(&pem).context(InvalidClientCertSnafu { path: cert })
+}
+
+#[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+fn identity(cert: &Path, key: &Path) -> Result<Identity, TlsError> {
+    Identity::from_pkcs8_pem(&read(cert)?, &read(key)?)
+        .context(InvalidClientCertSnafu { path: cert })
+}
+
+/// Send requests to hosts with their own TLS settings through dedicated clients
+pub struct HostTlsMiddleware {
+    clients: AHashMap<String, reqwest::Client>,
+}
+
+impl HostTlsMiddleware {
+    /// Create the clients for `hosts` with `builder`, which returns a client builder
+    /// with all other settings
+    pub fn new(
+        hosts: &AHashMap<String, TlsSettings>,
+        builder: impl Fn() -> ClientBuilder,
+    ) -> Result<Self, TlsError> {
+        let mut clients = AHashMap::new();
+
+        for (host, settings) in hosts {
+            let client = settings
+                .apply(builder())?
+                .build()
+                .context(BuildClientSnafu { host })?;
+
+            clients.insert(host.to_string(), client);
+        }
+
+        Ok(Self { clients })
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for HostTlsMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let client = (req.url().scheme() == "https")
+            .then(|| req.url().host_str())
+            .flatten()
+            .and_then(|host| self.clients.get(host));
+
+        match client {
+            Some(client) => client.execute(req).await.map_err(Into::into),
+            None => next.run(req, extensions).await,
+        }
+    }
+}
@@ -5,7 +5,7 @@ use std::{collections::HashMap, env::args, path::Path};
 use migrations::create_and_maybe_migration_from_oma_db_v2;
 use oma_pm_operation_type::{InstallOperation, OmaOperation, RemoveTag};
 use rusqlite::{Connection, Error, OpenFlags, Result};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use spdlog::debug;
 use thiserror::Error;
 
@@ -39,6 +39,8 @@ pub enum HistoryError {
     FailedParentPath(String),
     #[error("Has no upgrade system log in this machine")]
     NoUpgradeSystemLog,
+    #[error("Failed to serialize unattended upgrade report")]
+    SerializeReport(serde_json::Error),
 }
 
 pub const DATABASE_PATH: &str = "var/lib/oma/history.db";
@@ -62,7 +64,7 @@ pub struct HistoryInfo<'a> {
     pub topics_disabled: Vec<String>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize)]
 pub struct HistoryEntry {
     pub id: i64,
     pub time: i64,
@@ -77,6 +79,12 @@ pub struct HistoryEntry {
     pub is_undo: bool,
 }
 
+pub struct SnapshotHistoryEntry {
+    pub stage: String,
+    pub backend: String,
+    pub snapshot_id: String,
+}
+
 #[derive(Deserialize)]
 pub struct InstallHistoryEntry {
     #[serde(rename = "name")]
@@ -109,6 +117,50 @@ pub struct RemoveHistoryEntry {
     pub tags: Vec<RemoveTag>,
 }
 
+/// Outcome of an unattended upgrade run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnattendedStatus {
+    Success,
+    Failed,
+    NothingToDo,
+    OnBattery,
+    WouldRemove,
+}
+
+/// Why an unattended upgrade run kept back a package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnattendedSkipReason {
+    Denied,
+    Origin,
+    NotSecurity,
+    ModifiedConffile,
+    Dependency,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnattendedPackage {
+    pub name: String,
+    pub old_version: Option<String>,
+    pub new_version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnattendedSkip {
+    pub name: String,
+    pub reason: UnattendedSkipReason,
+}
+
+/// Structured report of an unattended upgrade run, stored as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnattendedReport {
+    pub status: UnattendedStatus,
+    pub upgraded: Vec<UnattendedPackage>,
+    pub skipped: Vec<UnattendedSkip>,
+    pub removed: Vec<String>,
+}
+
 pub struct History {
     connection: Connection,
     dry_run: bool,
@@ -331,6 +383,106 @@ impl History {
         Ok(())
     }
 
+    pub fn write_snapshot(
+        &mut self,
+        id: i64,
+        stage: &str,
+        backend: &str,
+        snapshot_id: &str,
+    ) -> HistoryResult<()> {
+        if self.dry_run {
+            debug!("In dry-run mode, oma will not write history entries");
+            return Ok(());
+        }
+
+        self.connection
+            .execute(
+                r#"INSERT INTO "history_snapshot_oma_1.14" (history_id, stage, backend, snapshot_id)
+                VALUES (?1, ?2, ?3, ?4)"#,
+                (id, stage, backend, snapshot_id),
+            )
+            .map_err(HistoryError::ExecuteError)?;
+
+        Ok(())
+    }
+
+    pub fn write_unattended_report(
+        &mut self,
+        time: i64,
+        report: &UnattendedReport,
+    ) -> HistoryResult<()> {
+        if self.dry_run {
+            debug!("In dry-run mode, oma will not write history entries");
+            return Ok(());
+        }
+
+        let json = serde_json::to_string(report).map_err(HistoryError::SerializeReport)?;
+        let status = serde_json::to_value(report.status)
+            .ok()
+            .and_then(|v| v.as_str().map(|s| s.to_string()))
+            .unwrap_or_default();
+
+        self.connection
+            .execute(
+                r#"INSERT INTO "history_unattended_oma_1.14" (time, status, report)
+                VALUES (?1, ?2, ?3)"#,
+                (time, status, json),
+            )
+            .map_err(HistoryError::ExecuteError)?;
+
+        Ok(())
+    }
+
+    pub fn list_unattended_reports(&self) -> HistoryResult<Vec<(i64, UnattendedReport)>> {
+        let mut stmt = self
+            .connection
+            .prepare(r#"SELECT time, report FROM "history_unattended_oma_1.14" ORDER BY time DESC"#)
+            .map_err(HistoryError::ExecuteError)?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(HistoryError::ExecuteError)?
+            .collect::<Result<Vec<_>>>()
+            .map_err(HistoryError::ParseDbError)?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(time, report)| match serde_json::from_str(&report) {
+                Ok(report) => Some((time, report)),
+                Err(e) => {
+                    debug!("Ignoring malformed unattended report at {time}: {e}");
+                    None
+                }
+            })
+            .collect())
+    }
+
+    pub fn find_history_snapshots_by_id(
+        &self,
+        id: i64,
+    ) -> HistoryResult<Vec<SnapshotHistoryEntry>> {
+        let mut query_snapshot_table = self
+            .connection
+            .prepare(
+                r#"SELECT stage, backend, snapshot_id FROM "history_snapshot_oma_1.14" WHERE history_id = (?1)"#,
+            )
+            .map_err(HistoryError::ExecuteError)?;
+
+        query_snapshot_table
+            .query_map([id], |row| {
+                Ok(SnapshotHistoryEntry {
+                    stage: row.get(0)?,
+                    backend: row.get(1)?,
+                    snapshot_id: row.get(2)?,
+                })
+            })
+            .map_err(HistoryError::ExecuteError)?
+            .collect::<Result<Vec<_>>>()
+            .map_err(HistoryError::ParseDbError)
+    }
+
     pub fn list(&self) -> HistoryResult<Vec<HistoryEntry>> {
         let mut res = vec![];
         let stmt = self.connection.prepare(
@@ -414,5 +414,26 @@ fn create_history_table(conn: &Connection) -> HistoryResult<()> {
     )
     .map_err(HistoryError::ExecuteError)?;
 
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS \"history_snapshot_oma_1.14\" (
+            history_id INTEGER NOT NULL,
+            stage TEXT NOT NULL,
+            backend TEXT NOT NULL,
+            snapshot_id TEXT NOT NULL
+        )",
+        (),
+    )
+    .map_err(HistoryError::ExecuteError)?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS \"history_unattended_oma_1.14\" (
+            time INTEGER NOT NULL,
+            status TEXT NOT NULL,
+            report TEXT NOT NULL
+        )",
+        (),
+    )
+    .map_err(HistoryError::ExecuteError)?;
+
     Ok(())
 }
@@ -41,6 +41,10 @@ fn main() -> Result<(), OmaAptError> {
         DownloadConfig {
             network_thread: None,
             download_dir: Some(Path::new("test").into()),
+            delta: false,
+            peers: vec![],
+            limits: Default::default(),
+            redirector_client: None,
         },
         None,
         move |event| {
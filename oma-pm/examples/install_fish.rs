@@ -135,6 +135,11 @@ fn main() -> Result<(), OmaAptError> {
         CommitConfig {
             network_thread: None,
             download_only: false,
+            snapshot: None,
+            delta: false,
+            peers: vec![],
+            limits: Default::default(),
+            redirector_client: None,
         },
         None,
         move |event| {
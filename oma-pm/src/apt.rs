@@ -25,7 +25,7 @@ use oma_apt::{
     util::DiskSpace,
 };
 
-use oma_fetch::{Event, Summary, checksum::ChecksumError};
+use oma_fetch::{Event, Summary, checksum::ChecksumError, throttle::NetworkLimits};
 use oma_utils::{GetLockError, dpkg::DpkgError, human_bytes::HumanBytes, is_termux};
 
 pub use oma_apt::config::Config as AptConfig;
@@ -38,6 +38,7 @@ pub use oma_pm_operation_type::*;
 use oma_utils::zbus::Connection;
 
 use crate::{
+    build_dep::{BuildDeps, Relation},
     commit::{CommitConfig, CustomDownloadMessage, DoInstall},
     dbus::create_session,
     download::download_pkgs,
@@ -45,6 +46,7 @@ use crate::{
     matches::MatcherError,
     pkginfo::{OmaDependency, OmaPackage, OmaPackageWithoutVersion, PtrIsNone},
     progress::InstallProgressManager,
+    snapshot::SnapshotError,
     sort::SummarySort,
     utils::run_task_with_pump,
 };
@@ -125,6 +127,8 @@ pub enum OmaAptError {
     PkgIsEssential(String),
     #[error("Package: {0} has no available candidate.")]
     PkgNoCandidate(String),
+    #[error("Build dependency {0} cannot be satisfied.")]
+    BuildDepUnsatisfiable(String),
     #[error("Package: {0} has no SHA256 checksum.")]
     PkgNoChecksum(String),
     #[error("Package: {0}: {1} is not available from any mirror.")]
@@ -167,11 +171,17 @@ pub enum OmaAptError {
     WrongDpkgStatus(String),
     #[error("Package {0} status field is missing, dpkg status is broken.")]
     DpkgStatusBroken(String),
+    #[error("Failed to extract {0} from {1}: {2}")]
+    ExtractFromDeb(String, String, String),
     #[error(transparent)]
     FailedGetArchiveDirLock(#[from] GetLockError),
     #[error("recv async event error")]
     RecvError,
     #[error(transparent)]
+    Snapshot(#[from] SnapshotError),
+    #[error(transparent)]
+    MirrorList(#[from] oma_fetch::mirror_list::MirrorListError),
+    #[error(transparent)]
     Anyhow(#[from] anyhow::Error),
 }
 
@@ -183,6 +193,14 @@ pub struct DownloadConfig {
     pub network_thread: Option<usize>,
     /// Path to downloaded files/archives.
     pub download_dir: Option<Arc<Path>>,
+    /// Try to rebuild upgraded packages from delta files and installed files.
+    pub delta: bool,
+    /// LAN peers to try before mirrors, see [`oma_fetch::peer`].
+    pub peers: Vec<String>,
+    /// Bandwidth and connection limits of downloads.
+    pub limits: NetworkLimits,
+    /// HTTP client not following redirects, used to query mirror redirectors.
+    pub redirector_client: Option<ClientWithMiddleware>,
 }
 
 pub fn apt_config_get(key: String) -> Option<String> {
@@ -455,6 +473,63 @@ impl OmaApt {
         Ok(no_marked_install)
     }
 
+    /// Mark build dependencies as automatically installed, and remove installed build conflicts
+    pub fn install_build_deps(&mut self, deps: &BuildDeps) -> OmaAptResult<()> {
+        for group in &deps.depends {
+            if group.iter().any(|r| relation_installed(&self.cache, r)) {
+                debug!("Build dependency {group:?} is already satisfied");
+                continue;
+            }
+
+            let Some(ver) = group
+                .iter()
+                .find_map(|r| find_relation_version(&self.cache, r))
+            else {
+                return Err(OmaAptError::BuildDepUnsatisfiable(
+                    group
+                        .iter()
+                        .map(|r| r.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" | "),
+                ));
+            };
+
+            let pkg = ver.parent();
+            ver.set_candidate();
+            pkg.protect();
+
+            // from_user = false：标记为自动安装，构建完成后可以被 autoremove 清理
+            pkg.mark_install(true, false);
+
+            debug!(
+                "Build dependency {} {} marked install: {}",
+                pkg.fullname(true),
+                ver.version(),
+                pkg.marked_install()
+            );
+        }
+
+        for r in &deps.conflicts {
+            let Some(pkg) = self.cache.get(&r.name) else {
+                continue;
+            };
+
+            if pkg
+                .installed()
+                .is_some_and(|inst| r.satisfied_by(inst.version()))
+            {
+                if pkg.is_essential() {
+                    return Err(OmaAptError::PkgIsEssential(pkg.fullname(true)));
+                }
+
+                pkg.mark_delete(false);
+                pkg.protect();
+            }
+        }
+
+        Ok(())
+    }
+
     /// Download packages
     pub fn download<F>(
         &self,
@@ -462,7 +537,7 @@ impl OmaApt {
         pkgs: Vec<OmaPackage>,
         config: DownloadConfig,
         custom_download_message: Option<CustomDownloadMessage>,
-        mut callback: F,
+        callback: F,
     ) -> OmaAptResult<Summary>
     where
         F: FnMut(Event) + 'static,
@@ -507,6 +582,27 @@ impl OmaApt {
             download_list.push(entry);
         }
 
+        self.download_entries(
+            client,
+            download_list,
+            config,
+            custom_download_message,
+            callback,
+        )
+    }
+
+    /// Download packages from install entries, e.g. the result of [`OmaApt::build_transaction`]
+    pub fn download_entries<F>(
+        &self,
+        client: &ClientWithMiddleware,
+        download_list: Vec<InstallEntry>,
+        config: DownloadConfig,
+        custom_download_message: Option<CustomDownloadMessage>,
+        mut callback: F,
+    ) -> OmaAptResult<Summary>
+    where
+        F: FnMut(Event) + 'static,
+    {
         if self.dry_run {
             return Ok(Summary {
                 success: vec![],
@@ -514,7 +610,7 @@ impl OmaApt {
             });
         }
 
-        let download_list = Arc::from(download_list.as_ref());
+        let download_list = Arc::from(download_list);
 
         let client_ptr = client.clone();
         let msg_formatter = custom_download_message.unwrap_or_else(|| {
@@ -813,6 +909,16 @@ impl OmaApt {
         })
     }
 
+    /// Get the package archive of a version from the local package cache, if it exists
+    pub fn cached_archive(&self, package: &str, version: &str, arch: &str) -> Option<PathBuf> {
+        let version = version.replace(':', "%3a");
+        let path = self
+            .get_archive_dir()
+            .join(format!("{package}_{version}_{arch}.deb"));
+
+        path.is_file().then_some(path)
+    }
+
     /// Mark version status (hold/unhold)
     pub fn mark_version_status<'a>(
         &'a self,
@@ -1417,6 +1523,48 @@ fn mark_install(
     Ok(pkg.marked_install())
 }
 
+fn relation_installed(cache: &Cache, r: &Relation) -> bool {
+    let Some(pkg) = cache.get(&r.name) else {
+        return false;
+    };
+
+    if pkg
+        .installed()
+        .is_some_and(|inst| r.satisfied_by(inst.version()))
+    {
+        return true;
+    }
+
+    // 虚包：没有版本约束时，只要有已安装的提供者即可满足
+    r.constraint.is_none() && pkg.provides().any(|p| p.package().is_installed())
+}
+
+fn find_relation_version<'a>(cache: &'a Cache, r: &Relation) -> Option<Version<'a>> {
+    let pkg = cache.get(&r.name)?;
+
+    if let Some(cand) = pkg
+        .candidate()
+        .filter(|c| c.is_downloadable() && r.satisfied_by(c.version()))
+    {
+        return Some(cand);
+    }
+
+    if let Some(ver) = pkg
+        .versions()
+        .find(|v| v.is_downloadable() && r.satisfied_by(v.version()))
+    {
+        return Some(ver);
+    }
+
+    if r.constraint.is_some() {
+        return None;
+    }
+
+    pkg.provides()
+        .filter_map(|p| p.package().candidate())
+        .find(|v| v.is_downloadable())
+}
+
 fn mark_install_inner(pkg: &Package) -> bool {
     // 根据 Packagekit 的源码
     // https://github.com/PackageKit/PackageKit/blob/a0a52ce90adb75a5df7ad1f0b1c9888f2eaf1a7b/backends/apt/apt-job.cpp#L388
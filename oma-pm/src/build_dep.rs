@@ -0,0 +1,369 @@
+//! Build dependencies of source packages, from `Sources` indexes or a local `debian/control`.
+
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use oma_apt::{tagfile::TagSection, util::cmp_versions};
+use spdlog::debug;
+
+const DEPENDS_FIELDS: &[&str] = &["Build-Depends", "Build-Depends-Arch"];
+const CONFLICTS_FIELDS: &[&str] = &["Build-Conflicts", "Build-Conflicts-Arch"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum BuildDepError {
+    #[error("Failed to read file: {0}: {1}")]
+    FailedReadFile(String, io::Error),
+    #[error("Failed to parse {0}: {1}")]
+    ParseControl(String, String),
+    #[error("No source package stanza found in {0}")]
+    NoSourceStanza(String),
+}
+
+pub type BuildDepResult<T> = Result<T, BuildDepError>;
+
+/// A single relation like `debhelper-compat (= 13)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relation {
+    pub name: String,
+    pub constraint: Option<(String, String)>,
+}
+
+impl Relation {
+    fn parse(s: &str) -> Option<Self> {
+        let (name, constraint) = match s.split_once('(') {
+            Some((name, rest)) => {
+                let rest = rest.trim_end().strip_suffix(')')?.trim();
+                let op_len = rest
+                    .find(|c: char| !matches!(c, '<' | '>' | '='))
+                    .unwrap_or(rest.len());
+                let (op, version) = rest.split_at(op_len);
+
+                if !matches!(op, "<<" | "<=" | "=" | ">=" | ">>") || version.trim().is_empty() {
+                    return None;
+                }
+
+                (name, Some((op.to_string(), version.trim().to_string())))
+            }
+            None => (s, None),
+        };
+
+        let name = name.trim();
+
+        // `:any` 和 `:native` 对于本机构建而言没有区别，直接按包名查找
+        let name = name
+            .strip_suffix(":any")
+            .or_else(|| name.strip_suffix(":native"))
+            .unwrap_or(name);
+
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return None;
+        }
+
+        Some(Self {
+            name: name.to_string(),
+            constraint,
+        })
+    }
+
+    /// Whether `version` satisfies the version constraint of this relation.
+    pub fn satisfied_by(&self, version: &str) -> bool {
+        let Some((op, want)) = &self.constraint else {
+            return true;
+        };
+
+        let Ok(ord) = cmp_versions(version, want) else {
+            return false;
+        };
+
+        match op.as_str() {
+            "<<" => ord == Ordering::Less,
+            "<=" => ord != Ordering::Greater,
+            "=" => ord == Ordering::Equal,
+            ">=" => ord != Ordering::Less,
+            ">>" => ord == Ordering::Greater,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Relation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.constraint {
+            Some((op, version)) => write!(f, "{} ({op} {version})", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Build dependencies of a source package, restricted to one architecture and build profile set.
+#[derive(Debug, Clone)]
+pub struct BuildDeps {
+    pub source: String,
+    pub version: Option<String>,
+    /// Each group is satisfied by any one of its alternatives.
+    pub depends: Vec<Vec<Relation>>,
+    pub conflicts: Vec<Relation>,
+}
+
+impl BuildDeps {
+    /// Read build dependencies from a `debian/control` file.
+    pub fn from_control(
+        path: impl AsRef<Path>,
+        arch: &str,
+        profiles: &[String],
+    ) -> BuildDepResult<Self> {
+        let path = path.as_ref();
+        let display = path.display().to_string();
+
+        let s = fs::read_to_string(path)
+            .map_err(|e| BuildDepError::FailedReadFile(display.clone(), e))?;
+
+        // debian/control 允许以 `#` 开头的注释行
+        let s = s
+            .lines()
+            .filter(|l| !l.starts_with('#'))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // 第一段是源码包的信息，其余为二进制包
+        let stanza = stanzas(&s)
+            .next()
+            .ok_or_else(|| BuildDepError::NoSourceStanza(display.clone()))?;
+
+        let section = TagSection::new(stanza)
+            .map_err(|e| BuildDepError::ParseControl(display.clone(), e.to_string()))?;
+
+        let source = section
+            .get("Source")
+            .ok_or(BuildDepError::NoSourceStanza(display))?;
+
+        Ok(Self::from_section(source, &section, arch, profiles))
+    }
+
+    /// Find the newest source package called `name` in the `*_Sources` indexes under `lists_dir`.
+    pub fn from_sources_index(
+        lists_dir: impl AsRef<Path>,
+        name: &str,
+        arch: &str,
+        profiles: &[String],
+    ) -> BuildDepResult<Option<Self>> {
+        let mut newest: Option<(String, TagSection)> = None;
+
+        for path in sources_indexes(lists_dir.as_ref())? {
+            let display = path.display().to_string();
+            let s = fs::read_to_string(&path)
+                .map_err(|e| BuildDepError::FailedReadFile(display.clone(), e))?;
+
+            let package_line = format!("Package: {name}");
+
+            for stanza in stanzas(&s).filter(|s| s.lines().any(|l| l.trim_end() == package_line)) {
+                let section = TagSection::new(stanza)
+                    .map_err(|e| BuildDepError::ParseControl(display.clone(), e.to_string()))?;
+
+                let Some(version) = section.get("Version") else {
+                    continue;
+                };
+
+                if newest.as_ref().is_none_or(|(v, _)| {
+                    cmp_versions(version, v).is_ok_and(|o| o == Ordering::Greater)
+                }) {
+                    newest = Some((version.to_string(), section));
+                }
+            }
+        }
+
+        Ok(newest.map(|(version, section)| {
+            debug!("Found source package {name} {version}");
+            let mut deps = Self::from_section(name, &section, arch, profiles);
+            deps.version = Some(version);
+            deps
+        }))
+    }
+
+    fn from_section(source: &str, section: &TagSection, arch: &str, profiles: &[String]) -> Self {
+        let field = |fields: &[&str]| {
+            fields
+                .iter()
+                .filter_map(|f| section.get(f))
+                .flat_map(|v| parse_relations(v, arch, profiles))
+                .collect::<Vec<_>>()
+        };
+
+        Self {
+            source: source.to_string(),
+            version: section.get("Version").map(|v| v.to_string()),
+            depends: field(DEPENDS_FIELDS),
+            conflicts: field(CONFLICTS_FIELDS).into_iter().flatten().collect(),
+        }
+    }
+}
+
+fn stanzas(s: &str) -> impl Iterator<Item = &str> {
+    s.split("\n\n")
+        .map(|s| s.trim_matches('\n'))
+        .filter(|s| !s.trim().is_empty())
+}
+
+fn sources_indexes(lists_dir: &Path) -> BuildDepResult<Vec<PathBuf>> {
+    let dir = fs::read_dir(lists_dir)
+        .map_err(|e| BuildDepError::FailedReadFile(lists_dir.display().to_string(), e))?;
+
+    Ok(dir
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .is_some_and(|n| n.to_string_lossy().ends_with("_Sources"))
+        })
+        .collect())
+}
+
+/// Parse a relation field like `Build-Depends`, dropping alternatives that do not
+/// apply to `arch` or the active build `profiles`.
+pub fn parse_relations(s: &str, arch: &str, profiles: &[String]) -> Vec<Vec<Relation>> {
+    let s = s
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    s.split(',')
+        .map(|group| {
+            group
+                .split('|')
+                .filter_map(|alt| {
+                    let (rest, profile_ok) = take_profiles(alt, profiles);
+                    let (rest, arch_ok) = take_archs(rest, arch);
+
+                    if !profile_ok || !arch_ok {
+                        return None;
+                    }
+
+                    Relation::parse(rest)
+                })
+                .collect::<Vec<_>>()
+        })
+        .filter(|group| !group.is_empty())
+        .collect()
+}
+
+/// Strip `<!nocheck> <stage1>` restriction lists, return whether any of them matches.
+fn take_profiles<'a>(s: &'a str, profiles: &[String]) -> (&'a str, bool) {
+    // 版本约束 `(<< 2.0)` 中也有 `<`，只在其后查找
+    let from = s.rfind(')').map_or(0, |i| i + 1);
+
+    let Some(start) = s[from..].find('<').map(|i| i + from) else {
+        return (s, true);
+    };
+
+    let (rest, restrictions) = s.split_at(start);
+
+    let matched = restrictions
+        .split('<')
+        .filter_map(|r| r.split_once('>').map(|(r, _)| r))
+        .any(|list| {
+            list.split_whitespace()
+                .all(|term| match term.strip_prefix('!') {
+                    Some(term) => !profiles.iter().any(|p| p == term),
+                    None => profiles.iter().any(|p| p == term),
+                })
+        });
+
+    (rest, matched)
+}
+
+/// Strip an `[amd64 !i386]` architecture restriction, return whether it matches `arch`.
+fn take_archs<'a>(s: &'a str, arch: &str) -> (&'a str, bool) {
+    let Some((rest, archs)) = s.split_once('[') else {
+        return (s, true);
+    };
+
+    let archs = archs.split(']').next().unwrap_or_default();
+    let mut negated = false;
+    let mut matched = false;
+
+    for a in archs.split_whitespace() {
+        let (a, neg) = match a.strip_prefix('!') {
+            Some(a) => (a, true),
+            None => (a, false),
+        };
+
+        negated |= neg;
+
+        if arch_matches(a, arch) {
+            matched = true;
+        }
+    }
+
+    // `[!i386 !armel]` 表示除列出的架构之外都需要
+    (rest, matched != negated)
+}
+
+fn arch_matches(pattern: &str, arch: &str) -> bool {
+    pattern == arch
+        || pattern == "any"
+        || pattern == "linux-any"
+        || pattern.strip_prefix("any-") == Some(arch)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rel(name: &str, constraint: Option<(&str, &str)>) -> Relation {
+        Relation {
+            name: name.to_string(),
+            constraint: constraint.map(|(op, v)| (op.to_string(), v.to_string())),
+        }
+    }
+
+    #[test]
+    fn test_parse_relations() {
+        let field = "debhelper-compat (= 13),\n libold-dev (<< 2.0) <!stage1>,\n libfoo-dev [amd64 arm64] | libbar-dev,\n python3:any,\n \
+                     check <!nocheck>,\n libi386-dev [!amd64],\n # comment\n doc-tool <stage1>";
+
+        assert_eq!(
+            parse_relations(field, "amd64", &[]),
+            vec![
+                vec![rel("debhelper-compat", Some(("=", "13")))],
+                vec![rel("libold-dev", Some(("<<", "2.0")))],
+                vec![rel("libfoo-dev", None), rel("libbar-dev", None)],
+                vec![rel("python3", None)],
+                vec![rel("check", None)],
+            ]
+        );
+
+        assert_eq!(
+            parse_relations(field, "riscv64", &["nocheck".to_string()]),
+            vec![
+                vec![rel("debhelper-compat", Some(("=", "13")))],
+                vec![rel("libold-dev", Some(("<<", "2.0")))],
+                vec![rel("libbar-dev", None)],
+                vec![rel("python3", None)],
+                vec![rel("libi386-dev", None)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_control() {
+        let path = std::env::temp_dir().join(format!("oma-build-dep-test-{}", std::process::id()));
+        fs::write(
+            &path,
+            "Source: foo\nBuild-Depends: bar (>= 1.0)\nBuild-Conflicts: baz\n\nPackage: foo\nDepends: qux\n",
+        )
+        .unwrap();
+
+        let deps = BuildDeps::from_control(&path, "amd64", &[]).unwrap();
+
+        assert_eq!(deps.source, "foo");
+        assert_eq!(deps.depends, vec![vec![rel("bar", Some((">=", "1.0")))]]);
+        assert_eq!(deps.conflicts, vec![rel("baz", None)]);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -12,11 +12,11 @@ use oma_apt::{
     progress::{AcquireProgress, InstallProgress},
     util::{apt_lock_inner, apt_unlock, apt_unlock_inner},
 };
-use oma_fetch::{Event, Summary};
+use oma_fetch::{Event, Summary, throttle::NetworkLimits};
 use oma_pm_operation_type::{InstallEntry, OmaOperation};
 use oma_utils::get_file_lock;
 use reqwest_middleware::ClientWithMiddleware;
-use spdlog::debug;
+use spdlog::{debug, warn};
 use std::io::Write;
 
 use crate::{
@@ -24,6 +24,7 @@ use crate::{
     dbus::change_status,
     download::download_pkgs,
     progress::{InstallProgressArgs, OmaAptInstallProgress},
+    snapshot::{SnapshotHook, SnapshotStage},
     utils::run_task_with_pump,
 };
 
@@ -32,6 +33,16 @@ const TIME_FORMAT: &str = "%H:%M:%S on %Y-%m-%d";
 pub struct CommitConfig {
     pub network_thread: Option<usize>,
     pub download_only: bool,
+    /// Take system snapshots before and after installing packages.
+    pub snapshot: Option<SnapshotHook>,
+    /// Try to download delta files instead of full packages for upgrades.
+    pub delta: bool,
+    /// LAN peers to try before mirrors.
+    pub peers: Vec<String>,
+    /// Bandwidth and connection limits of downloads.
+    pub limits: NetworkLimits,
+    /// HTTP client not following redirects, used to query mirror redirectors.
+    pub redirector_client: Option<ClientWithMiddleware>,
 }
 
 pub struct DoInstall<'a> {
@@ -62,7 +73,7 @@ impl<'a> DoInstall<'a> {
     }
 
     pub fn commit<F>(
-        self,
+        mut self,
         op: &OmaOperation,
         install_progress_manager: InstallProgressOpt,
         custom_download_message: CustomDownloadMessage,
@@ -80,7 +91,20 @@ impl<'a> DoInstall<'a> {
         }
 
         if !self.config.download_only {
+            let sysroot = Path::new(self.sysroot);
+            let mut snapshot = self.config.snapshot.take();
+
+            if let Some(hook) = &mut snapshot {
+                hook.run(sysroot, SnapshotStage::Pre)?;
+            }
+
             self.do_install(install_progress_manager, op)?;
+
+            if let Some(hook) = &mut snapshot
+                && let Err(e) = hook.run(sysroot, SnapshotStage::Post)
+            {
+                warn!("Failed to take snapshot after commit: {e}");
+            }
         }
 
         apt_unlock();
@@ -112,6 +136,11 @@ impl<'a> DoInstall<'a> {
         let config = DownloadConfig {
             network_thread: self.config.network_thread,
             download_dir: Some(Arc::from(path)),
+            // debpatch 只能从当前系统中已安装的文件重建软件包
+            delta: self.config.delta && Path::new(self.sysroot) == Path::new("/"),
+            peers: self.config.peers.clone(),
+            limits: self.config.limits,
+            redirector_client: self.config.redirector_client.clone(),
         };
         let client_ptr = self.client.clone();
 
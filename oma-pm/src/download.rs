@@ -1,20 +1,28 @@
-use std::{path::Path, sync::Arc};
+use std::{collections::HashMap, path::Path, sync::Arc};
 
 use flume::Sender;
+use oma_apt::raw::config as apt_config;
 use oma_fetch::{
     DownloadEntry, DownloadManager, DownloadSource, DownloadSourceType, Event, Summary,
     checksum::Checksum,
+    download::SuccessSummary,
+    mirror_list::{MirrorList, MirrorType, fetch_mirror_list, is_mirror_url},
+    resolve_cdrom_url,
+    throttle::NetworkLimits,
 };
-use oma_pm_operation_type::InstallEntry;
+use oma_pm_operation_type::{InstallEntry, InstallOperation};
 use oma_utils::url_no_escape::url_no_escape_times;
 use reqwest_middleware::ClientWithMiddleware;
 use spdlog::debug;
+use tokio::process::Command;
 
 use crate::{
     CustomDownloadMessage,
     apt::{DownloadConfig, OmaAptError, OmaAptResult},
 };
 
+const DEBPATCH: &str = "debpatch";
+
 /// Download packages (inner)
 pub async fn download_pkgs(
     client: ClientWithMiddleware,
@@ -27,6 +35,10 @@ pub async fn download_pkgs(
     let DownloadConfig {
         network_thread,
         download_dir,
+        delta,
+        peers,
+        limits,
+        redirector_client,
     } = config;
 
     debug!(
@@ -45,25 +57,98 @@ pub async fn download_pkgs(
     }
 
     let mut download_list = vec![];
+    // download_list 中各项在 download_pkg_list 中的位置
+    let mut positions = vec![];
     let mut total_size = 0;
+    let mut mirror_lists: HashMap<String, MirrorList> = HashMap::new();
+    let cdrom_mount_point = apt_config::find(
+        "Acquire::cdrom::mount".to_string(),
+        "/media/cdrom/".to_string(),
+    );
 
-    for entry in download_pkg_list.iter() {
-        let uris = entry.pkg_urls();
-        let sources = uris
-            .iter()
-            .map(|x| {
-                let (source_type, url) = if x.index_url.starts_with("file:") {
-                    (
-                        DownloadSourceType::Local(!download_only),
-                        url_no_escape_times(&x.download_url, 1),
-                    )
-                } else {
-                    (DownloadSourceType::Http, x.download_url.clone())
+    let rebuilt = if delta && !download_only {
+        download_deltas(
+            &client,
+            &download_pkg_list,
+            download_dir.as_deref().unwrap_or(Path::new(".")),
+            network_thread,
+            limits,
+            &tx,
+        )
+        .await
+    } else {
+        vec![]
+    };
+
+    for (idx, entry) in download_pkg_list.iter().enumerate() {
+        if rebuilt.iter().any(|x| x.index == idx) {
+            continue;
+        }
+
+        let mut sources = vec![];
+
+        for x in entry.pkg_urls() {
+            if is_mirror_url(&x.index_url) {
+                let Some(path) = x.download_url.strip_prefix(&x.index_url) else {
+                    debug!("{} is not in {}", x.download_url, x.index_url);
+                    continue;
                 };
 
-                DownloadSource { url, source_type }
-            })
-            .collect::<Vec<_>>();
+                if !mirror_lists.contains_key(&x.index_url) {
+                    let list = fetch_mirror_list(&client, &x.index_url).await?;
+                    mirror_lists.insert(x.index_url.clone(), list);
+                }
+
+                let arch = Some(entry.arch()).filter(|arch| *arch != "all");
+
+                sources.extend(mirror_lists[&x.index_url].sources(
+                    path,
+                    arch,
+                    MirrorType::Deb,
+                    !download_only,
+                ));
+
+                continue;
+            }
+
+            let url = resolve_cdrom_url(&x.download_url, &cdrom_mount_point)
+                .unwrap_or_else(|| x.download_url.clone());
+
+            let Some(source_type) = DownloadSourceType::from_url(&url, !download_only) else {
+                debug!("Unsupported package URL: {url}");
+                continue;
+            };
+
+            sources.push(DownloadSource { url, source_type });
+        }
+
+        // 镜像列表中可能没有适用于该架构或类型的镜像，此时没有可用的下载源
+        if sources.is_empty() {
+            return Err(OmaAptError::PkgUnavailable(
+                entry.name().to_string(),
+                entry.new_version().to_string(),
+            ));
+        }
+
+        // 本地文件路径不能包含 URL 转义
+        for source in &mut sources {
+            if let DownloadSourceType::Local(_) = source.source_type {
+                source.url = url_no_escape_times(&source.url, 1);
+            }
+        }
+
+        // 只有能通过校验和验证的软件包才能从局域网内的其他机器下载
+        if entry.sha256().is_some()
+            && sources
+                .iter()
+                .all(|x| x.source_type == DownloadSourceType::Http)
+        {
+            let filename = apt_style_filename(entry);
+            sources.extend(peers.iter().map(|peer| DownloadSource {
+                url: format!("{peer}/{filename}"),
+                source_type: DownloadSourceType::Peer,
+            }));
+        }
 
         debug!("Sources is: {:?}", sources);
 
@@ -77,6 +162,7 @@ pub async fn download_pkgs(
         let download_entry = DownloadEntry::builder()
             .source(sources)
             .filename(apt_style_filename(entry))
+            .size(entry.download_size())
             .allow_resume(true)
             .msg(msg)
             .maybe_hash({
@@ -103,12 +189,15 @@ pub async fn download_pkgs(
         total_size += entry.download_size();
 
         download_list.push(download_entry);
+        positions.push(idx);
     }
 
     let downloader = DownloadManager::builder()
         .client(client.clone())
+        .maybe_redirector_client(redirector_client)
         .download_list(download_list.into())
         .maybe_threads(network_thread)
+        .limits(limits)
         .total_size(total_size)
         .build();
 
@@ -122,15 +211,206 @@ pub async fn download_pkgs(
             }
         })
         .await
-        .unwrap();
+        .map_err(anyhow::Error::from)?;
 
     if !res.is_download_success() {
         return Err(OmaAptError::FailedToDownload(res.failed.len()));
     }
 
+    let mut res = res;
+
+    for s in &mut res.success {
+        s.index = positions[s.index];
+    }
+
+    res.success.extend(rebuilt);
+
     Ok(res)
 }
 
+/// Download delta files for upgrades and rebuild new packages from installed files with `debpatch`.
+///
+/// Returns packages rebuilt and verified successfully, others should be downloaded in full.
+async fn download_deltas(
+    client: &ClientWithMiddleware,
+    download_pkg_list: &[InstallEntry],
+    download_dir: &Path,
+    network_thread: Option<usize>,
+    limits: NetworkLimits,
+    tx: &Sender<Event>,
+) -> Vec<SuccessSummary> {
+    if Command::new(DEBPATCH).arg("--help").output().await.is_err() {
+        debug!("{DEBPATCH} is not available, skip delta download");
+        return vec![];
+    }
+
+    let delta_dir = download_dir.join("partial").join("deltas");
+    let mut candidates = vec![];
+    let mut download_list = vec![];
+
+    for (idx, entry) in download_pkg_list.iter().enumerate() {
+        // 只有升级时才有已安装的旧版本文件可用于重建，且重建结果必须能用 SHA256 校验
+        if *entry.op() != InstallOperation::Upgrade || entry.sha256().is_none() {
+            continue;
+        }
+
+        let Some(old_version) = entry.old_version() else {
+            continue;
+        };
+
+        let Some(url) = entry.pkg_urls().iter().find(|x| {
+            DownloadSourceType::from_url(&x.download_url, false) == Some(DownloadSourceType::Http)
+        }) else {
+            continue;
+        };
+
+        let Some(delta_url) = delta_url(&url.download_url, entry, old_version) else {
+            continue;
+        };
+
+        let filename = delta_url.rsplit_once('/').unwrap().1.to_string();
+
+        download_list.push(
+            DownloadEntry::builder()
+                .source(vec![DownloadSource {
+                    url: delta_url,
+                    source_type: DownloadSourceType::Http,
+                }])
+                .filename(filename.clone())
+                .dir(delta_dir.clone())
+                .allow_resume(false)
+                .build(),
+        );
+
+        candidates.push((idx, filename));
+    }
+
+    if download_list.is_empty() {
+        return vec![];
+    }
+
+    let downloader = DownloadManager::builder()
+        .client(client.clone())
+        .download_list(download_list.into())
+        .maybe_threads(network_thread)
+        .limits(limits)
+        .build();
+
+    let tx = tx.clone();
+
+    // 镜像源上没有对应的增量包是正常现象，因此只向用户报告下载进度，不报告错误
+    let summary = match downloader
+        .start_download(move |event| {
+            let tx = tx.clone();
+            async move {
+                match event {
+                    Event::NewProgressSpinner { .. }
+                    | Event::NewProgressBar { .. }
+                    | Event::ProgressInc { .. }
+                    | Event::ProgressDone(_)
+                    | Event::Throttled { .. }
+                    | Event::DownloadDone { .. } => {
+                        let _ = tx.send_async(event).await;
+                    }
+                    event => debug!("Delta download event: {event:?}"),
+                }
+            }
+        })
+        .await
+    {
+        Ok(summary) => summary,
+        Err(e) => {
+            debug!("Failed to download deltas: {e}");
+            return vec![];
+        }
+    };
+
+    let mut res = vec![];
+
+    for s in summary.success {
+        let Some((idx, delta)) = candidates.iter().find(|(_, f)| *f == s.file_name) else {
+            continue;
+        };
+
+        let entry = &download_pkg_list[*idx];
+        let delta_path = delta_dir.join(delta);
+        let filename = apt_style_filename(entry);
+
+        match rebuild_from_delta(entry, &delta_path, download_dir, &filename).await {
+            Ok(()) => res.push(SuccessSummary {
+                file_name: filename,
+                index: *idx,
+                wrote: true,
+                url: s.url,
+            }),
+            Err(e) => debug!("Failed to rebuild {filename} from delta, fall back: {e}"),
+        }
+
+        tokio::fs::remove_file(&delta_path).await.ok();
+    }
+
+    res
+}
+
+/// Get delta file URL next to the package in the pool, like `debdelta` naming:
+/// `{pkg}_{old_version}_{new_version}_{arch}.debdelta`
+fn delta_url(download_url: &str, entry: &InstallEntry, old_version: &str) -> Option<String> {
+    let (dir, _) = download_url.rsplit_once('/')?;
+
+    Some(format!(
+        "{dir}/{}_{}_{}_{}.debdelta",
+        entry.name_without_arch(),
+        old_version.replace(':', "%3a"),
+        entry.new_version().replace(':', "%3a"),
+        entry.arch()
+    ))
+}
+
+async fn rebuild_from_delta(
+    entry: &InstallEntry,
+    delta: &Path,
+    download_dir: &Path,
+    filename: &str,
+) -> Result<(), String> {
+    let partial = download_dir.join("partial").join(filename);
+
+    let output = Command::new(DEBPATCH)
+        .arg(delta)
+        .arg("/")
+        .arg(&partial)
+        .output()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        tokio::fs::remove_file(&partial).await.ok();
+        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    }
+
+    // 重建出的软件包必须与源中的完全一致，否则回退到完整下载
+    let checksum = entry
+        .sha256()
+        .ok_or("no SHA256 checksum")
+        .and_then(|c| Checksum::from_sha256_str(c).map_err(|_| "bad SHA256 checksum"));
+
+    let checksum = match checksum {
+        Ok(checksum) => checksum,
+        Err(e) => {
+            tokio::fs::remove_file(&partial).await.ok();
+            return Err(e.to_string());
+        }
+    };
+
+    if !checksum.cmp_file(&partial).unwrap_or(false) {
+        tokio::fs::remove_file(&partial).await.ok();
+        return Err("checksum mismatch".to_string());
+    }
+
+    tokio::fs::rename(&partial, download_dir.join(filename))
+        .await
+        .map_err(|e| e.to_string())
+}
+
 /// Get apt style file name
 fn apt_style_filename(entry: &InstallEntry) -> String {
     let package = entry.name_without_arch();
@@ -1,12 +1,22 @@
-use std::{fmt::Display, fs, io, path::Path, str::FromStr};
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+};
 
 use deb822_lossless::{Deb822, Paragraph};
+use oma_fetch::checksum::Checksum;
 use spdlog::{debug, info};
 
 use crate::apt::{OmaAptError, OmaAptResult};
 
 const DPKG_STATUS_PATH: &str = "var/lib/dpkg/status";
 
+/// Files dpkg leaves next to a conffile when the local and the packaged version differ.
+pub const CONFFILE_LEFTOVER_SUFFIXES: &[&str] = &[".dpkg-new", ".dpkg-old", ".dpkg-dist"];
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum DpkgMarkStatus {
     Hold,
@@ -112,3 +122,201 @@ pub fn mark_status<'a>(
 
     Ok(res)
 }
+
+/// A configuration file owned by an installed package, as recorded in dpkg status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conffile {
+    pub package: String,
+    pub path: String,
+    pub md5: String,
+    pub obsolete: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConffileState {
+    Unmodified,
+    Modified,
+    Missing,
+}
+
+impl Conffile {
+    /// Path of the conffile under `sysroot`.
+    pub fn real_path(&self, sysroot: impl AsRef<Path>) -> PathBuf {
+        sysroot.as_ref().join(self.path.trim_start_matches('/'))
+    }
+
+    /// Compare the file on disk with the md5sum recorded by dpkg.
+    pub fn state(&self, sysroot: impl AsRef<Path>) -> ConffileState {
+        let path = self.real_path(sysroot);
+
+        if !path.exists() {
+            return ConffileState::Missing;
+        }
+
+        // dpkg 在软件包尚未配置完成时会记录 `newconffile`
+        let Ok(checksum) = Checksum::from_md5_str(&self.md5) else {
+            return ConffileState::Unmodified;
+        };
+
+        match checksum.cmp_file(&path) {
+            Ok(true) => ConffileState::Unmodified,
+            Ok(false) => ConffileState::Modified,
+            Err(e) => {
+                debug!("Failed to checksum {}: {e}", path.display());
+                ConffileState::Modified
+            }
+        }
+    }
+
+    /// Existing `.dpkg-new`, `.dpkg-old` and `.dpkg-dist` files of this conffile.
+    pub fn leftovers(&self, sysroot: impl AsRef<Path>) -> Vec<PathBuf> {
+        let path = self.real_path(sysroot);
+
+        CONFFILE_LEFTOVER_SUFFIXES
+            .iter()
+            .map(|suffix| {
+                let mut p = path.clone().into_os_string();
+                p.push(suffix);
+                PathBuf::from(p)
+            })
+            .filter(|p| p.exists())
+            .collect()
+    }
+}
+
+/// Extract the file at `path` (e.g. `/etc/foo.conf`) from the data archive of `deb`.
+pub fn extract_file_from_deb(deb: impl AsRef<Path>, path: &str) -> OmaAptResult<Vec<u8>> {
+    let deb = deb.as_ref();
+    let err =
+        |e: String| OmaAptError::ExtractFromDeb(path.to_string(), deb.display().to_string(), e);
+
+    let mut dpkg_deb = Command::new("dpkg-deb")
+        .arg("--fsys-tarfile")
+        .arg(deb)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| err(e.to_string()))?;
+
+    let tarball = dpkg_deb.stdout.take().expect("stdout is piped");
+
+    // 软件包数据归档中的路径均以 `./` 开头
+    let tar = Command::new("tar")
+        .arg("-xOf")
+        .arg("-")
+        .arg(format!(".{path}"))
+        .stdin(tarball)
+        .output()
+        .map_err(|e| err(e.to_string()))?;
+
+    let dpkg_deb = dpkg_deb
+        .wait_with_output()
+        .map_err(|e| err(e.to_string()))?;
+
+    if !dpkg_deb.status.success() {
+        return Err(err(String::from_utf8_lossy(&dpkg_deb.stderr)
+            .trim()
+            .to_string()));
+    }
+
+    if !tar.status.success() {
+        return Err(err(String::from_utf8_lossy(&tar.stderr).trim().to_string()));
+    }
+
+    Ok(tar.stdout)
+}
+
+/// Get conffiles of all installed packages from dpkg status.
+pub fn conffiles(sysroot: impl AsRef<Path>) -> OmaAptResult<Vec<Conffile>> {
+    let path = sysroot.as_ref().join(DPKG_STATUS_PATH);
+
+    let dpkg_status = Deb822::from_file(&path).map_err(|e| {
+        OmaAptError::FailedOperateDirOrFile(path.to_string_lossy().to_string(), io::Error::other(e))
+    })?;
+
+    Ok(dpkg_status
+        .paragraphs()
+        .filter(|p| p.get("Status").is_some_and(|s| s.ends_with(" installed")))
+        .flat_map(|p| {
+            let package = p.get("Package").unwrap_or_default();
+            parse_conffiles_field(&package, &p.get("Conffiles").unwrap_or_default())
+        })
+        .collect())
+}
+
+/// Parse lines like ` /etc/foo.conf 0123456789abcdef0123456789abcdef obsolete`.
+fn parse_conffiles_field(package: &str, field: &str) -> Vec<Conffile> {
+    field
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let path = parts.next()?;
+            let md5 = parts.next()?;
+            let obsolete = parts.any(|flag| flag == "obsolete");
+
+            Some(Conffile {
+                package: package.to_string(),
+                path: path.to_string(),
+                md5: md5.to_string(),
+                obsolete,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_conffiles_field() {
+        let field = "\n /etc/foo.conf 0123456789abcdef0123456789abcdef\n /etc/foo/old.conf 0123456789abcdef0123456789abcdef obsolete\n /etc/bar newconffile";
+
+        assert_eq!(
+            parse_conffiles_field("foo", field),
+            vec![
+                Conffile {
+                    package: "foo".to_string(),
+                    path: "/etc/foo.conf".to_string(),
+                    md5: "0123456789abcdef0123456789abcdef".to_string(),
+                    obsolete: false,
+                },
+                Conffile {
+                    package: "foo".to_string(),
+                    path: "/etc/foo/old.conf".to_string(),
+                    md5: "0123456789abcdef0123456789abcdef".to_string(),
+                    obsolete: true,
+                },
+                Conffile {
+                    package: "foo".to_string(),
+                    path: "/etc/bar".to_string(),
+                    md5: "newconffile".to_string(),
+                    obsolete: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conffile_leftovers() {
+        let sysroot =
+            std::env::temp_dir().join(format!("oma-conffile-test-{}", std::process::id()));
+        fs::create_dir_all(sysroot.join("etc")).unwrap();
+        fs::write(sysroot.join("etc/foo.conf"), b"").unwrap();
+        fs::write(sysroot.join("etc/foo.conf.dpkg-dist"), b"").unwrap();
+
+        let conffile = Conffile {
+            package: "foo".to_string(),
+            path: "/etc/foo.conf".to_string(),
+            md5: "newconffile".to_string(),
+            obsolete: false,
+        };
+
+        assert_eq!(
+            conffile.leftovers(&sysroot),
+            vec![sysroot.join("etc/foo.conf.dpkg-dist")]
+        );
+
+        fs::remove_dir_all(&sysroot).unwrap();
+    }
+}
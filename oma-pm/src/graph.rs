@@ -0,0 +1,928 @@
+//! Dependency graphs of packages
+//!
+//! Unlike [`OmaPackage::get_deps`], which only returns the direct dependencies
+//! of one version, a [`DepGraph`] holds the whole dependency closure. Each OR
+//! group of an [`OmaDependencyGroup`] becomes a set of edges sharing the same
+//! `group`, and dependencies on virtual packages go through a virtual node
+//! pointing to its providers.
+//!
+//! A graph can be queried for dependency cycles, the shortest dependency path
+//! between two packages and the packages removed along with others, and can be
+//! exported as DOT, GraphML or (with serde) JSON.
+//!
+//! [`OmaDependencyGroup`]: crate::pkginfo::OmaDependencyGroup
+
+use std::{
+    collections::VecDeque,
+    fmt::{self, Write},
+};
+
+use ahash::HashMap;
+use oma_apt::{
+    Package, Version,
+    cache::{Cache, PackageSort},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::pkginfo::{OmaDepType, OmaDependency, OmaPackage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    Package,
+    /// Has no versions, only providers
+    Virtual,
+    /// Neither has versions nor providers
+    Missing,
+}
+
+impl fmt::Display for NodeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NodeKind::Package => "package",
+            NodeKind::Virtual => "virtual",
+            NodeKind::Missing => "missing",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub name: String,
+    /// The version whose dependencies are walked
+    pub version: Option<String>,
+    pub kind: NodeKind,
+    pub installed: bool,
+    pub auto_installed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    /// `None` if `from` is a virtual package provided by `to`
+    pub dep_type: Option<OmaDepType>,
+    /// Edges from the same package with the same group are alternatives (`a | b`)
+    pub group: usize,
+    /// Version requirement, e.g. `>= 1.0`
+    pub comp_ver: Option<String>,
+    /// The package name in the dependency, if `to` satisfies it through Provides
+    pub via: Option<String>,
+}
+
+impl GraphEdge {
+    fn is_critical(&self) -> bool {
+        matches!(
+            self.dep_type,
+            Some(OmaDepType::Depends | OmaDepType::PreDepends)
+        )
+    }
+}
+
+impl fmt::Display for GraphEdge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.dep_type {
+            Some(t) => write!(f, "{t}")?,
+            None => f.write_str("ProvidedBy")?,
+        }
+
+        if let Some(comp_ver) = &self.comp_ver {
+            write!(f, " ({comp_ver})")?;
+        }
+
+        if let Some(via) = &self.via {
+            write!(f, " via {via}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Packages removed along with the given ones, see [`DepGraph::removal_impact`]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RemovalImpact {
+    /// Installed packages whose dependencies would be broken
+    pub broken: Vec<usize>,
+    /// Automatically installed packages which would no longer be needed
+    pub orphaned: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DepGraph {
+    /// Nodes of the queried packages
+    pub roots: Vec<usize>,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl DepGraph {
+    /// Walk the whole dependency closure of `roots`
+    ///
+    /// The given version of each root and the candidate version of the other
+    /// packages are used.
+    pub fn closure<'a>(
+        cache: &'a Cache,
+        roots: &'a [OmaPackage],
+        dep_types: &[OmaDepType],
+    ) -> Self {
+        let mut walker = Walker::new(cache, dep_types, false);
+
+        for pkg in roots {
+            let root = walker.add_node(&pkg.package(cache), Some(pkg.version(cache)));
+            walker.graph.roots.push(root);
+        }
+
+        walker.walk()
+    }
+
+    /// Dependency graph of all installed packages
+    ///
+    /// Installed versions are used, and only edges to installed packages are
+    /// kept, so that a dependency is satisfied if any edge of its group remains.
+    pub fn installed<'a>(
+        cache: &'a Cache,
+        roots: &'a [OmaPackage],
+        dep_types: &[OmaDepType],
+    ) -> Self {
+        let mut walker = Walker::new(cache, dep_types, true);
+
+        for pkg in roots {
+            let root = walker.node(&pkg.package(cache));
+            walker.graph.roots.push(root);
+        }
+
+        let sort = PackageSort::default().installed();
+
+        for pkg in cache.packages(&sort) {
+            walker.node(&pkg);
+        }
+
+        walker.walk()
+    }
+
+    /// Find a node by its name, with or without the architecture
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|n| n.name == name).or_else(|| {
+            self.nodes
+                .iter()
+                .position(|n| n.name.split_once(':').is_some_and(|(n, _)| n == name))
+        })
+    }
+
+    /// The first edge from `from` to `to`
+    pub fn edge(&self, from: usize, to: usize) -> Option<&GraphEdge> {
+        self.edges.iter().find(|e| e.from == from && e.to == to)
+    }
+
+    fn out_edges(&self) -> Vec<Vec<&GraphEdge>> {
+        let mut out = vec![vec![]; self.nodes.len()];
+
+        for edge in &self.edges {
+            out[edge.from].push(edge);
+        }
+
+        out
+    }
+
+    fn adjacency(&self, reverse: bool) -> Vec<Vec<usize>> {
+        let mut adj = vec![vec![]; self.nodes.len()];
+
+        for edge in &self.edges {
+            if reverse {
+                adj[edge.to].push(edge.from);
+            } else {
+                adj[edge.from].push(edge.to);
+            }
+        }
+
+        adj
+    }
+
+    /// Nodes reachable from `from`, including themselves
+    fn reachable(&self, from: &[usize], reverse: bool) -> Vec<usize> {
+        let adj = self.adjacency(reverse);
+        let mut seen = vec![false; self.nodes.len()];
+        let mut queue = VecDeque::new();
+        let mut res = vec![];
+
+        for &i in from {
+            if !seen[i] {
+                seen[i] = true;
+                queue.push_back(i);
+            }
+        }
+
+        while let Some(i) = queue.pop_front() {
+            res.push(i);
+
+            for &j in &adj[i] {
+                if !seen[j] {
+                    seen[j] = true;
+                    queue.push_back(j);
+                }
+            }
+        }
+
+        res
+    }
+
+    /// Nodes depending on `nodes` directly or indirectly, including themselves
+    pub fn dependents(&self, nodes: &[usize]) -> Vec<usize> {
+        self.reachable(nodes, true)
+    }
+
+    /// Shortest dependency path from `from` to `to`, including both ends
+    pub fn shortest_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let adj = self.adjacency(false);
+        let mut prev = vec![None; self.nodes.len()];
+        let mut queue = VecDeque::from([from]);
+        prev[from] = Some(from);
+
+        while let Some(i) = queue.pop_front() {
+            if i == to {
+                let mut path = vec![to];
+                let mut cur = to;
+
+                while cur != from {
+                    cur = prev[cur]?;
+                    path.push(cur);
+                }
+
+                path.reverse();
+                return Some(path);
+            }
+
+            for &j in &adj[i] {
+                if prev[j].is_none() {
+                    prev[j] = Some(i);
+                    queue.push_back(j);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Strongly connected components forming dependency cycles
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        // Tarjan 算法，用显式栈代替递归，避免依赖链过长时栈溢出
+        let adj = self.adjacency(false);
+        let n = self.nodes.len();
+        let mut index = vec![usize::MAX; n];
+        let mut lowlink = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut stack = vec![];
+        let mut next = 0;
+        let mut res = vec![];
+
+        for start in 0..n {
+            if index[start] != usize::MAX {
+                continue;
+            }
+
+            // (节点, 下一个要访问的后继)
+            let mut call = vec![(start, 0)];
+            index[start] = next;
+            lowlink[start] = next;
+            next += 1;
+            stack.push(start);
+            on_stack[start] = true;
+
+            while let Some((v, pos)) = call.last_mut() {
+                let v = *v;
+
+                if let Some(&w) = adj[v].get(*pos) {
+                    *pos += 1;
+
+                    if index[w] == usize::MAX {
+                        index[w] = next;
+                        lowlink[w] = next;
+                        next += 1;
+                        stack.push(w);
+                        on_stack[w] = true;
+                        call.push((w, 0));
+                    } else if on_stack[w] {
+                        lowlink[v] = lowlink[v].min(index[w]);
+                    }
+
+                    continue;
+                }
+
+                call.pop();
+
+                if let Some(&(parent, _)) = call.last() {
+                    lowlink[parent] = lowlink[parent].min(lowlink[v]);
+                }
+
+                if lowlink[v] == index[v] {
+                    let mut scc = vec![];
+
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        scc.push(w);
+                        if w == v {
+                            break;
+                        }
+                    }
+
+                    if scc.len() > 1 || adj[v].contains(&v) {
+                        scc.reverse();
+                        res.push(scc);
+                    }
+                }
+            }
+        }
+
+        res
+    }
+
+    /// What would be removed along with `removed`
+    ///
+    /// Meant for the graph of [`DepGraph::installed`]: an installed package is
+    /// broken when all edges of one of its Depends or PreDepends groups point to
+    /// removed packages, or to virtual packages whose providers are all removed.
+    /// The orphaned packages are the automatically installed ones reachable from
+    /// the manually installed packages before, but not after the removal.
+    pub fn removal_impact(&self, removed: &[usize]) -> RemovalImpact {
+        let n = self.nodes.len();
+        let out = self.out_edges();
+        let mut gone = vec![false; n];
+
+        for &i in removed {
+            gone[i] = true;
+        }
+
+        let alive = |gone: &[bool], i: usize| {
+            !gone[i]
+                && (self.nodes[i].kind != NodeKind::Virtual || out[i].iter().any(|e| !gone[e.to]))
+        };
+
+        loop {
+            let mut changed = false;
+
+            for (i, node) in self.nodes.iter().enumerate() {
+                if gone[i] || !node.installed || node.kind != NodeKind::Package {
+                    continue;
+                }
+
+                let broken = out[i].chunk_by(|a, b| a.group == b.group).any(|group| {
+                    group[0].is_critical() && group.iter().all(|e| !alive(&gone, e.to))
+                });
+
+                if broken {
+                    gone[i] = true;
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        let is_installed =
+            |i: usize| self.nodes[i].installed && self.nodes[i].kind == NodeKind::Package;
+
+        let needed = |gone: &[bool]| {
+            let manual = (0..n)
+                .filter(|&i| !gone[i] && is_installed(i) && !self.nodes[i].auto_installed)
+                .collect::<Vec<_>>();
+
+            let mut needed = vec![false; n];
+            let mut queue = VecDeque::from(manual);
+
+            while let Some(i) = queue.pop_front() {
+                if needed[i] {
+                    continue;
+                }
+
+                needed[i] = true;
+                queue.extend(
+                    out[i]
+                        .iter()
+                        .map(|e| e.to)
+                        .filter(|&j| !gone[j] && !needed[j]),
+                );
+            }
+
+            needed
+        };
+
+        let before = needed(&vec![false; n]);
+        let after = needed(&gone);
+
+        RemovalImpact {
+            broken: (0..n)
+                .filter(|&i| gone[i] && !removed.contains(&i))
+                .collect(),
+            // 原本就不再需要的包不算在内
+            orphaned: (0..n)
+                .filter(|&i| is_installed(i) && before[i] && !after[i] && !gone[i])
+                .collect(),
+        }
+    }
+
+    /// The graph of `nodes` and the edges between them
+    pub fn subgraph(&self, nodes: &[usize]) -> DepGraph {
+        let mut map = vec![None; self.nodes.len()];
+        let mut graph = DepGraph::default();
+
+        for &i in nodes {
+            if map[i].is_none() {
+                map[i] = Some(graph.nodes.len());
+                graph.nodes.push(self.nodes[i].clone());
+            }
+        }
+
+        graph.roots = self.roots.iter().filter_map(|&i| map[i]).collect();
+        graph.edges = self
+            .edges
+            .iter()
+            .filter_map(|e| {
+                Some(GraphEdge {
+                    from: map[e.from]?,
+                    to: map[e.to]?,
+                    ..e.clone()
+                })
+            })
+            .collect();
+
+        graph
+    }
+
+    /// Export as Graphviz DOT, edges in cycles are colored red
+    pub fn to_dot(&self) -> String {
+        let in_cycle = self.cycle_ids();
+        let mut s = String::from("digraph dependencies {\n    node [shape=box];\n");
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            let mut label = dot_escape(&node.name);
+            if let Some(version) = &node.version {
+                label.push_str("\\n");
+                label.push_str(&dot_escape(version));
+            }
+
+            let mut attrs = vec![format!("label=\"{label}\"")];
+
+            match node.kind {
+                NodeKind::Package => {}
+                NodeKind::Virtual => attrs.push("style=dashed".to_string()),
+                NodeKind::Missing => attrs.push("style=dotted, color=red".to_string()),
+            }
+
+            if self.roots.contains(&i) {
+                attrs.push("penwidth=2".to_string());
+            }
+
+            writeln!(s, "    n{i} [{}];", attrs.join(", ")).ok();
+        }
+
+        for edge in &self.edges {
+            let mut attrs = vec![];
+
+            match edge.dep_type {
+                Some(OmaDepType::Depends) => {}
+                Some(t) => attrs.push(format!("label=\"{t}\"")),
+                None => attrs.push("style=dotted".to_string()),
+            }
+
+            if matches!(
+                edge.dep_type,
+                Some(OmaDepType::Recommends | OmaDepType::Suggests)
+            ) {
+                attrs.push("style=dashed".to_string());
+            }
+
+            if in_cycle[edge.from].is_some() && in_cycle[edge.from] == in_cycle[edge.to] {
+                attrs.push("color=red".to_string());
+            }
+
+            if attrs.is_empty() {
+                writeln!(s, "    n{} -> n{};", edge.from, edge.to).ok();
+            } else {
+                writeln!(
+                    s,
+                    "    n{} -> n{} [{}];",
+                    edge.from,
+                    edge.to,
+                    attrs.join(", ")
+                )
+                .ok();
+            }
+        }
+
+        s.push_str("}\n");
+        s
+    }
+
+    /// Export as GraphML
+    pub fn to_graphml(&self) -> String {
+        let mut s = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>\n",
+            "  <key id=\"version\" for=\"node\" attr.name=\"version\" attr.type=\"string\"/>\n",
+            "  <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+            "  <key id=\"installed\" for=\"node\" attr.name=\"installed\" attr.type=\"boolean\"/>\n",
+            "  <key id=\"root\" for=\"node\" attr.name=\"root\" attr.type=\"boolean\"/>\n",
+            "  <key id=\"type\" for=\"edge\" attr.name=\"type\" attr.type=\"string\"/>\n",
+            "  <key id=\"group\" for=\"edge\" attr.name=\"group\" attr.type=\"int\"/>\n",
+            "  <key id=\"requirement\" for=\"edge\" attr.name=\"requirement\" attr.type=\"string\"/>\n",
+            "  <key id=\"via\" for=\"edge\" attr.name=\"via\" attr.type=\"string\"/>\n",
+            "  <graph id=\"dependencies\" edgedefault=\"directed\">\n",
+        ));
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            writeln!(s, "    <node id=\"n{i}\">").ok();
+            graphml_data(&mut s, "name", &node.name);
+            if let Some(version) = &node.version {
+                graphml_data(&mut s, "version", version);
+            }
+            graphml_data(&mut s, "kind", &node.kind.to_string());
+            graphml_data(&mut s, "installed", &node.installed.to_string());
+            graphml_data(&mut s, "root", &self.roots.contains(&i).to_string());
+            s.push_str("    </node>\n");
+        }
+
+        for edge in &self.edges {
+            writeln!(
+                s,
+                "    <edge source=\"n{}\" target=\"n{}\">",
+                edge.from, edge.to
+            )
+            .ok();
+            let dep_type = match edge.dep_type {
+                Some(t) => t.to_string(),
+                None => "ProvidedBy".to_string(),
+            };
+            graphml_data(&mut s, "type", &dep_type);
+            graphml_data(&mut s, "group", &edge.group.to_string());
+            if let Some(comp_ver) = &edge.comp_ver {
+                graphml_data(&mut s, "requirement", comp_ver);
+            }
+            if let Some(via) = &edge.via {
+                graphml_data(&mut s, "via", via);
+            }
+            s.push_str("    </edge>\n");
+        }
+
+        s.push_str("  </graph>\n</graphml>\n");
+        s
+    }
+
+    /// The index of the cycle each node belongs to
+    fn cycle_ids(&self) -> Vec<Option<usize>> {
+        let mut ids = vec![None; self.nodes.len()];
+
+        for (id, cycle) in self.cycles().into_iter().enumerate() {
+            for i in cycle {
+                ids[i] = Some(id);
+            }
+        }
+
+        ids
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn graphml_data(s: &mut String, key: &str, value: &str) {
+    let value = value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+
+    writeln!(s, "      <data key=\"{key}\">{value}</data>").ok();
+}
+
+struct Walker<'a, 'b> {
+    cache: &'a Cache,
+    dep_types: &'b [OmaDepType],
+    installed_only: bool,
+    graph: DepGraph,
+    index: HashMap<String, usize>,
+    queue: VecDeque<(usize, Version<'a>)>,
+}
+
+impl<'a, 'b> Walker<'a, 'b> {
+    fn new(cache: &'a Cache, dep_types: &'b [OmaDepType], installed_only: bool) -> Self {
+        Self {
+            cache,
+            dep_types,
+            installed_only,
+            graph: DepGraph::default(),
+            index: HashMap::default(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    fn walk(mut self) -> DepGraph {
+        while let Some((from, version)) = self.queue.pop_front() {
+            self.expand(from, &version);
+        }
+
+        self.graph
+    }
+
+    fn push_node(&mut self, node: GraphNode) -> usize {
+        let i = self.graph.nodes.len();
+        self.index.insert(node.name.clone(), i);
+        self.graph.nodes.push(node);
+
+        i
+    }
+
+    /// Add a package with the version to walk, unless it is already in the graph
+    fn add_node(&mut self, pkg: &Package<'a>, version: Option<Version<'a>>) -> usize {
+        let name = pkg.fullname(true);
+
+        if let Some(&i) = self.index.get(&name) {
+            return i;
+        }
+
+        let kind = if pkg.has_versions() {
+            NodeKind::Package
+        } else if pkg.has_provides() {
+            NodeKind::Virtual
+        } else {
+            NodeKind::Missing
+        };
+
+        let installed = pkg.is_installed();
+
+        let i = self.push_node(GraphNode {
+            name,
+            version: version.as_ref().map(|v| v.version().to_string()),
+            kind,
+            installed,
+            auto_installed: installed && pkg.is_auto_installed(),
+        });
+
+        match version {
+            Some(version) => self.queue.push_back((i, version)),
+            None if kind == NodeKind::Virtual => self.add_providers(i, pkg),
+            None => {}
+        }
+
+        i
+    }
+
+    fn node(&mut self, pkg: &Package<'a>) -> usize {
+        let version = if self.installed_only {
+            pkg.installed()
+        } else {
+            pkg.candidate()
+        };
+
+        self.add_node(pkg, version)
+    }
+
+    fn missing_node(&mut self, name: &str) -> usize {
+        if let Some(&i) = self.index.get(name) {
+            return i;
+        }
+
+        self.push_node(GraphNode {
+            name: name.to_string(),
+            version: None,
+            kind: NodeKind::Missing,
+            installed: false,
+            auto_installed: false,
+        })
+    }
+
+    fn add_providers(&mut self, from: usize, pkg: &Package<'a>) {
+        let mut providers = vec![];
+
+        for provider in pkg.provides() {
+            let provider = provider.package();
+
+            if self.installed_only && !provider.is_installed() {
+                continue;
+            }
+
+            let to = self.node(&provider);
+            if !providers.contains(&to) {
+                providers.push(to);
+            }
+        }
+
+        for to in providers {
+            self.graph.edges.push(GraphEdge {
+                from,
+                to,
+                dep_type: None,
+                group: 0,
+                comp_ver: None,
+                via: None,
+            });
+        }
+    }
+
+    /// Nodes satisfying a dependency on `name`, and the name if satisfied through Provides
+    fn resolve(&mut self, name: &str) -> Vec<(usize, Option<String>)> {
+        let cache = self.cache;
+
+        let Some(pkg) = cache.get(name) else {
+            if self.installed_only {
+                return vec![];
+            }
+
+            return vec![(self.missing_node(name), None)];
+        };
+
+        if !pkg.has_versions() {
+            if self.installed_only && !pkg.provides().any(|p| p.package().is_installed()) {
+                return vec![];
+            }
+
+            return vec![(self.node(&pkg), None)];
+        }
+
+        let mut targets = vec![];
+
+        if !self.installed_only || pkg.is_installed() {
+            targets.push((self.node(&pkg), None));
+        }
+
+        // 真实存在的包也可能被其他包提供
+        for provider in pkg.provides() {
+            let provider = provider.package();
+
+            if provider == pkg || (self.installed_only && !provider.is_installed()) {
+                continue;
+            }
+
+            targets.push((self.node(&provider), Some(pkg.fullname(true))));
+        }
+
+        targets
+    }
+
+    fn expand(&mut self, from: usize, version: &Version<'a>) {
+        // 按照 dep_types 的顺序遍历，使输出稳定
+        let mut deps = version
+            .depends_map()
+            .iter()
+            .filter_map(|(t, deps)| {
+                let t = OmaDepType::from(t);
+                let order = self.dep_types.iter().position(|x| *x == t)?;
+                Some((order, t, OmaDependency::map_deps(deps)))
+            })
+            .collect::<Vec<_>>();
+
+        deps.sort_by_key(|(order, ..)| *order);
+
+        let mut group = 0;
+
+        for (_, dep_type, deps) in deps {
+            for alternatives in deps.inner() {
+                let mut targets: Vec<(usize, Option<String>, Option<String>)> = vec![];
+
+                for dep in alternatives {
+                    for (to, via) in self.resolve(&dep.name) {
+                        if !targets.iter().any(|(t, ..)| *t == to) {
+                            targets.push((to, via, dep.comp_ver.clone()));
+                        }
+                    }
+                }
+
+                if targets.is_empty() {
+                    continue;
+                }
+
+                for (to, via, comp_ver) in targets {
+                    self.graph.edges.push(GraphEdge {
+                        from,
+                        to,
+                        dep_type: Some(dep_type),
+                        group,
+                        comp_ver,
+                        via,
+                    });
+                }
+
+                group += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn graph(nodes: &[(&str, NodeKind, bool)], edges: &[(usize, usize, usize)]) -> DepGraph {
+        DepGraph {
+            roots: vec![0],
+            nodes: nodes
+                .iter()
+                .map(|(name, kind, auto)| GraphNode {
+                    name: name.to_string(),
+                    version: Some("1.0".to_string()),
+                    kind: *kind,
+                    installed: true,
+                    auto_installed: *auto,
+                })
+                .collect(),
+            edges: edges
+                .iter()
+                .map(|&(from, to, group)| GraphEdge {
+                    from,
+                    to,
+                    dep_type: (nodes[from].1 != NodeKind::Virtual).then_some(OmaDepType::Depends),
+                    group,
+                    comp_ver: None,
+                    via: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_cycles_and_path() {
+        use NodeKind::Package as P;
+
+        let g = graph(
+            &[
+                ("a", P, false),
+                ("b", P, true),
+                ("c", P, true),
+                ("d", P, true),
+            ],
+            &[(0, 1, 0), (1, 2, 0), (2, 1, 0), (2, 3, 0), (3, 3, 0)],
+        );
+
+        assert_eq!(g.cycles(), vec![vec![3], vec![1, 2]]);
+        assert_eq!(g.shortest_path(0, 3), Some(vec![0, 1, 2, 3]));
+        assert_eq!(g.shortest_path(3, 0), None);
+        assert_eq!(g.dependents(&[2]), vec![2, 1, 0]);
+        assert_eq!(g.find("c"), Some(2));
+    }
+
+    #[test]
+    fn test_removal_impact() {
+        use NodeKind::{Package as P, Virtual as V};
+
+        // a -> b | c, a -> awk -> (mawk, gawk), b -> e, d -> f
+        let g = graph(
+            &[
+                ("a", P, false),
+                ("b", P, true),
+                ("c", P, true),
+                ("awk", V, false),
+                ("mawk", P, true),
+                ("gawk", P, true),
+                ("e", P, true),
+                ("d", P, false),
+                ("f", P, true),
+            ],
+            &[
+                (0, 1, 0),
+                (0, 2, 0),
+                (0, 3, 1),
+                (3, 4, 0),
+                (3, 5, 0),
+                (1, 6, 0),
+                (7, 8, 0),
+            ],
+        );
+
+        let impact = g.removal_impact(&[1]);
+        assert!(impact.broken.is_empty());
+        assert_eq!(impact.orphaned, vec![6]);
+
+        let impact = g.removal_impact(&[1, 2]);
+        assert_eq!(impact.broken, vec![0]);
+        assert_eq!(impact.orphaned, vec![4, 5, 6]);
+
+        let impact = g.removal_impact(&[4, 5]);
+        assert_eq!(impact.broken, vec![0]);
+        assert_eq!(impact.orphaned, vec![1, 2, 6]);
+    }
+
+    #[test]
+    fn test_export() {
+        use NodeKind::Package as P;
+
+        let g = graph(
+            &[("a\"", P, false), ("b&", P, true)],
+            &[(0, 1, 0), (1, 0, 0)],
+        );
+
+        let dot = g.to_dot();
+        assert!(dot.contains("n0 [label=\"a\\\"\\n1.0\", penwidth=2];"));
+        assert!(dot.contains("n0 -> n1 [color=red];"));
+
+        let graphml = g.to_graphml();
+        assert!(graphml.contains("<data key=\"name\">b&amp;</data>"));
+        assert!(graphml.contains("<edge source=\"n1\" target=\"n0\">"));
+
+        let sub = g.subgraph(&[1]);
+        assert!(sub.roots.is_empty());
+        assert!(sub.edges.is_empty());
+    }
+}
@@ -17,10 +17,15 @@
 //! ## Modules
 //!
 //! - `apt`: Handles interactions with `apt`.
+//! - `build_dep`: Parses build dependencies of source packages.
+//! - `graph`: Builds and queries dependency graphs of packages.
 //! - `matches`: Provides utilities for matching package information.
 //! - `pkginfo`: Contains definitions and structures for package information.
+//! - `pin`: Manages APT pinning preferences.
 //! - `progress`: Tracks the progress of package management operations.
 //! - `search`: Defines the structure and handling of search results.
+//! - `snapshot`: System snapshots taken around a commit.
+//! - `verify`: Verifies installed files against dpkg md5sums.
 //! - `dbus`: Manages D-Bus communication.
 //!
 //! ## Re-exports
@@ -30,9 +35,14 @@
 //! - `PackageStatus`: Package status definitions from the `search` module.
 
 pub mod apt;
+pub mod build_dep;
+pub mod graph;
 pub mod matches;
+pub mod pin;
 pub mod pkginfo;
 pub mod progress;
+pub mod snapshot;
+pub mod verify;
 
 mod commit;
 mod dbus;
@@ -45,6 +55,7 @@ pub use oma_apt;
 pub use oma_fetch::Event as PackageDownloadEvent;
 pub use oma_fetch::SingleDownloadError as PackageDownloadError;
 mod dpkg;
+pub use dpkg::{Conffile, ConffileState, conffiles, extract_file_from_deb};
 mod lock;
 
 #[cfg(test)]
@@ -1,4 +1,8 @@
-use std::{borrow::Cow, cmp::Ordering, path::Path};
+use std::{
+    borrow::Cow,
+    cmp::{Ordering, Reverse},
+    path::Path,
+};
 
 use bon::Builder;
 use cxx::UniquePtr;
@@ -363,18 +367,33 @@ impl<'a> PackagesMatcher<'a> {
     /// Find mirror candidate and downloadable package version.
     pub fn find_candidate_by_pkgname(&self, pkg: &str) -> MatcherResult<OmaPackage> {
         if let Some(pkg) = self.cache.get(pkg) {
+            // candidate 版本已经考虑了 APT pin，能下载的话直接使用
+            if let Some(candidate) = pkg.candidate()
+                && candidate.is_downloadable()
+            {
+                debug!(
+                    "Pkg: {} selected candidate version: {}",
+                    pkg.fullname(true),
+                    candidate.version(),
+                );
+                return Ok(OmaPackage::new(&candidate, &pkg)?);
+            }
+
             // candidate 版本不一定是源中能下载的版本
-            // 所以要一个个版本遍历直到找到能下载的版本中最高的版本
-            for version in pkg.versions() {
-                if version.is_downloadable() {
-                    let pkginfo = OmaPackage::new(&version, &pkg)?;
-                    debug!(
-                        "Pkg: {} selected version: {}",
-                        pkg.fullname(true),
-                        version.version(),
-                    );
-                    return Ok(pkginfo);
-                }
+            // 所以要在能下载的版本中选择 pin 优先级最高的版本，优先级相同时选择最高的版本
+            let version = pkg
+                .versions()
+                .filter(|v| v.is_downloadable() && v.priority() >= 0)
+                .min_by_key(|v| Reverse(v.priority()));
+
+            if let Some(version) = version {
+                let pkginfo = OmaPackage::new(&version, &pkg)?;
+                debug!(
+                    "Pkg: {} selected version: {}",
+                    pkg.fullname(true),
+                    version.version(),
+                );
+                return Ok(pkginfo);
             }
         }
 
@@ -437,13 +456,13 @@ mod test {
         let res = matcher.match_pkgs_and_versions_from_glob("apt*").unwrap();
 
         for i in res_filter {
-            i.pkg_info(&cache).unwrap();
+            i.pkg_info(&cache, &[]).unwrap();
         }
 
         println!("---\n");
 
         for i in res {
-            i.pkg_info(&cache).unwrap();
+            i.pkg_info(&cache, &[]).unwrap();
         }
     }
 
@@ -465,7 +484,7 @@ mod test {
             .unwrap();
 
         for i in res_filter {
-            i.pkg_info(&cache).unwrap();
+            i.pkg_info(&cache, &[]).unwrap();
         }
     }
 
@@ -485,7 +504,7 @@ mod test {
         let res_filter = matcher.match_from_branch("apt/stable").unwrap();
 
         for i in res_filter {
-            i.pkg_info(&cache).unwrap();
+            i.pkg_info(&cache, &[]).unwrap();
         }
     }
 }
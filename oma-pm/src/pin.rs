@@ -0,0 +1,504 @@
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use glob_match::glob_match;
+use oma_apt::{Package, PackageFile, Version};
+use serde::{Deserialize, Serialize};
+use spdlog::debug;
+
+const PREFERENCES_DIR: &str = "etc/apt/preferences.d";
+const PIN_FILE_PREFIX: &str = "oma-pin-";
+const PIN_FILE_EXT: &str = ".pref";
+const PIN_FILE_HEADER: &str = "# Generated by oma pin, do not edit.";
+
+#[derive(Debug, thiserror::Error)]
+pub enum PinError {
+    #[error("Invalid package name: {0}")]
+    InvalidPackage(String),
+    #[error("Invalid pin: {0}")]
+    InvalidTarget(String),
+    #[error("Failed to create file or directory: {0}: {1}")]
+    FailedOperateDirOrFile(String, io::Error),
+}
+
+pub type PinResult<T> = Result<T, PinError>;
+
+/// What a pin applies to, see apt_preferences(5).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinTarget {
+    /// `Pin: version 1.0*`
+    Version(String),
+    /// `Pin: origin "repo.aosc.io"`
+    Origin(String),
+    /// `Pin: release a=stable`
+    Release(String),
+}
+
+impl FromStr for PinTarget {
+    type Err = PinError;
+
+    /// Parse `origin:HOST`, `release:FILTER`, `version:VERSION` or a bare version.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let target = if let Some(origin) = s.strip_prefix("origin:") {
+            PinTarget::Origin(origin.trim_matches('"').to_string())
+        } else if let Some(release) = s.strip_prefix("release:") {
+            PinTarget::Release(release.to_string())
+        } else {
+            PinTarget::Version(s.strip_prefix("version:").unwrap_or(s).to_string())
+        };
+
+        let value = match &target {
+            PinTarget::Version(v) | PinTarget::Origin(v) | PinTarget::Release(v) => v,
+        };
+
+        // 换行会破坏 preferences 文件的格式
+        if value.trim().is_empty() || value.contains(['\n', '\r']) {
+            return Err(PinError::InvalidTarget(s.to_string()));
+        }
+
+        Ok(target)
+    }
+}
+
+impl Display for PinTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PinTarget::Version(v) => write!(f, "version {v}"),
+            PinTarget::Origin(o) => write!(f, "origin \"{o}\""),
+            PinTarget::Release(r) => write!(f, "release {r}"),
+        }
+    }
+}
+
+impl PinTarget {
+    /// Whether a version matches this pin, see apt_preferences(5).
+    fn matches(&self, version: &Version) -> bool {
+        match self {
+            PinTarget::Version(v) => glob_match(v, version.version()),
+            PinTarget::Origin(o) => version
+                .package_files()
+                .any(|f| f.site().is_some_and(|site| site == o.as_str())),
+            PinTarget::Release(r) => version
+                .package_files()
+                .any(|f| release_filter_matches(r, &f)),
+        }
+    }
+
+    fn parse_pin_field(s: &str) -> Option<Self> {
+        let (kind, value) = s.trim().split_once(' ')?;
+        let value = value.trim();
+
+        match kind {
+            "version" => Some(PinTarget::Version(value.to_string())),
+            "origin" => Some(PinTarget::Origin(value.trim_matches('"').to_string())),
+            "release" => Some(PinTarget::Release(value.to_string())),
+            _ => None,
+        }
+    }
+}
+
+/// A pin managed by oma, stored as a fragment in `/etc/apt/preferences.d`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pin {
+    pub package: String,
+    pub target: PinTarget,
+    pub priority: i32,
+}
+
+impl Pin {
+    /// Whether this pin applies to the given version.
+    pub fn matches(&self, version: &Version) -> bool {
+        glob_match(&self.package, version.parent().name()) && self.target.matches(version)
+    }
+
+    fn render(&self) -> String {
+        format!(
+            "{PIN_FILE_HEADER}\nPackage: {}\nPin: {}\nPin-Priority: {}\n",
+            self.package, self.target, self.priority
+        )
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        let mut package = None;
+        let mut target = None;
+        let mut priority = None;
+
+        for line in s.lines() {
+            let Some((k, v)) = line.split_once(':') else {
+                continue;
+            };
+
+            match k.trim() {
+                "Package" => package = Some(v.trim().to_string()),
+                "Pin" => target = PinTarget::parse_pin_field(v),
+                "Pin-Priority" => priority = v.trim().parse().ok(),
+                _ => continue,
+            }
+        }
+
+        Some(Self {
+            package: package?,
+            target: target?,
+            priority: priority?,
+        })
+    }
+}
+
+/// Manage pins in `/etc/apt/preferences.d`, one file per package.
+pub struct PinManager {
+    dir: PathBuf,
+    dry_run: bool,
+}
+
+impl PinManager {
+    pub fn new(sysroot: impl AsRef<Path>, dry_run: bool) -> Self {
+        Self {
+            dir: sysroot.as_ref().join(PREFERENCES_DIR),
+            dry_run,
+        }
+    }
+
+    fn pin_path(&self, package: &str) -> PinResult<PathBuf> {
+        if package.is_empty() || package.contains(|c: char| c.is_whitespace() || c == '/') {
+            return Err(PinError::InvalidPackage(package.to_string()));
+        }
+
+        // APT 会忽略文件名中包含字母、数字、`_`、`-` 和 `.` 以外字符的文件，
+        // 其他字节（包括 `_` 本身）转义为 `_XX`，保证不同软件包对应不同的文件
+        let mut name = String::with_capacity(package.len());
+
+        for b in package.bytes() {
+            if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.') {
+                name.push(b as char);
+            } else {
+                name.push_str(&format!("_{b:02x}"));
+            }
+        }
+
+        Ok(self
+            .dir
+            .join(format!("{PIN_FILE_PREFIX}{name}{PIN_FILE_EXT}")))
+    }
+
+    /// Path used by older versions, which replaced every disallowed character with `_`.
+    ///
+    /// Only returned if the file exists and pins `package`, as other packages may map to it.
+    fn legacy_pin_path(&self, package: &str) -> Option<PathBuf> {
+        let name = package
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '-' | '.') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+
+        let path = self
+            .dir
+            .join(format!("{PIN_FILE_PREFIX}{name}{PIN_FILE_EXT}"));
+
+        let pin = fs::read_to_string(&path).ok()?;
+
+        Pin::parse(&pin)
+            .is_some_and(|pin| pin.package == package)
+            .then_some(path)
+    }
+
+    /// Add or replace the pin of a package.
+    pub fn add(&self, pin: &Pin) -> PinResult<()> {
+        let path = self.pin_path(&pin.package)?;
+        let legacy = self.legacy_pin_path(&pin.package).filter(|p| *p != path);
+
+        if self.dry_run {
+            debug!("Would write {}:\n{}", path.display(), pin.render());
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| PinError::FailedOperateDirOrFile(self.dir.display().to_string(), e))?;
+
+        // 先写入临时文件再重命名，避免 APT 读到写了一半的文件
+        let tmp = self.dir.join(format!(
+            ".{}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ));
+
+        fs::write(&tmp, pin.render())
+            .map_err(|e| PinError::FailedOperateDirOrFile(tmp.display().to_string(), e))?;
+
+        fs::rename(&tmp, &path)
+            .map_err(|e| PinError::FailedOperateDirOrFile(path.display().to_string(), e))?;
+
+        // 旧文件名的 pin 会与新文件同时生效，需要删除
+        if let Some(legacy) = legacy {
+            fs::remove_file(&legacy)
+                .map_err(|e| PinError::FailedOperateDirOrFile(legacy.display().to_string(), e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the pin of a package, return `false` if it is not pinned by oma.
+    pub fn remove(&self, package: &str) -> PinResult<bool> {
+        let path = self.pin_path(package)?;
+
+        let path = if path.is_file() {
+            path
+        } else if let Some(legacy) = self.legacy_pin_path(package) {
+            legacy
+        } else {
+            return Ok(false);
+        };
+
+        if !self.dry_run {
+            fs::remove_file(&path)
+                .map_err(|e| PinError::FailedOperateDirOrFile(path.display().to_string(), e))?;
+        }
+
+        Ok(true)
+    }
+
+    /// List pins managed by oma.
+    pub fn list(&self) -> PinResult<Vec<Pin>> {
+        let dir = match fs::read_dir(&self.dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => {
+                return Err(PinError::FailedOperateDirOrFile(
+                    self.dir.display().to_string(),
+                    e,
+                ));
+            }
+        };
+
+        let mut res = vec![];
+
+        for entry in dir.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+
+            if !name.starts_with(PIN_FILE_PREFIX) || !name.ends_with(PIN_FILE_EXT) {
+                continue;
+            }
+
+            let path = entry.path();
+            let s = fs::read_to_string(&path)
+                .map_err(|e| PinError::FailedOperateDirOrFile(path.display().to_string(), e))?;
+
+            match Pin::parse(&s) {
+                Some(pin) => res.push(pin),
+                None => debug!("Ignoring malformed pin file {}", path.display()),
+            }
+        }
+
+        res.sort_by(|a, b| a.package.cmp(&b.package));
+
+        Ok(res)
+    }
+}
+
+fn release_filter_matches(filter: &str, file: &PackageFile) -> bool {
+    filter.split(',').all(|cond| {
+        let cond = cond.trim();
+        // 不带键名的条件等同于 `a=`
+        let (key, value) = cond.split_once('=').unwrap_or(("a", cond));
+
+        let field = match key.trim() {
+            "a" => file.archive(),
+            "n" => file.codename(),
+            "o" => file.origin(),
+            "l" => file.label(),
+            "c" => file.component(),
+            "b" => file.arch(),
+            _ => return false,
+        };
+
+        field.is_some_and(|field| field == value.trim().trim_matches('"'))
+    })
+}
+
+/// Why APT chose the candidate version of a package.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum CandidateReason {
+    /// The candidate is the newest version available.
+    Newest,
+    /// A newer version is available, but a pin gives the candidate a higher priority.
+    Pinned {
+        pin: String,
+        newer: String,
+        newer_priority: i32,
+    },
+    /// A newer version is available, but its origin has a lower priority.
+    Priority {
+        priority: i32,
+        newer: String,
+        newer_priority: i32,
+    },
+    /// A newer version is available with the same priority, but not from a downloadable origin.
+    Origin { newer: String, origin: String },
+}
+
+impl CandidateReason {
+    pub fn from_package(pkg: &Package, pins: &[Pin]) -> Option<Self> {
+        let candidate = pkg.candidate()?;
+        // versions() 按版本号从高到低排列
+        let newest = pkg.versions().next()?;
+
+        if newest.version() == candidate.version() {
+            return Some(CandidateReason::Newest);
+        }
+
+        let newer = newest.version().to_string();
+        let newer_priority = newest.priority();
+
+        // 候选版本被提升或更新的版本被压低都算作 pin 生效
+        if let Some(pin) = pins
+            .iter()
+            .find(|p| p.matches(&candidate) || p.matches(&newest))
+        {
+            return Some(CandidateReason::Pinned {
+                pin: pin.target.to_string(),
+                newer,
+                newer_priority,
+            });
+        }
+
+        let priority = candidate.priority();
+
+        if priority != newer_priority {
+            return Some(CandidateReason::Priority {
+                priority,
+                newer,
+                newer_priority,
+            });
+        }
+
+        let origin = newest
+            .package_files()
+            .find_map(|f| {
+                f.origin()
+                    .filter(|s| !s.is_empty())
+                    .or(f.site())
+                    .map(|s| s.to_string())
+            })
+            .unwrap_or_else(|| "local".to_string());
+
+        Some(CandidateReason::Origin { newer, origin })
+    }
+}
+
+impl Display for CandidateReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CandidateReason::Newest => write!(f, "newest version"),
+            CandidateReason::Pinned {
+                pin,
+                newer,
+                newer_priority,
+            } => write!(
+                f,
+                "pinned by {pin} over newer version {newer} (priority {newer_priority})"
+            ),
+            CandidateReason::Priority {
+                priority,
+                newer,
+                newer_priority,
+            } => write!(
+                f,
+                "priority {priority} over newer version {newer} (priority {newer_priority})"
+            ),
+            CandidateReason::Origin { newer, origin } => {
+                write!(f, "newer version {newer} is only available from {origin}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_pin_target() {
+        assert_eq!(
+            "1.0*".parse::<PinTarget>().unwrap(),
+            PinTarget::Version("1.0*".to_string())
+        );
+        assert_eq!(
+            "version:1:2.0".parse::<PinTarget>().unwrap(),
+            PinTarget::Version("1:2.0".to_string())
+        );
+        assert_eq!(
+            "origin:repo.aosc.io".parse::<PinTarget>().unwrap(),
+            PinTarget::Origin("repo.aosc.io".to_string())
+        );
+        assert_eq!(
+            "release:a=stable".parse::<PinTarget>().unwrap(),
+            PinTarget::Release("a=stable".to_string())
+        );
+        assert!(
+            "release:a=stable\nPin-Priority: 1001"
+                .parse::<PinTarget>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_pin_path() {
+        let pm = PinManager::new("/", true);
+        let paths = ["foo+bar", "foo:bar", "foo_bar", "foo_2bbar", "foo*"]
+            .iter()
+            .map(|p| pm.pin_path(p).unwrap())
+            .collect::<std::collections::HashSet<_>>();
+
+        assert_eq!(paths.len(), 5);
+        assert_eq!(
+            pm.pin_path("foo_bar").unwrap(),
+            Path::new("/")
+                .join(PREFERENCES_DIR)
+                .join("oma-pin-foo_5fbar.pref")
+        );
+    }
+
+    #[test]
+    fn test_pin_manager() {
+        let sysroot = std::env::temp_dir().join(format!("oma-pin-test-{}", std::process::id()));
+        let pm = PinManager::new(&sysroot, false);
+
+        let pin = Pin {
+            package: "libstdc++".to_string(),
+            target: PinTarget::Origin("repo.aosc.io".to_string()),
+            priority: -1,
+        };
+
+        pm.add(&pin).unwrap();
+
+        assert!(
+            sysroot
+                .join(PREFERENCES_DIR)
+                .join("oma-pin-libstdc_2b_2b.pref")
+                .is_file()
+        );
+        assert_eq!(pm.list().unwrap(), [pin]);
+        assert!(pm.remove("libstdc++").unwrap());
+        assert!(!pm.remove("libstdc++").unwrap());
+        assert!(pm.list().unwrap().is_empty());
+        assert!(
+            pm.add(&Pin {
+                package: "foo bar".to_string(),
+                target: PinTarget::Version("1.0".to_string()),
+                priority: 1001,
+            })
+            .is_err()
+        );
+
+        fs::remove_dir_all(&sysroot).unwrap();
+    }
+}
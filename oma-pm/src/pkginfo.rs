@@ -12,7 +12,10 @@ use oma_utils::human_bytes::HumanBytes;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::apt::{OmaAptError, OmaAptResult};
+use crate::{
+    apt::{OmaAptError, OmaAptResult},
+    pin::{CandidateReason, Pin},
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OmaDependency {
@@ -103,7 +106,7 @@ impl OmaDependency {
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize, Serialize)]
 pub enum OmaDepType {
     Depends,
     PreDepends,
@@ -177,6 +180,12 @@ pub struct PackageInfo {
     pub description: String,
     /// Brief description of the package.
     pub short_description: String,
+    /// APT pin priority of this version.
+    #[serde(default)]
+    pub pin_priority: i32,
+    /// Why APT chose the candidate version, `None` if this version is not the candidate.
+    #[serde(default)]
+    pub candidate_reason: Option<CandidateReason>,
 }
 
 impl Display for PackageInfo {
@@ -191,6 +200,8 @@ impl Display for PackageInfo {
             download_size,
             apt_sources,
             description,
+            pin_priority,
+            candidate_reason,
             ..
         } = self;
 
@@ -220,6 +231,12 @@ impl Display for PackageInfo {
             }
         }
 
+        writeln!(f, "APT-Pin-Priority: {pin_priority}")?;
+
+        if let Some(reason) = candidate_reason {
+            writeln!(f, "APT-Candidate: {reason}")?;
+        }
+
         writeln!(f, "Description: {description}")?;
 
         Ok(())
@@ -303,7 +320,7 @@ impl OmaPackage {
         })
     }
 
-    pub fn pkg_info(&self, cache: &Cache) -> OmaAptResult<PackageInfo> {
+    pub fn pkg_info(&self, cache: &Cache, pins: &[Pin]) -> OmaAptResult<PackageInfo> {
         let package: Box<str> = Box::from(self.raw_pkg.fullname(true));
         let version: Box<str> = Box::from(self.version_raw.version());
         let ver = Version::new(
@@ -332,6 +349,12 @@ impl OmaPackage {
             .summary()
             .unwrap_or_else(|| "No description".to_string());
 
+        let pkg = self.package(cache);
+        let candidate_reason = pkg
+            .candidate()
+            .filter(|c| c.version() == ver.version())
+            .and_then(|_| CandidateReason::from_package(&pkg, pins));
+
         Ok(PackageInfo {
             package,
             version,
@@ -343,6 +366,8 @@ impl OmaPackage {
             apt_sources: pkg_files,
             description,
             short_description,
+            pin_priority: ver.priority(),
+            candidate_reason,
         })
     }
 
@@ -435,6 +460,6 @@ fn test_pkginfo_display() {
     let pkg = cache.get("apt").unwrap();
     let version = pkg.candidate().unwrap();
     let info = OmaPackage::new(&version, &pkg).unwrap();
-    let info = info.pkg_info(&cache).unwrap();
+    let info = info.pkg_info(&cache, &[]).unwrap();
     println!("{info}");
 }
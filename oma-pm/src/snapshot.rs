@@ -0,0 +1,402 @@
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use chrono::Local;
+use spdlog::debug;
+
+const BTRFS_SNAPSHOT_DIR: &str = ".snapshots/oma";
+const ARCHIVE_SNAPSHOT_DIR: &str = "var/lib/oma/snapshots";
+const ARCHIVE_PATHS: &[&str] = &["etc", "var/lib/dpkg"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("Failed to execute `{0}': {1}")]
+    ExecuteCommand(String, io::Error),
+    #[error("`{0}' exited with status: {1}")]
+    CommandFailed(String, String),
+    #[error("Failed to create file or directory: {0}: {1}")]
+    FailedOperateDirOrFile(String, io::Error),
+    #[error("Snapshot {0} does not exist")]
+    NotFound(String),
+    #[error("Snapshot backend {0} is not available on this system")]
+    Unavailable(&'static str),
+    #[error("Unknown snapshot backend: {0}")]
+    UnknownBackend(String),
+    #[error(
+        "The root filesystem is mounted with explicit `{0}', so changing the default subvolume has no effect. \
+        To roll back manually, run `btrfs subvolume snapshot {1} <new subvolume>' and change `{0}' to the new subvolume"
+    )]
+    ExplicitSubvolume(String, String),
+}
+
+pub type SnapshotResult<T> = Result<T, SnapshotError>;
+
+/// When a snapshot is taken relative to a commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotStage {
+    Pre,
+    Post,
+}
+
+impl SnapshotStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SnapshotStage::Pre => "pre",
+            SnapshotStage::Post => "post",
+        }
+    }
+}
+
+impl Display for SnapshotStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What the caller has to do after a rollback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollbackStatus {
+    /// Files have been restored in place.
+    Restored,
+    /// The snapshot will become active on the next boot.
+    RebootRequired,
+}
+
+/// A snapshot taken around a commit.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub stage: SnapshotStage,
+    pub backend: &'static str,
+    pub id: String,
+}
+
+/// Pluggable system snapshot backend.
+pub trait SnapshotBackend {
+    /// Backend name, recorded along with snapshot IDs.
+    fn name(&self) -> &'static str;
+    /// Whether this backend can snapshot the given sysroot.
+    fn is_available(&self, sysroot: &Path) -> bool;
+    /// Take a snapshot, returning its ID.
+    fn create(&self, sysroot: &Path, stage: SnapshotStage) -> SnapshotResult<String>;
+    /// Restore the snapshot with the given ID.
+    fn rollback(&self, sysroot: &Path, id: &str) -> SnapshotResult<RollbackStatus>;
+}
+
+/// Snapshot backend and callback used by [`crate::CommitConfig`].
+pub struct SnapshotHook {
+    pub backend: Box<dyn SnapshotBackend>,
+    /// Called with every snapshot taken, e.g. to record it in the history database.
+    pub callback: Box<dyn FnMut(&Snapshot)>,
+}
+
+impl SnapshotHook {
+    pub(crate) fn run(&mut self, sysroot: &Path, stage: SnapshotStage) -> SnapshotResult<()> {
+        debug!(
+            "Taking {stage} snapshot with {} backend",
+            self.backend.name()
+        );
+
+        let id = self.backend.create(sysroot, stage)?;
+
+        (self.callback)(&Snapshot {
+            stage,
+            backend: self.backend.name(),
+            id,
+        });
+
+        Ok(())
+    }
+}
+
+/// Get snapshot backend by name.
+pub fn backend_from_name(name: &str) -> SnapshotResult<Box<dyn SnapshotBackend>> {
+    match name {
+        BtrfsSnapshot::NAME => Ok(Box::new(BtrfsSnapshot)),
+        ArchiveSnapshot::NAME => Ok(Box::new(ArchiveSnapshot)),
+        name => Err(SnapshotError::UnknownBackend(name.to_string())),
+    }
+}
+
+/// Use Btrfs snapshot if sysroot is a Btrfs subvolume, otherwise fall back to archive.
+pub fn detect_backend(sysroot: &Path) -> Box<dyn SnapshotBackend> {
+    if BtrfsSnapshot.is_available(sysroot) {
+        Box::new(BtrfsSnapshot)
+    } else {
+        Box::new(ArchiveSnapshot)
+    }
+}
+
+fn snapshot_id(stage: SnapshotStage) -> String {
+    format!("{}-{stage}", Local::now().format("%Y%m%d%H%M%S"))
+}
+
+fn run_command(cmd: &mut Command) -> SnapshotResult<()> {
+    let cmd_str = format!("{cmd:?}");
+    debug!("Running {cmd_str}");
+
+    let status = cmd
+        .status()
+        .map_err(|e| SnapshotError::ExecuteCommand(cmd_str.clone(), e))?;
+
+    if !status.success() {
+        return Err(SnapshotError::CommandFailed(cmd_str, status.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Read-only Btrfs subvolume snapshot of the whole sysroot.
+pub struct BtrfsSnapshot;
+
+impl BtrfsSnapshot {
+    pub const NAME: &'static str = "btrfs";
+
+    fn snapshot_path(sysroot: &Path, id: &str) -> PathBuf {
+        sysroot.join(BTRFS_SNAPSHOT_DIR).join(id)
+    }
+
+    /// The `subvol=` or `subvolid=` option the root filesystem is explicitly mounted with,
+    /// from the kernel command line or fstab.
+    fn explicit_root_subvol(sysroot: &Path) -> Option<String> {
+        // 只有 sysroot 为当前根目录时内核命令行才与其相关
+        let cmdline = if sysroot == Path::new("/") {
+            fs::read_to_string("/proc/cmdline").unwrap_or_default()
+        } else {
+            String::new()
+        };
+
+        let rootflags = cmdline
+            .split_whitespace()
+            .filter_map(|arg| arg.strip_prefix("rootflags="));
+
+        let fstab = fs::read_to_string(sysroot.join("etc/fstab")).unwrap_or_default();
+
+        let fstab_options = fstab.lines().filter_map(|line| {
+            if line.trim_start().starts_with('#') {
+                return None;
+            }
+
+            // 字段依次为设备、挂载点、文件系统类型和挂载选项
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+
+            match fields.as_slice() {
+                [_, "/", _, options, ..] => Some(*options),
+                _ => None,
+            }
+        });
+
+        rootflags
+            .chain(fstab_options)
+            .flat_map(|options| options.split(','))
+            .find(|opt| opt.starts_with("subvol=") || opt.starts_with("subvolid="))
+            .map(|opt| opt.to_string())
+    }
+}
+
+impl SnapshotBackend for BtrfsSnapshot {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn is_available(&self, sysroot: &Path) -> bool {
+        Command::new("btrfs")
+            .arg("subvolume")
+            .arg("show")
+            .arg(sysroot)
+            .output()
+            .is_ok_and(|out| out.status.success())
+    }
+
+    fn create(&self, sysroot: &Path, stage: SnapshotStage) -> SnapshotResult<String> {
+        if !self.is_available(sysroot) {
+            return Err(SnapshotError::Unavailable(Self::NAME));
+        }
+
+        let dir = sysroot.join(BTRFS_SNAPSHOT_DIR);
+        fs::create_dir_all(&dir)
+            .map_err(|e| SnapshotError::FailedOperateDirOrFile(dir.display().to_string(), e))?;
+
+        let id = snapshot_id(stage);
+
+        run_command(
+            Command::new("btrfs")
+                .args(["subvolume", "snapshot", "-r"])
+                .arg(sysroot)
+                .arg(Self::snapshot_path(sysroot, &id)),
+        )?;
+
+        Ok(id)
+    }
+
+    fn rollback(&self, sysroot: &Path, id: &str) -> SnapshotResult<RollbackStatus> {
+        let snapshot = Self::snapshot_path(sysroot, id);
+
+        if !snapshot.is_dir() {
+            return Err(SnapshotError::NotFound(id.to_string()));
+        }
+
+        // 显式指定了子卷时 set-default 不会改变下次启动的根，不能报告回滚成功
+        if let Some(subvol) = Self::explicit_root_subvol(sysroot) {
+            return Err(SnapshotError::ExplicitSubvolume(
+                subvol,
+                snapshot.display().to_string(),
+            ));
+        }
+
+        // 只读快照无法直接作为根使用，因此需要先创建一个可写快照，再将其设置为默认子卷
+        let target = Self::snapshot_path(sysroot, &format!("{id}-rollback"));
+
+        run_command(
+            Command::new("btrfs")
+                .args(["subvolume", "snapshot"])
+                .arg(&snapshot)
+                .arg(&target),
+        )?;
+
+        run_command(
+            Command::new("btrfs")
+                .args(["subvolume", "set-default"])
+                .arg(&target),
+        )?;
+
+        Ok(RollbackStatus::RebootRequired)
+    }
+}
+
+/// Copy of `/etc` and `/var/lib/dpkg`, using reflinks where the filesystem supports them.
+pub struct ArchiveSnapshot;
+
+impl ArchiveSnapshot {
+    pub const NAME: &'static str = "archive";
+
+    fn snapshot_path(sysroot: &Path, id: &str) -> PathBuf {
+        sysroot.join(ARCHIVE_SNAPSHOT_DIR).join(id)
+    }
+}
+
+fn copy_tree(from: &Path, to: &Path) -> SnapshotResult<()> {
+    run_command(
+        Command::new("cp")
+            .args(["-a", "--reflink=auto"])
+            .arg(from)
+            .arg(to),
+    )
+}
+
+fn remove_dir_if_exists(path: &Path) -> SnapshotResult<()> {
+    match fs::remove_dir_all(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(SnapshotError::FailedOperateDirOrFile(
+            path.display().to_string(),
+            e,
+        )),
+    }
+}
+
+impl SnapshotBackend for ArchiveSnapshot {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn is_available(&self, _sysroot: &Path) -> bool {
+        true
+    }
+
+    fn create(&self, sysroot: &Path, stage: SnapshotStage) -> SnapshotResult<String> {
+        let id = snapshot_id(stage);
+        let dir = Self::snapshot_path(sysroot, &id);
+
+        for path in ARCHIVE_PATHS {
+            let to = dir.join(path);
+            let parent = to.parent().unwrap_or(&dir);
+
+            fs::create_dir_all(parent).map_err(|e| {
+                SnapshotError::FailedOperateDirOrFile(parent.display().to_string(), e)
+            })?;
+
+            if let Err(e) = copy_tree(&sysroot.join(path), &to) {
+                remove_dir_if_exists(&dir).ok();
+                return Err(e);
+            }
+        }
+
+        Ok(id)
+    }
+
+    fn rollback(&self, sysroot: &Path, id: &str) -> SnapshotResult<RollbackStatus> {
+        let dir = Self::snapshot_path(sysroot, id);
+
+        if !dir.is_dir() {
+            return Err(SnapshotError::NotFound(id.to_string()));
+        }
+
+        // /etc 中可能有用户在快照后新建的文件，因此只覆盖快照中存在的文件
+        copy_tree(&dir.join("etc/."), &sysroot.join("etc"))?;
+
+        // dpkg 数据库需要整体替换，否则新安装软件包的 info 文件会残留
+        let dpkg = sysroot.join("var/lib/dpkg");
+        let new = sysroot.join("var/lib/dpkg.oma-rollback");
+        let old = sysroot.join("var/lib/dpkg.oma-old");
+
+        remove_dir_if_exists(&new)?;
+        copy_tree(&dir.join("var/lib/dpkg"), &new)?;
+
+        remove_dir_if_exists(&old)?;
+        fs::rename(&dpkg, &old)
+            .map_err(|e| SnapshotError::FailedOperateDirOrFile(dpkg.display().to_string(), e))?;
+
+        if let Err(e) = fs::rename(&new, &dpkg) {
+            fs::rename(&old, &dpkg).ok();
+            return Err(SnapshotError::FailedOperateDirOrFile(
+                dpkg.display().to_string(),
+                e,
+            ));
+        }
+
+        remove_dir_if_exists(&old)?;
+
+        Ok(RollbackStatus::Restored)
+    }
+}
+
+#[test]
+fn test_archive_snapshot_rollback() {
+    let sysroot = std::env::temp_dir().join(format!("oma-snapshot-test-{}", std::process::id()));
+    fs::create_dir_all(sysroot.join("etc")).unwrap();
+    fs::create_dir_all(sysroot.join("var/lib/dpkg/info")).unwrap();
+    fs::write(sysroot.join("etc/foo.conf"), "old").unwrap();
+    fs::write(sysroot.join("var/lib/dpkg/status"), "old").unwrap();
+
+    let id = ArchiveSnapshot
+        .create(&sysroot, SnapshotStage::Pre)
+        .unwrap();
+
+    fs::write(sysroot.join("etc/foo.conf"), "new").unwrap();
+    fs::write(sysroot.join("etc/bar.conf"), "new").unwrap();
+    fs::write(sysroot.join("var/lib/dpkg/status"), "new").unwrap();
+    fs::write(sysroot.join("var/lib/dpkg/info/bar.list"), "new").unwrap();
+
+    assert_eq!(
+        ArchiveSnapshot.rollback(&sysroot, &id).unwrap(),
+        RollbackStatus::Restored
+    );
+
+    assert_eq!(
+        fs::read_to_string(sysroot.join("etc/foo.conf")).unwrap(),
+        "old"
+    );
+    assert!(sysroot.join("etc/bar.conf").exists());
+    assert_eq!(
+        fs::read_to_string(sysroot.join("var/lib/dpkg/status")).unwrap(),
+        "old"
+    );
+    assert!(!sysroot.join("var/lib/dpkg/info/bar.list").exists());
+    assert!(!sysroot.join("var/lib/dpkg.oma-old").exists());
+
+    fs::remove_dir_all(&sysroot).unwrap();
+}
@@ -0,0 +1,276 @@
+//! Verify installed files against the md5sums recorded by dpkg, like `dpkg --verify`.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use ahash::{HashMap, HashSet};
+use oma_fetch::checksum::Checksum;
+use spdlog::debug;
+
+const DPKG_INFO_DIR: &str = "var/lib/dpkg/info";
+const DPKG_DIVERSIONS_PATH: &str = "var/lib/dpkg/diversions";
+
+#[derive(Debug, thiserror::Error)]
+pub enum IntegrityError {
+    #[error("Failed to read file: {0}: {1}")]
+    FailedReadFile(String, io::Error),
+    #[error("Failed to read md5sums from {0}: {1}")]
+    ReadArchiveMd5sums(String, String),
+}
+
+pub type IntegrityResult<T> = Result<T, IntegrityError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileProblem {
+    /// Content differs from the recorded md5sum
+    Modified,
+    /// File does not exist
+    Missing,
+    /// File is now owned by another package
+    Replaced,
+}
+
+impl FileProblem {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileProblem::Modified => "modified",
+            FileProblem::Missing => "missing",
+            FileProblem::Replaced => "replaced",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileIssue {
+    pub path: String,
+    pub problem: FileProblem,
+}
+
+/// `dpkg-divert` records: original path -> (diverted path, diverting package)
+pub type Diversions = HashMap<String, (String, String)>;
+
+/// Read `md5sums` of an installed package from the dpkg database.
+///
+/// Returns `None` if the package does not ship any md5sums.
+pub fn dpkg_md5sums(
+    sysroot: impl AsRef<Path>,
+    package: &str,
+    arch: &str,
+) -> IntegrityResult<Option<String>> {
+    read_info_file(sysroot.as_ref(), package, arch, "md5sums")
+}
+
+/// Read `md5sums` from the control archive of a package archive.
+pub fn archive_md5sums(deb: impl AsRef<Path>) -> IntegrityResult<String> {
+    let deb = deb.as_ref();
+
+    let output = Command::new("dpkg-deb")
+        .arg("--info")
+        .arg(deb)
+        .arg("md5sums")
+        .output()
+        .map_err(|e| {
+            IntegrityError::ReadArchiveMd5sums(deb.display().to_string(), e.to_string())
+        })?;
+
+    if !output.status.success() {
+        return Err(IntegrityError::ReadArchiveMd5sums(
+            deb.display().to_string(),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Read `dpkg-divert` records from the dpkg database.
+pub fn diversions(sysroot: impl AsRef<Path>) -> IntegrityResult<Diversions> {
+    let path = sysroot.as_ref().join(DPKG_DIVERSIONS_PATH);
+
+    let s = match fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Diversions::default()),
+        Err(e) => {
+            return Err(IntegrityError::FailedReadFile(
+                path.display().to_string(),
+                e,
+            ));
+        }
+    };
+
+    Ok(parse_diversions(&s))
+}
+
+/// Check files listed in `md5sums` of an installed package.
+pub fn verify_files(
+    sysroot: impl AsRef<Path>,
+    package: &str,
+    arch: &str,
+    md5sums: &str,
+    diversions: &Diversions,
+) -> IntegrityResult<Vec<FileIssue>> {
+    let sysroot = sysroot.as_ref();
+
+    let list = read_info_file(sysroot, package, arch, "list")?;
+    let owned = list.as_deref().map(|l| l.lines().collect::<HashSet<_>>());
+
+    let mut issues = vec![];
+
+    for (md5, path) in parse_md5sums(md5sums) {
+        // 文件被其他软件包接管 (Replaces) 后，dpkg 会将其从本包的文件列表中移除
+        if owned.as_ref().is_some_and(|o| !o.contains(path.as_str())) {
+            issues.push(FileIssue {
+                path,
+                problem: FileProblem::Replaced,
+            });
+            continue;
+        }
+
+        // 被其他软件包转移的文件，本包的版本位于转移后的路径
+        let real = match diversions.get(&path) {
+            Some((to, by)) if by != package => to.as_str(),
+            _ => path.as_str(),
+        };
+
+        let real = sysroot.join(real.trim_start_matches('/'));
+
+        if let Some(problem) = check_file(&real, md5) {
+            issues.push(FileIssue { path, problem });
+        }
+    }
+
+    Ok(issues)
+}
+
+fn check_file(path: &Path, md5: &str) -> Option<FileProblem> {
+    if fs::metadata(path).is_err_and(|e| e.kind() == io::ErrorKind::NotFound) {
+        return Some(FileProblem::Missing);
+    }
+
+    let Ok(checksum) = Checksum::from_md5_str(md5) else {
+        debug!("Invalid md5sum {md5} of {}", path.display());
+        return None;
+    };
+
+    match checksum.cmp_file(path) {
+        Ok(true) => None,
+        Ok(false) => Some(FileProblem::Modified),
+        Err(e) => {
+            debug!("Failed to checksum {}: {e}", path.display());
+            Some(FileProblem::Modified)
+        }
+    }
+}
+
+/// Read `<package>:<arch>.<ext>` or `<package>.<ext>` from the dpkg info directory.
+fn read_info_file(
+    sysroot: &Path,
+    package: &str,
+    arch: &str,
+    ext: &str,
+) -> IntegrityResult<Option<String>> {
+    let dir = sysroot.join(DPKG_INFO_DIR);
+
+    // Multi-Arch: same 的软件包文件名中带有架构
+    let candidates: [PathBuf; 2] = [
+        dir.join(format!("{package}:{arch}.{ext}")),
+        dir.join(format!("{package}.{ext}")),
+    ];
+
+    for path in candidates {
+        match fs::read_to_string(&path) {
+            Ok(s) => return Ok(Some(s)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(IntegrityError::FailedReadFile(
+                    path.display().to_string(),
+                    e,
+                ));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Parse lines like `0123456789abcdef0123456789abcdef  usr/bin/foo`.
+fn parse_md5sums(s: &str) -> impl Iterator<Item = (&str, String)> {
+    s.lines().filter_map(|line| {
+        let (md5, path) = line.split_once(char::is_whitespace)?;
+        let path = path.trim_start();
+
+        if path.is_empty() {
+            return None;
+        }
+
+        Some((md5, format!("/{}", path.trim_start_matches('/'))))
+    })
+}
+
+/// The diversions file consists of triples of lines: from, to and the diverting package.
+fn parse_diversions(s: &str) -> Diversions {
+    let lines = s.lines().collect::<Vec<_>>();
+
+    lines
+        .chunks_exact(3)
+        .map(|c| (c[0].to_string(), (c[1].to_string(), c[2].to_string())))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_verify_files() {
+        let sysroot = std::env::temp_dir().join(format!("oma-verify-test-{}", std::process::id()));
+        let info = sysroot.join(DPKG_INFO_DIR);
+        fs::create_dir_all(&info).unwrap();
+        fs::create_dir_all(sysroot.join("usr/bin")).unwrap();
+
+        fs::write(sysroot.join("usr/bin/ok"), b"ok\n").unwrap();
+        fs::write(sysroot.join("usr/bin/changed"), b"changed\n").unwrap();
+        fs::write(sysroot.join("usr/bin/foo.distrib"), b"ok\n").unwrap();
+        fs::write(sysroot.join("usr/bin/foo"), b"diverted\n").unwrap();
+
+        fs::write(
+            info.join("foo:amd64.list"),
+            "/usr\n/usr/bin\n/usr/bin/ok\n/usr/bin/changed\n/usr/bin/gone\n/usr/bin/foo\n",
+        )
+        .unwrap();
+
+        // md5sum of "ok\n"
+        let ok = "eff5bc1ef8ec9d03e640fc4370f5eacd";
+
+        let md5sums = format!(
+            "{ok}  usr/bin/ok\n{ok}  usr/bin/changed\n{ok}  usr/bin/gone\n{ok}  usr/bin/foo\n{ok}  usr/bin/taken\n"
+        );
+
+        let diversions = parse_diversions("/usr/bin/foo\n/usr/bin/foo.distrib\nbar\n");
+
+        let issues = verify_files(&sysroot, "foo", "amd64", &md5sums, &diversions).unwrap();
+
+        assert_eq!(
+            issues,
+            vec![
+                FileIssue {
+                    path: "/usr/bin/changed".to_string(),
+                    problem: FileProblem::Modified,
+                },
+                FileIssue {
+                    path: "/usr/bin/gone".to_string(),
+                    problem: FileProblem::Missing,
+                },
+                FileIssue {
+                    path: "/usr/bin/taken".to_string(),
+                    problem: FileProblem::Replaced,
+                },
+            ]
+        );
+
+        fs::remove_dir_all(&sysroot).unwrap();
+    }
+}
@@ -255,14 +255,21 @@ impl<'a> IndexTargetConfig<'a> {
 
         let mut sort_res = vec![];
 
-        for (_, v) in &mut res_map {
+        for (name, v) in &mut res_map {
             v.sort_unstable_by(|a, b| {
                 compress_file(&a.item.name).cmp(&compress_file(&b.item.name))
             });
             if v[0].item.size == 0 {
                 continue;
             }
-            sort_res.push(v.last().unwrap().to_owned());
+
+            let mut entry = v.last().unwrap().to_owned();
+
+            if !entry.keep_compress {
+                entry.pdiff_index = find_pdiff_index(checksums, name);
+            }
+
+            sort_res.push(entry);
         }
 
         Ok(fallback_of_filter(sort_res))
@@ -321,6 +328,13 @@ impl<'a> IndexTargetConfig<'a> {
     }
 }
 
+/// Find the PDiff index (`<name>.diff/Index`) of an uncompressed index file from InRelease
+fn find_pdiff_index(checksums: &[ChecksumItem], name: &str) -> Option<ChecksumItem> {
+    let index_name = format!("{name}.diff/Index");
+
+    checksums.iter().find(|x| x.name == index_name).cloned()
+}
+
 fn fallback_of_filter(res: Vec<ChecksumDownloadEntry>) -> Vec<ChecksumDownloadEntry> {
     if res.len() <= 1 {
         return res;
@@ -393,6 +407,7 @@ fn to_download_entry(
             .1
             .to_string(),
         fallback_of: config.get("Fallback-Of").cloned(),
+        pdiff_index: None,
     }
 }
 
@@ -426,6 +441,7 @@ pub struct ChecksumDownloadEntry {
     pub optional: bool,
     pub config_key: String,
     pub fallback_of: Option<String>,
+    pub pdiff_index: Option<ChecksumItem>,
 }
 
 fn get_matches_language(locales: impl IntoIterator<Item = String>) -> Vec<String> {
@@ -534,6 +550,7 @@ fn test_fallback_of_filter() {
             optional: false,
             config_key: "key1".to_string(),
             fallback_of: None,
+            pdiff_index: None,
         },
         ChecksumDownloadEntry {
             item: ChecksumItem {
@@ -546,6 +563,7 @@ fn test_fallback_of_filter() {
             optional: false,
             config_key: "key2".to_string(),
             fallback_of: Some("key1".to_string()),
+            pdiff_index: None,
         },
         ChecksumDownloadEntry {
             item: ChecksumItem {
@@ -558,6 +576,7 @@ fn test_fallback_of_filter() {
             optional: false,
             config_key: "key3".to_string(),
             fallback_of: None,
+            pdiff_index: None,
         },
     ];
 
@@ -568,3 +587,25 @@ fn test_fallback_of_filter() {
         assert!(filtered.iter().any(|x| x.config_key == i));
     }
 }
+
+#[test]
+fn test_find_pdiff_index() {
+    let checksums = vec![
+        ChecksumItem {
+            name: "main/binary-amd64/Packages".to_string(),
+            size: 100,
+            checksum: "abc".to_string(),
+        },
+        ChecksumItem {
+            name: "main/binary-amd64/Packages.diff/Index".to_string(),
+            size: 10,
+            checksum: "def".to_string(),
+        },
+    ];
+
+    assert_eq!(
+        find_pdiff_index(&checksums, "main/binary-amd64/Packages").map(|x| x.checksum),
+        Some("def".to_string())
+    );
+    assert!(find_pdiff_index(&checksums, "main/binary-arm64/Packages").is_none());
+}
@@ -22,6 +22,7 @@ use oma_fetch::{
         Response,
         header::{CONTENT_LENGTH, HeaderValue},
     },
+    throttle::NetworkLimits,
 };
 
 use oma_fetch::{SingleDownloadError, Summary};
@@ -44,6 +45,7 @@ use crate::{
         ChecksumItem, InReleaseChecksum, InReleaseError, Release, file_is_compress,
         split_ext_and_filename, verify_inrelease,
     },
+    pdiff::{PdiffResult, PdiffTask},
     sourceslist::{OmaSourceEntry, OmaSourceEntryFrom, scan_sources_lists_paths},
     util::DatabaseFilenameReplacer,
 };
@@ -93,6 +95,10 @@ pub enum RefreshError {
     DownloadManagerBuilderError(BuilderError),
     #[error("No metadata file to download")]
     NoMetadataToDownload,
+    #[error(transparent)]
+    MirrorList(#[from] oma_fetch::mirror_list::MirrorListError),
+    #[error("No usable mirror in mirror list: {0}")]
+    NoMirrorAvailable(String),
 }
 
 type Result<T> = std::result::Result<T, RefreshError>;
@@ -102,9 +108,13 @@ pub struct OmaRefresh {
     source: PathBuf,
     #[builder(default = 4)]
     threads: usize,
+    #[builder(default)]
+    limits: NetworkLimits,
     arch: String,
     download_dir: PathBuf,
     client: ClientWithMiddleware,
+    /// Same as `client` but not following redirects, used to query mirror redirectors
+    redirector_client: Option<ClientWithMiddleware>,
     #[cfg(feature = "aosc")]
     refresh_topics: bool,
     #[cfg(not(feature = "apt"))]
@@ -253,12 +263,12 @@ impl OmaRefresh {
                 .flat_map(|x| x.file_name().map(|s| s.to_string())),
         );
 
-        let (tasks, total, optional_index_files) =
+        let (mut tasks, mut total, optional_index_files, pdiff_tasks) =
             self_arc.collect_all_release_entry(&replacer, mirror_sources)?;
 
         debug!("oma will download source metadata: {tasks:#?}");
 
-        if tasks.is_empty() {
+        if tasks.is_empty() && pdiff_tasks.is_empty() {
             return Err(RefreshError::NoMetadataToDownload);
         }
 
@@ -266,15 +276,39 @@ impl OmaRefresh {
             download_list.insert(i.filename.clone());
         }
 
+        for i in &pdiff_tasks {
+            download_list.insert(i.file_name.clone());
+        }
+
         remove_unused_db(&self_arc.download_dir, download_list).ok();
 
+        let mut pdiff_success = vec![];
+
+        if !pdiff_tasks.is_empty() {
+            let sc2 = self_arc.clone();
+            let (tx, rx) = flume::unbounded::<Event>();
+            let (success, fallback) =
+                run_task_with_pump(&async_rt_handle, &rx, &mut callback, async move {
+                    Ok(sc2.apply_pdiffs(tx, pdiff_tasks).await)
+                })?;
+
+            for (summary, size) in success {
+                total = total.saturating_sub(size);
+                pdiff_success.push(summary);
+            }
+
+            tasks.extend(fallback);
+        }
+
         let sc2 = self_arc.clone();
         let (tx, rx) = flume::unbounded::<Event>();
-        let res = run_task_with_pump(&async_rt_handle, &rx, &mut callback, async move {
+        let mut res = run_task_with_pump(&async_rt_handle, &rx, &mut callback, async move {
             sc2.download_release_data(tx, tasks, total, optional_index_files)
                 .await
         })?;
 
+        res.success.extend(pdiff_success);
+
         // 有元数据更新才执行 success invoke
         let should_run_invoke = res.has_wrote();
 
@@ -317,8 +351,10 @@ impl OmaRefresh {
     ) -> Result<Summary> {
         let dm = DownloadManager::builder()
             .client(self.client.clone())
+            .maybe_redirector_client(self.redirector_client.clone())
             .download_list(tasks.into())
             .threads(self.threads)
+            .limits(self.limits)
             .total_size(total)
             .build();
 
@@ -363,6 +399,84 @@ impl OmaRefresh {
         Ok(res)
     }
 
+    /// Try to update metadata files by PDiff, returns successfully updated files and their
+    /// sizes, and full download tasks of the files which could not be patched.
+    async fn apply_pdiffs(
+        &self,
+        tx: Sender<Event>,
+        pdiff_tasks: Vec<PdiffTask>,
+    ) -> (Vec<(SuccessSummary, u64)>, Vec<DownloadEntry>) {
+        let total = pdiff_tasks.len();
+        let threads = Arc::new(tokio::sync::Semaphore::new(self.threads));
+        let mut set = tokio::task::JoinSet::new();
+
+        for (index, task) in pdiff_tasks.into_iter().enumerate() {
+            let client = self.client.clone();
+            let download_dir = self.download_dir.clone();
+            let threads = threads.clone();
+            let tx = tx.clone();
+
+            set.spawn(async move {
+                let _permit = threads.acquire_owned().await;
+
+                let _ = tx
+                    .send_async(Event::DownloadEvent(oma_fetch::Event::NewProgressSpinner {
+                        index,
+                        total,
+                        msg: format!("{} (PDiff)", task.msg),
+                    }))
+                    .await;
+
+                let res = task.run(&client, &download_dir).await;
+
+                let _ = tx
+                    .send_async(Event::DownloadEvent(oma_fetch::Event::ProgressDone(index)))
+                    .await;
+
+                (index, task, res)
+            });
+        }
+
+        let mut success = vec![];
+        let mut fallback = vec![];
+
+        while let Some(res) = set.join_next().await {
+            let Ok((index, task, res)) = res else {
+                continue;
+            };
+
+            match res {
+                Ok(res) => {
+                    let _ = tx
+                        .send_async(Event::DownloadEvent(oma_fetch::Event::DownloadDone {
+                            index,
+                            msg: task.msg.clone().into(),
+                        }))
+                        .await;
+
+                    success.push((
+                        SuccessSummary {
+                            file_name: task.file_name,
+                            index,
+                            wrote: matches!(res, PdiffResult::Patched),
+                            url: task.index_url,
+                        },
+                        task.size,
+                    ));
+                }
+                Err(e) => {
+                    debug!(
+                        "Failed to update {} by PDiff, falling back to full download: {e}",
+                        task.file_name
+                    );
+                    fallback.push(task.fallback);
+                }
+            }
+        }
+
+        (success, fallback)
+    }
+
     #[cfg(feature = "apt")]
     fn run_success_post_invoke(&self) {
         use spdlog::warn;
@@ -514,9 +628,15 @@ impl OmaRefresh {
         &self,
         replacer: &DatabaseFilenameReplacer,
         mirror_sources: MirrorSources,
-    ) -> Result<(Vec<DownloadEntry>, u64, HashSet<String>)> {
+    ) -> Result<(Vec<DownloadEntry>, u64, HashSet<String>, Vec<PdiffTask>)> {
         let mut total = 0;
         let mut tasks = vec![];
+        let mut pdiff_tasks = vec![];
+
+        #[cfg(feature = "apt")]
+        let pdiffs = apt_config::find_bool("Acquire::PDiffs".to_string(), true);
+        #[cfg(not(feature = "apt"))]
+        let pdiffs = true;
 
         #[cfg(feature = "apt")]
         let index_target_config = IndexTargetConfig::new_from_apt_config(&self.arch);
@@ -624,11 +744,12 @@ impl OmaRefresh {
                     &release,
                     replacer,
                     &mut optional_index_files,
+                    pdiffs.then_some(&mut pdiff_tasks),
                 )?;
             }
         }
 
-        Ok((tasks, total, optional_index_files))
+        Ok((tasks, total, optional_index_files, pdiff_tasks))
     }
 }
 
@@ -759,18 +880,11 @@ fn collect_flat_repo_no_release(
 
     let dist_url = mirror_source.dist_path();
 
-    let from = match mirror_source.from()? {
-        OmaSourceEntryFrom::Http => DownloadSourceType::Http,
-        OmaSourceEntryFrom::Local => DownloadSourceType::Local(mirror_source.is_flat()),
-    };
-
-    let download_url = format!("{dist_url}/Packages");
     let file_path = format!("{dist_url}Packages");
 
-    let sources = vec![DownloadSource {
-        url: download_url.clone(),
-        source_type: from,
-    }];
+    let sources = download_sources(mirror_source, mirror_source.is_flat(), |entry| {
+        format!("{}/Packages", entry.dist_path())
+    })?;
 
     let task = DownloadEntry::builder()
         .source(sources)
@@ -786,6 +900,7 @@ fn collect_flat_repo_no_release(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn collect_download_task(
     c: &ChecksumDownloadEntry,
     mirror_source: &MirrorSource,
@@ -794,19 +909,14 @@ fn collect_download_task(
     release: &Release,
     replacer: &DatabaseFilenameReplacer,
     optional_set: &mut HashSet<String>,
+    pdiff_tasks: Option<&mut Vec<PdiffTask>>,
 ) -> Result<()> {
     let file_type = &c.msg;
 
     let msg = mirror_source.get_human_download_message(Some(file_type))?;
 
-    let from = match mirror_source.from()? {
-        OmaSourceEntryFrom::Http => DownloadSourceType::Http,
-        OmaSourceEntryFrom::Local => DownloadSourceType::Local(
-            mirror_source.is_flat()
-                && (!file_is_compress(&c.item.name)
-                    || (file_is_compress(&c.item.name) && c.keep_compress)),
-        ),
-    };
+    let local_as_symlink = mirror_source.is_flat()
+        && (!file_is_compress(&c.item.name) || (file_is_compress(&c.item.name) && c.keep_compress));
 
     let not_compress_filename_before = if file_is_compress(&c.item.name) {
         Cow::Owned(split_ext_and_filename(&c.item.name).1)
@@ -814,19 +924,19 @@ fn collect_download_task(
         Cow::Borrowed(&c.item.name)
     };
 
+    let not_compress_item = release
+        .checksum_type_and_list()
+        .1
+        .iter()
+        .find(|x| x.name == *not_compress_filename_before);
+
     let checksum = if c.keep_compress {
         Some(&c.item.checksum)
     } else {
-        release
-            .checksum_type_and_list()
-            .1
-            .iter()
-            .find(|x| x.name == *not_compress_filename_before)
-            .as_ref()
-            .map(|c| &c.checksum)
+        not_compress_item.map(|c| &c.checksum)
     };
 
-    let download_url = if release.acquire_by_hash() {
+    let download_path = if release.acquire_by_hash() {
         let path = Path::new(&c.item.name);
         let parent = path.parent().unwrap_or(path);
         let dir = match release.checksum_type_and_list().0 {
@@ -835,17 +945,21 @@ fn collect_download_task(
             InReleaseChecksum::Md5 => "MD5Sum",
         };
 
-        let path = parent.join("by-hash").join(dir).join(&c.item.checksum);
-
-        mirror_source.get_download_url(&path.display().to_string())
+        Cow::Owned(
+            parent
+                .join("by-hash")
+                .join(dir)
+                .join(&c.item.checksum)
+                .display()
+                .to_string(),
+        )
     } else {
-        mirror_source.get_download_url(&c.item.name)
+        Cow::Borrowed(&c.item.name)
     };
 
-    let sources = vec![DownloadSource {
-        url: download_url.to_string(),
-        source_type: from,
-    }];
+    let sources = download_sources(mirror_source, local_as_symlink, |entry| {
+        entry.get_download_url(&download_path)
+    })?;
 
     let file_name = if c.keep_compress {
         mirror_source.get_download_file_name(Some(&c.item.name), replacer)?
@@ -857,12 +971,18 @@ fn collect_download_task(
         optional_set.insert(file_name.clone());
     }
 
+    let checksum = if let Some(checksum) = checksum {
+        Some(to_checksum(release.checksum_type_and_list().0, checksum)?)
+    } else {
+        None
+    };
+
     let task = DownloadEntry::builder()
         .source(sources)
-        .filename(file_name)
+        .filename(file_name.clone())
         .dir(download_dir.join("partial"))
         .allow_resume(false)
-        .msg(msg.into())
+        .msg(msg.clone().into())
         .final_dir(download_dir.to_path_buf())
         .file_type({
             if c.keep_compress {
@@ -879,22 +999,63 @@ fn collect_download_task(
                 }
             }
         })
-        .maybe_hash(if let Some(checksum) = checksum {
-            match release.checksum_type_and_list().0 {
-                InReleaseChecksum::Sha256 => Some(Checksum::from_sha256_str(checksum)?),
-                InReleaseChecksum::Sha512 => Some(Checksum::from_sha512_str(checksum)?),
-                InReleaseChecksum::Md5 => Some(Checksum::from_md5_str(checksum)?),
-            }
-        } else {
-            None
-        })
+        .maybe_hash(checksum.clone())
         .build();
 
+    // Only remote metadata with an existing local copy can be updated by PDiff
+    if let Some(pdiff_tasks) = pdiff_tasks
+        && let Some(pdiff_index) = &c.pdiff_index
+        && let Some(target_checksum) = checksum
+        && let Some(not_compress_item) = not_compress_item
+        && !c.keep_compress
+        && *mirror_source.from()? == OmaSourceEntryFrom::Http
+        && download_dir.join(&file_name).is_file()
+    {
+        pdiff_tasks.push(PdiffTask {
+            fallback: task,
+            file_name,
+            msg,
+            index_url: mirror_source.get_download_url(&pdiff_index.name),
+            index_checksum: to_checksum(release.checksum_type_and_list().0, &pdiff_index.checksum)?,
+            target_checksum,
+            size: not_compress_item.size,
+        });
+
+        return Ok(());
+    }
+
     tasks.push(task);
 
     Ok(())
 }
 
+/// Download sources of a file in `mirror_source`, one per mirror in order of preference
+fn download_sources(
+    mirror_source: &MirrorSource,
+    local_as_symlink: bool,
+    url: impl Fn(&OmaSourceEntry) -> String,
+) -> Result<Vec<DownloadSource>> {
+    mirror_source
+        .candidates()
+        .iter()
+        .map(|entry| {
+            let url = url(entry);
+            let source_type = DownloadSourceType::from_url(&url, local_as_symlink)
+                .ok_or_else(|| RefreshError::UnsupportedProtocol(url.clone()))?;
+
+            Ok(DownloadSource { url, source_type })
+        })
+        .collect()
+}
+
+fn to_checksum(checksum_type: InReleaseChecksum, checksum: &str) -> Result<Checksum> {
+    Ok(match checksum_type {
+        InReleaseChecksum::Sha256 => Checksum::from_sha256_str(checksum)?,
+        InReleaseChecksum::Sha512 => Checksum::from_sha512_str(checksum)?,
+        InReleaseChecksum::Md5 => Checksum::from_md5_str(checksum)?,
+    })
+}
+
 fn run_task_with_pump<Fut, T>(
     handle: &tokio::runtime::Handle,
     rx: &flume::Receiver<Event>,
@@ -1,5 +1,6 @@
 mod config;
 pub mod db;
 pub mod inrelease;
+mod pdiff;
 mod sourceslist;
 mod util;
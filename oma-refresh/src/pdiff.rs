@@ -0,0 +1,466 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use deb822_fast::{FromDeb822, FromDeb822Paragraph, Paragraph};
+use flate2::read::GzDecoder;
+use oma_fetch::{
+    DownloadEntry,
+    checksum::{Checksum, ChecksumError},
+    reqwest::Method,
+    send_request_with_url_and_method,
+};
+use reqwest_middleware::ClientWithMiddleware;
+use spdlog::debug;
+
+use crate::inrelease::ChecksumItem;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PdiffError {
+    #[error("Bad PDiff Index")]
+    BadIndex,
+    #[error("PDiff Index does not match InRelease")]
+    OutdatedIndex,
+    #[error("Local file is not listed in PDiff history")]
+    NotInHistory,
+    #[error("PDiff patch {0} is missing")]
+    MissingPatch(String),
+    #[error("Unsupported PDiff patch compression: {0}")]
+    UnsupportedCompression(String),
+    #[error("Bad ed command in PDiff patch: {0}")]
+    BadEdCommand(String),
+    #[error("Checksum mismatch: {0}")]
+    ChecksumMismatch(String),
+    #[error("Failed to download {0}: {1}")]
+    Download(String, reqwest_middleware::Error),
+    #[error(transparent)]
+    Checksum(#[from] ChecksumError),
+    #[error("Failed to operate file {0}: {1}")]
+    OperateFile(PathBuf, std::io::Error),
+    #[error(transparent)]
+    JoinError(#[from] tokio::task::JoinError),
+}
+
+/// A metadata file which may be updated by applying PDiff patches on the local copy
+pub(crate) struct PdiffTask {
+    /// Full download entry, used when the local copy can not be patched
+    pub fallback: DownloadEntry,
+    pub file_name: String,
+    pub msg: String,
+    pub index_url: String,
+    pub index_checksum: Checksum,
+    pub target_checksum: Checksum,
+    pub size: u64,
+}
+
+#[derive(Debug, FromDeb822)]
+struct PdiffIndexEntry {
+    #[deb822(field = "SHA256-Current")]
+    current: Option<String>,
+    #[deb822(field = "SHA256-History")]
+    history: Option<String>,
+    #[deb822(field = "SHA256-Patches")]
+    patches: Option<String>,
+    #[deb822(field = "SHA256-Download")]
+    download: Option<String>,
+    #[deb822(field = "X-Patch-Precedence")]
+    patch_precedence: Option<String>,
+}
+
+/// Parsed `*.diff/Index` file
+#[derive(Debug)]
+pub struct PdiffIndex {
+    pub current: (String, u64),
+    pub history: Vec<ChecksumItem>,
+    pub patches: Vec<ChecksumItem>,
+    pub download: Vec<ChecksumItem>,
+    pub merged: bool,
+}
+
+/// A single patch to download and apply
+#[derive(Debug, PartialEq, Eq)]
+pub struct PdiffPatch {
+    pub name: String,
+    pub checksum: String,
+    pub download_name: String,
+    pub download_checksum: String,
+}
+
+impl FromStr for PdiffIndex {
+    type Err = PdiffError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let source: Paragraph = input.parse().map_err(|_| PdiffError::BadIndex)?;
+        let source: PdiffIndexEntry =
+            FromDeb822Paragraph::from_paragraph(&source).map_err(|_| PdiffError::BadIndex)?;
+
+        let current = source.current.ok_or(PdiffError::BadIndex)?;
+        let (hash, size) = current
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or(PdiffError::BadIndex)?;
+        let size = size
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| PdiffError::BadIndex)?;
+
+        Ok(Self {
+            current: (hash.to_string(), size),
+            history: parse_checksum_list(source.history.as_deref())?,
+            patches: parse_checksum_list(source.patches.as_deref())?,
+            download: parse_checksum_list(source.download.as_deref())?,
+            merged: source
+                .patch_precedence
+                .is_some_and(|x| x.trim().eq_ignore_ascii_case("merged")),
+        })
+    }
+}
+
+fn parse_checksum_list(list: Option<&str>) -> Result<Vec<ChecksumItem>, PdiffError> {
+    let Some(list) = list else {
+        return Ok(vec![]);
+    };
+
+    list.trim()
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| ChecksumItem::from_str(x).map_err(|_| PdiffError::BadIndex))
+        .collect()
+}
+
+impl PdiffIndex {
+    /// Get the patches needed to bring a local file with the given SHA256 checksum and size to the current version
+    pub fn patches_from(&self, local: &Checksum, size: u64) -> Result<Vec<PdiffPatch>, PdiffError> {
+        let pos = self
+            .history
+            .iter()
+            .position(|x| {
+                x.size == size && Checksum::from_sha256_str(&x.checksum).is_ok_and(|c| c == *local)
+            })
+            .ok_or(PdiffError::NotInHistory)?;
+
+        // Merged patches bring any listed version to the current version directly
+        let history = if self.merged {
+            &self.history[pos..=pos]
+        } else {
+            &self.history[pos..]
+        };
+
+        history
+            .iter()
+            .map(|h| {
+                let patch = self
+                    .patches
+                    .iter()
+                    .find(|x| x.name == h.name)
+                    .ok_or_else(|| PdiffError::MissingPatch(h.name.clone()))?;
+
+                let download = self
+                    .download
+                    .iter()
+                    .find(|x| {
+                        x.name
+                            .strip_prefix(&h.name)
+                            .is_some_and(|ext| ext.is_empty() || ext.starts_with('.'))
+                    })
+                    .ok_or_else(|| PdiffError::MissingPatch(h.name.clone()))?;
+
+                Ok(PdiffPatch {
+                    name: h.name.clone(),
+                    checksum: patch.checksum.clone(),
+                    download_name: download.name.clone(),
+                    download_checksum: download.checksum.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Split file content into lines, the last element is the (possibly empty) content after the last newline
+pub fn split_lines(content: &[u8]) -> Vec<Vec<u8>> {
+    content.split(|x| *x == b'\n').map(|x| x.to_vec()).collect()
+}
+
+pub fn join_lines(lines: &[Vec<u8>]) -> Vec<u8> {
+    lines.join(&b'\n')
+}
+
+/// Apply an ed-style patch (as produced by `diff --ed`) to the given lines
+///
+/// Like APT's `rred`, only the `a`, `c` and `d` commands are supported, and commands
+/// must be ordered from the end of the file to the beginning.
+pub fn apply_ed_patch(lines: &mut Vec<Vec<u8>>, patch: &[u8]) -> Result<(), PdiffError> {
+    let mut patch_lines = patch.split(|x| *x == b'\n');
+
+    while let Some(command) = patch_lines.next() {
+        if command.is_empty() {
+            continue;
+        }
+
+        let bad_command = || PdiffError::BadEdCommand(String::from_utf8_lossy(command).to_string());
+
+        let (range, op) = command.split_at(command.len() - 1);
+        let range = std::str::from_utf8(range).map_err(|_| bad_command())?;
+        let (start, end) = range.split_once(',').unwrap_or((range, range));
+
+        let start = start.parse::<usize>().map_err(|_| bad_command())?;
+        let end = end.parse::<usize>().map_err(|_| bad_command())?;
+
+        // The last element of `lines` is the content after the last newline
+        let line_count = lines.len() - 1;
+
+        if start > end || end > line_count {
+            return Err(bad_command());
+        }
+
+        let mut read_text = || -> Result<Vec<Vec<u8>>, PdiffError> {
+            let mut text = vec![];
+            loop {
+                match patch_lines.next() {
+                    Some(b".") => return Ok(text),
+                    Some(line) => text.push(line.to_vec()),
+                    None => return Err(bad_command()),
+                }
+            }
+        };
+
+        match op {
+            b"a" => {
+                let text = read_text()?;
+                lines.splice(start..start, text);
+            }
+            b"c" if start != 0 => {
+                let text = read_text()?;
+                lines.splice(start - 1..end, text);
+            }
+            b"d" if start != 0 => {
+                lines.drain(start - 1..end);
+            }
+            _ => return Err(bad_command()),
+        }
+    }
+
+    Ok(())
+}
+
+fn verify(checksum: &Checksum, data: &[u8], name: &str) -> Result<(), PdiffError> {
+    let mut validator = checksum.get_validator();
+    validator.update(data);
+
+    if !validator.finish() {
+        return Err(PdiffError::ChecksumMismatch(name.to_string()));
+    }
+
+    Ok(())
+}
+
+async fn fetch(client: &ClientWithMiddleware, url: &str) -> Result<Vec<u8>, PdiffError> {
+    let resp = send_request_with_url_and_method(url, client, Method::GET)
+        .await
+        .map_err(|e| PdiffError::Download(url.to_string(), e))?;
+
+    let bytes = resp
+        .bytes()
+        .await
+        .map_err(|e| PdiffError::Download(url.to_string(), e.into()))?;
+
+    Ok(bytes.to_vec())
+}
+
+fn decompress_patch(patch: &PdiffPatch, data: Vec<u8>) -> Result<Vec<u8>, PdiffError> {
+    if patch.download_name == patch.name {
+        return Ok(data);
+    }
+
+    match patch.download_name.strip_prefix(&patch.name) {
+        Some(".gz") => {
+            let mut buf = vec![];
+            GzDecoder::new(&*data)
+                .read_to_end(&mut buf)
+                .map_err(|e| PdiffError::OperateFile(PathBuf::from(&patch.download_name), e))?;
+            Ok(buf)
+        }
+        _ => Err(PdiffError::UnsupportedCompression(
+            patch.download_name.clone(),
+        )),
+    }
+}
+
+/// Outcome of a successful PDiff update
+pub(crate) enum PdiffResult {
+    /// Local file is already up to date
+    UpToDate,
+    /// Local file was patched to the current version
+    Patched,
+}
+
+impl PdiffTask {
+    /// Update the local copy by applying PDiff patches, the result is verified against the InRelease checksum
+    pub(crate) async fn run(
+        &self,
+        client: &ClientWithMiddleware,
+        download_dir: &Path,
+    ) -> Result<PdiffResult, PdiffError> {
+        let local = download_dir.join(&self.file_name);
+        let content = tokio::fs::read(&local)
+            .await
+            .map_err(|e| PdiffError::OperateFile(local.clone(), e))?;
+
+        let target_checksum = self.target_checksum.clone();
+        let (content, up_to_date) = tokio::task::spawn_blocking(move || {
+            let up_to_date = verify(&target_checksum, &content, "").is_ok();
+            (content, up_to_date)
+        })
+        .await?;
+
+        if up_to_date {
+            return Ok(PdiffResult::UpToDate);
+        }
+
+        let index = fetch(client, &self.index_url).await?;
+        verify(&self.index_checksum, &index, &self.index_url)?;
+        let index = String::from_utf8_lossy(&index).parse::<PdiffIndex>()?;
+
+        let current = Checksum::from_sha256_str(&index.current.0)?;
+        if index.current.1 != self.size
+            || (matches!(self.target_checksum, Checksum::Sha256(_))
+                && current != self.target_checksum)
+        {
+            return Err(PdiffError::OutdatedIndex);
+        }
+
+        let local_clone = local.clone();
+        let local_sha256 =
+            tokio::task::spawn_blocking(move || Checksum::from_file_sha256(&local_clone)).await??;
+
+        let patches = index.patches_from(&local_sha256, content.len() as u64)?;
+        debug!(
+            "{} will apply PDiff patches: {:?}",
+            self.file_name,
+            patches.iter().map(|x| &x.name).collect::<Vec<_>>()
+        );
+
+        let base_url = self
+            .index_url
+            .strip_suffix("Index")
+            .ok_or(PdiffError::BadIndex)?;
+
+        let mut downloaded = Vec::with_capacity(patches.len());
+
+        for patch in &patches {
+            let url = format!("{base_url}{}", patch.download_name);
+            let data = fetch(client, &url).await?;
+            verify(
+                &Checksum::from_sha256_str(&patch.download_checksum)?,
+                &data,
+                &url,
+            )?;
+            downloaded.push(data);
+        }
+
+        let target_checksum = self.target_checksum.clone();
+        let file_name = self.file_name.clone();
+        let result = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, PdiffError> {
+            let mut lines = split_lines(&content);
+            drop(content);
+
+            for (patch, data) in patches.iter().zip(downloaded) {
+                let data = decompress_patch(patch, data)?;
+                verify(
+                    &Checksum::from_sha256_str(&patch.checksum)?,
+                    &data,
+                    &patch.name,
+                )?;
+                apply_ed_patch(&mut lines, &data)?;
+            }
+
+            let result = join_lines(&lines);
+            verify(&target_checksum, &result, &file_name)?;
+
+            Ok(result)
+        })
+        .await??;
+
+        let partial_dir = download_dir.join("partial");
+        tokio::fs::create_dir_all(&partial_dir)
+            .await
+            .map_err(|e| PdiffError::OperateFile(partial_dir.clone(), e))?;
+
+        let tmp = partial_dir.join(&self.file_name);
+        tokio::fs::write(&tmp, result)
+            .await
+            .map_err(|e| PdiffError::OperateFile(tmp.clone(), e))?;
+        tokio::fs::rename(&tmp, &local)
+            .await
+            .map_err(|e| PdiffError::OperateFile(local, e))?;
+
+        Ok(PdiffResult::Patched)
+    }
+}
+
+#[cfg(test)]
+const TEST_INDEX: &str = r#"SHA256-Current: 0a3b5c3d8a1f2cde8b3b1f7a7c8ddc0e5b30f0a7dd79d5f8e8c0c2b3b8c1a9f0 40
+SHA256-History:
+ 1111111111111111111111111111111111111111111111111111111111111111 30 T-2024-01-01-0000.00
+ 2222222222222222222222222222222222222222222222222222222222222222 35 T-2024-01-02-0000.00
+SHA256-Patches:
+ 3333333333333333333333333333333333333333333333333333333333333333 10 T-2024-01-01-0000.00
+ 4444444444444444444444444444444444444444444444444444444444444444 12 T-2024-01-02-0000.00
+SHA256-Download:
+ 5555555555555555555555555555555555555555555555555555555555555555 20 T-2024-01-01-0000.00.gz
+ 6666666666666666666666666666666666666666666666666666666666666666 22 T-2024-01-02-0000.00.gz
+"#;
+
+#[test]
+fn test_parse_pdiff_index() {
+    let index = TEST_INDEX.parse::<PdiffIndex>().unwrap();
+    let local = Checksum::from_sha256_str(
+        "1111111111111111111111111111111111111111111111111111111111111111",
+    )
+    .unwrap();
+
+    assert_eq!(index.current.1, 40);
+    assert_eq!(index.history.len(), 2);
+    assert_eq!(index.patches.len(), 2);
+    assert_eq!(index.download.len(), 2);
+    assert!(!index.merged);
+
+    let patches = index.patches_from(&local, 30).unwrap();
+    assert_eq!(
+        patches.iter().map(|x| x.name.as_str()).collect::<Vec<_>>(),
+        vec!["T-2024-01-01-0000.00", "T-2024-01-02-0000.00"]
+    );
+    assert_eq!(patches[1].download_name, "T-2024-01-02-0000.00.gz");
+
+    assert!(matches!(
+        index.patches_from(&local, 31),
+        Err(PdiffError::NotInHistory)
+    ));
+
+    let merged = format!("{TEST_INDEX}X-Patch-Precedence: merged\n")
+        .parse::<PdiffIndex>()
+        .unwrap();
+    assert!(merged.merged);
+    let patches = merged.patches_from(&local, 30).unwrap();
+    assert_eq!(patches.len(), 1);
+    assert_eq!(patches[0].name, "T-2024-01-01-0000.00");
+}
+
+#[test]
+fn test_apply_ed_patch() {
+    let mut lines = split_lines(b"a\nb\nc\nd\ne\n");
+
+    apply_ed_patch(&mut lines, b"5a\nf\ng\n.\n3,4c\nC\n.\n1d\n").unwrap();
+    assert_eq!(join_lines(&lines), b"b\nC\ne\nf\ng\n");
+
+    let mut lines = split_lines(b"a\n");
+    apply_ed_patch(&mut lines, b"0a\nz\n.\n").unwrap();
+    assert_eq!(join_lines(&lines), b"z\na\n");
+
+    let mut lines = split_lines(b"a\n");
+    assert!(apply_ed_patch(&mut lines, b"3d\n").is_err());
+    assert!(apply_ed_patch(&mut lines, b"1x\n").is_err());
+    assert!(apply_ed_patch(&mut lines, b"1a\nno terminator\n").is_err());
+}
@@ -12,9 +12,11 @@ use oma_apt_sources_lists::{
     Signature, SourceEntry, SourceLine, SourceListType, SourcesList, SourcesListError,
 };
 use oma_fetch::{
-    SingleDownloadError,
+    SingleDownloadError, local_path,
+    metalink::redirector_url,
+    mirror_list::{MirrorType, fetch_mirror_list},
     reqwest::{Method, Response, StatusCode},
-    send_request_with_url_and_method,
+    resolve_cdrom_url, send_request_with_url_and_method,
 };
 use oma_utils::concat_url;
 use once_cell::sync::OnceCell;
@@ -151,7 +153,23 @@ pub fn scan_sources_list_from_paths(
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum OmaSourceEntryFrom {
     Http,
+    /// `file:`, `copy:` and `cdrom:`
     Local,
+    /// `mirror+file:`, `mirror+http:` etc., resolved to the mirrors in the list before fetching
+    Mirror,
+}
+
+const DEFAULT_CDROM_MOUNT_POINT: &str = "/media/cdrom/";
+
+fn cdrom_mount_point() -> String {
+    #[cfg(feature = "apt")]
+    return oma_apt::raw::config::find(
+        "Acquire::cdrom::mount".to_string(),
+        DEFAULT_CDROM_MOUNT_POINT.to_string(),
+    );
+
+    #[cfg(not(feature = "apt"))]
+    return DEFAULT_CDROM_MOUNT_POINT.to_string();
 }
 
 impl OmaSourceEntry {
@@ -172,13 +190,27 @@ impl OmaSourceEntry {
                 .map_err(|_| RefreshError::InvalidUrl(self.url().to_string()))?;
 
             match url.scheme() {
-                "file" => Ok(OmaSourceEntryFrom::Local),
-                "http" | "https" => Ok(OmaSourceEntryFrom::Http),
+                "file" | "copy" | "cdrom" => Ok(OmaSourceEntryFrom::Local),
+                // 由 oma-fetch 向重定向服务获取镜像列表及校验和
+                "http" | "https" | "metalink+http" | "metalink+https" => {
+                    Ok(OmaSourceEntryFrom::Http)
+                }
+                "mirror" | "mirror+file" | "mirror+http" | "mirror+https" => {
+                    Ok(OmaSourceEntryFrom::Mirror)
+                }
                 x => Err(RefreshError::UnsupportedProtocol(x.to_string())),
             }
         })
     }
 
+    /// The same entry served from another base URL
+    fn with_url(&self, url: String) -> Self {
+        let mut source = self.source.clone();
+        source.url = url;
+
+        Self::new(source, self.arch.clone())
+    }
+
     pub fn components(&self) -> &[String] {
         &self.source.components
     }
@@ -286,9 +318,63 @@ pub struct MirrorSources(pub Vec<MirrorSource>);
 pub struct MirrorSource {
     pub sources: Vec<OmaSourceEntry>,
     release_file_name: OnceCell<String>,
+    candidates: OnceCell<Vec<OmaSourceEntry>>,
 }
 
 impl MirrorSource {
+    /// Concrete `http:`/`file:`/`copy:` entries to fetch from, in order of preference
+    ///
+    /// Entries from mirror lists are only available after [`MirrorSource::fetch`].
+    pub fn candidates(&self) -> &[OmaSourceEntry] {
+        self.candidates
+            .get()
+            .map(|c| c.as_slice())
+            .unwrap_or(&self.sources[..1])
+    }
+
+    async fn resolve_candidates(
+        &self,
+        client: &ClientWithMiddleware,
+    ) -> Result<&[OmaSourceEntry], RefreshError> {
+        if let Some(candidates) = self.candidates.get() {
+            return Ok(candidates);
+        }
+
+        let first = self.sources.first().unwrap();
+
+        let candidates = match first.from()? {
+            OmaSourceEntryFrom::Http => vec![first.clone()],
+            OmaSourceEntryFrom::Local => match resolve_cdrom_url(first.url(), &cdrom_mount_point())
+            {
+                Some(url) => vec![first.with_url(url)],
+                None => vec![first.clone()],
+            },
+            OmaSourceEntryFrom::Mirror => {
+                let list = fetch_mirror_list(client, first.url()).await?;
+
+                let candidates = list
+                    .urls(Some(&*first.arch), MirrorType::Index)
+                    .into_iter()
+                    .map(|url| first.with_url(url.to_string()))
+                    .filter(|entry| {
+                        matches!(
+                            entry.from(),
+                            Ok(OmaSourceEntryFrom::Http | OmaSourceEntryFrom::Local)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                if candidates.is_empty() {
+                    return Err(RefreshError::NoMirrorAvailable(first.url().to_string()));
+                }
+
+                candidates
+            }
+        };
+
+        Ok(self.candidates.get_or_init(|| candidates))
+    }
+
     pub fn set_release_file_name(&self, file_name: String) {
         self.release_file_name
             .set(file_name)
@@ -368,21 +454,54 @@ impl MirrorSource {
         download_dir: &Path,
         tx: Sender<Event>,
     ) -> Result<(), RefreshError> {
-        match self.from()? {
-            OmaSourceEntryFrom::Http => {
-                self.fetch_http_release(client, replacer, index, total, tmp_dir, download_dir, tx)
+        let candidates = self.resolve_candidates(client).await?;
+        let mut res = Ok(());
+
+        // 依次尝试镜像列表中的各个镜像，直到成功获取 Release 文件
+        for entry in candidates {
+            res = match entry.from()? {
+                OmaSourceEntryFrom::Http => {
+                    self.fetch_http_release(
+                        entry,
+                        client,
+                        replacer,
+                        index,
+                        total,
+                        tmp_dir,
+                        download_dir,
+                        tx.clone(),
+                    )
                     .await
-            }
-            OmaSourceEntryFrom::Local => {
-                self.fetch_local_release(replacer, index, total, download_dir, tx)
+                }
+                OmaSourceEntryFrom::Local => {
+                    self.fetch_local_release(
+                        entry,
+                        replacer,
+                        index,
+                        total,
+                        download_dir,
+                        tx.clone(),
+                    )
                     .await
+                }
+                OmaSourceEntryFrom::Mirror => {
+                    Err(RefreshError::UnsupportedProtocol(entry.url().to_string()))
+                }
+            };
+
+            match &res {
+                Ok(()) => break,
+                Err(e) => debug!("Failed to fetch release file from {}: {e}", entry.url()),
             }
         }
+
+        res
     }
 
     #[allow(clippy::too_many_arguments)]
     async fn fetch_http_release(
         &self,
+        entry: &OmaSourceEntry,
         client: &ClientWithMiddleware,
         replacer: &DatabaseFilenameReplacer,
         index: usize,
@@ -401,7 +520,7 @@ impl MirrorSource {
             }))
             .await;
 
-        let mut url = self.get_download_url("InRelease");
+        let mut url = redirector_url(&entry.get_download_url("InRelease")).to_string();
         let mut is_release = false;
 
         let resp = send_request_with_url_and_method(&url, client, Method::GET).await;
@@ -412,7 +531,7 @@ impl MirrorSource {
         let resp = match resp {
             Ok(resp) => resp,
             Err(e) if e.status().is_some_and(|e| e == StatusCode::NOT_FOUND) => {
-                url = self.get_download_url("Release");
+                url = redirector_url(&entry.get_download_url("Release")).to_string();
                 let resp = send_request_with_url_and_method(&url, client, Method::GET).await;
 
                 if resp.is_err() && self.is_flat() {
@@ -450,10 +569,8 @@ impl MirrorSource {
         .await
         .map_err(|e| RefreshError::DownloadFailed(Some(e)))?;
 
-        self.set_release_file_name(file_name);
-
         if is_release && !self.trusted() {
-            let url = self.get_download_url("Release.gpg");
+            let url = redirector_url(&entry.get_download_url("Release.gpg")).to_string();
 
             let resp = send_request_with_url_and_method(&url, client, Method::GET)
                 .await
@@ -461,10 +578,10 @@ impl MirrorSource {
                 .map_err(|e| SingleDownloadError::ReqwestMiddlewareError { source: e })
                 .map_err(|e| RefreshError::DownloadFailed(Some(e)))?;
 
-            let file_name = self.get_download_file_name(Some("Release.gpg"), replacer)?;
+            let gpg_file_name = self.get_download_file_name(Some("Release.gpg"), replacer)?;
 
             self.download_file(
-                &file_name,
+                &gpg_file_name,
                 resp,
                 index,
                 total,
@@ -476,6 +593,9 @@ impl MirrorSource {
             .map_err(|e| RefreshError::DownloadFailed(Some(e)))?;
         }
 
+        // 签名也获取成功后才记录，以便失败时尝试下一个镜像
+        self.set_release_file_name(file_name);
+
         Ok(())
     }
 
@@ -552,18 +672,20 @@ impl MirrorSource {
 
     async fn fetch_local_release(
         &self,
+        entry: &OmaSourceEntry,
         replacer: &DatabaseFilenameReplacer,
         index: usize,
         total: usize,
         download_dir: &Path,
         tx: Sender<Event>,
     ) -> Result<(), RefreshError> {
-        let dist_path_with_protocol = self.dist_path();
-        let dist_path = dist_path_with_protocol
-            .strip_prefix("file:")
-            .unwrap_or(dist_path_with_protocol);
+        let dist_path_with_protocol = entry.dist_path();
+        let dist_path = local_path(dist_path_with_protocol).unwrap_or(dist_path_with_protocol);
         let dist_path = Path::new(dist_path);
 
+        // copy: 的介质可能随后被移除，因此需要复制文件而不是创建符号链接
+        let copy = dist_path_with_protocol.starts_with("copy:");
+
         let mut name = None;
 
         let msg = self.get_human_download_message(None)?;
@@ -591,10 +713,8 @@ impl MirrorSource {
                         .map_err(|e| RefreshError::OperateFile(dst.clone(), e))?;
                 }
 
-                debug!("get_release_file: Symlinking {} ...", dst.display());
-                fs::symlink(p, &dst)
-                    .await
-                    .map_err(|e| RefreshError::OperateFile(dst.clone(), e))?;
+                debug!("get_release_file: Linking {} ...", dst.display());
+                link_or_copy(&p, &dst, copy).await?;
 
                 if index == 1 {
                     is_release = true;
@@ -622,9 +742,7 @@ impl MirrorSource {
                         .map_err(|e| RefreshError::OperateFile(dst.clone(), e))?;
                 }
 
-                fs::symlink(p, download_dir.join(file_name))
-                    .await
-                    .map_err(|e| RefreshError::OperateFile(dst.clone(), e))?;
+                link_or_copy(&p, &dst, copy).await?;
             }
         }
 
@@ -632,13 +750,23 @@ impl MirrorSource {
             .send_async(Event::DownloadEvent(oma_fetch::Event::ProgressDone(index)))
             .await;
 
-        let name = name.ok_or_else(|| RefreshError::NoInReleaseFile(self.url().to_string()))?;
+        let name = name.ok_or_else(|| RefreshError::NoInReleaseFile(entry.url().to_string()))?;
         self.set_release_file_name(name);
 
         Ok(())
     }
 }
 
+async fn link_or_copy(src: &Path, dst: &Path, copy: bool) -> Result<(), RefreshError> {
+    let res = if copy {
+        fs::copy(src, dst).await.map(|_| ())
+    } else {
+        fs::symlink(src, dst).await
+    };
+
+    res.map_err(|e| RefreshError::OperateFile(dst.to_path_buf(), e))
+}
+
 impl MirrorSources {
     pub fn from_sourcelist(
         sourcelist: &[OmaSourceEntry],
@@ -663,6 +791,7 @@ impl MirrorSources {
             res.push(MirrorSource {
                 sources: v,
                 release_file_name: OnceCell::new(),
+                candidates: OnceCell::new(),
             });
         }
 
@@ -1032,6 +1161,71 @@ fn test_file_protocol_translate() {
     assert_eq!(res1, res2);
 }
 
+// Only the scheme of non-HTTP URLs should be stripped, like apt does.
+#[test]
+fn test_local_and_mirror_protocol_translate() {
+    let replacer = DatabaseFilenameReplacer::new().unwrap();
+
+    assert_eq!(replacer.replace("copy:/media/debs").unwrap(), "_media_debs");
+    assert_eq!(
+        replacer.replace("cdrom:[AOSC OS]/debs").unwrap(),
+        "[AOSC OS]_debs"
+    );
+    assert_eq!(
+        replacer
+            .replace("mirror+file:/etc/apt/mirrors.txt/dists/stable/InRelease")
+            .unwrap(),
+        "_etc_apt_mirrors.txt_dists_stable_InRelease"
+    );
+    assert_eq!(
+        replacer
+            .replace("mirror+http://example.com/mirrors.txt/dists/stable/InRelease")
+            .unwrap(),
+        "example.com_mirrors.txt_dists_stable_InRelease"
+    );
+}
+
+#[test]
+fn test_source_entry_from() {
+    let from = |url: &str| {
+        let entry = SourceEntry {
+            enabled: true,
+            source: false,
+            options: vec![],
+            url: url.to_string(),
+            suite: "stable".to_string(),
+            components: vec!["main".to_string()],
+            is_deb822: false,
+            archs: None,
+            signed_by: None,
+            trusted: false,
+        };
+
+        OmaSourceEntry::new(entry, "amd64".into()).from().cloned()
+    };
+
+    assert_eq!(
+        from("https://repo.aosc.io/debs").unwrap(),
+        OmaSourceEntryFrom::Http
+    );
+    assert_eq!(
+        from("metalink+https://repo.aosc.io/debs").unwrap(),
+        OmaSourceEntryFrom::Http
+    );
+    assert_eq!(from("file:///debs").unwrap(), OmaSourceEntryFrom::Local);
+    assert_eq!(from("copy:/debs").unwrap(), OmaSourceEntryFrom::Local);
+    assert_eq!(from("cdrom:[AOSC OS]/").unwrap(), OmaSourceEntryFrom::Local);
+    assert_eq!(
+        from("mirror+file:/etc/apt/mirrors.txt").unwrap(),
+        OmaSourceEntryFrom::Mirror
+    );
+    assert_eq!(
+        from("mirror://example.com/mirrors.txt").unwrap(),
+        OmaSourceEntryFrom::Mirror
+    );
+    assert!(from("ftp://example.com/debs").is_err());
+}
+
 // Dots (.) in flat repo URLs should be preserved in resolved database name.
 #[test]
 fn test_flat_repo_file_name_1() {
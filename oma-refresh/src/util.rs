@@ -32,10 +32,13 @@ impl DatabaseFilenameReplacer {
                 .ok_or_else(|| RefreshError::InvalidUrl(url.to_string()))?
                 .1
         } else {
-            // file:/// or file:/
-            url.strip_prefix("file://")
-                .or_else(|| url.strip_prefix("file:"))
-                .ok_or_else(|| RefreshError::InvalidUrl(url.to_string()))?
+            // file:/// or file:/，以及 copy:/、cdrom:[...]/、mirror+file:/ 等
+            // 与 apt 一样只去掉协议部分
+            let path = url
+                .get(url_parsed.scheme().len() + 1..)
+                .ok_or_else(|| RefreshError::InvalidUrl(url.to_string()))?;
+
+            path.strip_prefix("//").unwrap_or(path)
         };
 
         let url = if let Some(host) = host {
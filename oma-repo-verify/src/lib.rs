@@ -1,5 +1,5 @@
 use std::{
-    io::Read,
+    io::{Read, Write},
     path::{Path, PathBuf},
     sync::OnceLock,
     time::SystemTime,
@@ -9,8 +9,9 @@ use anyhow::bail;
 use chrono::{DateTime, NaiveDate, Utc};
 use oma_apt_sources_lists::Signature;
 use sequoia_openpgp::{
-    Cert, KeyHandle,
+    Cert, KeyHandle, armor,
     cert::CertParser,
+    crypto::KeyPair,
     packet::Tag,
     parse::{
         PacketParserBuilder, Parse,
@@ -20,6 +21,7 @@ use sequoia_openpgp::{
         },
     },
     policy::{AsymmetricAlgorithm, HashAlgoSecurity, StandardPolicy},
+    serialize::stream::{Armorer, Message, Signer},
     types::HashAlgorithm,
 };
 use sequoia_policy_config::ConfiguredStandardPolicy;
@@ -43,6 +45,8 @@ pub enum VerifyError {
     TrustedDirNotExist,
     #[error("Failed to read decoded InRelease file: {0}")]
     FailedToReadInRelease(std::io::Error),
+    #[error("No usable unencrypted signing key in {0}")]
+    NoSigningKey(String),
     #[error(transparent)]
     Anyhow(#[from] anyhow::Error),
 }
@@ -279,6 +283,60 @@ pub fn verify_release_inner(
     Ok(())
 }
 
+/// Create an ASCII-armored detached signature of `data` with the secret key in `secret_key`
+pub fn sign_detached(data: &[u8], secret_key: impl AsRef<Path>) -> VerifyResult<Vec<u8>> {
+    let keypair = signing_keypair(secret_key.as_ref())?;
+
+    let mut sig = vec![];
+    let message = Message::new(&mut sig);
+    let message = Armorer::new(message).kind(armor::Kind::Signature).build()?;
+    let mut message = Signer::new(message, keypair)?.detached().build()?;
+    message
+        .write_all(data)
+        .map_err(|e| VerifyError::Anyhow(e.into()))?;
+    message.finalize()?;
+
+    Ok(sig)
+}
+
+/// Create a cleartext signed message (e.g. `InRelease`) of `data` with the secret key in `secret_key`
+pub fn sign_cleartext(data: &[u8], secret_key: impl AsRef<Path>) -> VerifyResult<Vec<u8>> {
+    let keypair = signing_keypair(secret_key.as_ref())?;
+
+    let mut signed = vec![];
+    let message = Message::new(&mut signed);
+    let mut message = Signer::new(message, keypair)?.cleartext().build()?;
+    message
+        .write_all(data)
+        .map_err(|e| VerifyError::Anyhow(e.into()))?;
+    message.finalize()?;
+
+    Ok(signed)
+}
+
+fn signing_keypair(secret_key: &Path) -> VerifyResult<KeyPair> {
+    let p = POLICY.get_or_init(policy);
+
+    let cert = Cert::from_file(secret_key)
+        .map_err(|e| VerifyError::CertParseFileError(secret_key.display().to_string(), e))?;
+
+    let keypair = cert
+        .keys()
+        .unencrypted_secret()
+        .with_policy(p, None)
+        .supported()
+        .alive()
+        .revoked(false)
+        .for_signing()
+        .next()
+        .ok_or_else(|| VerifyError::NoSigningKey(secret_key.display().to_string()))?
+        .key()
+        .clone()
+        .into_keypair()?;
+
+    Ok(keypair)
+}
+
 pub enum KeyBlockOrPaths<'a> {
     Block(&'a str),
     Paths(Vec<PathBuf>),
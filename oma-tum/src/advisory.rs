@@ -0,0 +1,224 @@
+//! Per-host advisory status of Topic Update Manifest entries.
+
+use ahash::HashMap;
+use snafu::{ResultExt, Whatever};
+use spdlog::warn;
+
+use crate::{
+    TopicUpdateEntry, TopicUpdateManifest, compare_version, is_right_version,
+    parser::{VersionToken, parse_version_expr},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdvisoryStatus {
+    /// The installed version is still affected
+    Pending,
+    /// The installed version is not affected
+    Fixed,
+}
+
+impl AdvisoryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdvisoryStatus::Pending => "pending",
+            AdvisoryStatus::Fixed => "fixed",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AdvisoryPackage<'a> {
+    pub name: &'a str,
+    pub installed_version: String,
+    /// Version which is not affected, `None` if unknown or no fix is available yet
+    pub fixed_version: Option<String>,
+    pub status: AdvisoryStatus,
+}
+
+#[derive(Debug)]
+pub struct Advisory<'a> {
+    pub id: &'a str,
+    pub security: bool,
+    pub name: &'a HashMap<String, String>,
+    pub caution: Option<&'a HashMap<String, String>>,
+    pub packages: Vec<AdvisoryPackage<'a>>,
+}
+
+impl Advisory<'_> {
+    pub fn status(&self) -> AdvisoryStatus {
+        if self
+            .packages
+            .iter()
+            .any(|p| p.status == AdvisoryStatus::Pending)
+        {
+            AdvisoryStatus::Pending
+        } else {
+            AdvisoryStatus::Fixed
+        }
+    }
+}
+
+/// Evaluate every conventional TUM entry against the installed packages.
+///
+/// `versions` returns the installed and candidate version of a package, or `None`
+/// if it is not installed. Entries without any installed package are omitted.
+pub fn get_advisories<'a, F>(tum: &'a [TopicUpdateManifest], versions: F) -> Vec<Advisory<'a>>
+where
+    F: Fn(&str) -> Option<(String, Option<String>)>,
+{
+    let mut res = vec![];
+
+    for i in tum {
+        for (id, entry) in &i.entries {
+            // 累积更新只是若干个常规更新的集合，其中的每个更新都会单独列出
+            let TopicUpdateEntry::Conventional {
+                security,
+                packages,
+                packages_v2,
+                name,
+                caution,
+                ..
+            } = entry
+            else {
+                continue;
+            };
+
+            let is_v2 = !packages_v2.is_empty();
+            let pkgs = if is_v2 { packages_v2 } else { packages };
+
+            let mut advisory_pkgs = vec![];
+
+            for (pkg_name, version) in pkgs {
+                let Some((installed, candidate)) = versions(pkg_name) else {
+                    continue;
+                };
+
+                let res = if is_v2 {
+                    package_status_v2(&installed, candidate.as_deref(), version.as_deref())
+                } else {
+                    package_status(&installed, version.as_deref())
+                        .map(|status| (status, version.clone()))
+                };
+
+                match res {
+                    Ok((status, fixed_version)) => advisory_pkgs.push(AdvisoryPackage {
+                        name: pkg_name,
+                        installed_version: installed,
+                        fixed_version,
+                        status,
+                    }),
+                    Err(e) => warn!("{e}"),
+                }
+            }
+
+            if advisory_pkgs.is_empty() {
+                continue;
+            }
+
+            advisory_pkgs.sort_unstable_by(|a, b| a.name.cmp(b.name));
+
+            res.push(Advisory {
+                id,
+                security: *security,
+                name,
+                caution: caution.as_ref(),
+                packages: advisory_pkgs,
+            });
+        }
+    }
+
+    res.sort_unstable_by(|a, b| a.id.cmp(b.id));
+
+    res
+}
+
+fn package_status(installed: &str, fixed: Option<&str>) -> Result<AdvisoryStatus, Whatever> {
+    // 没有版本号表示该软件包在这个更新中被移除
+    let Some(fixed) = fixed else {
+        return Ok(AdvisoryStatus::Pending);
+    };
+
+    Ok(if compare_version(installed, fixed, VersionToken::GtEq)? {
+        AdvisoryStatus::Fixed
+    } else {
+        AdvisoryStatus::Pending
+    })
+}
+
+fn package_status_v2(
+    installed: &str,
+    candidate: Option<&str>,
+    expr: Option<&str>,
+) -> Result<(AdvisoryStatus, Option<String>), Whatever> {
+    let Some(expr) = expr else {
+        return Ok((AdvisoryStatus::Pending, None));
+    };
+
+    // v2 的版本表达式描述的是受影响的版本
+    let tokens = parse_version_expr(expr)
+        .with_whatever_context(|e| format!("Parse version expr '{expr}' got error: {e}"))?;
+
+    let status = if is_right_version(tokens.clone(), installed)? {
+        AdvisoryStatus::Pending
+    } else {
+        AdvisoryStatus::Fixed
+    };
+
+    let fixed_version = match candidate {
+        Some(candidate) if !is_right_version(tokens, candidate)? => Some(candidate.to_string()),
+        _ => None,
+    };
+
+    Ok((status, fixed_version))
+}
+
+#[test]
+fn test_get_advisories() {
+    let tum = crate::parse_single_tum(
+        br#"{
+            "openssl-fix": {
+                "type": "conventional",
+                "security": true,
+                "name": { "default": "OpenSSL fix" },
+                "packages": { "openssl": "3.0.2", "libfoo": "1.0" }
+            },
+            "curl-fix": {
+                "type": "conventional",
+                "security": true,
+                "name": { "default": "curl fix" },
+                "packages-v2": { "curl": "<8.5.0" }
+            },
+            "all": {
+                "type": "cumulative",
+                "name": { "default": "All" },
+                "topics": ["openssl-fix", "curl-fix"]
+            }
+        }"#,
+    )
+    .unwrap();
+
+    let tum = [tum];
+
+    let advisories = get_advisories(&tum, |name| match name {
+        "openssl" => Some(("3.0.1".to_string(), Some("3.0.2".to_string()))),
+        "curl" => Some(("8.5.0".to_string(), Some("8.5.0".to_string()))),
+        _ => None,
+    });
+
+    assert_eq!(advisories.len(), 2);
+
+    assert_eq!(advisories[0].id, "curl-fix");
+    assert_eq!(advisories[0].status(), AdvisoryStatus::Fixed);
+    assert_eq!(
+        advisories[0].packages[0].fixed_version.as_deref(),
+        Some("8.5.0")
+    );
+
+    assert_eq!(advisories[1].id, "openssl-fix");
+    assert_eq!(advisories[1].status(), AdvisoryStatus::Pending);
+    assert_eq!(advisories[1].packages.len(), 1);
+    assert_eq!(
+        advisories[1].packages[0].fixed_version.as_deref(),
+        Some("3.0.2")
+    );
+}
@@ -1,3 +1,4 @@
+pub mod advisory;
 pub mod parser;
 use std::{
     fs::{self, read_dir},
@@ -9,10 +9,14 @@ use oma_console::OmaFormatter;
 
 use crate::{
     GlobalOptions,
+    build_dep::BuildDep,
+    bundle::Bundle,
     clean::Clean,
     command_not_found::CommandNotFound,
+    conffiles::Conffiles,
     config::OmaConfig,
     contents_find::{Files, Provides},
+    daemon::Daemon,
     depends::Depends,
     download::Download,
     error::OutputError,
@@ -23,12 +27,16 @@ use crate::{
     install::Install,
     lang::SYSTEM_LANG,
     list::List,
+    manifest::{Apply, Export},
     mark::Mark,
     pick::Pick,
+    pin::Pin,
     rdepends::Rdepends,
     refresh::Refresh,
     remove::{Purge, Remove},
+    repo::Repo,
     search::Search,
+    serve_cache::ServeCache,
     show::Show,
     subcommand::{
         generate::GenerateManpages,
@@ -38,8 +46,11 @@ use crate::{
     },
     tui::Tui,
     upgrade::Upgrade,
+    verify::Verify,
 };
 
+#[cfg(feature = "aosc")]
+use crate::advisories::Advisories;
 #[cfg(feature = "aosc")]
 use crate::topics::Topics;
 
@@ -133,6 +144,10 @@ pub enum SubCmd {
     )]
     #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
     Install(Install),
+    /// Install build dependencies of a source package
+    #[command(about = fl!("clap-build-dep-help"), help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    BuildDep(BuildDep),
     /// Upgrade packages installed on the system
     #[command(visible_alias = "full-upgrade", about = fl!("clap-upgrade-help"), help_template = &*HELP_TEMPLATE)]
     #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
@@ -141,6 +156,22 @@ pub enum SubCmd {
     #[command(about = fl!("clap-download-help"), help_template = &*HELP_TEMPLATE)]
     #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
     Download(Download),
+    /// Create or apply offline package bundle
+    #[command(about = fl!("clap-bundle-help"), help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Bundle(Bundle),
+    /// Create or update a local APT repository
+    #[command(about = fl!("clap-repo-help"), help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Repo(Repo),
+    /// Apply a declarative package manifest to the system
+    #[command(about = fl!("clap-apply-help"), help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Apply(Apply),
+    /// Export installed package(s) as a declarative package manifest
+    #[command(about = fl!("clap-export-help"), help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Export(Export),
     /// Remove the specified package(s)
     #[command(
         visible_alias = "del",
@@ -179,10 +210,22 @@ pub enum SubCmd {
     #[command(about = fl!("clap-pick-help"), help_template = &*HELP_TEMPLATE)]
     #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
     Pick(Pick),
+    /// Manage APT pinning preferences
+    #[command(about = fl!("clap-pin-help"), help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Pin(Pin),
+    /// Verify installed files of package(s) against dpkg md5sums
+    #[command(about = fl!("clap-verify-help"), help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Verify(Verify),
     /// Mark status for one or multiple package(s)
     #[command(about = fl!("clap-mark-help"), help_template = &*HELP_TEMPLATE)]
     #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
     Mark(Mark),
+    /// Manage modified configuration files
+    #[command(visible_alias = "conffile", about = fl!("clap-conffiles-help"), help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Conffiles(Conffiles),
     /// List available package(s)
     #[command(about = fl!("clap-list-help"), help_template = &*HELP_TEMPLATE)]
     #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
@@ -207,6 +250,14 @@ pub enum SubCmd {
     #[command(about = fl!("clap-undo-help"), help_template = &*HELP_TEMPLATE)]
     #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
     Undo(Undo),
+    /// Run oma as a D-Bus system service
+    #[command(about = fl!("clap-daemon-help"), help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Daemon(Daemon),
+    /// Share the package cache with other machines in the LAN
+    #[command(about = fl!("clap-serve-cache-help"), help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    ServeCache(ServeCache),
     /// oma tui interface
     #[command(about = fl!("clap-tui-help"), help_template = &*HELP_TEMPLATE)]
     #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
@@ -225,6 +276,11 @@ pub enum SubCmd {
     #[command(visible_alias = "mirrors", about = fl!("clap-mirror-help"), help_template = &*HELP_TEMPLATE)]
     #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
     Mirror(crate::mirror::CliMirror),
+    #[cfg(feature = "aosc")]
+    /// List security advisories affecting installed package(s)
+    #[command(visible_alias = "advisory", about = fl!("clap-advisories-help"), help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Advisories(Advisories),
     /// purge (like apt purge) the specified package(s)
     #[command(hide = true, help_template = &*HELP_TEMPLATE)]
     #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
@@ -1,11 +1,17 @@
 use std::{borrow::Cow, path::PathBuf};
 
+use ahash::AHashMap;
 use apt_auth_config::AuthConfig;
 use clap::ColorChoice;
-use oma_pm::oma_apt;
+use oma_fetch::{
+    proxy::{ProxyConfig, ProxySetting},
+    throttle::NetworkLimits,
+    tls::{HostTlsMiddleware, TlsSettings},
+};
+use oma_pm::{apt::AptConfig, oma_apt};
 use oma_utils::is_termux;
 use once_cell::sync::OnceCell;
-use reqwest::Client;
+use reqwest::{Client, redirect::Policy};
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
 use spdlog::debug;
 
@@ -13,9 +19,10 @@ use crate::{
     GlobalOptions,
     args::{OhManagerAilurus, SubCmd},
     config_file::{
-        BatteryTristate, ConfigFile, GeneralConfig, NetworkConfig, SearchEngine,
-        TakeWakeLockTristate,
+        BatteryTristate, ConfigFile, GeneralConfig, NetworkConfig, ProxyTable, SearchEngine,
+        SnapshotMode, TakeWakeLockTristate, UnattendedConfig,
     },
+    error::OutputError,
     subcommand::utils::is_terminal,
 };
 
@@ -26,6 +33,7 @@ pub struct OmaConfig {
     pub color: ColorChoice,
     pub follow_terminal_color: bool,
     cli_no_progress: bool,
+    pub json_events: bool,
     pub no_check_dbus: bool,
     pub check_battery: BatteryTristate,
     pub take_wake_lock: TakeWakeLockTristate,
@@ -41,13 +49,21 @@ pub struct OmaConfig {
     no_progress: OnceCell<bool>,
     pub save_log_count: usize,
     pub user_agent: Cow<'static, str>,
+    pub delta_debs: bool,
+    pub peers: Vec<String>,
+    pub peer_discovery: bool,
+    pub network_limits: NetworkLimits,
+    proxy: ProxyTable,
     pub yn_mode: bool,
     subcmd: Option<SubCmd>,
     http_client: OnceCell<ClientWithMiddleware>,
+    http_client_no_redirect: OnceCell<ClientWithMiddleware>,
     #[cfg(feature = "aosc")]
     http_client_blocking: OnceCell<reqwest::blocking::Client>,
     rustls_crypto_provider: OnceCell<()>,
     pub amo: bool,
+    pub snapshot: SnapshotMode,
+    pub unattended: UnattendedConfig,
 }
 
 impl Default for OmaConfig {
@@ -58,6 +74,7 @@ impl Default for OmaConfig {
             color: ColorChoice::Auto,
             follow_terminal_color: GeneralConfig::default_follow_terminal_color(),
             cli_no_progress: false,
+            json_events: false,
             no_check_dbus: GeneralConfig::default_no_check_dbus(),
             check_battery: GeneralConfig::default_check_battery(),
             take_wake_lock: GeneralConfig::default_take_wake_lock(),
@@ -73,13 +90,21 @@ impl Default for OmaConfig {
             no_progress: OnceCell::new(),
             save_log_count: GeneralConfig::default_save_log_count(),
             user_agent: NetworkConfig::default_user_agent(),
+            delta_debs: NetworkConfig::default_delta_debs(),
+            peers: vec![],
+            peer_discovery: NetworkConfig::default_peer_discovery(),
+            network_limits: NetworkLimits::default(),
+            proxy: ProxyTable::default(),
             yn_mode: GeneralConfig::default_yn_mode(),
             subcmd: None,
             http_client: OnceCell::new(),
+            http_client_no_redirect: OnceCell::new(),
             rustls_crypto_provider: OnceCell::new(),
             #[cfg(feature = "aosc")]
             http_client_blocking: OnceCell::new(),
             amo: GeneralConfig::default_amo(),
+            snapshot: GeneralConfig::default_snapshot(),
+            unattended: UnattendedConfig::default(),
         }
     }
 }
@@ -88,7 +113,11 @@ impl OmaConfig {
     pub fn from_config_file(config: ConfigFile) -> Self {
         let mut oma_config = Self::default();
 
-        let ConfigFile { general, network } = config;
+        let ConfigFile {
+            general,
+            network,
+            unattended,
+        } = config;
 
         if let Some(general) = general {
             let GeneralConfig {
@@ -101,6 +130,7 @@ impl OmaConfig {
                 search_engine,
                 yn_mode,
                 amo,
+                snapshot,
                 ..
             } = general;
 
@@ -117,11 +147,29 @@ impl OmaConfig {
             oma_config.search_engine = search_engine;
             oma_config.yn_mode = yn_mode;
             oma_config.amo = amo;
+            oma_config.snapshot = snapshot;
         }
 
         if let Some(network) = network {
             oma_config.download_threads = network.network_threads;
             oma_config.user_agent = network.user_agent;
+            oma_config.delta_debs = network.delta_debs;
+            oma_config.peers = network.peers;
+            oma_config.peer_discovery = network.peer_discovery;
+
+            // 配置文件中的速率以 KiB/s 为单位，与 apt 的 Acquire::http::Dl-Limit 一致
+            let kib = |rate: u64| Some(rate.saturating_mul(1024)).filter(|rate| *rate != 0);
+            oma_config.network_limits = NetworkLimits {
+                max_rate: kib(network.max_download_rate),
+                max_rate_per_host: kib(network.max_download_rate_per_host),
+                max_connections_per_host: Some(network.max_connections_per_host)
+                    .filter(|count| *count != 0),
+            };
+            oma_config.proxy = network.proxy;
+        }
+
+        if let Some(unattended) = unattended {
+            oma_config.unattended = unattended;
         }
 
         oma_config
@@ -136,6 +184,7 @@ impl OmaConfig {
             color,
             follow_terminal_color,
             no_progress,
+            json_events,
             no_check_dbus,
             no_check_battery,
             no_take_wake_lock,
@@ -164,6 +213,7 @@ impl OmaConfig {
         }
 
         self.cli_no_progress = no_progress;
+        self.json_events = json_events;
 
         if no_check_battery {
             self.check_battery = BatteryTristate::Ignore
@@ -184,6 +234,7 @@ impl OmaConfig {
     pub fn no_progress(&self) -> bool {
         *self.no_progress.get_or_init(|| {
             self.cli_no_progress
+                || self.json_events
                 || !is_terminal()
                 || self.debug
                 || self.dry_run
@@ -222,33 +273,192 @@ impl OmaConfig {
         });
     }
 
-    pub fn http_client(&self) -> Result<&ClientWithMiddleware, reqwest::Error> {
-        self.http_client.get_or_try_init(|| {
-            self.init_tls_config();
-            let auth = AuthConfig::system(&self.sysroot).ok();
+    /// Proxy settings of APT, overridden by `[network.proxy]` of the config file
+    ///
+    /// Like APT, `https` uses the settings of `http` unless set on its own.
+    fn proxy_config(&self) -> ProxyConfig {
+        let apt = AptConfig::new();
+        let mut proxy = ProxyConfig::default();
+
+        for scheme in ["http", "https"] {
+            let find = |key: &str| {
+                apt.get(&format!("Acquire::{scheme}::{key}"))
+                    .or_else(|| apt.get(&format!("Acquire::http::{key}")))
+            };
+
+            if let Some(setting) = find("Proxy").as_deref().and_then(ProxySetting::parse) {
+                proxy.schemes.insert(scheme.to_string(), setting);
+            }
 
-            Client::builder()
-                .user_agent(self.user_agent.as_ref())
-                .build()
-                .map(|client| {
-                    if let Some(auth) = auth {
-                        ClientBuilder::new(client)
-                            .with_init(apt_auth_config::reqwuest::AuthMiddleware::new(auth))
-                            .build()
-                    } else {
-                        client.into()
+            if let Some(script) = find("Proxy-Auto-Detect") {
+                proxy.auto_detect.insert(scheme.to_string(), script.into());
+            }
+
+            // Acquire::http::Proxy::<host> "DIRECT";
+            let keys: &[&str] = match scheme {
+                "http" => &["http"],
+                _ => &["http", scheme],
+            };
+
+            for key in keys {
+                let hosts = apt
+                    .tree(&format!("Acquire::{key}::Proxy"))
+                    .and_then(|tree| tree.child());
+
+                for host in hosts.into_iter().flatten() {
+                    if let (Some(tag), Some(value)) = (host.tag(), host.value())
+                        && let Some(setting) = ProxySetting::parse(&value)
+                    {
+                        proxy.hosts.insert((scheme.to_string(), tag), setting);
                     }
-                })
-        })
+                }
+            }
+
+            if let Some(setting) = self.proxy.url.as_deref().and_then(ProxySetting::parse) {
+                proxy.schemes.insert(scheme.to_string(), setting);
+            }
+
+            if let Some(script) = &self.proxy.auto_detect {
+                proxy.auto_detect.insert(scheme.to_string(), script.clone());
+            }
+
+            for (host, value) in &self.proxy.hosts {
+                if let Some(setting) = ProxySetting::parse(value) {
+                    proxy
+                        .hosts
+                        .insert((scheme.to_string(), host.clone()), setting);
+                }
+            }
+        }
+
+        proxy
+    }
+
+    /// TLS settings of APT for all hosts, and the ones set for each host
+    ///
+    /// e.g. `Acquire::https::CaInfo` and `Acquire::https::<host>::SslCert`.
+    fn tls_config(&self) -> (TlsSettings, AHashMap<String, TlsSettings>) {
+        let apt = AptConfig::new();
+
+        let settings = |prefix: &str| {
+            let path = |key: &str| apt.get(&format!("{prefix}::{key}")).map(PathBuf::from);
+            let flag = |key: &str| {
+                let key = format!("{prefix}::{key}");
+                apt.contains(&key).then(|| apt.bool(&key, true))
+            };
+
+            TlsSettings {
+                ca_info: path("CaInfo"),
+                ssl_cert: path("SslCert"),
+                ssl_key: path("SslKey"),
+                verify_peer: flag("Verify-Peer"),
+                verify_host: flag("Verify-Host"),
+            }
+        };
+
+        let global = settings("Acquire::https");
+        let mut hosts = AHashMap::new();
+
+        let nodes = apt.tree("Acquire::https").and_then(|tree| tree.child());
+
+        for node in nodes.into_iter().flatten() {
+            // 只有含子项的节点才可能是主机名
+            let Some(host) = node.tag().filter(|_| node.child().is_some()) else {
+                continue;
+            };
+
+            let host_settings = settings(&format!("Acquire::https::{host}"));
+
+            if !host_settings.is_empty() {
+                hosts.insert(host, host_settings.or(&global));
+            }
+        }
+
+        (global, hosts)
+    }
+
+    pub fn http_client(&self) -> Result<&ClientWithMiddleware, OutputError> {
+        self.http_client
+            .get_or_try_init(|| self.build_http_client(true))
+    }
+
+    /// Same as [`Self::http_client`] but not following redirects, used to query mirror redirectors.
+    pub fn http_client_no_redirect(&self) -> Result<&ClientWithMiddleware, OutputError> {
+        self.http_client_no_redirect
+            .get_or_try_init(|| self.build_http_client(false))
+    }
+
+    fn build_http_client(
+        &self,
+        follow_redirects: bool,
+    ) -> Result<ClientWithMiddleware, OutputError> {
+        self.init_tls_config();
+        let auth = AuthConfig::system(&self.sysroot).ok();
+        let proxy = self.proxy_config();
+        let (tls, tls_hosts) = self.tls_config();
+
+        let client_builder = || {
+            let builder = Client::builder()
+                .user_agent(self.user_agent.as_ref())
+                .redirect(if follow_redirects {
+                    Policy::default()
+                } else {
+                    Policy::none()
+                });
+
+            // 未设置代理时保留 reqwest 对环境变量的处理
+            if proxy.is_empty() {
+                builder
+            } else {
+                builder.proxy(proxy.clone().into_proxy())
+            }
+        };
+
+        let client = tls.apply(client_builder())?.build()?;
+        let mut builder = ClientBuilder::new(client);
+
+        if let Some(auth) = auth {
+            builder = builder.with(apt_auth_config::reqwuest::AuthMiddleware::new(auth));
+        }
+
+        // 设置了客户端证书等的服务器使用单独的 HTTP 客户端
+        if !tls_hosts.is_empty() {
+            builder = builder.with(HostTlsMiddleware::new(&tls_hosts, client_builder)?);
+        }
+
+        Ok(builder.build())
+    }
+
+    /// Get reachable LAN peers serving their package cache.
+    pub fn lan_peers(&self) -> Result<Vec<String>, OutputError> {
+        if self.peers.is_empty() && !self.peer_discovery {
+            return Ok(vec![]);
+        }
+
+        let client = self.http_client()?;
+
+        Ok(crate::RT.block_on(oma_fetch::peer::discover_peers(
+            client,
+            &self.peers,
+            self.peer_discovery,
+        )))
     }
 
     #[cfg(feature = "aosc")]
-    pub fn http_client_blocking(&self) -> Result<&reqwest::blocking::Client, reqwest::Error> {
+    pub fn http_client_blocking(&self) -> Result<&reqwest::blocking::Client, OutputError> {
         self.http_client_blocking.get_or_try_init(|| {
             self.init_tls_config();
-            reqwest::blocking::Client::builder()
-                .user_agent(self.user_agent.as_ref())
-                .build()
+            let proxy = self.proxy_config();
+            let (tls, _) = self.tls_config();
+
+            let mut builder =
+                reqwest::blocking::Client::builder().user_agent(self.user_agent.as_ref());
+
+            if !proxy.is_empty() {
+                builder = builder.proxy(proxy.into_proxy());
+            }
+
+            Ok(tls.apply_blocking(builder)?.build()?)
         })
     }
 
@@ -1,4 +1,4 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap, path::PathBuf};
 
 use crate::{DEFAULT_USER_AGENT, fl};
 use serde::Deserialize;
@@ -8,6 +8,7 @@ use spdlog::{error, warn};
 pub struct ConfigFile {
     pub general: Option<GeneralConfig>,
     pub network: Option<NetworkConfig>,
+    pub unattended: Option<UnattendedConfig>,
 }
 
 impl Default for ConfigFile {
@@ -26,11 +27,20 @@ impl Default for ConfigFile {
                 save_log_count: GeneralConfig::default_save_log_count(),
                 yn_mode: GeneralConfig::default_yn_mode(),
                 amo: GeneralConfig::default_amo(),
+                snapshot: GeneralConfig::default_snapshot(),
             }),
             network: Some(NetworkConfig {
                 network_threads: NetworkConfig::default_network_thread(),
                 user_agent: NetworkConfig::default_user_agent(),
+                delta_debs: NetworkConfig::default_delta_debs(),
+                peers: vec![],
+                peer_discovery: NetworkConfig::default_peer_discovery(),
+                max_download_rate: 0,
+                max_download_rate_per_host: 0,
+                max_connections_per_host: 0,
+                proxy: ProxyTable::default(),
             }),
+            unattended: Some(UnattendedConfig::default()),
         }
     }
 }
@@ -62,6 +72,8 @@ pub struct GeneralConfig {
     pub yn_mode: bool,
     #[serde(default = "GeneralConfig::default_amo")]
     pub amo: bool,
+    #[serde(default = "GeneralConfig::default_snapshot")]
+    pub snapshot: SnapshotMode,
 }
 
 #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
@@ -80,6 +92,15 @@ pub enum SearchEngine {
     Text,
 }
 
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotMode {
+    None,
+    Auto,
+    Btrfs,
+    Archive,
+}
+
 #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum TakeWakeLockTristate {
@@ -94,6 +115,46 @@ pub struct NetworkConfig {
     pub network_threads: usize,
     #[serde(default = "NetworkConfig::default_user_agent")]
     pub user_agent: Cow<'static, str>,
+    #[serde(default = "NetworkConfig::default_delta_debs")]
+    pub delta_debs: bool,
+    #[serde(default)]
+    pub peers: Vec<String>,
+    #[serde(default = "NetworkConfig::default_peer_discovery")]
+    pub peer_discovery: bool,
+    #[serde(default)]
+    pub max_download_rate: u64,
+    #[serde(default)]
+    pub max_download_rate_per_host: u64,
+    #[serde(default)]
+    pub max_connections_per_host: usize,
+    #[serde(default)]
+    pub proxy: ProxyTable,
+}
+
+/// `[network.proxy]`, overrides APT's `Acquire::http::Proxy` and `Acquire::https::Proxy`
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct ProxyTable {
+    /// Proxy of all HTTP(S) requests, `DIRECT` for none
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Proxy of each host, `DIRECT` for none
+    #[serde(default)]
+    pub hosts: HashMap<String, String>,
+    /// Script printing the proxy for the URL given as its argument
+    #[serde(default)]
+    pub auto_detect: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct UnattendedConfig {
+    #[serde(default)]
+    pub security_only: bool,
+    #[serde(default)]
+    pub origins: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+    #[serde(default)]
+    pub allow_on_battery: bool,
 }
 
 impl NetworkConfig {
@@ -104,6 +165,14 @@ impl NetworkConfig {
     pub const fn default_user_agent() -> Cow<'static, str> {
         Cow::Borrowed(DEFAULT_USER_AGENT)
     }
+
+    pub const fn default_delta_debs() -> bool {
+        false
+    }
+
+    pub const fn default_peer_discovery() -> bool {
+        false
+    }
 }
 
 impl GeneralConfig {
@@ -158,6 +227,10 @@ impl GeneralConfig {
     pub const fn default_amo() -> bool {
         cfg!(feature = "aosc")
     }
+
+    pub const fn default_snapshot() -> SnapshotMode {
+        SnapshotMode::None
+    }
 }
 
 impl ConfigFile {
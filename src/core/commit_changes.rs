@@ -11,6 +11,9 @@ use oma_pm::{
     CommitConfig,
     apt::{InstallEntry, InstallProgressOpt, OmaApt, OmaAptArgs, OmaAptError, RemoveEntry},
     oma_apt::{self, PackageSort},
+    snapshot::{
+        ArchiveSnapshot, BtrfsSnapshot, Snapshot, SnapshotBackend, SnapshotHook, detect_backend,
+    },
     sort::SummarySort,
 };
 use spdlog::{debug, error, info, warn};
@@ -20,11 +23,13 @@ use crate::utils::get_lists_dir;
 use crate::{
     NOT_ALLOW_CTRLC, color_formatter,
     config::OmaConfig,
+    config_file::SnapshotMode,
     core::space_tips,
     error::OutputError,
     exit_handle::{ExitHandle, ExitStatus},
     fl,
     install_progress::{NoInstallProgressManager, OmaInstallProgressManager, osc94_progress},
+    json_events::{self, JsonEvent, JsonInstallProgressManager, JsonProgress},
     lang::{DEFAULT_LANGUAGE, SYSTEM_LANG},
     msg,
     pb::{NoProgressBar, OmaMultiProgressBar, RenderPackagesDownloadProgress},
@@ -109,6 +114,12 @@ impl CommitChanges<'_> {
                     true
                 } else if config.protect_essentials {
                     false
+                } else if config.json_events {
+                    json_events::answer_prompt(
+                        "essential",
+                        &fl!("essential-tips", pkg = pkg),
+                        false,
+                    )
                 } else {
                     ask_user_do_as_i_say(pkg).unwrap_or(false)
                 }
@@ -116,6 +127,8 @@ impl CommitChanges<'_> {
             |features| {
                 if dry_run {
                     true
+                } else if config.json_events {
+                    json_events::answer_prompt("features", &fl!("features-tips-1"), false)
                 } else {
                     handle_features(features, config.protect_essentials).unwrap_or(false)
                 }
@@ -139,6 +152,24 @@ impl CommitChanges<'_> {
 
         apt.init_dbus_status()?;
 
+        if config.json_events {
+            JsonEvent::Summary(&op).emit();
+
+            if !dry_run {
+                if !yes {
+                    return Err(json_events::unanswered_prompt(
+                        "pending-operations",
+                        &fl!("pending-op"),
+                        "--yes",
+                    ));
+                }
+
+                json_events::answer_prompt("pending-operations", &fl!("pending-op"), true);
+            }
+        }
+
+        let is_pager = !yes && !config.json_events;
+
         if check_tum {
             #[cfg(feature = "aosc")]
             let tum = oma_tum::get_tum(get_lists_dir())?;
@@ -154,7 +185,7 @@ impl CommitChanges<'_> {
                 remove,
                 *disk_size,
                 matches_tum,
-                !yes,
+                is_pager,
                 dry_run,
                 config.yn_mode,
             )? {
@@ -167,7 +198,7 @@ impl CommitChanges<'_> {
                 remove,
                 *disk_size,
                 None,
-                !yes,
+                is_pager,
                 dry_run,
                 config.yn_mode,
             )? {
@@ -195,9 +226,12 @@ impl CommitChanges<'_> {
         let (tx, rx) = unbounded();
 
         let no_progress = config.no_progress();
+        let json_events = config.json_events;
 
         thread::spawn(move || {
-            let mut pb: Box<dyn RenderPackagesDownloadProgress> = if no_progress {
+            let mut pb: Box<dyn RenderPackagesDownloadProgress> = if json_events {
+                Box::new(JsonProgress)
+            } else if no_progress {
                 Box::new(NoProgressBar::default())
             } else {
                 Box::new(OmaMultiProgressBar::default())
@@ -205,8 +239,19 @@ impl CommitChanges<'_> {
             pb.render_progress(&rx, false);
         });
 
+        let (snapshot_tx, snapshot_rx) = unbounded();
+
+        let snapshot = snapshot_backend(config).map(|backend| SnapshotHook {
+            backend,
+            callback: Box::new(move |snapshot: &Snapshot| {
+                snapshot_tx.send(snapshot.clone()).ok();
+            }),
+        });
+
         let res = apt.commit(
-            InstallProgressOpt::TermLike(if config.no_progress() {
+            InstallProgressOpt::TermLike(if config.json_events {
+                Box::new(JsonInstallProgressManager)
+            } else if config.no_progress() {
                 Box::new(NoInstallProgressManager)
             } else {
                 Box::new(OmaInstallProgressManager::new(yes))
@@ -216,6 +261,11 @@ impl CommitChanges<'_> {
             CommitConfig {
                 network_thread: Some(config.download_threads),
                 download_only,
+                snapshot,
+                delta: config.delta_debs,
+                peers: config.lan_peers()?,
+                limits: config.network_limits,
+                redirector_client: Some(config.http_client_no_redirect()?.clone()),
             },
             download_message(),
             move |event| {
@@ -227,6 +277,15 @@ impl CommitChanges<'_> {
 
         osc94_progress(100.0, true);
 
+        // 快照记录写入失败不能影响下面对事务结果的处理
+        for snapshot in snapshot_rx.drain() {
+            if let Err(e) =
+                history.write_snapshot(id, snapshot.stage.as_str(), snapshot.backend, &snapshot.id)
+            {
+                warn!("Failed to record snapshot {} in history: {e}", snapshot.id);
+            }
+        }
+
         match res {
             Ok(_) => {
                 NOT_ALLOW_CTRLC.store(true, Ordering::Relaxed);
@@ -301,6 +360,15 @@ impl CommitChanges<'_> {
     }
 }
 
+fn snapshot_backend(config: &OmaConfig) -> Option<Box<dyn SnapshotBackend>> {
+    match config.snapshot {
+        SnapshotMode::None => None,
+        SnapshotMode::Auto => Some(detect_backend(&config.sysroot)),
+        SnapshotMode::Btrfs => Some(Box::new(BtrfsSnapshot)),
+        SnapshotMode::Archive => Some(Box::new(ArchiveSnapshot)),
+    }
+}
+
 fn fix_broken(
     apt: &mut OmaApt,
     no_fixbroken: bool,
@@ -10,6 +10,7 @@ use crate::{
     config::OmaConfig,
     error::OutputError,
     fl,
+    json_events::JsonProgress,
     pb::{NoProgressBar, OmaMultiProgressBar, RenderRefreshProgress},
     utils::get_lists_dir,
 };
@@ -36,8 +37,10 @@ impl Refresh<'_> {
             .download_dir(get_lists_dir())
             .source(sysroot.clone())
             .threads(config.download_threads)
+            .limits(config.network_limits)
             .arch(arch)
-            .client(config.http_client()?.clone());
+            .client(config.http_client()?.clone())
+            .redirector_client(config.http_client_no_redirect()?.clone());
 
         #[cfg(feature = "aosc")]
         let msg = fl!("do-not-edit-topic-sources-list");
@@ -54,9 +57,12 @@ impl Refresh<'_> {
         let (tx, rx) = unbounded();
 
         let no_progress = config.no_progress();
+        let json_events = config.json_events;
 
         thread::spawn(move || {
-            let mut pb: Box<dyn RenderRefreshProgress> = if no_progress {
+            let mut pb: Box<dyn RenderRefreshProgress> = if json_events {
+                Box::new(JsonProgress)
+            } else if no_progress {
                 Box::new(NoProgressBar::default())
             } else {
                 Box::new(OmaMultiProgressBar::default())
@@ -16,7 +16,7 @@ use crate::{
     config::OmaConfig,
     config_file::{BatteryTristate, TakeWakeLockTristate},
     error::OutputError,
-    fl,
+    fl, json_events,
 };
 type Result<T> = std::result::Result<T, OutputError>;
 
@@ -30,13 +30,13 @@ pub fn dbus_check(yes: bool, config: &OmaConfig) -> Result<Option<OwnedFd>> {
         return Ok(None);
     }
 
-    let Some(conn) = connect_dbus_impl() else {
+    let Some(conn) = connect_dbus_impl(yes, config)? else {
         return Ok(None);
     };
 
     match config.check_battery {
         BatteryTristate::Ask => {
-            ask_continue_no_use_battery(&conn, yes);
+            ask_continue_no_use_battery(&conn, yes, config)?;
         }
         BatteryTristate::Warn => {
             if is_battery(&conn) {
@@ -63,6 +63,44 @@ pub fn dbus_check(yes: bool, config: &OmaConfig) -> Result<Option<OwnedFd>> {
     }
 }
 
+/// Non-interactive variant of [`dbus_check`] for unattended upgrades.
+///
+/// Returns `None` if the device is running on battery power and `allow_on_battery` is not set,
+/// otherwise the inhibitor lock fd (if taken).
+pub fn unattended_dbus_check(
+    config: &OmaConfig,
+    allow_on_battery: bool,
+) -> Option<Option<OwnedFd>> {
+    if config.no_check_dbus || is_termux() {
+        return Some(None);
+    }
+
+    let Ok(conn) = RT.block_on(create_dbus_connection()) else {
+        warn!("{}", fl!("failed-check-dbus"));
+        return Some(None);
+    };
+
+    if !allow_on_battery && is_battery(&conn) {
+        return None;
+    }
+
+    if config.take_wake_lock != TakeWakeLockTristate::Yes {
+        return Some(None);
+    }
+
+    let fd = RT.block_on(take_wake_lock(
+        &conn,
+        InhibitTypeUnion::all(),
+        &fl!("changing-system"),
+        "oma",
+    ));
+
+    Some(
+        fd.inspect_err(|e| debug!("Failed to take wake lock: {e}"))
+            .ok(),
+    )
+}
+
 pub fn is_ssh_from_loginctl() -> bool {
     let conn = RT.block_on(create_dbus_connection());
 
@@ -90,10 +128,10 @@ fn no_take_wake_lock_warn() {
     warn!("{}", fl!("session-check-disabled"));
 }
 
-fn connect_dbus_impl() -> Option<Connection> {
+fn connect_dbus_impl(yes: bool, config: &OmaConfig) -> Result<Option<Connection>> {
     let Ok(conn) = RT.block_on(create_dbus_connection()) else {
         if is_termux() {
-            return None;
+            return Ok(None);
         }
 
         warn!("{}", fl!("failed-check-dbus"));
@@ -101,6 +139,20 @@ fn connect_dbus_impl() -> Option<Connection> {
         info!("{}", fl!("failed-check-dbus-tips-2"));
         info!("{}", fl!("failed-check-dbus-tips-3"));
 
+        if config.json_events {
+            if !yes {
+                return Err(json_events::unanswered_prompt(
+                    "dbus-unavailable",
+                    &fl!("failed-check-dbus"),
+                    "--no-check-dbus",
+                ));
+            }
+
+            json_events::answer_prompt("dbus-unavailable", &fl!("failed-check-dbus"), true);
+
+            return Ok(None);
+        }
+
         let theme = ColorfulTheme::default();
         let ans = Confirm::with_theme(&theme)
             .with_prompt(fl!("continue"))
@@ -109,18 +161,32 @@ fn connect_dbus_impl() -> Option<Connection> {
 
         handle_dialoguer_question_result(ans);
 
-        return None;
+        return Ok(None);
     };
 
-    Some(conn)
+    Ok(Some(conn))
 }
 
-fn ask_continue_no_use_battery(conn: &Connection, yes: bool) {
+fn ask_continue_no_use_battery(conn: &Connection, yes: bool, config: &OmaConfig) -> Result<()> {
     let is_battery = is_battery(conn);
 
     if is_battery {
+        if config.json_events {
+            if !yes {
+                return Err(json_events::unanswered_prompt(
+                    "battery",
+                    &fl!("battery"),
+                    "--yes",
+                ));
+            }
+
+            json_events::answer_prompt("battery", &fl!("battery"), true);
+
+            return Ok(());
+        }
+
         if yes {
-            return;
+            return Ok(());
         }
         let theme = ColorfulTheme::default();
         warn!("{}", fl!("battery"));
@@ -131,6 +197,8 @@ fn ask_continue_no_use_battery(conn: &Connection, yes: bool) {
 
         handle_dialoguer_question_result(cont);
     }
+
+    Ok(())
 }
 
 fn handle_dialoguer_question_result(res: std::result::Result<bool, dialoguer::Error>) {
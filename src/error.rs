@@ -9,14 +9,19 @@ use oma_contents::OmaContentsError;
 use oma_fetch::SingleDownloadError;
 use oma_fetch::checksum::ChecksumError;
 use oma_fetch::download::BuilderError;
+use oma_fetch::tls::TlsError;
 use oma_history::HistoryError;
 
 #[cfg(feature = "aosc")]
 use oma_mirror::MirrorError;
 
+use oma_apt_pkg::QueryError;
 use oma_apt_pkg::search::OmaSearchError;
 use oma_pm::oma_apt::error::AptErrors;
-use oma_pm::{apt::OmaAptError, matches::MatcherError};
+use oma_pm::{
+    apt::OmaAptError, build_dep::BuildDepError, matches::MatcherError, pin::PinError,
+    verify::IntegrityError,
+};
 use oma_refresh::db::RefreshError;
 use oma_refresh::inrelease::InReleaseError;
 use oma_repo_verify::VerifyError;
@@ -175,6 +180,59 @@ impl From<OmaAptError> for OutputError {
     }
 }
 
+impl From<BuildDepError> for OutputError {
+    fn from(value: BuildDepError) -> Self {
+        match value {
+            BuildDepError::FailedReadFile(path, e) => Self {
+                description: fl!("failed-to-operate-path", p = path),
+                source: Some(Box::new(e)),
+            },
+            BuildDepError::ParseControl(path, e) => Self {
+                description: fl!("build-dep-bad-control", path = path),
+                source: Some(Box::new(io::Error::other(e))),
+            },
+            BuildDepError::NoSourceStanza(path) => Self {
+                description: fl!("build-dep-no-source", path = path),
+                source: None,
+            },
+        }
+    }
+}
+
+impl From<IntegrityError> for OutputError {
+    fn from(value: IntegrityError) -> Self {
+        match value {
+            IntegrityError::FailedReadFile(path, e) => Self {
+                description: fl!("failed-to-operate-path", p = path),
+                source: Some(Box::new(e)),
+            },
+            IntegrityError::ReadArchiveMd5sums(path, e) => Self {
+                description: fl!("verify-bad-archive", path = path),
+                source: Some(Box::new(io::Error::other(e))),
+            },
+        }
+    }
+}
+
+impl From<PinError> for OutputError {
+    fn from(value: PinError) -> Self {
+        match value {
+            PinError::InvalidPackage(name) => Self {
+                description: fl!("pin-invalid-package", name = name),
+                source: None,
+            },
+            PinError::InvalidTarget(pin) => Self {
+                description: fl!("pin-invalid-target", pin = pin),
+                source: None,
+            },
+            PinError::FailedOperateDirOrFile(path, e) => Self {
+                description: fl!("failed-to-operate-path", p = path),
+                source: Some(Box::new(e)),
+            },
+        }
+    }
+}
+
 #[cfg(feature = "aosc")]
 impl From<MirrorError> for OutputError {
     fn from(value: MirrorError) -> Self {
@@ -292,6 +350,22 @@ impl From<OmaSearchError> for OutputError {
                 description: value.to_string(),
                 source: None,
             },
+            OmaSearchError::InvalidQuery(e) => e.into(),
+        }
+    }
+}
+
+impl From<QueryError> for OutputError {
+    fn from(value: QueryError) -> Self {
+        let description = match value {
+            QueryError::EmptyValue(key) => fl!("search-query-empty-value", key = key),
+            QueryError::InvalidSize(value) => fl!("search-query-invalid-size", value = value),
+            QueryError::InvalidStatus(value) => fl!("search-query-invalid-status", value = value),
+        };
+
+        OutputError {
+            description,
+            source: None,
         }
     }
 }
@@ -364,6 +438,10 @@ impl From<RefreshError> for OutputError {
                         description: fl!("failed-to-read-decode-inrelease"),
                         source: Some(Box::new(e)),
                     },
+                    VerifyError::NoSigningKey(key) => Self {
+                        description: fl!("no-signing-key", path = key),
+                        source: None,
+                    },
                 },
                 InReleaseError::BadInReleaseData => Self {
                     description: fl!("can-not-parse-date"),
@@ -484,6 +562,14 @@ impl From<RefreshError> for OutputError {
                 description: fl!("oma-refresh-no-metadata-to-download"),
                 source: None,
             },
+            RefreshError::MirrorList(e) => Self {
+                description: fl!("failed-to-read-mirror-list"),
+                source: Some(Box::new(e)),
+            },
+            RefreshError::NoMirrorAvailable(url) => Self {
+                description: fl!("no-mirror-available", url = url),
+                source: None,
+            },
             RefreshError::CreateTokioRuntime(error) => Self {
                 description: error.to_string(),
                 source: None,
@@ -777,6 +863,10 @@ pub fn oma_apt_error_to_output(err: OmaAptError) -> OutputError {
             description: fl!("no-candidate-ver", pkg = s),
             source: None,
         },
+        OmaAptError::BuildDepUnsatisfiable(s) => OutputError {
+            description: fl!("build-dep-unsatisfiable", dep = s),
+            source: None,
+        },
         OmaAptError::PkgNoChecksum(s) => OutputError {
             description: fl!("pkg-no-checksum", name = s),
             source: None,
@@ -916,6 +1006,7 @@ pub fn oma_apt_error_to_output(err: OmaAptError) -> OutputError {
         OmaAptError::DpkgStatusGetPkg(_) => anyhow::anyhow!("{err}").into(),
         OmaAptError::WrongDpkgStatus(_) => anyhow::anyhow!("{err}").into(),
         OmaAptError::DpkgStatusBroken(_) => anyhow::anyhow!("{err}").into(),
+        OmaAptError::ExtractFromDeb(_, _, _) => anyhow::anyhow!("{err}").into(),
         OmaAptError::FailedGetArchiveDirLock(get_lock_error) => match get_lock_error {
             GetLockError::SetLock(errno) => OutputError {
                 description: fl!("oma-archive-lock"),
@@ -931,6 +1022,14 @@ pub fn oma_apt_error_to_output(err: OmaAptError) -> OutputError {
             },
         },
         OmaAptError::RecvError => anyhow::anyhow!("{err}").into(),
+        OmaAptError::Snapshot(e) => OutputError {
+            description: fl!("failed-to-take-snapshot"),
+            source: Some(Box::new(e)),
+        },
+        OmaAptError::MirrorList(e) => OutputError {
+            description: fl!("failed-to-read-mirror-list"),
+            source: Some(Box::new(e)),
+        },
         OmaAptError::Anyhow(error) => error.into(),
     }
 }
@@ -965,6 +1064,16 @@ impl From<reqwest::Error> for OutputError {
             };
         }
 
+        if e.is_connect()
+            && is_tls_error(&e)
+            && let Some(host) = e.url().and_then(|url| url.host_str())
+        {
+            return Self {
+                description: fl!("tls-handshake-failed", host = host.to_string()),
+                source: Some(Box::new(e)),
+            };
+        }
+
         if let Some(filename) = filename
             && filename.len() <= 256
         {
@@ -981,6 +1090,54 @@ impl From<reqwest::Error> for OutputError {
     }
 }
 
+/// Whether a TLS error, e.g. an untrusted certificate, caused this error
+#[cfg(feature = "rustls")]
+fn is_tls_error(e: &(dyn Error + 'static)) -> bool {
+    let mut next = Some(e);
+
+    while let Some(e) = next {
+        // io::Error 的 source() 会跳过其包装的错误本身
+        let inner = e.downcast_ref::<io::Error>().and_then(|e| e.get_ref());
+
+        if e.is::<rustls::Error>() || inner.is_some_and(|e| e.is::<rustls::Error>()) {
+            return true;
+        }
+
+        next = e.source();
+    }
+
+    false
+}
+
+#[cfg(not(feature = "rustls"))]
+fn is_tls_error(_: &(dyn Error + 'static)) -> bool {
+    false
+}
+
+impl From<TlsError> for OutputError {
+    fn from(e: TlsError) -> Self {
+        debug!("{:?}", e);
+        match e {
+            TlsError::ReadFile { path, source } => Self {
+                description: fl!("tls-read-file-failed", path = path.display().to_string()),
+                source: Some(Box::new(source)),
+            },
+            TlsError::InvalidCaInfo { path, source } => Self {
+                description: fl!("tls-invalid-ca-info", path = path.display().to_string()),
+                source: Some(Box::new(source)),
+            },
+            TlsError::InvalidClientCert { path, source } => Self {
+                description: fl!("tls-invalid-client-cert", path = path.display().to_string()),
+                source: Some(Box::new(source)),
+            },
+            TlsError::BuildClient { host, source } => Self {
+                description: fl!("failed-to-create-http-client-for", host = host),
+                source: Some(Box::new(source)),
+            },
+        }
+    }
+}
+
 fn oma_checksum_error(e: ChecksumError) -> OutputError {
     debug!("{:?}", e);
     match e {
@@ -1073,6 +1230,10 @@ impl From<HistoryError> for OutputError {
                 description: fl!("failed-to-execute-query-stmt"),
                 source: Some(Box::new(error)),
             },
+            HistoryError::SerializeReport(e) => Self {
+                description: e.to_string(),
+                source: None,
+            },
             HistoryError::NoUpgradeSystemLog => unreachable!(),
         }
     }
@@ -1137,6 +1298,10 @@ impl From<SingleDownloadError> for OutputError {
                 description: fl!("checksum-mismatch-download-err"),
                 source: None,
             },
+            SingleDownloadError::UnexpectedRange => Self {
+                description: fl!("unexpected-range-download-err"),
+                source: None,
+            },
             SingleDownloadError::AcquireError => Self {
                 description: value.to_string(),
                 source: None,
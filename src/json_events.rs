@@ -0,0 +1,180 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    os::fd::FromRawFd,
+    sync::{Mutex, OnceLock},
+};
+
+use oma_fetch::Event;
+use oma_pm::{apt::OmaOperation, progress::InstallProgressManager};
+use oma_refresh::db::Event as RefreshEvent;
+use serde::Serialize;
+use spdlog::debug;
+
+use crate::{
+    error::{Chain, OutputError},
+    fl,
+    pb::{RenderPackagesDownloadProgress, RenderRefreshProgress},
+};
+
+static SINK: OnceLock<Box<dyn Fn(&str) + Send + Sync>> = OnceLock::new();
+
+/// Events printed to stdout with `--json-events`, one JSON object per line.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "kebab-case")]
+pub enum JsonEvent<'a> {
+    Download(&'a Event),
+    Refresh(&'a RefreshEvent),
+    Dpkg {
+        package: &'a str,
+        steps_done: u64,
+        total_steps: u64,
+    },
+    Prompt {
+        id: &'a str,
+        message: &'a str,
+        /// `None` if the prompt can not be answered without user interaction.
+        answer: Option<bool>,
+    },
+    Summary(&'a OmaOperation),
+    Error {
+        description: String,
+        causes: Vec<String>,
+    },
+}
+
+impl JsonEvent<'_> {
+    pub fn emit(&self) {
+        let Some(sink) = SINK.get() else {
+            return;
+        };
+
+        match serde_json::to_string(self) {
+            Ok(line) => sink(&line),
+            Err(e) => debug!("Failed to serialize event {:?}: {}", self, e),
+        }
+    }
+}
+
+impl From<&OutputError> for JsonEvent<'_> {
+    fn from(e: &OutputError) -> Self {
+        JsonEvent::Error {
+            description: e.to_string(),
+            causes: Chain::new(e).skip(1).map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+/// Take over stdout for the event stream.
+///
+/// Everything else written to stdout afterwards (pager, dpkg and maintainer scripts, etc.)
+/// goes to stderr instead, so that the stream only contains JSON events.
+pub fn init() -> io::Result<()> {
+    let fd = unsafe { libc::fcntl(libc::STDOUT_FILENO, libc::F_DUPFD_CLOEXEC, 3) };
+
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let stream = Mutex::new(unsafe { File::from_raw_fd(fd) });
+
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    init_with(move |line| {
+        let mut stream = stream.lock().unwrap();
+
+        if let Err(e) = writeln!(stream, "{line}").and_then(|_| stream.flush()) {
+            debug!("Failed to write event: {}", e);
+        }
+    });
+
+    Ok(())
+}
+
+/// Send serialized events to `sink` instead of stdout, e.g. as D-Bus signals in `oma daemon`.
+pub fn init_with(sink: impl Fn(&str) + Send + Sync + 'static) {
+    SINK.get_or_init(|| Box::new(sink));
+}
+
+/// Emit a prompt that was answered without user interaction.
+pub fn answer_prompt(id: &str, message: &str, answer: bool) -> bool {
+    JsonEvent::Prompt {
+        id,
+        message,
+        answer: Some(answer),
+    }
+    .emit();
+
+    answer
+}
+
+/// Emit a prompt that can not be answered without user interaction, returning the error to abort with.
+pub fn unanswered_prompt(id: &str, message: &str, hint: &str) -> OutputError {
+    JsonEvent::Prompt {
+        id,
+        message,
+        answer: None,
+    }
+    .emit();
+
+    OutputError {
+        description: fl!(
+            "json-events-prompt-unanswered",
+            prompt = message,
+            hint = hint
+        ),
+        source: None,
+    }
+}
+
+pub struct JsonProgress;
+
+impl RenderPackagesDownloadProgress for JsonProgress {
+    fn render_progress(&mut self, rx: &flume::Receiver<Event>, _download_only: bool) {
+        while let Ok(event) = rx.recv() {
+            JsonEvent::Download(&event).emit();
+
+            if let Event::AllDone = event {
+                break;
+            }
+        }
+    }
+}
+
+impl RenderRefreshProgress for JsonProgress {
+    fn render_refresh_progress(&mut self, rx: &flume::Receiver<RefreshEvent>) {
+        while let Ok(event) = rx.recv() {
+            match event {
+                RefreshEvent::DownloadEvent(ref event) => JsonEvent::Download(event).emit(),
+                ref event => JsonEvent::Refresh(event).emit(),
+            }
+
+            if let RefreshEvent::Done = event {
+                break;
+            }
+        }
+    }
+}
+
+pub struct JsonInstallProgressManager;
+
+impl InstallProgressManager for JsonInstallProgressManager {
+    fn status_change(&self, pkgname: &str, steps_done: u64, total_steps: u64) {
+        JsonEvent::Dpkg {
+            package: pkgname,
+            steps_done,
+            total_steps,
+        }
+        .emit();
+    }
+
+    fn no_interactive(&self) -> bool {
+        true
+    }
+
+    fn use_pty(&self) -> bool {
+        false
+    }
+}
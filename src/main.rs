@@ -16,6 +16,7 @@ mod dbus;
 mod error;
 mod exit_handle;
 mod install_progress;
+mod json_events;
 mod lang;
 mod logger;
 mod menu;
@@ -54,6 +55,7 @@ use crate::config::OmaConfig;
 use crate::config_file::ConfigFile;
 use crate::error::Chain;
 use crate::exit_handle::ExitHandle;
+use crate::json_events::JsonEvent;
 #[cfg(not(feature = "tokio-console"))]
 use crate::logger::init_logger;
 use crate::logger::remove_old_log_file_impl;
@@ -117,6 +119,15 @@ pub struct GlobalOptions {
         help = fl!("clap-no-progress-help")
     )]
     no_progress: bool,
+    /// Print machine-readable events to stdout
+    #[arg(
+        long,
+        global = true,
+        env = "OMA_JSON_EVENTS",
+        value_parser = FalseyValueParser::new(),
+        help = fl!("clap-json-events-help")
+    )]
+    json_events: bool,
     /// Run oma do not check dbus
     #[arg(
         long,
@@ -212,10 +223,20 @@ fn main() {
     config_ctx.init_apt_config();
 
     let no_bell = config_ctx.no_bell;
+    let json_events = config_ctx.json_events;
+
+    if json_events && let Err(e) = json_events::init() {
+        eprintln!("Failed to initialize JSON event stream: {e}");
+        exit(1);
+    }
 
     match try_main(config_ctx, matches) {
         Ok(exit_code) => exit_code.handle(!no_bell),
         Err(e) => {
+            if json_events {
+                JsonEvent::from(&e).emit();
+            }
+
             if let Err(e) = display_error(e) {
                 eprintln!("Failed to display error: {e}");
             }
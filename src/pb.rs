@@ -265,6 +265,19 @@ impl OmaMultiProgressBar {
                     pb.inc(size);
                 }
             }
+            Event::Throttled { index, throttled } => {
+                if let Some(pb) = self.pb_map.get(&(index + 1)) {
+                    let suffix = format!(" {}", fl!("download-throttled"));
+                    let msg = pb.message();
+                    let msg = msg.strip_suffix(&suffix).unwrap_or(&msg);
+
+                    if throttled {
+                        pb.set_message(format!("{msg}{suffix}"));
+                    } else {
+                        pb.set_message(msg.to_string());
+                    }
+                }
+            }
             Event::NextUrl {
                 index: _,
                 file_name,
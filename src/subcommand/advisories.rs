@@ -0,0 +1,171 @@
+use std::{collections::HashMap, io::stdout, sync::atomic::Ordering};
+
+use clap::Args;
+use oma_console::print::Action;
+use oma_pm::{
+    apt::{OmaApt, OmaAptArgs},
+    oma_apt::PackageSort,
+};
+use oma_tum::advisory::{Advisory, AdvisoryStatus, get_advisories};
+use spdlog::info;
+
+use crate::{
+    NOT_DISPLAY_ABORT, color_formatter, config::OmaConfig, console::style, error::OutputError,
+    exit_handle::ExitHandle, fl, lang::SYSTEM_LANG, table::PagerPrinter, utils::get_lists_dir,
+};
+
+use crate::args::CliExecuter;
+
+#[derive(Debug, Args)]
+pub struct Advisories {
+    /// Only list advisories which still affect installed package(s)
+    #[arg(long, help = fl!("clap-advisories-pending-help"))]
+    pending: bool,
+    /// Also list non-security updates
+    #[arg(short, long, help = fl!("clap-advisories-all-help"))]
+    all: bool,
+    /// Set output format as JSON
+    #[arg(long, help = fl!("clap-json-help"))]
+    json: bool,
+}
+
+impl CliExecuter for Advisories {
+    fn execute(self, config: OmaConfig) -> Result<ExitHandle, OutputError> {
+        let Advisories { pending, all, json } = self;
+
+        let oma_apt_args = OmaAptArgs::builder()
+            .sysroot(config.sysroot.to_string_lossy().to_string())
+            .another_apt_options(&config.apt_options)
+            .build();
+
+        let apt = OmaApt::new(vec![], oma_apt_args, false)?;
+
+        // TUM 中的包名不带架构
+        let versions = apt
+            .cache
+            .packages(&PackageSort::default().installed())
+            .filter_map(|pkg| {
+                let installed = pkg.installed()?.version().to_string();
+                let candidate = pkg.candidate().map(|v| v.version().to_string());
+                Some((pkg.name().to_string(), (installed, candidate)))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let tum = oma_tum::get_tum(get_lists_dir())?;
+
+        let advisories = get_advisories(&tum, |name| versions.get(name).cloned())
+            .into_iter()
+            .filter(|a| all || a.security)
+            .filter(|a| !pending || a.status() == AdvisoryStatus::Pending)
+            .collect::<Vec<_>>();
+
+        let mut printer = PagerPrinter::new(stdout());
+        NOT_DISPLAY_ABORT.store(true, Ordering::Relaxed);
+
+        if json {
+            for a in &advisories {
+                printer.println(advisory_json(a)).ok();
+            }
+
+            return Ok(ExitHandle::default());
+        }
+
+        for a in &advisories {
+            print_advisory(&mut printer, a);
+        }
+
+        if advisories.is_empty() {
+            info!("{}", fl!("advisories-none"));
+        } else {
+            let pending_count = advisories
+                .iter()
+                .filter(|a| a.status() == AdvisoryStatus::Pending)
+                .count();
+
+            info!(
+                "{}",
+                fl!(
+                    "advisories-summary",
+                    total = advisories.len(),
+                    pending = pending_count
+                )
+            );
+        }
+
+        Ok(ExitHandle::default())
+    }
+}
+
+fn localized(map: &ahash::HashMap<String, String>) -> Option<&str> {
+    map.get(&*SYSTEM_LANG)
+        .or_else(|| map.get("default"))
+        .map(|s| s.as_str())
+}
+
+fn advisory_json(a: &Advisory) -> serde_json::Value {
+    serde_json::json!({
+        "id": a.id,
+        "name": localized(a.name).unwrap_or(a.id),
+        "caution": a.caution.and_then(localized),
+        "security": a.security,
+        "status": a.status().as_str(),
+        "packages": a.packages.iter().map(|p| serde_json::json!({
+            "name": p.name,
+            "installed_version": p.installed_version,
+            "fixed_version": p.fixed_version,
+            "status": p.status.as_str(),
+        })).collect::<Vec<_>>(),
+    })
+}
+
+fn print_advisory(printer: &mut PagerPrinter<std::io::Stdout>, a: &Advisory) {
+    let status = match a.status() {
+        AdvisoryStatus::Pending => color_formatter()
+            .color_str(fl!("advisories-pending"), Action::WARN)
+            .to_string(),
+        AdvisoryStatus::Fixed => color_formatter()
+            .color_str(fl!("advisories-fixed"), Action::EmphasisSecondary)
+            .to_string(),
+    };
+
+    let name = localized(a.name).unwrap_or(a.id);
+
+    let name = if a.security {
+        style(name).red().bold().to_string()
+    } else {
+        color_formatter()
+            .color_str(name, Action::Emphasis)
+            .bold()
+            .to_string()
+    };
+
+    printer
+        .println(format!(
+            "[{status}] {name} {}",
+            color_formatter().color_str(format!("({})", a.id), Action::Secondary)
+        ))
+        .ok();
+
+    if let Some(caution) = a.caution.and_then(localized) {
+        for line in caution.trim().lines() {
+            printer.println(format!("    {line}")).ok();
+        }
+    }
+
+    for p in &a.packages {
+        let fixed = p.fixed_version.as_deref().unwrap_or("-");
+
+        let versions = match p.status {
+            AdvisoryStatus::Pending => color_formatter()
+                .color_str(format!("{} -> {fixed}", p.installed_version), Action::WARN)
+                .to_string(),
+            AdvisoryStatus::Fixed => color_formatter()
+                .color_str(&p.installed_version, Action::EmphasisSecondary)
+                .to_string(),
+        };
+
+        printer.println(format!("    {} {versions}", p.name)).ok();
+    }
+
+    printer.println("").ok();
+}
@@ -0,0 +1,183 @@
+use std::path::Path;
+
+use clap::Args;
+use clap_complete::ArgValueCompleter;
+use oma_console::print::Action;
+use oma_pm::apt::{OmaApt, OmaAptArgs};
+use oma_pm::build_dep::BuildDeps;
+use oma_utils::dpkg::dpkg_arch;
+use spdlog::{info, warn};
+
+use crate::args::ARG_HELP_HEADING;
+use crate::completions::pkgnames_and_path_completions;
+use crate::config::OmaConfig;
+use crate::core::commit_changes::CommitChanges;
+use crate::core::refresh::Refresh;
+use crate::dbus::dbus_check;
+use crate::error::OutputError;
+use crate::exit_handle::ExitHandle;
+use crate::root::root;
+use crate::utils::get_lists_dir;
+use crate::{color_formatter, fl};
+
+use super::utils::lock_oma;
+use crate::args::CliExecuter;
+
+#[derive(Debug, Args)]
+#[command(next_help_heading = &**ARG_HELP_HEADING)]
+pub struct BuildDep {
+    /// Source package name, or path to debian/control
+    #[arg(
+        add = ArgValueCompleter::new(pkgnames_and_path_completions),
+        help = fl!("clap-build-dep-target-help"),
+        help_heading = &**crate::args::ARG_HELP_HEADING_MUST,
+    )]
+    target: String,
+    /// Build profiles to activate
+    #[arg(short = 'P', long, value_delimiter = ',', help = fl!("clap-build-dep-build-profiles-help"))]
+    build_profiles: Vec<String>,
+    /// Bypass confirmation prompts
+    #[arg(short, long, help = fl!("clap-yes-help"))]
+    yes: bool,
+    /// Install package(s) without fsync(2)
+    #[arg(
+        long,
+        help = &**crate::args::FORCE_UNSAFE_IO_TRANSLATE
+    )]
+    force_unsafe_io: bool,
+    /// Do not refresh repository metadata
+    #[arg(long, help = fl!("clap-no-refresh-help"))]
+    no_refresh: bool,
+    /// Ignore repository and package dependency issues
+    #[arg(long, help = fl!("clap-force-yes-help"))]
+    force_yes: bool,
+    #[cfg(feature = "aosc")]
+    /// Do not refresh topics manifest.json file
+    #[arg(long, help = fl!("clap-no-refresh-topics-help"))]
+    no_refresh_topics: bool,
+    /// Only download dependencies, not install
+    #[arg(long, short, help = fl!("clap-download-only-help"))]
+    download_only: bool,
+    /// Do not clean local package cache
+    #[arg(long, help = fl!("clap-noclean-help"), env = "OMA_NO_CLEAN", value_parser = clap::builder::FalseyValueParser::new())]
+    no_clean: bool,
+}
+
+impl CliExecuter for BuildDep {
+    fn execute(self, config: OmaConfig) -> Result<ExitHandle, OutputError> {
+        let BuildDep {
+            target,
+            build_profiles,
+            yes,
+            force_unsafe_io,
+            no_refresh,
+            force_yes,
+            #[cfg(feature = "aosc")]
+            no_refresh_topics,
+            download_only,
+            no_clean,
+        } = self;
+
+        #[cfg(feature = "aosc")]
+        let mut config = config;
+
+        #[cfg(feature = "aosc")]
+        config.update_from_cli_no_refresh_topics(no_refresh_topics);
+
+        let _lock_fd = if !config.dry_run {
+            root()?;
+            Some(lock_oma(&config.sysroot)?)
+        } else {
+            None
+        };
+
+        let _fds = dbus_check(yes, &config)?;
+
+        if !no_refresh {
+            Refresh::builder().config(&config).build().run()?;
+        }
+
+        if yes {
+            warn!("{}", fl!("automatic-mode-warn"));
+        }
+
+        let oma_apt_args = OmaAptArgs::builder()
+            .sysroot(config.sysroot.to_string_lossy().to_string())
+            .yes(yes)
+            .force_yes(force_yes)
+            .another_apt_options(&config.apt_options)
+            .dpkg_force_unsafe_io(force_unsafe_io)
+            .build();
+
+        let mut apt = OmaApt::new(vec![], oma_apt_args, config.dry_run)?;
+        let arch = dpkg_arch(&config.sysroot)?;
+
+        let deps = find_build_deps(&apt, &target, &arch, &build_profiles)?;
+
+        info!(
+            "{}",
+            fl!(
+                "build-dep-resolving",
+                name = color_formatter()
+                    .color_str(&deps.source, Action::Emphasis)
+                    .to_string(),
+                version = deps.version.as_deref().unwrap_or_default()
+            )
+        );
+
+        apt.install_build_deps(&deps)?;
+
+        CommitChanges::builder()
+            .apt(apt)
+            .yes(yes)
+            .download_only(download_only)
+            .config(&config)
+            .no_clean(no_clean)
+            .build()
+            .run()
+    }
+}
+
+fn find_build_deps(
+    apt: &OmaApt,
+    target: &str,
+    arch: &str,
+    profiles: &[String],
+) -> Result<BuildDeps, OutputError> {
+    let path = Path::new(target);
+
+    // 源码目录或 debian/control 文件
+    if path.is_dir() {
+        return Ok(BuildDeps::from_control(
+            path.join("debian/control"),
+            arch,
+            profiles,
+        )?);
+    } else if target.contains('/') || path.is_file() {
+        return Ok(BuildDeps::from_control(path, arch, profiles)?);
+    }
+
+    let lists_dir = get_lists_dir();
+
+    if let Some(deps) = BuildDeps::from_sources_index(&lists_dir, target, arch, profiles)? {
+        return Ok(deps);
+    }
+
+    // 和 apt build-dep 一样，也接受二进制包名
+    let source = apt
+        .cache
+        .get(target)
+        .and_then(|pkg| pkg.candidate())
+        .map(|ver| ver.source_name().to_string());
+
+    if let Some(source) = source.filter(|s| s != target)
+        && let Some(deps) = BuildDeps::from_sources_index(&lists_dir, &source, arch, profiles)?
+    {
+        return Ok(deps);
+    }
+
+    Err(OutputError {
+        description: fl!("build-dep-no-source-index", name = target),
+        source: None,
+    })
+}
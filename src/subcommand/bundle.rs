@@ -0,0 +1,554 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+
+use chrono::Local;
+use clap::{Args, Subcommand};
+use clap_complete::ArgValueCompleter;
+use digest_io::IoWrapper;
+use faster_hex::hex_string;
+use flume::unbounded;
+use oma_pm::apt::{DownloadConfig, OmaApt, OmaAptArgs};
+use oma_pm::matches::{GetArchMethod, PackagesMatcher};
+use oma_pm::sort::SummarySort;
+use oma_repo_verify::{KeyBlockOrPaths, verify_release_by_sysroot, verify_release_inner};
+use oma_utils::dpkg::dpkg_arch;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use spdlog::{error, info};
+
+use crate::args::{CliExecuter, HELP_TEMPLATE};
+use crate::completions::pkgnames_completions;
+use crate::config::OmaConfig;
+use crate::core::commit_changes::CommitChanges;
+use crate::core::refresh::Refresh;
+use crate::dbus::dbus_check;
+use crate::error::OutputError;
+use crate::exit_handle::ExitHandle;
+use crate::pb::{NoProgressBar, OmaMultiProgressBar, RenderPackagesDownloadProgress};
+use crate::root::root;
+use crate::{fl, success};
+
+use super::utils::{download_message, handle_no_result, lock_oma};
+
+const MANIFEST: &str = "manifest.json";
+const MANIFEST_SIGNATURE: &str = "manifest.json.asc";
+const DEBS_DIR: &str = "debs";
+const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Args)]
+pub struct Bundle {
+    #[command(subcommand)]
+    bundle_subcmd: BundleSubCmd,
+}
+
+#[derive(Debug, Subcommand)]
+#[command(subcommand_help_heading = &**crate::args::HELP_HEADING)]
+pub enum BundleSubCmd {
+    /// Create an offline bundle with package(s) and all their dependencies
+    #[command(about = fl!("clap-bundle-create-help"))]
+    #[command(help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Create {
+        /// Package(s) to include in the bundle
+        #[arg(required = true, add = ArgValueCompleter::new(pkgnames_completions), help = fl!("clap-bundle-create-packages-help"))]
+        #[arg(help_heading = &**crate::args::ARG_HELP_HEADING_MUST)]
+        packages: Vec<String>,
+        /// Path to write the bundle to
+        #[arg(short, long, default_value = "oma-bundle.tar", help = fl!("clap-bundle-output-help"))]
+        output: PathBuf,
+        /// OpenPGP secret key file used to sign the bundle manifest
+        #[arg(long, required = true, help = fl!("clap-bundle-sign-key-help"))]
+        sign_key: PathBuf,
+        /// Do not refresh repository metadata
+        #[arg(long, help = fl!("clap-no-refresh-help"))]
+        no_refresh: bool,
+    },
+    /// Install package(s) from an offline bundle
+    #[command(about = fl!("clap-bundle-apply-help"))]
+    #[command(help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Apply {
+        /// Path to the bundle
+        #[arg(help = fl!("clap-bundle-path-help"))]
+        #[arg(help_heading = &**crate::args::ARG_HELP_HEADING_MUST)]
+        bundle: PathBuf,
+        /// OpenPGP certificate file used to verify the bundle manifest
+        #[arg(long, help = fl!("clap-bundle-keyring-help"))]
+        keyring: Option<PathBuf>,
+        /// Bypass confirmation prompts
+        #[arg(short, long, help = fl!("clap-yes-help"))]
+        yes: bool,
+        /// Install package(s) without fsync(2)
+        #[arg(
+            long,
+            help = &**crate::args::FORCE_UNSAFE_IO_TRANSLATE
+        )]
+        force_unsafe_io: bool,
+        /// Replace configuration file(s) in the system those shipped in the package(s) to be installed (invokes `dpkg --force-confnew`)
+        #[arg(long, help = fl!("clap-force-confnew-help"))]
+        force_confnew: bool,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleManifest {
+    version: u32,
+    arch: String,
+    created: i64,
+    packages: Vec<BundlePackage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundlePackage {
+    name: String,
+    version: String,
+    arch: String,
+    filename: String,
+    sha256: String,
+}
+
+impl CliExecuter for Bundle {
+    fn execute(self, config: OmaConfig) -> Result<ExitHandle, OutputError> {
+        match self.bundle_subcmd {
+            BundleSubCmd::Create {
+                packages,
+                output,
+                sign_key,
+                no_refresh,
+            } => create(packages, output, sign_key, no_refresh, &config),
+            BundleSubCmd::Apply {
+                bundle,
+                keyring,
+                yes,
+                force_unsafe_io,
+                force_confnew,
+            } => apply(
+                bundle,
+                keyring,
+                yes,
+                force_unsafe_io,
+                force_confnew,
+                &config,
+            ),
+        }
+    }
+}
+
+fn create(
+    packages: Vec<String>,
+    output: PathBuf,
+    sign_key: PathBuf,
+    no_refresh: bool,
+    config: &OmaConfig,
+) -> Result<ExitHandle, OutputError> {
+    let no_progress = config.no_progress();
+
+    if !no_refresh {
+        root()?;
+        Refresh::builder().config(config).build().run()?;
+    }
+
+    let oma_apt_args = OmaAptArgs::builder()
+        .sysroot(config.sysroot.to_string_lossy().to_string())
+        .another_apt_options(&config.apt_options)
+        .build();
+
+    let mut apt = OmaApt::new(vec![], oma_apt_args, config.dry_run)?;
+    let matcher = PackagesMatcher::builder()
+        .cache(&apt.cache)
+        .filter_candidate(true)
+        .filter_downloadable_candidate(true)
+        .select_dbg(false)
+        .native_arch(GetArchMethod::SpecifySysroot(&config.sysroot))
+        .build();
+
+    let (pkgs, no_result) = matcher.match_pkgs_and_versions(packages.iter().map(|x| x.as_str()))?;
+    handle_no_result(no_result, no_progress)?;
+
+    apt.install(&pkgs, false)?;
+    apt.resolve(false, false)?;
+
+    let op = apt.build_transaction(
+        SummarySort::default().names().operation(),
+        |_| false,
+        |_| true,
+    )?;
+
+    if op.install.is_empty() {
+        success!("{}", fl!("no-need-to-do-anything"));
+        return Ok(ExitHandle::default());
+    }
+
+    if config.dry_run {
+        info!("Running in dry-run mode, Exit.");
+        return Ok(ExitHandle::default());
+    }
+
+    let staging = staging_dir()?;
+    let debs_dir = staging.join(DEBS_DIR);
+    create_dir(&debs_dir)?;
+
+    let res =
+        create_inner(&apt, &op.install, &staging, &debs_dir, &sign_key, config).and_then(|len| {
+            run_tar(
+                Command::new("tar")
+                    .arg("-cf")
+                    .arg(&output)
+                    .arg("-C")
+                    .arg(&staging)
+                    .args([MANIFEST, MANIFEST_SIGNATURE, DEBS_DIR]),
+                &output,
+            )?;
+
+            Ok(len)
+        });
+
+    fs::remove_dir_all(&staging).ok();
+
+    let len = res?;
+
+    success!(
+        "{}",
+        fl!(
+            "bundle-created",
+            len = len,
+            path = output.display().to_string()
+        )
+    );
+
+    Ok(ExitHandle::default().ring(true))
+}
+
+fn create_inner(
+    apt: &OmaApt,
+    install: &[oma_pm::apt::InstallEntry],
+    staging: &Path,
+    debs_dir: &Path,
+    sign_key: &Path,
+    config: &OmaConfig,
+) -> Result<usize, OutputError> {
+    let (tx, rx) = unbounded();
+    let no_progress = config.no_progress();
+
+    thread::spawn(move || {
+        let mut pb: Box<dyn RenderPackagesDownloadProgress> = if no_progress {
+            Box::new(NoProgressBar::default())
+        } else {
+            Box::new(OmaMultiProgressBar::default())
+        };
+        pb.render_progress(&rx, true);
+    });
+
+    let summary = apt.download_entries(
+        config.http_client()?,
+        install.to_vec(),
+        DownloadConfig {
+            network_thread: Some(config.download_threads),
+            download_dir: Some(Arc::from(debs_dir)),
+            delta: false,
+            peers: vec![],
+            limits: config.network_limits,
+            redirector_client: Some(config.http_client_no_redirect()?.clone()),
+        },
+        download_message(),
+        move |event| {
+            if let Err(e) = tx.send(event) {
+                error!("{}", e);
+            }
+        },
+    )?;
+
+    if !summary.is_download_success() {
+        return Err(OutputError {
+            description: fl!("download-failed-with-len", len = summary.failed.len()),
+            source: None,
+        });
+    }
+
+    let mut packages = vec![];
+
+    for s in &summary.success {
+        let entry = &install[s.index];
+        let path = debs_dir.join(&s.file_name);
+
+        packages.push(BundlePackage {
+            name: entry.name().to_string(),
+            version: entry.new_version().to_string(),
+            arch: entry.arch().to_string(),
+            filename: s.file_name.clone(),
+            sha256: sha256_file(&path).map_err(|e| OutputError {
+                description: fl!("failed-to-operate-path", p = path.display().to_string()),
+                source: Some(Box::new(e)),
+            })?,
+        });
+    }
+
+    let manifest = BundleManifest {
+        version: MANIFEST_VERSION,
+        arch: dpkg_arch(&config.sysroot)?,
+        created: Local::now().timestamp(),
+        packages,
+    };
+
+    let manifest = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| anyhow::anyhow!("Failed to serialize bundle manifest: {e}"))?;
+
+    let signature =
+        oma_repo_verify::sign_detached(&manifest, sign_key).map_err(|e| OutputError {
+            description: fl!("bundle-failed-to-sign"),
+            source: Some(Box::new(e)),
+        })?;
+
+    write_file(&staging.join(MANIFEST), &manifest)?;
+    write_file(&staging.join(MANIFEST_SIGNATURE), &signature)?;
+
+    Ok(summary.success.len())
+}
+
+fn apply(
+    bundle: PathBuf,
+    keyring: Option<PathBuf>,
+    yes: bool,
+    force_unsafe_io: bool,
+    force_confnew: bool,
+    config: &OmaConfig,
+) -> Result<ExitHandle, OutputError> {
+    let _lock_fd = if !config.dry_run {
+        root()?;
+        Some(lock_oma(&config.sysroot)?)
+    } else {
+        None
+    };
+
+    let _fds = dbus_check(yes, config)?;
+
+    let staging = staging_dir()?;
+
+    let res = apply_inner(
+        &bundle,
+        &staging,
+        keyring,
+        yes,
+        force_unsafe_io,
+        force_confnew,
+        config,
+    );
+
+    fs::remove_dir_all(&staging).ok();
+
+    res
+}
+
+fn apply_inner(
+    bundle: &Path,
+    staging: &Path,
+    keyring: Option<PathBuf>,
+    yes: bool,
+    force_unsafe_io: bool,
+    force_confnew: bool,
+    config: &OmaConfig,
+) -> Result<ExitHandle, OutputError> {
+    // 先只解出清单及其签名，验证通过后再解出清单中列出的软件包
+    extract(bundle, staging, &[MANIFEST, MANIFEST_SIGNATURE])?;
+
+    let manifest = read_file(&staging.join(MANIFEST), bundle)?;
+    let signature = read_file(&staging.join(MANIFEST_SIGNATURE), bundle)?;
+    let manifest = String::from_utf8(manifest).map_err(|_| bad_bundle(bundle))?;
+
+    match keyring {
+        Some(keyring) => verify_release_inner(
+            &manifest,
+            &signature,
+            false,
+            KeyBlockOrPaths::Paths(vec![keyring]),
+        ),
+        None => verify_release_by_sysroot(&manifest, &signature, None, &config.sysroot, false),
+    }
+    .map_err(|e| OutputError {
+        description: fl!("bundle-bad-signature"),
+        source: Some(Box::new(e)),
+    })?;
+
+    let manifest: BundleManifest =
+        serde_json::from_str(&manifest).map_err(|_| bad_bundle(bundle))?;
+
+    if manifest.version != MANIFEST_VERSION {
+        return Err(bad_bundle(bundle));
+    }
+
+    let arch = dpkg_arch(&config.sysroot)?;
+    if manifest.arch != arch {
+        return Err(OutputError {
+            description: fl!("bundle-arch-mismatch", bundle = manifest.arch, arch = arch),
+            source: None,
+        });
+    }
+
+    let mut members = vec![];
+
+    for pkg in &manifest.packages {
+        if !is_valid_filename(&pkg.filename) {
+            return Err(bad_bundle(bundle));
+        }
+
+        members.push(format!("{DEBS_DIR}/{}", pkg.filename));
+    }
+
+    create_dir(&staging.join(DEBS_DIR))?;
+    extract(bundle, staging, &members)?;
+
+    let mut local_debs = vec![];
+
+    for pkg in &manifest.packages {
+        let path = staging.join(DEBS_DIR).join(&pkg.filename);
+
+        if !is_regular_file(&path)
+            || sha256_file(&path).ok().as_deref() != Some(pkg.sha256.as_str())
+        {
+            return Err(OutputError {
+                description: fl!("bundle-checksum-mismatch", name = pkg.filename.as_str()),
+                source: None,
+            });
+        }
+
+        local_debs.push(path.to_string_lossy().to_string());
+    }
+
+    let oma_apt_args = OmaAptArgs::builder()
+        .sysroot(config.sysroot.to_string_lossy().to_string())
+        .yes(yes)
+        .dpkg_force_confnew(force_confnew)
+        .dpkg_force_unsafe_io(force_unsafe_io)
+        .another_apt_options(&config.apt_options)
+        .build();
+
+    let mut apt = OmaApt::new(local_debs.clone(), oma_apt_args, config.dry_run)?;
+    let matcher = PackagesMatcher::builder()
+        .cache(&apt.cache)
+        .filter_candidate(true)
+        .filter_downloadable_candidate(false)
+        .select_dbg(false)
+        .native_arch(GetArchMethod::SpecifySysroot(&config.sysroot))
+        .build();
+
+    let (pkgs, no_result) =
+        matcher.match_pkgs_and_versions(local_debs.iter().map(|x| x.as_str()))?;
+    handle_no_result(no_result, config.no_progress())?;
+
+    apt.install(&pkgs, false)?;
+
+    CommitChanges::builder()
+        .apt(apt)
+        .yes(yes)
+        .config(config)
+        .no_clean(false)
+        .build()
+        .run()
+}
+
+/// Create a private staging directory, like mkdtemp(3) it is only accessible by the owner
+fn staging_dir() -> Result<PathBuf, OutputError> {
+    let template = std::env::temp_dir().join("oma-bundle-XXXXXX");
+    let mut buf = template.as_os_str().as_bytes().to_vec();
+    buf.push(0);
+
+    // mkdtemp 以 0700 权限创建名称随机的目录，避免被他人预先创建或放置符号链接
+    let ptr = unsafe { libc::mkdtemp(buf.as_mut_ptr().cast()) };
+
+    if ptr.is_null() {
+        return Err(OutputError {
+            description: fl!("failed-to-operate-path", p = template.display().to_string()),
+            source: Some(Box::new(io::Error::last_os_error())),
+        });
+    }
+
+    buf.pop();
+
+    Ok(PathBuf::from(OsString::from_vec(buf)))
+}
+
+/// Extract the given members of the bundle only
+fn extract<S: AsRef<std::ffi::OsStr>>(
+    bundle: &Path,
+    staging: &Path,
+    members: &[S],
+) -> Result<(), OutputError> {
+    run_tar(
+        Command::new("tar")
+            .arg("-xf")
+            .arg(bundle)
+            .arg("-C")
+            .arg(staging)
+            .args(["--no-same-owner", "--no-same-permissions", "--"])
+            .args(members),
+        bundle,
+    )
+}
+
+fn is_valid_filename(filename: &str) -> bool {
+    !filename.is_empty()
+        && !filename.starts_with('.')
+        && !filename.starts_with('-')
+        && !filename.contains(['/', '\\', '\0'])
+        && filename.ends_with(".deb")
+}
+
+fn is_regular_file(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_file())
+}
+
+fn bad_bundle(bundle: &Path) -> OutputError {
+    OutputError {
+        description: fl!("bundle-bad-format", path = bundle.display().to_string()),
+        source: None,
+    }
+}
+
+fn run_tar(cmd: &mut Command, archive: &Path) -> Result<(), OutputError> {
+    let status = cmd.status().map_err(|e| OutputError {
+        description: "Failed to execute `tar'".to_string(),
+        source: Some(Box::new(e)),
+    })?;
+
+    if !status.success() {
+        return Err(bad_bundle(archive));
+    }
+
+    Ok(())
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut f = File::open(path)?;
+    let mut sha256 = IoWrapper(Sha256::new());
+    io::copy(&mut f, &mut sha256)?;
+
+    Ok(hex_string(&sha256.0.finalize()))
+}
+
+fn create_dir(path: &Path) -> Result<(), OutputError> {
+    fs::create_dir_all(path).map_err(|e| OutputError {
+        description: fl!("failed-to-operate-path", p = path.display().to_string()),
+        source: Some(Box::new(e)),
+    })
+}
+
+fn read_file(path: &Path, bundle: &Path) -> Result<Vec<u8>, OutputError> {
+    if !is_regular_file(path) {
+        return Err(bad_bundle(bundle));
+    }
+
+    fs::read(path).map_err(|_| bad_bundle(bundle))
+}
+
+fn write_file(path: &Path, data: &[u8]) -> Result<(), OutputError> {
+    fs::write(path, data).map_err(|e| OutputError {
+        description: fl!("failed-to-operate-path", p = path.display().to_string()),
+        source: Some(Box::new(e)),
+    })
+}
@@ -0,0 +1,384 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{Write, stdout},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::Context;
+use clap::{ArgGroup, Args, Subcommand};
+use oma_console::print::Action;
+use oma_pm::{
+    Conffile, ConffileState,
+    apt::{OmaApt, OmaAptArgs},
+    conffiles, extract_file_from_deb,
+};
+use spdlog::info;
+
+use crate::args::{CliExecuter, HELP_TEMPLATE};
+use crate::config::OmaConfig;
+use crate::error::OutputError;
+use crate::exit_handle::ExitHandle;
+use crate::root::root;
+use crate::table::PagerPrinter;
+use crate::{color_formatter, fl, success};
+
+/// dpkg 保留本地版本时，软件包中的版本会被放在这些文件中
+const PACKAGED_SUFFIXES: &[&str] = &[".dpkg-new", ".dpkg-dist"];
+
+#[derive(Debug, Args)]
+pub struct Conffiles {
+    #[command(subcommand)]
+    subcmd: Option<ConffilesSubCmd>,
+}
+
+#[derive(Debug, Subcommand)]
+#[command(subcommand_help_heading = &**crate::args::HELP_HEADING)]
+pub enum ConffilesSubCmd {
+    /// List configuration file(s) which differ from the packaged version
+    #[command(about = fl!("clap-conffiles-list-help"))]
+    #[command(help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    List {
+        /// Also list unmodified configuration file(s)
+        #[arg(short, long, help = fl!("clap-conffiles-all-help"))]
+        all: bool,
+    },
+    /// Show differences between the packaged and the local version
+    #[command(about = fl!("clap-conffiles-diff-help"))]
+    #[command(help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Diff {
+        #[arg(help = fl!("clap-conffiles-path-help"))]
+        #[arg(help_heading = &**crate::args::ARG_HELP_HEADING_MUST)]
+        path: PathBuf,
+    },
+    /// Keep the local version, take the packaged version or merge both
+    #[command(about = fl!("clap-conffiles-resolve-help"))]
+    #[command(help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    #[command(group(ArgGroup::new("action").required(true).args(["keep", "take", "merge"])))]
+    Resolve {
+        #[arg(help = fl!("clap-conffiles-path-help"))]
+        #[arg(help_heading = &**crate::args::ARG_HELP_HEADING_MUST)]
+        path: PathBuf,
+        /// Keep the local version and remove leftover files
+        #[arg(long, help = fl!("clap-conffiles-keep-help"))]
+        keep: bool,
+        /// Replace the local version with the packaged version
+        #[arg(long, help = fl!("clap-conffiles-take-help"))]
+        take: bool,
+        /// Merge the packaged version into the local version interactively
+        #[arg(long, help = fl!("clap-conffiles-merge-help"))]
+        merge: bool,
+    },
+}
+
+impl CliExecuter for Conffiles {
+    fn execute(self, config: OmaConfig) -> Result<ExitHandle, OutputError> {
+        match self.subcmd.unwrap_or(ConffilesSubCmd::List { all: false }) {
+            ConffilesSubCmd::List { all } => list(&config, all),
+            ConffilesSubCmd::Diff { path } => diff(&config, &path),
+            ConffilesSubCmd::Resolve {
+                path, take, merge, ..
+            } => {
+                if !config.dry_run {
+                    root()?;
+                }
+
+                let conffile = find_conffile(&config, &path)?;
+
+                if take {
+                    take_packaged(&config, &conffile)?;
+                } else if merge {
+                    merge_packaged(&config, &conffile)?;
+                } else {
+                    remove_leftovers(&config, &conffile, false)?;
+                }
+
+                success!(
+                    "{}",
+                    fl!(
+                        "conffiles-resolved",
+                        path = color_formatter()
+                            .color_str(&conffile.path, Action::Emphasis)
+                            .to_string()
+                    )
+                );
+
+                Ok(ExitHandle::default())
+            }
+        }
+    }
+}
+
+fn list(config: &OmaConfig, all: bool) -> Result<ExitHandle, OutputError> {
+    let mut printer = PagerPrinter::new(stdout());
+    let mut count = 0;
+
+    for c in conffiles(&config.sysroot)?.iter().filter(|c| !c.obsolete) {
+        let state = c.state(&config.sysroot);
+        let leftovers = c.leftovers(&config.sysroot);
+
+        if !all && state == ConffileState::Unmodified && leftovers.is_empty() {
+            continue;
+        }
+
+        count += 1;
+
+        let state = match state {
+            ConffileState::Modified => color_formatter()
+                .color_str(fl!("conffiles-modified"), Action::WARN)
+                .to_string(),
+            ConffileState::Missing => color_formatter()
+                .color_str(fl!("conffiles-missing"), Action::WARN)
+                .to_string(),
+            ConffileState::Unmodified => color_formatter()
+                .color_str(fl!("conffiles-unmodified"), Action::EmphasisSecondary)
+                .to_string(),
+        };
+
+        printer
+            .println(format!(
+                "{} {} [{state}]",
+                color_formatter()
+                    .color_str(&c.path, Action::Emphasis)
+                    .bold(),
+                color_formatter().color_str(&c.package, Action::Secondary),
+            ))
+            .ok();
+
+        for l in leftovers {
+            printer.println(format!("    {}", l.display())).ok();
+        }
+    }
+
+    if count == 0 {
+        info!("{}", fl!("conffiles-none"));
+    }
+
+    Ok(ExitHandle::default())
+}
+
+fn diff(config: &OmaConfig, path: &Path) -> Result<ExitHandle, OutputError> {
+    let conffile = find_conffile(config, path)?;
+    let packaged = TempFile::new(&packaged_content(config, &conffile)?)?;
+
+    let status = Command::new("diff")
+        .arg("-u")
+        .arg("--color=auto")
+        .arg("--label")
+        .arg(fl!(
+            "conffiles-diff-packaged",
+            path = conffile.path.as_str()
+        ))
+        .arg("--label")
+        .arg(&conffile.path)
+        .arg(&packaged.0)
+        .arg(conffile.real_path(&config.sysroot))
+        .status()
+        .context("Failed to run diff")?;
+
+    match status.code() {
+        Some(0) => info!("{}", fl!("conffiles-no-diff")),
+        Some(1) => {}
+        _ => {
+            return Err(OutputError {
+                description: fl!("conffiles-diff-failed"),
+                source: None,
+            });
+        }
+    }
+
+    Ok(ExitHandle::default())
+}
+
+fn find_conffile(config: &OmaConfig, path: &Path) -> Result<Conffile, OutputError> {
+    let path = std::path::absolute(path).context("Failed to get absolute path")?;
+    let path = path.to_string_lossy();
+
+    // 同一路径可能同时作为旧软件包的废弃配置文件存在
+    conffiles(&config.sysroot)?
+        .into_iter()
+        .filter(|c| c.path == path)
+        .min_by_key(|c| c.obsolete)
+        .ok_or_else(|| OutputError {
+            description: fl!("conffiles-not-found", path = path.to_string()),
+            source: None,
+        })
+}
+
+/// Read the packaged version from dpkg leftovers, or from the cached package archive.
+fn packaged_content(config: &OmaConfig, conffile: &Conffile) -> Result<Vec<u8>, OutputError> {
+    if let Some(p) = leftover_path(config, conffile, PACKAGED_SUFFIXES) {
+        return Ok(fs::read(&p).with_context(|| format!("Failed to read {}", p.display()))?);
+    }
+
+    let oma_apt_args = OmaAptArgs::builder()
+        .sysroot(config.sysroot.to_string_lossy().to_string())
+        .another_apt_options(&config.apt_options)
+        .build();
+
+    let apt = OmaApt::new(vec![], oma_apt_args, false)?;
+
+    let version = apt
+        .cache
+        .get(&conffile.package)
+        .and_then(|pkg| pkg.installed())
+        .map(|ver| (ver.version().to_string(), ver.arch().to_string()));
+
+    let Some((version, arch)) = version else {
+        return Err(OutputError {
+            description: fl!("conffiles-not-found", path = conffile.path.as_str()),
+            source: None,
+        });
+    };
+
+    let Some(deb) = apt.cached_archive(&conffile.package, &version, &arch) else {
+        return Err(OutputError {
+            description: fl!(
+                "conffiles-no-cached-deb",
+                name = conffile.package.as_str(),
+                version = version
+            ),
+            source: None,
+        });
+    };
+
+    Ok(extract_file_from_deb(&deb, &conffile.path)?)
+}
+
+fn take_packaged(config: &OmaConfig, conffile: &Conffile) -> Result<(), OutputError> {
+    let content = packaged_content(config, conffile)?;
+    replace_local(config, conffile, &content)
+}
+
+fn merge_packaged(config: &OmaConfig, conffile: &Conffile) -> Result<(), OutputError> {
+    let packaged = TempFile::new(&packaged_content(config, conffile)?)?;
+    let merged = TempFile::new(b"")?;
+
+    // sdiff 会逐段询问保留哪一边，结果写入 -o 指定的文件
+    let status = Command::new("sdiff")
+        .arg("-o")
+        .arg(&merged.0)
+        .arg(conffile.real_path(&config.sysroot))
+        .arg(&packaged.0)
+        .status()
+        .context("Failed to run sdiff")?;
+
+    if !matches!(status.code(), Some(0 | 1)) {
+        return Err(OutputError {
+            description: fl!("conffiles-merge-failed"),
+            source: None,
+        });
+    }
+
+    let content = fs::read(&merged.0).context("Failed to read merged file")?;
+
+    replace_local(config, conffile, &content)
+}
+
+/// Back up the local version to `.dpkg-old` and write `content` in its place.
+fn replace_local(
+    config: &OmaConfig,
+    conffile: &Conffile,
+    content: &[u8],
+) -> Result<(), OutputError> {
+    let real_path = conffile.real_path(&config.sysroot);
+    let backup = with_suffix(&real_path, ".dpkg-old");
+
+    if config.dry_run {
+        info!(
+            "Would back up {} to {} and replace it",
+            real_path.display(),
+            backup.display()
+        );
+        return remove_leftovers(config, conffile, false);
+    }
+
+    if real_path.exists() {
+        fs::copy(&real_path, &backup)
+            .with_context(|| format!("Failed to back up {}", real_path.display()))?;
+    }
+
+    // 直接覆盖写入，保留原文件的权限和属主
+    fs::write(&real_path, content)
+        .with_context(|| format!("Failed to write {}", real_path.display()))?;
+
+    remove_leftovers(config, conffile, false)
+}
+
+/// Remove `.dpkg-new` and `.dpkg-dist`, and also `.dpkg-old` if `all` is set.
+fn remove_leftovers(config: &OmaConfig, conffile: &Conffile, all: bool) -> Result<(), OutputError> {
+    for p in conffile.leftovers(&config.sysroot) {
+        if !all
+            && !PACKAGED_SUFFIXES
+                .iter()
+                .any(|s| p.to_string_lossy().ends_with(s))
+        {
+            continue;
+        }
+
+        if config.dry_run {
+            info!("Would remove {}", p.display());
+            continue;
+        }
+
+        fs::remove_file(&p).with_context(|| format!("Failed to remove {}", p.display()))?;
+    }
+
+    Ok(())
+}
+
+fn leftover_path(config: &OmaConfig, conffile: &Conffile, suffixes: &[&str]) -> Option<PathBuf> {
+    let real_path = conffile.real_path(&config.sysroot);
+
+    suffixes
+        .iter()
+        .map(|s| with_suffix(&real_path, s))
+        .find(|p| p.is_file())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut p = path.to_path_buf().into_os_string();
+    p.push(suffix);
+    PathBuf::from(p)
+}
+
+/// 临时文件，离开作用域时删除
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(content: &[u8]) -> Result<Self, OutputError> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "oma-conffile-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        // 以 root 运行时不能跟随他人预先放置的符号链接
+        let mut f = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+
+        let tmp = Self(path);
+
+        f.write_all(content)
+            .with_context(|| format!("Failed to write {}", tmp.0.display()))?;
+
+        Ok(tmp)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
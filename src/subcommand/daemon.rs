@@ -0,0 +1,463 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+};
+
+use anyhow::Context;
+use clap::Args;
+use oma_history::DATABASE_PATH;
+use oma_pm::{
+    apt::{OmaApt, OmaAptArgs, Upgrade as AptUpgrade},
+    matches::{GetArchMethod, PackagesMatcher, SearchEngine},
+    sort::SummarySort,
+};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use spdlog::{debug, info};
+use zbus::{
+    Connection, connection, fdo, interface,
+    message::Header,
+    object_server::SignalEmitter,
+    proxy,
+    zvariant::{Array, Dict, OwnedValue, Signature, Value},
+};
+
+use crate::{
+    RT,
+    args::CliExecuter,
+    config::OmaConfig,
+    config_file::SearchEngine as ConfigSearchEngine,
+    core::{commit_changes::CommitChanges, refresh::Refresh},
+    error::{Chain, OutputError},
+    exit_handle::{ExitHandle, ExitStatus},
+    fl,
+    history::Undo,
+    json_events::{self, JsonEvent},
+    root::root,
+    search::search,
+};
+
+use super::utils::{handle_no_result, lock_oma};
+
+const DAEMON_SERVICE: &str = "io.aosc.OmaDaemon";
+const DAEMON_PATH: &str = "/io/aosc/OmaDaemon";
+
+/// polkit actions, see `data/policykit/io.aosc.oma.apply.policy`.
+const REFRESH_ACTION: &str = "io.aosc.oma.refresh";
+const APPLY_ACTION: &str = "io.aosc.oma.apply.run";
+
+/// `AllowUserInteraction` flag of `CheckAuthorization`.
+const POLKIT_ALLOW_USER_INTERACTION: u32 = 1;
+
+#[derive(Debug, Args)]
+pub struct Daemon;
+
+impl CliExecuter for Daemon {
+    fn execute(self, mut config: OmaConfig) -> Result<ExitHandle, OutputError> {
+        root()?;
+
+        // 守护进程无法进行交互，所有提示都按 --json-events 的方式自动回答，事件则以 D-Bus 信号发出
+        config.json_events = true;
+
+        let (tx, rx) = flume::unbounded::<String>();
+
+        json_events::init_with(move |event| {
+            tx.send(event.to_string()).ok();
+        });
+
+        RT.block_on(serve(config, rx))
+    }
+}
+
+async fn serve(
+    config: OmaConfig,
+    events: flume::Receiver<String>,
+) -> Result<ExitHandle, OutputError> {
+    let conn = connection::Builder::system()
+        .and_then(|b| b.name(DAEMON_SERVICE))
+        .and_then(|b| {
+            b.serve_at(
+                DAEMON_PATH,
+                OmaDaemon {
+                    config: Arc::new(config),
+                    busy: AtomicBool::new(false),
+                },
+            )
+        })
+        .context("Failed to set up D-Bus service")?
+        .build()
+        .await
+        .context("Failed to connect to system bus")?;
+
+    let iface = conn
+        .object_server()
+        .interface::<_, OmaDaemon>(DAEMON_PATH)
+        .await
+        .context("Failed to get D-Bus interface")?;
+
+    info!("{}", fl!("daemon-started", name = DAEMON_SERVICE));
+
+    while let Ok(event) = events.recv_async().await {
+        if let Err(e) = OmaDaemon::event(iface.signal_emitter(), &event).await {
+            debug!("Failed to emit event signal: {}", e);
+        }
+    }
+
+    Ok(ExitHandle::default())
+}
+
+struct OmaDaemon {
+    config: Arc<OmaConfig>,
+    busy: AtomicBool,
+}
+
+#[interface(name = "io.aosc.OmaDaemon1")]
+impl OmaDaemon {
+    async fn refresh(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] conn: &Connection,
+    ) -> fdo::Result<()> {
+        check_authorization(conn, &header, REFRESH_ACTION).await?;
+
+        self.run_exclusive(|config| Refresh::builder().config(config).build().run())
+            .await
+    }
+
+    /// Read-only, so it does not take the busy flag and may see the state before a running commit.
+    async fn search(&self, query: String) -> fdo::Result<OwnedValue> {
+        let res = self
+            .run(move |config| {
+                // 拆分出查询中的过滤条件
+                let terms = query
+                    .split_whitespace()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>();
+
+                search(
+                    &terms,
+                    match config.search_engine {
+                        ConfigSearchEngine::Indicium => SearchEngine::Indicium(Box::new(|_| {})),
+                        ConfigSearchEngine::StrSim => SearchEngine::Strsim,
+                        ConfigSearchEngine::Text => SearchEngine::Text,
+                    },
+                    config,
+                )
+            })
+            .await?;
+
+        to_variant(&res)
+    }
+
+    /// Only resolves in memory and never writes, so unprivileged clients may call it without
+    /// blocking `commit`, `refresh` or `undo`.
+    async fn resolve_transaction(
+        &self,
+        install: Vec<String>,
+        remove: Vec<String>,
+        upgrade: bool,
+    ) -> fdo::Result<OwnedValue> {
+        let op = self
+            .run(move |config| {
+                let mut apt = OmaApt::new(vec![], oma_apt_args(config), config.dry_run)?;
+                mark_changes(&mut apt, config, &install, &remove, upgrade)?;
+                apt.resolve(true, false)?;
+
+                let op = apt.build_transaction(
+                    SummarySort::default().names().operation(),
+                    |_| false,
+                    |_| false,
+                )?;
+
+                Ok(op)
+            })
+            .await?;
+
+        to_variant(&op)
+    }
+
+    async fn commit(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] conn: &Connection,
+        install: Vec<String>,
+        remove: Vec<String>,
+        upgrade: bool,
+    ) -> fdo::Result<()> {
+        check_authorization(conn, &header, APPLY_ACTION).await?;
+
+        self.run_exclusive(move |config| {
+            let _lock_fd = lock_oma(&config.sysroot)?;
+
+            let mut apt = OmaApt::new(vec![], oma_apt_args(config), config.dry_run)?;
+            mark_changes(&mut apt, config, &install, &remove, upgrade)?;
+
+            let exit = CommitChanges::builder()
+                .apt(apt)
+                .yes(true)
+                .check_tum(upgrade)
+                .is_upgrade(upgrade)
+                .config(config)
+                .no_clean(false)
+                .build()
+                .run()?;
+
+            check_exit(exit)
+        })
+        .await
+    }
+
+    /// Read-only, SQLite serializes it against the history writes of a running commit.
+    async fn list_history(&self) -> fdo::Result<OwnedValue> {
+        let list = self
+            .run(|config| {
+                let history = oma_history::History::new(
+                    config.sysroot.join(DATABASE_PATH),
+                    false,
+                    config.dry_run,
+                )?;
+
+                Ok(history.list()?)
+            })
+            .await?;
+
+        to_variant(&list)
+    }
+
+    async fn undo(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] conn: &Connection,
+        id: i64,
+    ) -> fdo::Result<()> {
+        check_authorization(conn, &header, APPLY_ACTION).await?;
+
+        self.run_exclusive(move |config| check_exit(Undo::non_interactive(id).run(config)?))
+            .await
+    }
+
+    /// Same as the events printed by `--json-events`.
+    #[zbus(signal)]
+    async fn event(emitter: &SignalEmitter<'_>, event: &str) -> zbus::Result<()>;
+}
+
+impl OmaDaemon {
+    /// Run `f` in a new thread, as libapt and the refresh/commit routines are blocking.
+    ///
+    /// Read-only jobs use this directly and are not ordered against [`Self::run_exclusive`] jobs:
+    /// they never write, so the worst case is a result from before a running commit finishes.
+    async fn run<T, F>(&self, f: F) -> fdo::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&OmaConfig) -> Result<T, OutputError> + Send + 'static,
+    {
+        let config = self.config.clone();
+        let (tx, rx) = flume::bounded(1);
+
+        thread::spawn(move || {
+            tx.send(f(&config).map_err(to_dbus_error)).ok();
+        });
+
+        rx.recv_async()
+            .await
+            .unwrap_or_else(|_| Err(fdo::Error::Failed("oma daemon job panicked".to_string())))
+    }
+
+    /// Like [`Self::run`], but refuses to start while another job touching the package database is running.
+    async fn run_exclusive<T, F>(&self, f: F) -> fdo::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&OmaConfig) -> Result<T, OutputError> + Send + 'static,
+    {
+        if self.busy.swap(true, Ordering::AcqRel) {
+            return Err(fdo::Error::Failed(fl!("daemon-busy")));
+        }
+
+        let res = self.run(f).await;
+        self.busy.store(false, Ordering::Release);
+
+        res
+    }
+}
+
+#[proxy(
+    interface = "org.freedesktop.PolicyKit1.Authority",
+    default_service = "org.freedesktop.PolicyKit1",
+    default_path = "/org/freedesktop/PolicyKit1/Authority"
+)]
+trait PolkitAuthority {
+    async fn check_authorization(
+        &self,
+        subject: &(&str, HashMap<&str, Value<'_>>),
+        action_id: &str,
+        details: &HashMap<&str, &str>,
+        flags: u32,
+        cancellation_id: &str,
+    ) -> zbus::Result<(bool, bool, HashMap<String, String>)>;
+}
+
+async fn check_authorization(
+    conn: &Connection,
+    header: &Header<'_>,
+    action: &str,
+) -> fdo::Result<()> {
+    let Some(sender) = header.sender() else {
+        return Err(fdo::Error::AccessDenied(fl!(
+            "daemon-not-authorized",
+            action = action
+        )));
+    };
+
+    let subject = (
+        "system-bus-name",
+        HashMap::from([("name", Value::from(sender.as_str()))]),
+    );
+
+    let (authorized, _, _) = PolkitAuthorityProxy::new(conn)
+        .await?
+        .check_authorization(
+            &subject,
+            action,
+            &HashMap::new(),
+            POLKIT_ALLOW_USER_INTERACTION,
+            "",
+        )
+        .await?;
+
+    if !authorized {
+        debug!("{sender} is not authorized for {action}");
+        return Err(fdo::Error::AccessDenied(fl!(
+            "daemon-not-authorized",
+            action = action
+        )));
+    }
+
+    Ok(())
+}
+
+fn oma_apt_args(config: &OmaConfig) -> OmaAptArgs {
+    OmaAptArgs::builder()
+        .sysroot(config.sysroot.to_string_lossy().to_string())
+        .another_apt_options(&config.apt_options)
+        .yes(true)
+        .build()
+}
+
+fn mark_changes(
+    apt: &mut OmaApt,
+    config: &OmaConfig,
+    install: &[String],
+    remove: &[String],
+    upgrade: bool,
+) -> Result<(), OutputError> {
+    let matcher = PackagesMatcher::builder()
+        .cache(&apt.cache)
+        .filter_candidate(true)
+        .filter_downloadable_candidate(false)
+        .select_dbg(false)
+        .native_arch(GetArchMethod::SpecifySysroot(&config.sysroot))
+        .build();
+
+    let (install, no_result) =
+        matcher.match_pkgs_and_versions(install.iter().map(|x| x.as_str()).collect())?;
+
+    handle_no_result(no_result, true)?;
+
+    let mut delete = vec![];
+    let mut no_result = vec![];
+
+    for i in remove {
+        let res = matcher.match_pkgs_from_glob(i)?;
+        if res.is_empty() {
+            no_result.push(i.as_str());
+        } else {
+            delete.extend(res);
+        }
+    }
+
+    handle_no_result(no_result, true)?;
+
+    apt.install(&install, false)?;
+    apt.remove(delete, false, false)?;
+
+    if upgrade {
+        apt.upgrade(AptUpgrade::FullUpgrade)?;
+    }
+
+    Ok(())
+}
+
+fn check_exit(exit: ExitHandle) -> Result<(), OutputError> {
+    match exit.get_status() {
+        ExitStatus::Success => Ok(()),
+        status => Err(OutputError {
+            description: format!("oma exited with status {status:?}"),
+            source: None,
+        }),
+    }
+}
+
+fn to_dbus_error(e: OutputError) -> fdo::Error {
+    JsonEvent::from(&e).emit();
+
+    let mut msg = e.to_string();
+
+    for cause in Chain::new(&e).skip(1) {
+        msg.push_str(": ");
+        msg.push_str(&cause.to_string());
+    }
+
+    fdo::Error::Failed(msg)
+}
+
+/// Convert a serializable value to a D-Bus variant, with objects as `a{sv}` and arrays as `av`.
+fn to_variant(value: &impl Serialize) -> fdo::Result<OwnedValue> {
+    let json = serde_json::to_value(value).map_err(|e| fdo::Error::Failed(e.to_string()))?;
+
+    json_to_value(json)
+        .try_into()
+        .map_err(|e: zbus::zvariant::Error| fdo::Error::Failed(e.to_string()))
+}
+
+fn json_to_value(json: JsonValue) -> Value<'static> {
+    match json {
+        JsonValue::Bool(b) => Value::from(b),
+        JsonValue::Number(n) => {
+            if let Some(n) = n.as_i64() {
+                Value::from(n)
+            } else if let Some(n) = n.as_u64() {
+                Value::from(n)
+            } else {
+                Value::from(n.as_f64().unwrap_or_default())
+            }
+        }
+        JsonValue::String(s) => Value::from(s),
+        // D-Bus 没有空值，因此数组和字典中的 null 会被直接略过
+        JsonValue::Array(values) => {
+            let mut array = Array::new(&Signature::Variant);
+
+            for v in values.into_iter().filter(|v| !v.is_null()) {
+                array
+                    .append(Value::new(json_to_value(v)))
+                    .expect("Element signature is always `v`");
+            }
+
+            Value::from(array)
+        }
+        JsonValue::Object(map) => {
+            let mut dict = Dict::new(&Signature::Str, &Signature::Variant);
+
+            for (k, v) in map.into_iter().filter(|(_, v)| !v.is_null()) {
+                dict.add(k, json_to_value(v))
+                    .expect("Dict signature is always `a{sv}`");
+            }
+
+            Value::from(dict)
+        }
+        JsonValue::Null => Value::from(Dict::new(&Signature::Str, &Signature::Variant)),
+    }
+}
@@ -75,6 +75,10 @@ impl CliExecuter for Download {
             DownloadConfig {
                 network_thread: Some(config.download_threads),
                 download_dir: Some(path.clone()),
+                delta: false,
+                peers: config.lan_peers()?,
+                limits: config.network_limits,
+                redirector_client: Some(config.http_client_no_redirect()?.clone()),
             },
             download_message(),
             move |event| {
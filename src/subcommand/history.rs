@@ -1,33 +1,61 @@
 use anyhow::Context;
-use clap::Args;
+use clap::{Args, Subcommand};
+use dialoguer::Confirm;
+use dialoguer::theme::ColorfulTheme;
 use oma_history::DATABASE_PATH;
 use oma_pm::apt::{InstallOperation, OmaAptArgs};
 use oma_pm::matches::{GetArchMethod, PackagesMatcher};
 use oma_pm::oma_apt::PackageSort;
 use oma_pm::pkginfo::PtrIsNone;
+use oma_pm::snapshot::{RollbackStatus, SnapshotStage, backend_from_name};
 use oma_pm::{apt::OmaApt, pkginfo::OmaPackage};
 use ratatui::crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use ratatui::{TerminalOptions, Viewport};
-use spdlog::warn;
+use spdlog::{info, warn};
 use std::time::Duration;
 
-use crate::WRITER;
+use crate::args::HELP_TEMPLATE;
 use crate::config::OmaConfig;
 use crate::core::commit_changes::CommitChanges;
 use crate::core::refresh::Refresh;
 use crate::exit_handle::ExitHandle;
 use crate::exit_handle::ExitStatus;
 use crate::subcommand::history_tui::HistorySelectTui;
-use crate::{dbus::dbus_check, error::OutputError, fl, root::root};
+use crate::{WRITER, success};
+use crate::{dbus::dbus_check, error::OutputError, fl, json_events, root::root};
 
 use super::utils::{handle_no_result, lock_oma};
 use crate::args::CliExecuter;
 
 #[derive(Debug, Args)]
-pub struct History;
+pub struct History {
+    #[command(subcommand)]
+    history_subcmd: Option<HistorySubCmd>,
+}
+
+#[derive(Debug, Subcommand)]
+#[command(subcommand_help_heading = &**crate::args::HELP_HEADING)]
+pub enum HistorySubCmd {
+    /// Restore the system snapshot taken before a history entry
+    #[command(about = fl!("clap-history-rollback-help"))]
+    #[command(help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Rollback {
+        /// History entry ID to roll back
+        #[arg(help = fl!("clap-history-rollback-id-help"))]
+        id: i64,
+        /// Bypass confirmation prompts
+        #[arg(short, long, help = fl!("clap-yes-help"))]
+        yes: bool,
+    },
+}
 
 impl CliExecuter for History {
     fn execute(self, config: OmaConfig) -> Result<ExitHandle, OutputError> {
+        if let Some(HistorySubCmd::Rollback { id, yes }) = self.history_subcmd {
+            return rollback(id, yes, &config);
+        }
+
         let history =
             oma_history::History::new(config.sysroot.join(DATABASE_PATH), true, config.dry_run)?;
 
@@ -37,6 +65,74 @@ impl CliExecuter for History {
     }
 }
 
+fn rollback(id: i64, yes: bool, config: &OmaConfig) -> Result<ExitHandle, OutputError> {
+    root()?;
+
+    let _lock_fd = lock_oma(&config.sysroot)?;
+
+    let history =
+        oma_history::History::new(config.sysroot.join(DATABASE_PATH), true, config.dry_run)?;
+
+    let Some(snapshot) = history
+        .find_history_snapshots_by_id(id)?
+        .into_iter()
+        .find(|s| s.stage == SnapshotStage::Pre.as_str())
+    else {
+        return Err(OutputError {
+            description: fl!("history-rollback-no-snapshot", id = id),
+            source: None,
+        });
+    };
+
+    let backend = backend_from_name(&snapshot.backend).map_err(|e| OutputError {
+        description: fl!("failed-to-rollback-snapshot"),
+        source: Some(Box::new(e)),
+    })?;
+
+    if config.dry_run {
+        info!("Running in dry-run mode, Exit.");
+        return Ok(ExitHandle::default());
+    }
+
+    if !yes {
+        let confirm = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(fl!(
+                "history-rollback-confirm",
+                backend = snapshot.backend.as_str(),
+                snapshot = snapshot.snapshot_id.as_str(),
+                id = id
+            ))
+            .default(false)
+            .interact()
+            .map_err(|_| anyhow::anyhow!(""))?;
+
+        if !confirm {
+            return Ok(ExitHandle::default().status(ExitStatus::Other(1)));
+        }
+    }
+
+    let status = backend
+        .rollback(&config.sysroot, &snapshot.snapshot_id)
+        .map_err(|e| OutputError {
+            description: fl!("failed-to-rollback-snapshot"),
+            source: Some(Box::new(e)),
+        })?;
+
+    success!(
+        "{}",
+        fl!(
+            "history-rollback-success",
+            snapshot = snapshot.snapshot_id.as_str()
+        )
+    );
+
+    if status == RollbackStatus::RebootRequired {
+        info!("{}", fl!("history-rollback-reboot"));
+    }
+
+    Ok(ExitHandle::default())
+}
+
 fn tui(history: &oma_history::History, undo: bool) -> Result<Option<i64>, OutputError> {
     let tui = HistorySelectTui::new(history, undo)?;
     enable_raw_mode().context("Failed to enable termin raw mode")?;
@@ -68,6 +164,12 @@ fn tui(history: &oma_history::History, undo: bool) -> Result<Option<i64>, Output
 
 #[derive(Debug, Args)]
 pub struct Undo {
+    /// History entry ID to undo
+    #[arg(help = fl!("clap-undo-id-help"))]
+    id: Option<i64>,
+    /// Bypass confirmation prompts
+    #[arg(short, long, help = fl!("clap-yes-help"))]
+    yes: bool,
     /// Do not fix apt broken status
     #[arg(long, help = fl!("clap-no-fixbroken-help"))]
     no_fixbroken: bool,
@@ -105,9 +207,35 @@ pub struct Undo {
 
 impl CliExecuter for Undo {
     fn execute(self, config: OmaConfig) -> Result<ExitHandle, OutputError> {
+        self.run(&config)
+    }
+}
+
+impl Undo {
+    /// Undo the given history entry without asking, as used by `oma daemon`.
+    pub(crate) fn non_interactive(id: i64) -> Self {
+        Self {
+            id: Some(id),
+            yes: true,
+            no_fixbroken: false,
+            no_fix_dpkg_status: false,
+            force_unsafe_io: false,
+            force_yes: false,
+            force_confnew: false,
+            autoremove: false,
+            remove_config: false,
+            download_only: false,
+            no_refresh: false,
+            no_clean: false,
+        }
+    }
+
+    pub(crate) fn run(self, config: &OmaConfig) -> Result<ExitHandle, OutputError> {
         root()?;
 
         let Undo {
+            id,
+            yes,
             no_fixbroken,
             force_unsafe_io,
             force_yes,
@@ -122,13 +250,26 @@ impl CliExecuter for Undo {
 
         let _lock_fd = lock_oma(&config.sysroot)?;
 
-        let _fds = dbus_check(false, &config)?;
+        let _fds = dbus_check(yes, config)?;
 
         let history =
             oma_history::History::new(config.sysroot.join(DATABASE_PATH), true, config.dry_run)?;
 
-        let Some(id) = tui(&history, true)? else {
-            return Ok(ExitHandle::default().status(ExitStatus::Other(130)));
+        let id = match id {
+            Some(id) => id,
+            None if config.json_events => {
+                return Err(json_events::unanswered_prompt(
+                    "undo-select",
+                    &fl!("undo-detail"),
+                    "oma undo <ID>",
+                ));
+            }
+            None => {
+                let Some(id) = tui(&history, true)? else {
+                    return Ok(ExitHandle::default().status(ExitStatus::Other(130)));
+                };
+                id
+            }
         };
 
         let op = history.find_history_by_id(id)?;
@@ -137,7 +278,7 @@ impl CliExecuter for Undo {
         let (opt_in, opt_out) = history.find_history_topics_status_by_id(id)?;
 
         if !no_refresh {
-            Refresh::builder().config(&config).build().run()?;
+            Refresh::builder().config(config).build().run()?;
         }
 
         let no_progress = config.no_progress();
@@ -230,11 +371,11 @@ impl CliExecuter for Undo {
             .is_undo(true)
             .no_fixbroken(no_fixbroken)
             .fix_dpkg_status(!no_fix_dpkg_status)
-            .yes(false)
+            .yes(yes)
             .remove_config(remove_config)
             .autoremove(autoremove)
             .download_only(download_only)
-            .config(&config)
+            .config(config)
             .no_clean(no_clean)
             .build()
             .run()?;
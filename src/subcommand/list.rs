@@ -2,10 +2,12 @@ use std::{borrow::Cow, io::stdout, sync::atomic::Ordering};
 
 use clap::Args;
 use clap_complete::ArgValueCompleter;
+use oma_apt_pkg::{PackageFields, PackageStatus, Query};
 use oma_console::print::Action;
 use oma_pm::{
     apt::{OmaApt, OmaAptArgs},
-    oma_apt::{PackageSort, PkgCurrentState, PkgSelectedState},
+    oma_apt::{PackageSort, PkgCurrentState, PkgSelectedState, Version, records::RecordField},
+    pin::{CandidateReason, PinManager},
 };
 use spdlog::info;
 
@@ -64,12 +66,16 @@ impl CliExecuter for List {
             hold,
         } = self;
 
+        let query = Query::from_terms(&packages)?;
+        let packages = &query.keywords;
+
         let oma_apt_args = OmaAptArgs::builder()
             .sysroot(config.sysroot.to_string_lossy().to_string())
             .another_apt_options(&config.apt_options)
             .build();
 
         let apt = OmaApt::new(vec![], oma_apt_args, false)?;
+        let pins = PinManager::new(&config.sysroot, false).list()?;
 
         let mut sort = PackageSort::default();
 
@@ -102,7 +108,7 @@ impl CliExecuter for List {
             Box::new(filter_pkgs)
         } else {
             Box::new(filter_pkgs.filter(|x| {
-                for i in &packages {
+                for i in packages {
                     if glob_match::glob_match(i, x.name()) {
                         return true;
                     }
@@ -121,9 +127,8 @@ impl CliExecuter for List {
 
         for pkg in filter_pkgs {
             let name = pkg.fullname(true);
-            pkg_count += 1;
 
-            let versions = if all {
+            let versions: Vec<_> = if all {
                 pkg.versions().collect()
             } else {
                 if !packages.is_empty() {
@@ -153,6 +158,25 @@ impl CliExecuter for List {
                 }
             };
 
+            let status = if pkg.is_upgradable() {
+                PackageStatus::Upgrade
+            } else if pkg.is_installed() {
+                PackageStatus::Installed
+            } else {
+                PackageStatus::Avail
+            };
+
+            let versions = versions
+                .into_iter()
+                .filter(|v| version_matches(&query, v, status))
+                .collect::<Vec<_>>();
+
+            if versions.is_empty() {
+                continue;
+            }
+
+            pkg_count += 1;
+
             for version in &versions {
                 let mut branches = vec![];
 
@@ -213,6 +237,15 @@ impl CliExecuter for List {
                     status.push("residual-config")
                 }
 
+                let candidate_reason = pkg
+                    .candidate()
+                    .filter(|c| c.version() == version.version())
+                    .and_then(|_| CandidateReason::from_package(&pkg, &pins));
+
+                if matches!(candidate_reason, Some(CandidateReason::Pinned { .. })) {
+                    status.push("pinned");
+                }
+
                 if !json {
                     let s = if status.is_empty() {
                         Cow::Borrowed("")
@@ -245,6 +278,8 @@ impl CliExecuter for List {
                                 "new_version": new_version,
                                 "architecture": arch,
                                 "status": status,
+                                "pin_priority": version.priority(),
+                                "candidate_reason": candidate_reason,
                             }
                         ))
                         .ok();
@@ -259,3 +294,23 @@ impl CliExecuter for List {
         Ok(ExitHandle::default())
     }
 }
+
+/// Whether the version matches the filters of the query
+fn version_matches(query: &Query, version: &Version, status: PackageStatus) -> bool {
+    if !query.has_filters() {
+        return true;
+    }
+
+    let maintainer = version.get_record(RecordField::Maintainer);
+    let tag = version.get_record("Tag");
+
+    query.matches(&PackageFields {
+        section: version.section().ok(),
+        maintainer: maintainer.as_deref(),
+        tag: tag.as_deref(),
+        architecture: Some(version.arch()),
+        priority: version.priority_str().ok(),
+        installed_size: Some(version.installed_size()),
+        status,
+    })
+}
@@ -0,0 +1,530 @@
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fs,
+    io::{Write, stdout},
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use oma_pm::{
+    apt::{OmaApt, OmaAptArgs, OmaAptError},
+    matches::{GetArchMethod, PackagesMatcher},
+    oma_apt::{PackageSort, PkgSelectedState},
+    pkginfo::OmaPackage,
+};
+use serde::{Deserialize, Serialize};
+use spdlog::{info, warn};
+
+use crate::{
+    config::OmaConfig,
+    core::{commit_changes::CommitChanges, refresh::Refresh},
+    dbus::dbus_check,
+    error::OutputError,
+    exit_handle::{ExitHandle, ExitStatus},
+    fl,
+    root::root,
+    success,
+};
+
+use super::utils::{handle_no_result, lock_oma};
+use crate::args::CliExecuter;
+
+/// Declarative description of the packages on a system, used by `oma apply` and `oma export`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Manifest {
+    /// Package(s) that must be installed.
+    pub packages: BTreeMap<String, PackageSpec>,
+    /// Package(s) (or globs) that must not be installed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub forbidden: Vec<String>,
+    /// Package(s) that must be held at their installed version.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub held: Vec<String>,
+    #[cfg(feature = "aosc")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub topics: Vec<String>,
+    #[cfg(feature = "aosc")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>,
+}
+
+/// `foo = "*"`, `foo = ">= 1.0"` or `foo = { version = ">= 1.0", branch = "stable" }`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum PackageSpec {
+    Version(String),
+    Detailed {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        version: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        branch: Option<String>,
+    },
+}
+
+impl PackageSpec {
+    fn version(&self) -> Option<&str> {
+        match self {
+            PackageSpec::Version(v) => Some(v.as_str()),
+            PackageSpec::Detailed { version, .. } => version.as_deref(),
+        }
+        .filter(|v| v.trim() != "*")
+    }
+
+    fn branch(&self) -> Option<&str> {
+        match self {
+            PackageSpec::Version(_) => None,
+            PackageSpec::Detailed { branch, .. } => branch.as_deref(),
+        }
+    }
+}
+
+/// Version constraint like `>= 1.0`, a bare version means `= version`.
+#[derive(Debug, PartialEq, Eq)]
+struct VersionReq {
+    op: &'static str,
+    version: debversion::Version,
+}
+
+impl VersionReq {
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+
+        let (op, ver) = [">=", "<=", ">>", "<<", "=", ">", "<"]
+            .into_iter()
+            .find_map(|op| s.strip_prefix(op).map(|v| (op, v)))
+            .unwrap_or(("=", s));
+
+        Some(Self {
+            op,
+            version: ver.trim().parse().ok()?,
+        })
+    }
+
+    fn matches(&self, version: &str) -> bool {
+        let Ok(version) = version.parse::<debversion::Version>() else {
+            return false;
+        };
+
+        let cmp = version.cmp(&self.version);
+
+        match self.op {
+            ">=" => cmp != Ordering::Less,
+            "<=" => cmp != Ordering::Greater,
+            ">>" | ">" => cmp == Ordering::Greater,
+            "<<" | "<" => cmp == Ordering::Less,
+            _ => cmp == Ordering::Equal,
+        }
+    }
+}
+
+impl Manifest {
+    fn from_path(path: &Path) -> Result<Self, OutputError> {
+        let s = fs::read_to_string(path).map_err(|e| OutputError {
+            description: fl!("failed-to-operate-path", p = path.display().to_string()),
+            source: Some(Box::new(e)),
+        })?;
+
+        toml::from_str(&s).map_err(|e| OutputError {
+            description: fl!("manifest-bad-format", path = path.display().to_string()),
+            source: Some(Box::new(e)),
+        })
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct Apply {
+    /// Path to the manifest
+    #[arg(required = true, help = fl!("clap-apply-manifest-help"))]
+    #[arg(help_heading = &**crate::args::ARG_HELP_HEADING_MUST)]
+    manifest: PathBuf,
+    /// Bypass confirmation prompts
+    #[arg(short, long, help = fl!("clap-yes-help"))]
+    yes: bool,
+    /// Do not refresh repository metadata
+    #[arg(long, help = fl!("clap-no-refresh-help"))]
+    no_refresh: bool,
+    /// Install package(s) without fsync(2)
+    #[arg(long, help = &**crate::args::FORCE_UNSAFE_IO_TRANSLATE)]
+    force_unsafe_io: bool,
+    /// Ignore repository and package dependency issues
+    #[arg(long, help = fl!("clap-force-yes-help"))]
+    force_yes: bool,
+    /// Replace configuration file(s) in the system those shipped in the package(s) to be installed (invokes `dpkg --force-confnew`)
+    #[arg(long, help = fl!("clap-force-confnew-help"))]
+    force_confnew: bool,
+    /// Auto remove unnecessary package(s)
+    #[arg(long, help = fl!("clap-autoremove-help"))]
+    autoremove: bool,
+    /// Remove package(s) also remove configuration file(s), like apt purge
+    #[arg(long, visible_alias = "purge", help = fl!("clap-remove-config-help"))]
+    remove_config: bool,
+    /// Do not clean local package cache
+    #[arg(long, help = fl!("clap-noclean-help"), env = "OMA_NO_CLEAN", value_parser = clap::builder::FalseyValueParser::new())]
+    no_clean: bool,
+}
+
+impl CliExecuter for Apply {
+    fn execute(self, config: OmaConfig) -> Result<ExitHandle, OutputError> {
+        let Apply {
+            manifest,
+            yes,
+            no_refresh,
+            force_unsafe_io,
+            force_yes,
+            force_confnew,
+            autoremove,
+            remove_config,
+            no_clean,
+        } = self;
+
+        let manifest = Manifest::from_path(&manifest)?;
+
+        let _lock_fd = if !config.dry_run {
+            root()?;
+            Some(lock_oma(&config.sysroot)?)
+        } else {
+            None
+        };
+
+        let _fds = dbus_check(yes, &config)?;
+
+        if yes {
+            warn!("{}", fl!("automatic-mode-warn"));
+        }
+
+        #[cfg(feature = "aosc")]
+        let topics_enabled = apply_sources(&manifest, &config)?;
+        #[cfg(not(feature = "aosc"))]
+        let topics_enabled = vec![];
+
+        if !no_refresh {
+            Refresh::builder().config(&config).build().run()?;
+        }
+
+        let oma_apt_args = OmaAptArgs::builder()
+            .sysroot(config.sysroot.to_string_lossy().to_string())
+            .yes(yes)
+            .force_yes(force_yes)
+            .dpkg_force_confnew(force_confnew)
+            .another_apt_options(&config.apt_options)
+            .dpkg_force_unsafe_io(force_unsafe_io)
+            .build();
+
+        let mut apt = OmaApt::new(vec![], oma_apt_args, config.dry_run)?;
+
+        let matcher = PackagesMatcher::builder()
+            .cache(&apt.cache)
+            .filter_candidate(false)
+            .filter_downloadable_candidate(false)
+            .select_dbg(false)
+            .native_arch(GetArchMethod::SpecifySysroot(&config.sysroot))
+            .build();
+
+        let mut install = vec![];
+        let mut no_result = vec![];
+
+        for (name, spec) in &manifest.packages {
+            let Some(pkg) = apt.cache.get(name) else {
+                no_result.push(name.as_str());
+                continue;
+            };
+
+            let req = match spec.version() {
+                Some(v) => Some(VersionReq::parse(v).ok_or_else(|| OutputError {
+                    description: fl!("manifest-bad-version", name = name.as_str(), version = v),
+                    source: None,
+                })?),
+                None => None,
+            };
+
+            // 已安装的版本满足要求时无需改动
+            if let Some(installed) = pkg.installed()
+                && req.as_ref().is_none_or(|r| r.matches(installed.version()))
+            {
+                continue;
+            }
+
+            let mut versions = match spec.branch() {
+                Some(branch) => matcher.match_from_branch(&format!("{name}/{branch}"))?,
+                None => pkg
+                    .versions()
+                    .map(|v| OmaPackage::new(&v, &pkg))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(OmaAptError::from)?,
+            };
+
+            versions.retain(|v| {
+                req.as_ref()
+                    .is_none_or(|r| r.matches(v.version(&apt.cache).version()))
+            });
+
+            // 优先使用满足要求的候选版本，否则使用满足要求的最高版本
+            let selected = match versions
+                .iter()
+                .position(|v| v.is_candidate_version(&apt.cache))
+            {
+                Some(idx) => Some(versions.swap_remove(idx)),
+                None => versions.into_iter().max_by(|a, b| {
+                    oma_pm::oma_apt::util::cmp_versions(
+                        a.version(&apt.cache).version(),
+                        b.version(&apt.cache).version(),
+                    )
+                    .unwrap_or(Ordering::Equal)
+                }),
+            };
+
+            match selected {
+                Some(v) => install.push(v),
+                None => no_result.push(name.as_str()),
+            }
+        }
+
+        handle_no_result(no_result, config.no_progress())?;
+
+        let mut remove = vec![];
+
+        for pat in &manifest.forbidden {
+            remove.extend(
+                matcher
+                    .match_pkgs_from_glob(pat)?
+                    .into_iter()
+                    .filter(|p| p.package(&apt.cache).is_installed()),
+            );
+        }
+
+        apt.install(&install, false)?;
+        apt.remove(remove, remove_config, !autoremove)?;
+
+        let exit = CommitChanges::builder()
+            .apt(apt)
+            .yes(yes)
+            .remove_config(remove_config)
+            .autoremove(autoremove)
+            .topics_enabled(topics_enabled)
+            .config(&config)
+            .no_clean(no_clean)
+            .build()
+            .run()?;
+
+        if exit.get_status() == ExitStatus::Success && !manifest.held.is_empty() {
+            let oma_apt_args = OmaAptArgs::builder()
+                .sysroot(config.sysroot.to_string_lossy().to_string())
+                .another_apt_options(&config.apt_options)
+                .build();
+
+            let apt = OmaApt::new(vec![], oma_apt_args, false)?;
+
+            // dry-run 模式下需要保持的软件包可能尚未安装
+            let held = manifest
+                .held
+                .into_iter()
+                .filter(|p| apt.cache.get(p).is_some_and(|p| p.is_installed()))
+                .collect::<Vec<_>>();
+
+            for (pkg, is_set) in apt.mark_version_status(&held, true, config.dry_run)? {
+                if is_set {
+                    success!("{}", fl!("set-to-hold", name = pkg));
+                }
+            }
+        }
+
+        Ok(exit)
+    }
+}
+
+/// 按照清单启用测试源及镜像源，须在刷新软件源之前进行，返回新启用的测试源
+#[cfg(feature = "aosc")]
+fn apply_sources(manifest: &Manifest, config: &OmaConfig) -> Result<Vec<String>, OutputError> {
+    use oma_mirror::MirrorManager;
+    use oma_topics::TopicManager;
+
+    if !manifest.mirrors.is_empty() {
+        let mut mm = MirrorManager::new(&config.sysroot)?;
+        mm.set(
+            &manifest
+                .mirrors
+                .iter()
+                .map(|m| m.as_str())
+                .collect::<Vec<_>>(),
+        )?;
+
+        if !config.dry_run {
+            mm.write_status(Some(&fl!("do-not-edit-topic-sources-list")))?;
+        }
+    }
+
+    if manifest.topics.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let arch = oma_utils::dpkg::dpkg_arch(&config.sysroot)?;
+    let mut tm = TopicManager::new(
+        config.http_client()?.clone(),
+        &config.sysroot,
+        arch,
+        config.dry_run,
+    )?;
+
+    tm.refresh()?;
+
+    let mut enabled = vec![];
+
+    for topic in &manifest.topics {
+        if tm.enabled_topics().iter().any(|t| &t.name == topic) {
+            continue;
+        }
+
+        tm.add(topic)?;
+        enabled.push(topic.clone());
+    }
+
+    tm.write_enabled(false)?;
+    tm.write_sources_list(
+        &fl!("do-not-edit-topic-sources-list"),
+        false,
+        |topic, mirror| {
+            warn!(
+                "{}",
+                fl!("topic-not-in-mirror", topic = topic, mirror = mirror)
+            );
+            warn!("{}", fl!("skip-write-mirror"));
+        },
+    )?;
+
+    Ok(enabled)
+}
+
+#[derive(Debug, Args)]
+pub struct Export {
+    /// Write the manifest to a file instead of stdout
+    #[arg(short, long, help = fl!("clap-export-output-help"))]
+    output: Option<PathBuf>,
+    /// Require the currently installed version of each package
+    #[arg(long, help = fl!("clap-export-with-version-help"))]
+    with_version: bool,
+}
+
+impl CliExecuter for Export {
+    fn execute(self, config: OmaConfig) -> Result<ExitHandle, OutputError> {
+        let Export {
+            output,
+            with_version,
+        } = self;
+
+        let oma_apt_args = OmaAptArgs::builder()
+            .sysroot(config.sysroot.to_string_lossy().to_string())
+            .another_apt_options(&config.apt_options)
+            .build();
+
+        let apt = OmaApt::new(vec![], oma_apt_args, false)?;
+
+        let mut manifest = Manifest::default();
+
+        for pkg in apt.cache.packages(&PackageSort::default().installed()) {
+            if pkg.selected_state() == PkgSelectedState::Hold {
+                manifest.held.push(pkg.fullname(true));
+            }
+
+            if pkg.is_auto_installed() {
+                continue;
+            }
+
+            let version = match pkg.installed() {
+                Some(v) if with_version => format!("= {}", v.version()),
+                _ => "*".to_string(),
+            };
+
+            manifest
+                .packages
+                .insert(pkg.fullname(true), PackageSpec::Version(version));
+        }
+
+        #[cfg(feature = "aosc")]
+        {
+            let arch = oma_utils::dpkg::dpkg_arch(&config.sysroot)?;
+            let tm = oma_topics::TopicManager::new(
+                config.http_client()?.clone(),
+                &config.sysroot,
+                arch,
+                true,
+            )?;
+
+            manifest.topics = tm.enabled_topics().iter().map(|t| t.name.clone()).collect();
+
+            let mm = oma_mirror::MirrorManager::new(&config.sysroot)?;
+            manifest.mirrors = mm.enabled_mirrors().keys().map(|m| m.to_string()).collect();
+        }
+
+        let s = toml::to_string_pretty(&manifest).map_err(|e| OutputError {
+            description: e.to_string(),
+            source: None,
+        })?;
+
+        match output {
+            Some(path) => {
+                fs::write(&path, s).map_err(|e| OutputError {
+                    description: fl!("failed-to-operate-path", p = path.display().to_string()),
+                    source: Some(Box::new(e)),
+                })?;
+
+                info!(
+                    "{}",
+                    fl!(
+                        "manifest-exported",
+                        len = manifest.packages.len(),
+                        path = path.display().to_string()
+                    )
+                );
+            }
+            None => {
+                stdout().write_all(s.as_bytes()).map_err(|e| OutputError {
+                    description: e.to_string(),
+                    source: None,
+                })?;
+            }
+        }
+
+        Ok(ExitHandle::default())
+    }
+}
+
+#[test]
+fn test_version_req() {
+    let req = VersionReq::parse(">= 1.0").unwrap();
+    assert!(req.matches("1.0"));
+    assert!(req.matches("1:0.1"));
+    assert!(!req.matches("0.9"));
+
+    let req = VersionReq::parse("1.2-1").unwrap();
+    assert_eq!(req.op, "=");
+    assert!(req.matches("1.2-1"));
+    assert!(!req.matches("1.2-2"));
+
+    let req = VersionReq::parse("<<2").unwrap();
+    assert!(req.matches("1.9"));
+    assert!(!req.matches("2"));
+}
+
+#[test]
+fn test_parse_manifest() {
+    let manifest: Manifest = toml::from_str(
+        r#"
+forbidden = ["telnet*"]
+held = ["linux-kernel"]
+
+[packages]
+vim = "*"
+gcc = ">= 13"
+llvm = { version = "= 18.1.8", branch = "stable" }
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(manifest.packages["vim"].version(), None);
+    assert_eq!(manifest.packages["gcc"].version(), Some(">= 13"));
+    assert_eq!(manifest.packages["llvm"].branch(), Some("stable"));
+    assert_eq!(manifest.forbidden, ["telnet*"]);
+    assert_eq!(manifest.held, ["linux-kernel"]);
+}
@@ -1,6 +1,12 @@
+#[cfg(feature = "aosc")]
+pub mod advisories;
+pub mod build_dep;
+pub mod bundle;
 pub mod clean;
 pub mod command_not_found;
+pub mod conffiles;
 pub mod contents_find;
+pub mod daemon;
 pub mod depends;
 pub mod download;
 pub mod fix_broken;
@@ -9,18 +15,24 @@ pub mod history;
 mod history_tui;
 pub mod install;
 pub mod list;
+pub mod manifest;
 pub mod mark;
 #[cfg(feature = "aosc")]
 pub mod mirror;
 pub mod pick;
+pub mod pin;
 pub mod rdepends;
 pub mod refresh;
 pub mod remove;
+pub mod repo;
 pub mod search;
+pub mod serve_cache;
 pub mod show;
 pub mod size_analyzer;
 #[cfg(feature = "aosc")]
 pub mod topics;
 pub mod tree;
+mod unattended;
 pub mod upgrade;
 pub mod utils;
+pub mod verify;
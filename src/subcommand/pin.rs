@@ -0,0 +1,142 @@
+use std::io::stdout;
+
+use clap::{Args, Subcommand};
+use clap_complete::ArgValueCompleter;
+use oma_console::print::Action;
+use oma_pm::pin::{Pin as PinEntry, PinManager, PinTarget};
+use spdlog::info;
+use tabled::Tabled;
+
+use crate::args::{CliExecuter, HELP_TEMPLATE};
+use crate::completions::pkgnames_completions;
+use crate::config::OmaConfig;
+use crate::error::OutputError;
+use crate::exit_handle::ExitHandle;
+use crate::root::root;
+use crate::table::PagerPrinter;
+use crate::{color_formatter, fl, success};
+
+#[derive(Debug, Args)]
+pub struct Pin {
+    #[command(subcommand)]
+    pin_subcmd: PinSubCmd,
+}
+
+#[derive(Debug, Subcommand)]
+#[command(subcommand_help_heading = &**crate::args::HELP_HEADING)]
+pub enum PinSubCmd {
+    #[command(about = fl!("clap-pin-add-help"))]
+    #[command(help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Add {
+        #[arg(add = ArgValueCompleter::new(pkgnames_completions), help = fl!("clap-pin-package-help"))]
+        #[arg(help_heading = &**crate::args::ARG_HELP_HEADING_MUST)]
+        package: String,
+        #[arg(help = fl!("clap-pin-target-help"), long_help = fl!("clap-pin-target-long-help"))]
+        #[arg(help_heading = &**crate::args::ARG_HELP_HEADING_MUST)]
+        pin: PinTarget,
+        #[arg(allow_negative_numbers = true, help = fl!("clap-pin-priority-help"))]
+        #[arg(help_heading = &**crate::args::ARG_HELP_HEADING_MUST)]
+        priority: i32,
+    },
+    #[command(about = fl!("clap-pin-remove-help"))]
+    #[command(help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Remove {
+        #[arg(required = true, help = fl!("clap-pin-package-help"))]
+        #[arg(help_heading = &**crate::args::ARG_HELP_HEADING_MUST)]
+        packages: Vec<String>,
+    },
+    #[command(about = fl!("clap-pin-list-help"))]
+    #[command(help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    List,
+}
+
+#[derive(Debug, Tabled)]
+struct PinDisplay {
+    package: String,
+    pin: String,
+    priority: i32,
+}
+
+impl CliExecuter for Pin {
+    fn execute(self, config: OmaConfig) -> Result<ExitHandle, OutputError> {
+        let pm = PinManager::new(&config.sysroot, config.dry_run);
+
+        match self.pin_subcmd {
+            PinSubCmd::Add {
+                package,
+                pin,
+                priority,
+            } => {
+                if !config.dry_run {
+                    root()?;
+                }
+
+                pm.add(&PinEntry {
+                    package: package.clone(),
+                    target: pin,
+                    priority,
+                })?;
+
+                success!(
+                    "{}",
+                    fl!(
+                        "pin-added",
+                        name = color_formatter()
+                            .color_str(&package, Action::Emphasis)
+                            .to_string(),
+                        priority = priority
+                    )
+                );
+            }
+            PinSubCmd::Remove { packages } => {
+                if !config.dry_run {
+                    root()?;
+                }
+
+                for package in packages {
+                    let name = color_formatter()
+                        .color_str(&package, Action::Emphasis)
+                        .to_string();
+
+                    if pm.remove(&package)? {
+                        success!("{}", fl!("pin-removed", name = name));
+                    } else {
+                        info!("{}", fl!("pin-not-found", name = name));
+                    }
+                }
+            }
+            PinSubCmd::List => {
+                let pins = pm.list()?;
+
+                if pins.is_empty() {
+                    info!("{}", fl!("pin-list-empty"));
+                    return Ok(ExitHandle::default());
+                }
+
+                let mut printer = PagerPrinter::new(stdout());
+
+                printer
+                    .print_table(
+                        pins.into_iter().map(|p| PinDisplay {
+                            package: p.package,
+                            pin: p.target.to_string(),
+                            priority: p.priority,
+                        }),
+                        vec![
+                            &fl!("pin-package"),
+                            &fl!("pin-target"),
+                            &fl!("pin-priority"),
+                        ],
+                        None,
+                        None,
+                    )
+                    .ok();
+            }
+        }
+
+        Ok(ExitHandle::default())
+    }
+}
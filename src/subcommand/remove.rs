@@ -241,14 +241,28 @@ fn check_is_current_kernel_deleting(
                 sysinfo::System::kernel_version().context("Failed to get kernel version")
             })?;
 
-        if oma_pm::utils::pkg_is_current_kernel(
+        if !oma_pm::utils::pkg_is_current_kernel(
             &config.sysroot,
             &image_name,
             pkg.raw_pkg.name(),
             current_kernel_ver,
-        ) && (config.protect_essentials
-            || !ask_user_delete_current_kernel(pkg.raw_pkg.name()).unwrap_or(false))
-        {
+        ) {
+            continue;
+        }
+
+        let delete = if config.protect_essentials {
+            false
+        } else if config.json_events {
+            crate::json_events::answer_prompt(
+                "delete-current-kernel",
+                &fl!("delete-current-kernel-tips", kernel = pkg.raw_pkg.name()),
+                false,
+            )
+        } else {
+            ask_user_delete_current_kernel(pkg.raw_pkg.name()).unwrap_or(false)
+        };
+
+        if !delete {
             return Err(OutputError {
                 description: fl!("not-allow-delete-using-kernel", ver = current_kernel_ver),
                 source: None,
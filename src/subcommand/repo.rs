@@ -0,0 +1,703 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use chrono::Utc;
+use clap::{Args, Subcommand};
+use deb822_lossless::{Deb822, Paragraph};
+use faster_hex::hex_string;
+use md5::Md5;
+use oma_contents::parser::parse_contents;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use sha2::{Digest, Sha256};
+use spdlog::{info, warn};
+
+use crate::args::{CliExecuter, HELP_TEMPLATE};
+use crate::config::OmaConfig;
+use crate::error::OutputError;
+use crate::exit_handle::ExitHandle;
+use crate::{fl, success};
+
+const PACKAGES: &str = "Packages";
+const RELEASE: &str = "Release";
+const IN_RELEASE: &str = "InRelease";
+const RELEASE_GPG: &str = "Release.gpg";
+const CONTENTS_PREFIX: &str = "Contents-";
+const ARCH_ALL: &str = "all";
+
+#[derive(Debug, Args)]
+pub struct Repo {
+    #[command(subcommand)]
+    repo_subcmd: RepoSubCmd,
+}
+
+#[derive(Debug, Subcommand)]
+#[command(subcommand_help_heading = &**crate::args::HELP_HEADING)]
+pub enum RepoSubCmd {
+    /// Create a flat repository from package archive(s) in a directory
+    #[command(about = fl!("clap-repo-create-help"))]
+    #[command(help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    Create {
+        /// Repository directory
+        #[arg(help = fl!("clap-repo-dir-help"))]
+        #[arg(help_heading = &**crate::args::ARG_HELP_HEADING_MUST)]
+        dir: PathBuf,
+        /// OpenPGP secret key file used to sign the repository metadata
+        #[arg(long, help = fl!("clap-repo-sign-key-help"))]
+        sign_key: Option<PathBuf>,
+        /// Value of the `Origin' field in the Release file
+        #[arg(long, help = fl!("clap-repo-origin-help"))]
+        origin: Option<String>,
+    },
+    /// Add package archive(s) to a repository created by `oma repo create'
+    #[command(about = fl!("clap-repo-add-deb-help"))]
+    #[command(help_template = &*HELP_TEMPLATE)]
+    #[command(next_help_heading = &**crate::args::ARG_HELP_HEADING)]
+    AddDeb {
+        /// Repository directory
+        #[arg(help = fl!("clap-repo-dir-help"))]
+        #[arg(help_heading = &**crate::args::ARG_HELP_HEADING_MUST)]
+        dir: PathBuf,
+        /// Package archive(s) to add
+        #[arg(required = true, help = fl!("clap-repo-debs-help"))]
+        #[arg(help_heading = &**crate::args::ARG_HELP_HEADING_MUST)]
+        debs: Vec<PathBuf>,
+        /// OpenPGP secret key file used to sign the repository metadata
+        #[arg(long, help = fl!("clap-repo-sign-key-help"))]
+        sign_key: Option<PathBuf>,
+        /// Value of the `Origin' field in the Release file
+        #[arg(long, help = fl!("clap-repo-origin-help"))]
+        origin: Option<String>,
+    },
+}
+
+impl CliExecuter for Repo {
+    fn execute(self, _config: OmaConfig) -> Result<ExitHandle, OutputError> {
+        match self.repo_subcmd {
+            RepoSubCmd::Create {
+                dir,
+                sign_key,
+                origin,
+            } => create(&dir, sign_key.as_deref(), origin),
+            RepoSubCmd::AddDeb {
+                dir,
+                debs,
+                sign_key,
+                origin,
+            } => add_deb(&dir, &debs, sign_key.as_deref(), origin),
+        }
+    }
+}
+
+fn create(
+    dir: &Path,
+    sign_key: Option<&Path>,
+    origin: Option<String>,
+) -> Result<ExitHandle, OutputError> {
+    let mut debs = vec![];
+    find_debs(dir, &mut debs)?;
+    debs.sort();
+
+    let mut index = RepoIndex {
+        origin,
+        ..Default::default()
+    };
+
+    for deb in read_debs(dir, &debs)? {
+        index.add(deb)?;
+    }
+
+    index.write(dir, sign_key)?;
+
+    success!(
+        "{}",
+        fl!(
+            "repo-created",
+            len = index.packages.len(),
+            path = dir.display().to_string()
+        )
+    );
+
+    Ok(ExitHandle::default())
+}
+
+fn add_deb(
+    dir: &Path,
+    debs: &[PathBuf],
+    sign_key: Option<&Path>,
+    origin: Option<String>,
+) -> Result<ExitHandle, OutputError> {
+    let mut index = RepoIndex::load(dir)?;
+
+    if origin.is_some() {
+        index.origin = origin;
+    }
+
+    // 软件包放在仓库根目录下，已在仓库中的则原地更新索引
+    let mut targets = vec![];
+    for deb in debs {
+        let name = deb.file_name().ok_or_else(|| OutputError {
+            description: fl!("repo-bad-deb", path = deb.display().to_string()),
+            source: None,
+        })?;
+
+        let target = dir.join(name);
+
+        if !same_file(deb, &target) {
+            fs::copy(deb, &target).map_err(|e| OutputError {
+                description: fl!("failed-to-operate-path", p = target.display().to_string()),
+                source: Some(Box::new(e)),
+            })?;
+        }
+
+        targets.push(target);
+    }
+
+    for deb in read_debs(dir, &targets)? {
+        index.add(deb)?;
+    }
+
+    index.write(dir, sign_key)?;
+
+    success!(
+        "{}",
+        fl!(
+            "repo-updated",
+            len = targets.len(),
+            path = dir.display().to_string()
+        )
+    );
+
+    Ok(ExitHandle::default())
+}
+
+/// Metadata of a package archive in the repository
+struct DebInfo {
+    /// Path relative to the repository directory
+    filename: String,
+    control: String,
+    files: Vec<String>,
+    hashes: FileHashes,
+}
+
+struct FileHashes {
+    size: u64,
+    md5: String,
+    sha256: String,
+}
+
+#[derive(Default)]
+struct RepoIndex {
+    /// (Package, Version, Architecture) -> stanza
+    packages: BTreeMap<(String, String, String), String>,
+    /// Architecture -> path -> `section/package'
+    contents: BTreeMap<String, BTreeMap<String, BTreeSet<String>>>,
+    origin: Option<String>,
+}
+
+impl RepoIndex {
+    /// Load the existing index of a repository
+    fn load(dir: &Path) -> Result<Self, OutputError> {
+        let mut index = RepoIndex::default();
+
+        let packages = dir.join(PACKAGES);
+        let Some(s) = read_index(&packages)? else {
+            return Err(OutputError {
+                description: fl!("repo-not-exist", path = dir.display().to_string()),
+                source: None,
+            });
+        };
+
+        let deb822: Deb822 = s.parse().map_err(|e| OutputError {
+            description: fl!("repo-bad-index", path = packages.display().to_string()),
+            source: Some(Box::new(e)),
+        })?;
+
+        for p in deb822.paragraphs() {
+            let Some(key) = package_key(&p) else {
+                return Err(OutputError {
+                    description: fl!("repo-bad-index", path = packages.display().to_string()),
+                    source: None,
+                });
+            };
+
+            index.packages.insert(key, p.to_string());
+        }
+
+        let archs = index
+            .packages
+            .keys()
+            .map(|(_, _, arch)| arch.clone())
+            .collect::<BTreeSet<_>>();
+
+        for arch in archs {
+            let path = dir.join(format!("{CONTENTS_PREFIX}{arch}"));
+            let Some(s) = read_index(&path)? else {
+                continue;
+            };
+
+            let contents = parse_contents(&s).map_err(|e| OutputError {
+                description: fl!("repo-bad-index", path = path.display().to_string()),
+                source: Some(Box::new(e)),
+            })?;
+
+            let entry = index.contents.entry(arch).or_default();
+            for (file, pkgs) in contents {
+                entry
+                    .entry(file.to_string())
+                    .or_default()
+                    .extend(pkgs.into_iter().map(|p| p.to_string()));
+            }
+        }
+
+        if let Some(s) = read_index(&dir.join(RELEASE))?
+            && let Ok(release) = s.parse::<Paragraph>()
+        {
+            index.origin = release.get("Origin");
+        }
+
+        Ok(index)
+    }
+
+    fn add(&mut self, deb: DebInfo) -> Result<(), OutputError> {
+        let bad_deb = || OutputError {
+            description: fl!("repo-bad-deb", path = deb.filename.clone()),
+            source: None,
+        };
+
+        let mut p: Paragraph = deb.control.parse().map_err(|_| bad_deb())?;
+        let key = package_key(&p).ok_or_else(bad_deb)?;
+
+        p.set("Filename", &format!("./{}", deb.filename));
+        p.set("Size", &deb.hashes.size.to_string());
+        p.set("MD5sum", &deb.hashes.md5);
+        p.set("SHA256", &deb.hashes.sha256);
+
+        let section = p.get("Section").unwrap_or_else(|| "misc".to_string());
+        let qualified = format!("{section}/{}", key.0);
+
+        // Contents 只记录包名，先移除该软件包原有的文件，避免新版本中已删除的文件残留
+        let contents = self.contents.entry(key.2.clone()).or_default();
+        contents.retain(|_, pkgs| {
+            pkgs.retain(|p| p.rsplit('/').next() != Some(key.0.as_str()));
+            !pkgs.is_empty()
+        });
+
+        for file in deb.files {
+            contents.entry(file).or_default().insert(qualified.clone());
+        }
+
+        if self.packages.insert(key, p.to_string()).is_some() {
+            info!("{}", fl!("repo-replaced", path = deb.filename));
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, dir: &Path, sign_key: Option<&Path>) -> Result<(), OutputError> {
+        let mut files = vec![];
+
+        let packages = self
+            .packages
+            .values()
+            .map(|s| s.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        files.extend(write_index(dir, PACKAGES, packages.as_bytes())?);
+
+        let archs = self
+            .packages
+            .keys()
+            .map(|(_, _, arch)| arch.as_str())
+            .collect::<BTreeSet<_>>();
+
+        let all = self.contents.get(ARCH_ALL);
+
+        for arch in &archs {
+            let mut contents = self.contents.get(*arch).cloned().unwrap_or_default();
+
+            // 与架构无关的软件包会出现在每个架构的 Contents 中
+            if let Some(all) = all {
+                for (file, pkgs) in all {
+                    contents
+                        .entry(file.clone())
+                        .or_default()
+                        .extend(pkgs.iter().cloned());
+                }
+            }
+
+            let contents = format_contents(&contents);
+
+            files.extend(write_index(
+                dir,
+                &format!("{CONTENTS_PREFIX}{arch}"),
+                contents.as_bytes(),
+            )?);
+        }
+
+        let release = format_release(
+            self.origin.as_deref(),
+            &archs.into_iter().collect::<Vec<_>>(),
+            &files,
+        );
+
+        write_file(&dir.join(RELEASE), release.as_bytes())?;
+
+        let Some(sign_key) = sign_key else {
+            warn!("{}", fl!("repo-unsigned"));
+            for name in [IN_RELEASE, RELEASE_GPG] {
+                remove_file(&dir.join(name))?;
+            }
+            return Ok(());
+        };
+
+        let sign_err = |e: oma_repo_verify::VerifyError| OutputError {
+            description: fl!("repo-failed-to-sign"),
+            source: Some(Box::new(e)),
+        };
+
+        let in_release =
+            oma_repo_verify::sign_cleartext(release.as_bytes(), sign_key).map_err(sign_err)?;
+        write_file(&dir.join(IN_RELEASE), &in_release)?;
+
+        let release_gpg =
+            oma_repo_verify::sign_detached(release.as_bytes(), sign_key).map_err(sign_err)?;
+        write_file(&dir.join(RELEASE_GPG), &release_gpg)?;
+
+        Ok(())
+    }
+}
+
+fn package_key(p: &Paragraph) -> Option<(String, String, String)> {
+    Some((p.get("Package")?, p.get("Version")?, p.get("Architecture")?))
+}
+
+/// Format Contents in the format read by `oma_contents::parser`
+fn format_contents(contents: &BTreeMap<String, BTreeSet<String>>) -> String {
+    let mut s = String::new();
+
+    for (file, pkgs) in contents {
+        let pkgs = pkgs
+            .iter()
+            .map(|p| p.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        s.push_str(&format!("{file:<59} {pkgs}\n"));
+    }
+
+    s
+}
+
+fn format_release(origin: Option<&str>, archs: &[&str], files: &[(String, FileHashes)]) -> String {
+    let mut s = String::new();
+
+    if let Some(origin) = origin {
+        s.push_str(&format!("Origin: {origin}\nLabel: {origin}\n"));
+    }
+
+    s.push_str(&format!(
+        "Date: {}\n",
+        Utc::now().format("%a, %d %b %Y %H:%M:%S UTC")
+    ));
+    s.push_str(&format!("Architectures: {}\n", archs.join(" ")));
+
+    s.push_str("MD5Sum:\n");
+    for (name, h) in files {
+        s.push_str(&format!(" {} {:>16} {name}\n", h.md5, h.size));
+    }
+
+    s.push_str("SHA256:\n");
+    for (name, h) in files {
+        s.push_str(&format!(" {} {:>16} {name}\n", h.sha256, h.size));
+    }
+
+    s
+}
+
+/// Write an index file and its compressed variants, returns their checksums for Release
+fn write_index(
+    dir: &Path,
+    name: &str,
+    data: &[u8],
+) -> Result<Vec<(String, FileHashes)>, OutputError> {
+    let compress_err = |e: io::Error| OutputError {
+        description: fl!(
+            "failed-to-operate-path",
+            p = dir.join(name).display().to_string()
+        ),
+        source: Some(Box::new(e)),
+    };
+
+    let xz = liblzma::encode_all(data, 6).map_err(compress_err)?;
+    let zst = zstd::encode_all(data, 0).map_err(compress_err)?;
+
+    let mut res = vec![];
+
+    for (name, data) in [
+        (name.to_string(), data),
+        (format!("{name}.xz"), xz.as_slice()),
+        (format!("{name}.zst"), zst.as_slice()),
+    ] {
+        write_file(&dir.join(&name), data)?;
+        res.push((name, hash_bytes(data)));
+    }
+
+    Ok(res)
+}
+
+fn read_debs(dir: &Path, debs: &[PathBuf]) -> Result<Vec<DebInfo>, OutputError> {
+    debs.par_iter().map(|deb| read_deb(dir, deb)).collect()
+}
+
+fn read_deb(dir: &Path, deb: &Path) -> Result<DebInfo, OutputError> {
+    let filename = deb
+        .strip_prefix(dir)
+        .unwrap_or(deb)
+        .to_string_lossy()
+        .to_string();
+
+    let bad_deb = |e: Option<Box<dyn std::error::Error + Send + Sync>>| OutputError {
+        description: fl!("repo-bad-deb", path = deb.display().to_string()),
+        source: e,
+    };
+
+    let control = Command::new("dpkg-deb")
+        .arg("--field")
+        .arg(deb)
+        .output()
+        .map_err(|e| bad_deb(Some(Box::new(e))))?;
+
+    if !control.status.success() {
+        return Err(bad_deb(None));
+    }
+
+    let files = list_deb_files(deb).map_err(|e| bad_deb(Some(Box::new(e))))?;
+
+    let hashes = hash_file(deb).map_err(|e| OutputError {
+        description: fl!("failed-to-operate-path", p = deb.display().to_string()),
+        source: Some(Box::new(e)),
+    })?;
+
+    Ok(DebInfo {
+        filename,
+        control: String::from_utf8_lossy(&control.stdout)
+            .trim_end()
+            .to_string()
+            + "\n",
+        files,
+        hashes,
+    })
+}
+
+/// List regular files in the data archive of a package archive
+fn list_deb_files(deb: &Path) -> io::Result<Vec<String>> {
+    let mut dpkg_deb = Command::new("dpkg-deb")
+        .arg("--fsys-tarfile")
+        .arg(deb)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let tarball = dpkg_deb.stdout.take().expect("stdout is piped");
+
+    let tar = Command::new("tar")
+        .arg("-tf")
+        .arg("-")
+        .stdin(tarball)
+        .stderr(Stdio::null())
+        .output()?;
+
+    if !dpkg_deb.wait()?.success() || !tar.status.success() {
+        return Err(io::Error::other("failed to list files in the data archive"));
+    }
+
+    Ok(parse_tar_list(&String::from_utf8_lossy(&tar.stdout)))
+}
+
+/// Contents 中的路径不带开头的 `./`，也不包含目录
+fn parse_tar_list(s: &str) -> Vec<String> {
+    s.lines()
+        .map(|l| l.trim_start_matches("./"))
+        .filter(|l| !l.is_empty() && !l.ends_with('/'))
+        .map(|l| l.to_string())
+        .collect()
+}
+
+fn find_debs(dir: &Path, debs: &mut Vec<PathBuf>) -> Result<(), OutputError> {
+    let err = |e: io::Error| OutputError {
+        description: fl!("failed-to-operate-path", p = dir.display().to_string()),
+        source: Some(Box::new(e)),
+    };
+
+    for entry in fs::read_dir(dir).map_err(err)? {
+        let entry = entry.map_err(err)?;
+        let path = entry.path();
+        let file_type = entry.file_type().map_err(err)?;
+
+        if file_type.is_dir() {
+            find_debs(&path, debs)?;
+        } else if path.extension().is_some_and(|ext| ext == "deb") {
+            debs.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn hash_file(path: &Path) -> io::Result<FileHashes> {
+    let mut f = File::open(path)?;
+    let mut md5 = Md5::new();
+    let mut sha256 = Sha256::new();
+    let mut size = 0;
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+
+        md5.update(&buf[..n]);
+        sha256.update(&buf[..n]);
+        size += n as u64;
+    }
+
+    Ok(FileHashes {
+        size,
+        md5: hex_string(&md5.finalize()),
+        sha256: hex_string(&sha256.finalize()),
+    })
+}
+
+fn hash_bytes(data: &[u8]) -> FileHashes {
+    FileHashes {
+        size: data.len() as u64,
+        md5: hex_string(&Md5::digest(data)),
+        sha256: hex_string(&Sha256::digest(data)),
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn read_index(path: &Path) -> Result<Option<String>, OutputError> {
+    match fs::read_to_string(path) {
+        Ok(s) => Ok(Some(s)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(OutputError {
+            description: fl!("failed-to-operate-path", p = path.display().to_string()),
+            source: Some(Box::new(e)),
+        }),
+    }
+}
+
+fn write_file(path: &Path, data: &[u8]) -> Result<(), OutputError> {
+    let err = |e: io::Error| OutputError {
+        description: fl!("failed-to-operate-path", p = path.display().to_string()),
+        source: Some(Box::new(e)),
+    };
+
+    // 先写入临时文件再重命名，避免 apt 读到写了一半的索引
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".oma-tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut f = File::create(&tmp).map_err(err)?;
+    f.write_all(data).map_err(err)?;
+    f.sync_all().map_err(err)?;
+    fs::rename(&tmp, path).map_err(err)
+}
+
+fn remove_file(path: &Path) -> Result<(), OutputError> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(OutputError {
+            description: fl!("failed-to-operate-path", p = path.display().to_string()),
+            source: Some(Box::new(e)),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_contents_roundtrip() {
+        let files = parse_tar_list("./\n./usr/\n./usr/bin/\n./usr/bin/foo\n./usr/share/a b\n");
+        assert_eq!(files, vec!["usr/bin/foo", "usr/share/a b"]);
+
+        let mut contents = BTreeMap::<String, BTreeSet<String>>::new();
+        for f in files {
+            contents
+                .entry(f)
+                .or_default()
+                .insert("utils/foo".to_string());
+        }
+        contents
+            .get_mut("usr/bin/foo")
+            .unwrap()
+            .insert("admin/bar".to_string());
+
+        let s = format_contents(&contents);
+        let parsed = parse_contents(&s).unwrap();
+
+        assert_eq!(
+            parsed,
+            vec![
+                ("usr/bin/foo", vec!["admin/bar", "utils/foo"]),
+                ("usr/share/a b", vec!["utils/foo"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_replaces_contents() {
+        let deb = |version: &str, files: &[&str]| DebInfo {
+            filename: format!("foo_{version}_amd64.deb"),
+            control: format!(
+                "Package: foo\nVersion: {version}\nArchitecture: amd64\nSection: utils\n"
+            ),
+            files: files.iter().map(|f| f.to_string()).collect(),
+            hashes: FileHashes {
+                size: 0,
+                md5: String::new(),
+                sha256: String::new(),
+            },
+        };
+
+        let mut index = RepoIndex::default();
+        index
+            .contents
+            .entry("amd64".to_string())
+            .or_default()
+            .entry("usr/bin/foo".to_string())
+            .or_default()
+            .insert("admin/bar".to_string());
+
+        index
+            .add(deb("1", &["usr/bin/foo", "usr/share/foo/old"]))
+            .unwrap();
+        index
+            .add(deb("1", &["usr/bin/foo", "usr/share/foo/new"]))
+            .unwrap();
+
+        let contents = &index.contents["amd64"];
+        assert_eq!(
+            contents.keys().collect::<Vec<_>>(),
+            vec!["usr/bin/foo", "usr/share/foo/new"]
+        );
+        assert_eq!(
+            contents["usr/bin/foo"].iter().collect::<Vec<_>>(),
+            vec!["admin/bar", "utils/foo"]
+        );
+    }
+}
@@ -3,10 +3,9 @@ use std::fmt::Display;
 use clap::{ArgAction, Args};
 use clap_complete::ArgValueCompleter;
 use oma_apt_pkg::search::{
-    IndiciumSearch, OmaSearch as _, PackageStatus, SearchResult, SearchType, StrSimSearch,
-    TextSearch,
+    IndiciumSearch, PackageStatus, QuerySearch, SearchResult, SearchType, StrSimSearch, TextSearch,
 };
-use oma_apt_pkg::{AptDb, DpkgState};
+use oma_apt_pkg::{AptDb, DpkgState, Query};
 use oma_console::{console::style, pager::Pager, print::Action, terminal::gen_prefix};
 use oma_pm::matches::SearchEngine;
 use oma_pm::oma_apt::raw::config as apt_config;
@@ -196,32 +195,43 @@ pub fn search(
     engine: SearchEngine,
     config: &OmaConfig,
 ) -> Result<Vec<SearchResult>, OutputError> {
+    let query = Query::from_terms(keywords)?;
+
     match engine {
         SearchEngine::Indicium(f) => {
-            let query = keywords.join(" ");
-
-            if config.amo && !config.no_check_dbus {
-                match RT.block_on(amo_search(&query)) {
+            // amo 不支持按字段过滤
+            if config.amo && !config.no_check_dbus && !query.has_filters() {
+                match RT.block_on(amo_search(&query.text())) {
                     Ok(r) => Ok(r),
-                    Err(_) => local_indicium_search(f, query),
+                    Err(_) => local_indicium_search(f, &query),
                 }
             } else {
-                local_indicium_search(f, query)
+                local_indicium_search(f, &query)
             }
         }
         SearchEngine::Strsim => {
             let (apt_db, dpkg) = load_apt_db_and_dpkg()?;
             let searcher = StrSimSearch::new(&apt_db, &dpkg);
-            Ok(searcher
-                .search(&keywords.join(" "))
+            Ok(QuerySearch::new(&apt_db, &dpkg, &searcher)
+                .search_query(&query)
                 .map_err(to_output_err)?)
         }
         SearchEngine::Text => {
             let (apt_db, dpkg) = load_apt_db_and_dpkg()?;
             let searcher = TextSearch::new(&apt_db, &dpkg);
+            let searcher = QuerySearch::new(&apt_db, &dpkg, &searcher);
+
+            if query.keywords.is_empty() {
+                return searcher.search_query(&query).map_err(to_output_err);
+            }
+
             let mut result = vec![];
-            for keyword in keywords {
-                let res = searcher.search(keyword).map_err(to_output_err)?;
+            for keyword in &query.keywords {
+                let query = Query {
+                    keywords: vec![keyword.clone()],
+                    filters: query.filters.clone(),
+                };
+                let res = searcher.search_query(&query).map_err(to_output_err)?;
                 result.extend(res);
             }
 
@@ -263,7 +273,7 @@ fn load_apt_db_and_dpkg() -> Result<(AptDb, DpkgState), OutputError> {
 
 fn local_indicium_search(
     f: Box<dyn Fn(usize) + 'static>,
-    query: String,
+    query: &Query,
 ) -> Result<Vec<SearchResult>, OutputError> {
     let (apt_db, dpkg) = load_apt_db_and_dpkg()?;
 
@@ -288,7 +298,9 @@ fn local_indicium_search(
         source: None,
     })?;
 
-    searcher.search(&query).map_err(to_output_err)
+    QuerySearch::new(&apt_db, &dpkg, &searcher)
+        .search_query(query)
+        .map_err(to_output_err)
 }
 
 async fn amo_search(query: &str) -> anyhow::Result<Vec<SearchResult>> {
@@ -0,0 +1,225 @@
+use std::{
+    fs,
+    net::SocketAddr,
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use clap::Args;
+use oma_fetch::peer::SERVICE_TYPE;
+use oma_pm::apt::{OmaApt, OmaAptArgs};
+use spdlog::{debug, info, warn};
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    RT, args::CliExecuter, config::OmaConfig, error::OutputError, exit_handle::ExitHandle, fl,
+};
+
+/// 请求头的最大长度，超出则直接断开连接
+const MAX_HEADER_SIZE: u64 = 16 * 1024;
+
+#[derive(Debug, Args)]
+pub struct ServeCache {
+    /// Address to listen on
+    #[arg(long, default_value = "0.0.0.0:8765", help = fl!("clap-serve-cache-listen-help"))]
+    listen: SocketAddr,
+    /// Do not announce the cache to the LAN via Avahi
+    #[arg(long, help = fl!("clap-serve-cache-no-announce-help"))]
+    no_announce: bool,
+}
+
+impl CliExecuter for ServeCache {
+    fn execute(self, config: OmaConfig) -> Result<ExitHandle, OutputError> {
+        let ServeCache {
+            listen,
+            no_announce,
+        } = self;
+
+        let oma_apt_args = OmaAptArgs::builder()
+            .sysroot(config.sysroot.to_string_lossy().to_string())
+            .another_apt_options(&config.apt_options)
+            .build();
+
+        let apt = OmaApt::new(vec![], oma_apt_args, false)?;
+        let archive_dir: Arc<Path> = Arc::from(apt.get_archive_dir());
+
+        let listener = RT
+            .block_on(TcpListener::bind(listen))
+            .with_context(|| format!("Failed to listen on {listen}"))?;
+
+        info!(
+            "{}",
+            fl!(
+                "serve-cache-started",
+                addr = listen.to_string(),
+                path = archive_dir.display().to_string()
+            )
+        );
+
+        // 保持 avahi-publish-service 运行，直到 oma 退出
+        let _announce = if !no_announce {
+            announce(listen.port())
+                .inspect_err(|e| warn!("{}", fl!("serve-cache-announce-failed", e = e.to_string())))
+                .ok()
+        } else {
+            None
+        };
+
+        RT.block_on(serve(listener, archive_dir))
+            .context("Failed to serve package cache")?;
+
+        Ok(ExitHandle::default())
+    }
+}
+
+fn announce(port: u16) -> std::io::Result<Child> {
+    let hostname = fs::read_to_string("/proc/sys/kernel/hostname").unwrap_or_default();
+
+    let mut cmd = Command::new("avahi-publish-service");
+    cmd.arg(format!("oma cache on {}", hostname.trim()))
+        .arg(SERVICE_TYPE)
+        .arg(port.to_string())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    // oma 被 Ctrl-C 终止时不会执行析构，需要让内核在父进程退出时结束 avahi-publish-service
+    unsafe {
+        cmd.pre_exec(|| {
+            libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM);
+            Ok(())
+        });
+    }
+
+    cmd.spawn()
+}
+
+async fn serve(listener: TcpListener, dir: Arc<Path>) -> std::io::Result<()> {
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let dir = dir.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &dir).await {
+                debug!("Failed to handle request from {addr}: {e}");
+            }
+        });
+    }
+}
+
+async fn handle(stream: TcpStream, dir: &Path) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader.take(MAX_HEADER_SIZE));
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+
+    // 忽略其余请求头，包括 Range：总是返回完整的文件
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or_default(), parts.next());
+
+    let head = match method {
+        "GET" => false,
+        "HEAD" => true,
+        _ => return respond(&mut writer, "405 Method Not Allowed", None).await,
+    };
+
+    let Some(target) = target else {
+        return respond(&mut writer, "400 Bad Request", None).await;
+    };
+
+    let name = target.trim_start_matches('/');
+
+    // 用于 oma 探测节点是否可用
+    if name.is_empty() {
+        return respond(&mut writer, "200 OK", None).await;
+    }
+
+    let Some(path) = resolve(dir, name) else {
+        return respond(&mut writer, "404 Not Found", None).await;
+    };
+
+    let Ok(file) = File::open(&path).await else {
+        return respond(&mut writer, "404 Not Found", None).await;
+    };
+
+    let len = file.metadata().await?.len();
+    respond(&mut writer, "200 OK", Some(len)).await?;
+
+    if !head {
+        tokio::io::copy(&mut file.take(len), &mut writer).await?;
+    }
+
+    writer.shutdown().await
+}
+
+/// 只提供缓存目录下的 .deb 文件，不允许访问子目录或符号链接
+fn resolve(dir: &Path, name: &str) -> Option<PathBuf> {
+    if name.contains('/') || name.starts_with('.') || !name.ends_with(".deb") {
+        return None;
+    }
+
+    let path = dir.join(name);
+
+    path.symlink_metadata()
+        .is_ok_and(|m| m.is_file())
+        .then_some(path)
+}
+
+async fn respond(
+    writer: &mut (impl AsyncWriteExt + Unpin),
+    status: &str,
+    content_length: Option<u64>,
+) -> std::io::Result<()> {
+    let header = match content_length {
+        Some(len) => format!(
+            "HTTP/1.1 {status}\r\nContent-Type: application/vnd.debian.binary-package\r\nContent-Length: {len}\r\nConnection: close\r\n\r\n"
+        ),
+        None => format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"),
+    };
+
+    writer.write_all(header.as_bytes()).await?;
+
+    if content_length.is_none() {
+        writer.shutdown().await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let dir = std::env::temp_dir().join(format!("oma-serve-cache-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("partial")).unwrap();
+        fs::write(dir.join("foo_1.0_amd64.deb"), b"").unwrap();
+        fs::write(dir.join("partial/bar_1.0_amd64.deb"), b"").unwrap();
+
+        assert_eq!(
+            resolve(&dir, "foo_1.0_amd64.deb"),
+            Some(dir.join("foo_1.0_amd64.deb"))
+        );
+        assert_eq!(resolve(&dir, "partial/bar_1.0_amd64.deb"), None);
+        assert_eq!(resolve(&dir, "../foo_1.0_amd64.deb"), None);
+        assert_eq!(resolve(&dir, "lock"), None);
+        assert_eq!(resolve(&dir, "baz_1.0_amd64.deb"), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -8,6 +8,7 @@ use oma_pm::{
     apt::{OmaApt, OmaAptArgs},
     matches::{GetArchMethod, PackagesMatcher},
     oma_apt::records::RecordField,
+    pin::{CandidateReason, Pin, PinManager},
     pkginfo::{AptSource, OmaPackage},
 };
 use spdlog::info;
@@ -87,10 +88,11 @@ impl CliExecuter for Show {
 
         handle_no_result(no_result, config.no_progress())?;
 
+        let pins = PinManager::new(&config.sysroot, false).list()?;
         let mut stdout = stdout();
 
         if !all {
-            for_each_show_package(json, &apt, &mut stdout, &pkgs)?;
+            for_each_show_package(json, &apt, &mut stdout, &pkgs, &pins)?;
 
             if pkgs.len() == 1 && !json {
                 let pkg = &pkgs[0];
@@ -102,7 +104,7 @@ impl CliExecuter for Show {
                 }
             }
         } else {
-            for_each_show_package(json, &apt, &mut stdout, &pkgs)?;
+            for_each_show_package(json, &apt, &mut stdout, &pkgs, &pins)?;
         }
 
         Ok(ExitHandle::default())
@@ -114,12 +116,13 @@ fn for_each_show_package(
     apt: &OmaApt,
     stdout: &mut std::io::Stdout,
     pkgs: &[OmaPackage],
+    pins: &[Pin],
 ) -> Result<(), OutputError> {
     for (i, pkg) in pkgs.iter().enumerate() {
         if json {
-            display_to_json(stdout, pkg, apt)?;
+            display_to_json(stdout, pkg, apt, pins)?;
         } else {
-            display_records(stdout, pkg, apt);
+            display_records(stdout, pkg, apt, pins);
         }
 
         if i != pkgs.len() - 1 {
@@ -134,11 +137,12 @@ fn display_to_json(
     stdout: &mut std::io::Stdout,
     pkg: &OmaPackage,
     apt: &OmaApt,
+    pins: &[Pin],
 ) -> Result<(), OutputError> {
     writeln!(
         stdout,
         "{}",
-        serde_json::to_string(&pkg.pkg_info(&apt.cache)?).map_err(|e| {
+        serde_json::to_string(&pkg.pkg_info(&apt.cache, pins)?).map_err(|e| {
             OutputError {
                 description: e.to_string(),
                 source: None,
@@ -150,7 +154,7 @@ fn display_to_json(
     Ok(())
 }
 
-fn display_records(stdout: &mut std::io::Stdout, pkg: &OmaPackage, apt: &OmaApt) {
+fn display_records(stdout: &mut std::io::Stdout, pkg: &OmaPackage, apt: &OmaApt, pins: &[Pin]) {
     let version = pkg.version(&apt.cache);
     for i in RECORDS {
         let Some(mut v) = version.get_record(i) else {
@@ -206,6 +210,23 @@ fn display_records(stdout: &mut std::io::Stdout, pkg: &OmaPackage, apt: &OmaApt)
             writeln!(stdout, "yes").ok();
         }
     }
+
+    writeln!(
+        stdout,
+        "{} {}",
+        key_style("APT-Pin-Priority:".into()),
+        version.priority()
+    )
+    .ok();
+
+    let pkg = version.parent();
+    if pkg
+        .candidate()
+        .is_some_and(|c| c.version() == version.version())
+        && let Some(reason) = CandidateReason::from_package(&pkg, pins)
+    {
+        writeln!(stdout, "{} {reason}", key_style("APT-Candidate:".into())).ok();
+    }
 }
 
 #[inline]
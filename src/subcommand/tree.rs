@@ -6,11 +6,12 @@ use std::{
     sync::LazyLock,
 };
 
-use clap::Args;
+use clap::{Args, ValueEnum};
 use clap_complete::ArgValueCompleter;
 use dialoguer::console::style;
 use oma_pm::{
     apt::{OmaApt, OmaAptArgs},
+    graph::DepGraph,
     matches::{GetArchMethod, PackagesMatcher},
     oma_apt::{BaseDep, Package, Version},
     pkginfo::OmaDepType,
@@ -33,6 +34,25 @@ static DEPTH_HELP: LazyLock<String> = LazyLock::new(|| {
     )
 });
 
+/// Dependency types followed by the graph queries, the same as the tree view
+const GRAPH_DEP_TYPES: [OmaDepType; 3] = [
+    OmaDepType::PreDepends,
+    OmaDepType::Depends,
+    OmaDepType::Recommends,
+];
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum TreeFormat {
+    /// Tree view, or plain text for queries
+    Tree,
+    /// Graphviz DOT
+    Dot,
+    /// GraphML
+    Graphml,
+    /// JSON
+    Json,
+}
+
 #[derive(Debug, Args)]
 pub struct Tree {
     /// Query Package(s) name
@@ -45,6 +65,18 @@ pub struct Tree {
     /// Maximum display depth of the dependency tree
     #[arg(short, long, default_value_t = 5, value_parser = clap::value_parser!(u8).range(1..=5), help = &**DEPTH_HELP)]
     depth: u8,
+    /// Output format, all formats but `tree` export the full dependency graph
+    #[arg(long, value_enum, default_value_t = TreeFormat::Tree, help = fl!("clap-tree-format-help"))]
+    format: TreeFormat,
+    /// Show the shortest dependency path to the specified package
+    #[arg(long, value_name = "PACKAGE", conflicts_with_all = ["removal", "cycles"], help = fl!("clap-tree-path-help"))]
+    path: Option<String>,
+    /// Show the packages that would be removed along with the specified package(s)
+    #[arg(long, conflicts_with = "cycles", help = fl!("clap-tree-removal-help"))]
+    removal: bool,
+    /// List circular dependencies
+    #[arg(long, help = fl!("clap-tree-cycles-help"))]
+    cycles: bool,
     /// Output result to stdout, not pager
     #[arg(long, help = fl!("clap-no-pager-help"))]
     no_pager: bool,
@@ -76,6 +108,10 @@ impl From<Why> for Tree {
             packages,
             reverse: true,
             depth,
+            format: TreeFormat::Tree,
+            path: None,
+            removal: false,
+            cycles: false,
             no_pager,
         }
     }
@@ -117,6 +153,10 @@ impl CliExecuter for Tree {
             packages,
             reverse: invert,
             depth: limit,
+            format,
+            path,
+            removal,
+            cycles,
             no_pager,
         } = self;
 
@@ -138,6 +178,41 @@ impl CliExecuter for Tree {
 
         handle_no_result(no_result, config.no_progress())?;
 
+        let query = match (path, removal, cycles) {
+            (Some(target), _, _) => Some(GraphQuery::Path(target)),
+            (_, true, _) => Some(GraphQuery::Removal),
+            (_, _, true) => Some(GraphQuery::Cycles),
+            _ if format != TreeFormat::Tree => Some(GraphQuery::Graph),
+            // 默认仍然显示树形视图
+            _ => None,
+        };
+
+        if let Some(query) = query {
+            let pb = create_progress_spinner(
+                config.no_progress() || no_pager || format != TreeFormat::Tree,
+                fl!("loading-tree"),
+            );
+
+            let graph = if invert || removal {
+                DepGraph::installed(&apt.cache, &pkgs, &GRAPH_DEP_TYPES)
+            } else {
+                DepGraph::closure(&apt.cache, &pkgs, &GRAPH_DEP_TYPES)
+            };
+
+            // 反向查询只关心依赖于给定软件包的已安装软件包
+            let graph = if invert && !removal {
+                graph.subgraph(&graph.dependents(&graph.roots))
+            } else {
+                graph
+            };
+
+            if let Some(pb) = pb {
+                pb.inner.finish_and_clear();
+            }
+
+            return graph_query(graph, query, invert, format, no_pager);
+        }
+
         let mut res = vec![];
 
         let pb = create_progress_spinner(config.no_progress() || no_pager, fl!("loading-tree"));
@@ -209,6 +284,185 @@ impl CliExecuter for Tree {
     }
 }
 
+enum GraphQuery {
+    /// Export the whole graph
+    Graph,
+    Path(String),
+    Removal,
+    Cycles,
+}
+
+fn graph_query(
+    graph: DepGraph,
+    query: GraphQuery,
+    invert: bool,
+    format: TreeFormat,
+    no_pager: bool,
+) -> Result<ExitHandle, OutputError> {
+    let names = |graph: &DepGraph, nodes: &[usize]| {
+        nodes
+            .iter()
+            .map(|&i| graph.nodes[i].name.clone())
+            .collect::<Vec<_>>()
+    };
+
+    let (graph, lines, json) = match query {
+        GraphQuery::Graph => {
+            let json = serde_json::to_value(&graph).map_err(|e| OutputError {
+                description: e.to_string(),
+                source: None,
+            })?;
+            (graph, vec![], json)
+        }
+        GraphQuery::Path(target) => {
+            let to = graph.find(&target);
+            let mut nodes = vec![];
+            let mut lines = vec![];
+            let mut json = vec![];
+
+            for &root in &graph.roots {
+                let name = graph.nodes[root].name.as_str();
+
+                // 反向查询时，路径从依赖于给定软件包的包开始
+                let path = to.and_then(|to| {
+                    if invert {
+                        graph.shortest_path(to, root)
+                    } else {
+                        graph.shortest_path(root, to)
+                    }
+                });
+
+                match &path {
+                    Some(path) => {
+                        lines.push(path_display(&graph, path));
+                        nodes.extend(path);
+                    }
+                    None if invert => {
+                        lines.push(fl!("tree-no-path", from = target.as_str(), to = name))
+                    }
+                    None => lines.push(fl!("tree-no-path", from = name, to = target.as_str())),
+                }
+
+                json.push(serde_json::json!({
+                    "package": name,
+                    "path": path.map(|p| names(&graph, &p)),
+                }));
+            }
+
+            (
+                graph.subgraph(&nodes),
+                lines,
+                serde_json::Value::Array(json),
+            )
+        }
+        GraphQuery::Removal => {
+            let impact = graph.removal_impact(&graph.roots);
+            let mut lines = vec![];
+
+            for (nodes, title) in [
+                (&impact.broken, fl!("tree-removal-broken")),
+                (&impact.orphaned, fl!("tree-removal-orphaned")),
+            ] {
+                if nodes.is_empty() {
+                    continue;
+                }
+
+                lines.push(title);
+                lines.extend(nodes.iter().map(|&i| {
+                    let node = &graph.nodes[i];
+                    format!(
+                        "  {} {}",
+                        node.name,
+                        style(format!("({})", node.version.as_deref().unwrap_or_default()))
+                            .yellow()
+                    )
+                }));
+            }
+
+            if lines.is_empty() {
+                lines.push(fl!("tree-removal-nothing"));
+            }
+
+            let json = serde_json::json!({
+                "broken": names(&graph, &impact.broken),
+                "orphaned": names(&graph, &impact.orphaned),
+            });
+
+            let nodes = graph
+                .roots
+                .iter()
+                .chain(&impact.broken)
+                .chain(&impact.orphaned)
+                .copied()
+                .collect::<Vec<_>>();
+
+            (graph.subgraph(&nodes), lines, json)
+        }
+        GraphQuery::Cycles => {
+            let cycles = graph.cycles();
+
+            let mut lines = cycles
+                .iter()
+                .map(|c| fl!("tree-cycle", pkgs = names(&graph, c).join(", ")))
+                .collect::<Vec<_>>();
+
+            if lines.is_empty() {
+                lines.push(fl!("tree-no-cycles"));
+            }
+
+            let json = cycles.iter().map(|c| names(&graph, c)).collect::<Vec<_>>();
+            let nodes = cycles.into_iter().flatten().collect::<Vec<_>>();
+
+            (graph.subgraph(&nodes), lines, serde_json::json!(json))
+        }
+    };
+
+    let output = match format {
+        TreeFormat::Tree => lines.join("\n"),
+        TreeFormat::Dot => graph.to_dot(),
+        TreeFormat::Graphml => graph.to_graphml(),
+        TreeFormat::Json => json.to_string(),
+    };
+
+    // 导出的图不经过分页器，方便重定向到文件或其他程序
+    if no_pager || format != TreeFormat::Tree {
+        writeln!(stdout(), "{}", output.trim_end()).ok();
+        return Ok(ExitHandle::default());
+    }
+
+    let mut pager = oma_display_with_normal_output(false, lines.len())?;
+    let mut w = pager.get_writer().map_err(|e| OutputError {
+        description: "Failed to get writer".to_string(),
+        source: Some(Box::new(e)),
+    })?;
+
+    writeln!(w, "{output}").ok();
+    drop(w);
+
+    pager.wait_for_exit().ok();
+
+    Ok(ExitHandle::default())
+}
+
+fn path_display(graph: &DepGraph, path: &[usize]) -> String {
+    let mut s = style(&graph.nodes[path[0]].name).bold().to_string();
+
+    for pair in path.windows(2) {
+        let label = graph
+            .edge(pair[0], pair[1])
+            .map(|e| e.to_string())
+            .unwrap_or_default();
+
+        s.push_str(&format!(
+            " {} {}",
+            style(format!("--{label}-->")).yellow(),
+            style(&graph.nodes[pair[1]].name).bold()
+        ));
+    }
+
+    s
+}
+
 fn dep_tree<'a>(
     pkg: PkgWrapper<'a>,
     apt: &'a OmaApt,
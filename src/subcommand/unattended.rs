@@ -0,0 +1,357 @@
+use std::collections::HashSet;
+
+use chrono::Local;
+use glob_match::glob_match;
+use oma_history::{
+    DATABASE_PATH, History, UnattendedPackage, UnattendedReport, UnattendedSkip,
+    UnattendedSkipReason, UnattendedStatus,
+};
+use oma_pm::{
+    ConffileState,
+    apt::{OmaApt, OmaAptArgs, Upgrade as AptUpgrade},
+    conffiles,
+    oma_apt::Package,
+};
+use spdlog::{debug, info, warn};
+
+use crate::config::OmaConfig;
+use crate::config_file::UnattendedConfig;
+use crate::core::commit_changes::CommitChanges;
+use crate::core::refresh::Refresh;
+use crate::dbus::unattended_dbus_check;
+use crate::error::OutputError;
+use crate::exit_handle::ExitHandle;
+use crate::fl;
+use crate::json_events;
+use crate::root::root;
+
+use super::utils::lock_oma;
+
+/// Apply updates allowed by the `[unattended]` policy, without any prompt.
+pub(crate) fn unattended_upgrade(
+    config: &OmaConfig,
+    no_refresh: bool,
+    force_unsafe_io: bool,
+    no_clean: bool,
+) -> Result<ExitHandle, OutputError> {
+    let _lock_fd = if !config.dry_run {
+        root()?;
+        Some(lock_oma(&config.sysroot)?)
+    } else {
+        None
+    };
+
+    let policy = &config.unattended;
+    let start_time = Local::now().timestamp();
+
+    let Some(_fds) = unattended_dbus_check(config, policy.allow_on_battery) else {
+        info!("{}", fl!("unattended-on-battery"));
+        write_report(config, start_time, report(UnattendedStatus::OnBattery))?;
+        return Ok(ExitHandle::default());
+    };
+
+    if !no_refresh {
+        Refresh::builder().config(config).build().run()?;
+    }
+
+    // yes 会让 dpkg 使用 --force-confold --force-confdef，不会在配置文件上停下来询问
+    let oma_apt_args = OmaAptArgs::builder()
+        .sysroot(config.sysroot.to_string_lossy().to_string())
+        .yes(true)
+        .another_apt_options(&config.apt_options)
+        .dpkg_force_unsafe_io(force_unsafe_io)
+        .build();
+
+    let mut apt = OmaApt::new(vec![], oma_apt_args, config.dry_run)?;
+
+    // 只升级已安装的软件包，不删除也不安装新的软件包
+    apt.upgrade(AptUpgrade::Upgrade)?;
+
+    let modified_conffiles = conffiles(&config.sysroot)?
+        .into_iter()
+        .filter(|c| !c.obsolete && c.state(&config.sysroot) == ConffileState::Modified)
+        .map(|c| c.package)
+        .collect::<HashSet<_>>();
+
+    let candidates = apt
+        .cache
+        .get_changes(true)
+        .filter(|pkg| pkg.marked_upgrade())
+        .collect::<Vec<_>>();
+
+    let mut skipped = vec![];
+
+    for pkg in &candidates {
+        if let Some(reason) = skip_reason(pkg, policy, &modified_conffiles) {
+            keep_back(pkg, reason, &mut skipped);
+        }
+    }
+
+    let candidates = candidates
+        .into_iter()
+        .map(|pkg| pkg.fullname(true))
+        .collect::<Vec<_>>();
+
+    // 保留部分软件包后，依赖它们新版本的软件包也需要保留
+    apt.resolve(true, false)?;
+
+    // 安全更新需要根据保留后的升级方案判断
+    if policy.security_only {
+        let security = security_packages(&apt)?;
+
+        let not_security = apt
+            .cache
+            .get_changes(true)
+            .filter(|pkg| pkg.marked_upgrade() && !security.contains(pkg.name()))
+            .collect::<Vec<_>>();
+
+        for pkg in &not_security {
+            keep_back(pkg, UnattendedSkipReason::NotSecurity, &mut skipped);
+        }
+
+        apt.resolve(true, false)?;
+    }
+
+    // 保护只能约束问题解决器，仍需确认保留的软件包没有被重新标记为升级
+    let kept_upgraded = apt
+        .cache
+        .get_changes(true)
+        .filter(|pkg| pkg.marked_upgrade())
+        .map(|pkg| pkg.fullname(true))
+        .filter(|name| skipped.iter().any(|s| s.name == *name))
+        .collect::<Vec<_>>();
+
+    if !kept_upgraded.is_empty() {
+        write_report(
+            config,
+            start_time,
+            UnattendedReport {
+                skipped,
+                ..report(UnattendedStatus::Failed)
+            },
+        )?;
+
+        return Err(OutputError {
+            description: fl!("unattended-kept-upgraded", pkgs = kept_upgraded.join(", ")),
+            source: None,
+        });
+    }
+
+    let mut upgraded = vec![];
+
+    for pkg in apt.cache.get_changes(true) {
+        if pkg.marked_upgrade() {
+            upgraded.push(UnattendedPackage {
+                name: pkg.fullname(true),
+                old_version: pkg.installed().map(|v| v.version().to_string()),
+                new_version: pkg
+                    .install_version()
+                    .map(|v| v.version().to_string())
+                    .unwrap_or_default(),
+            });
+        }
+    }
+
+    for name in candidates {
+        if !upgraded.iter().any(|p| p.name == name) && !skipped.iter().any(|s| s.name == name) {
+            skipped.push(UnattendedSkip {
+                name,
+                reason: UnattendedSkipReason::Dependency,
+            });
+        }
+    }
+
+    for s in &skipped {
+        info!(
+            "{}",
+            fl!(
+                "unattended-skipped",
+                name = s.name.as_str(),
+                reason = skip_reason_str(s.reason)
+            )
+        );
+    }
+
+    let removed = apt
+        .cache
+        .get_changes(true)
+        .filter(|pkg| pkg.marked_delete())
+        .map(|pkg| pkg.fullname(true))
+        .collect::<Vec<_>>();
+
+    if !removed.is_empty() {
+        warn!(
+            "{}",
+            fl!("unattended-would-remove", pkgs = removed.join(", "))
+        );
+
+        write_report(
+            config,
+            start_time,
+            UnattendedReport {
+                status: UnattendedStatus::WouldRemove,
+                upgraded: vec![],
+                skipped,
+                removed,
+            },
+        )?;
+
+        return Ok(ExitHandle::default());
+    }
+
+    if upgraded.is_empty() {
+        info!("{}", fl!("unattended-nothing-to-do"));
+        write_report(
+            config,
+            start_time,
+            UnattendedReport {
+                skipped,
+                ..report(UnattendedStatus::NothingToDo)
+            },
+        )?;
+
+        return Ok(ExitHandle::default());
+    }
+
+    // 无人值守升级不询问用户，在 JSON 事件中记录为已自动确认
+    let yes = !config.json_events
+        || json_events::answer_prompt(
+            "unattended",
+            &fl!("unattended-confirm", count = upgraded.len()),
+            true,
+        );
+
+    let res = CommitChanges::builder()
+        .apt(apt)
+        .yes(yes)
+        .is_upgrade(true)
+        .config(config)
+        .no_clean(no_clean)
+        .build()
+        .run();
+
+    write_report(
+        config,
+        start_time,
+        UnattendedReport {
+            status: if res.is_ok() {
+                UnattendedStatus::Success
+            } else {
+                UnattendedStatus::Failed
+            },
+            upgraded,
+            skipped,
+            removed: vec![],
+        },
+    )?;
+
+    res
+}
+
+fn keep_back(pkg: &Package, reason: UnattendedSkipReason, skipped: &mut Vec<UnattendedSkip>) {
+    debug!("Keep back {}: {reason:?}", pkg.fullname(true));
+    pkg.mark_keep();
+    // 避免问题解决器为满足其他升级而重新升级该软件包
+    pkg.protect();
+    skipped.push(UnattendedSkip {
+        name: pkg.fullname(true),
+        reason,
+    });
+}
+
+fn skip_reason(
+    pkg: &Package,
+    policy: &UnattendedConfig,
+    modified_conffiles: &HashSet<String>,
+) -> Option<UnattendedSkipReason> {
+    let name = pkg.name();
+
+    if policy.deny.iter().any(|p| glob_match(p, name)) {
+        return Some(UnattendedSkipReason::Denied);
+    }
+
+    if !policy.origins.is_empty() {
+        let allowed = pkg.install_version().is_some_and(|ver| {
+            ver.package_files().any(|f| {
+                policy.origins.iter().any(|o| {
+                    f.origin().is_some_and(|origin| origin == o.as_str())
+                        || f.site().is_some_and(|site| site == o.as_str())
+                })
+            })
+        });
+
+        if !allowed {
+            return Some(UnattendedSkipReason::Origin);
+        }
+    }
+
+    // 被修改过的配置文件在升级时可能需要用户决定如何处理
+    if modified_conffiles.contains(name) {
+        return Some(UnattendedSkipReason::ModifiedConffile);
+    }
+
+    None
+}
+
+#[cfg(feature = "aosc")]
+fn security_packages(apt: &OmaApt) -> Result<HashSet<String>, OutputError> {
+    use oma_pm::sort::SummarySort;
+
+    let op = apt.build_transaction(SummarySort::default(), |_| false, |_| false)?;
+    let tum = oma_tum::get_tum(crate::utils::get_lists_dir())?;
+    let matches = oma_tum::get_matches_tum(&tum, &op);
+
+    Ok(oma_tum::collection_all_matches_security_tum_pkgs(&matches)
+        .into_keys()
+        .map(|name| name.to_string())
+        .collect())
+}
+
+/// Upgrades from the security suites, like `trixie-security`
+#[cfg(not(feature = "aosc"))]
+fn security_packages(apt: &OmaApt) -> Result<HashSet<String>, OutputError> {
+    Ok(apt
+        .cache
+        .get_changes(true)
+        .filter(|pkg| pkg.marked_upgrade())
+        .filter(|pkg| {
+            pkg.install_version().is_some_and(|ver| {
+                ver.package_files().any(|f| {
+                    f.archive().is_some_and(|a| a.ends_with("-security"))
+                        || f.label().is_some_and(|l| l == "Debian-Security")
+                })
+            })
+        })
+        .map(|pkg| pkg.name().to_string())
+        .collect())
+}
+
+fn skip_reason_str(reason: UnattendedSkipReason) -> String {
+    match reason {
+        UnattendedSkipReason::Denied => fl!("unattended-reason-denied"),
+        UnattendedSkipReason::Origin => fl!("unattended-reason-origin"),
+        UnattendedSkipReason::NotSecurity => fl!("unattended-reason-not-security"),
+        UnattendedSkipReason::ModifiedConffile => fl!("unattended-reason-conffile"),
+        UnattendedSkipReason::Dependency => fl!("unattended-reason-dependency"),
+    }
+}
+
+fn report(status: UnattendedStatus) -> UnattendedReport {
+    UnattendedReport {
+        status,
+        upgraded: vec![],
+        skipped: vec![],
+        removed: vec![],
+    }
+}
+
+fn write_report(
+    config: &OmaConfig,
+    time: i64,
+    report: UnattendedReport,
+) -> Result<(), OutputError> {
+    let mut history = History::new(config.sysroot.join(DATABASE_PATH), true, config.dry_run)?;
+    history.write_unattended_report(time, &report)?;
+
+    Ok(())
+}
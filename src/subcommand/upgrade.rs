@@ -20,6 +20,7 @@ use crate::error::OutputError;
 use crate::fl;
 use crate::root::root;
 
+use super::unattended::unattended_upgrade;
 use super::utils::handle_no_result;
 use super::utils::lock_oma;
 use crate::args::CliExecuter;
@@ -70,6 +71,9 @@ pub(crate) struct Upgrade {
     /// Do not clean local package cache
     #[arg(long, help = fl!("clap-noclean-help"), env = "OMA_NO_CLEAN", value_parser = clap::builder::FalseyValueParser::new())]
     no_clean: bool,
+    /// Apply updates allowed by the unattended upgrade policy without prompting
+    #[arg(long, conflicts_with_all = ["packages", "download_only"], help = fl!("clap-upgrade-unattended-help"))]
+    unattended: bool,
 }
 
 impl CliExecuter for Upgrade {
@@ -89,8 +93,13 @@ impl CliExecuter for Upgrade {
             no_fix_dpkg_status,
             download_only,
             no_clean,
+            unattended,
         } = self;
 
+        if unattended {
+            return unattended_upgrade(&config, no_refresh, force_unsafe_io, no_clean);
+        }
+
         let _lock_fd = if !config.dry_run {
             root()?;
             Some(lock_oma(&config.sysroot)?)
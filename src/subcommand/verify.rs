@@ -0,0 +1,306 @@
+use std::{
+    io::stdout,
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+};
+
+use clap::Args;
+use clap_complete::ArgValueCompleter;
+use oma_console::print::Action;
+use oma_fetch::checksum::Checksum;
+use oma_pm::{
+    apt::{OmaApt, OmaAptArgs},
+    oma_apt::{PackageSort, records::RecordField},
+    pkginfo::OmaPackage,
+    verify::{
+        Diversions, FileIssue, FileProblem, IntegrityError, archive_md5sums, diversions,
+        dpkg_md5sums, verify_files,
+    },
+};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use spdlog::{debug, warn};
+
+use crate::{
+    NOT_DISPLAY_ABORT, color_formatter,
+    completions::pkgnames_completions,
+    config::OmaConfig,
+    core::commit_changes::CommitChanges,
+    dbus::dbus_check,
+    error::OutputError,
+    exit_handle::{ExitHandle, ExitStatus},
+    fl,
+    root::root,
+    success,
+    table::PagerPrinter,
+};
+
+use super::utils::lock_oma;
+use crate::args::CliExecuter;
+
+#[derive(Debug, Args)]
+pub struct Verify {
+    /// Package(s) to verify, verify all installed package(s) if not specified
+    #[arg(add = ArgValueCompleter::new(pkgnames_completions), help = fl!("clap-verify-packages-help"))]
+    #[arg(help_heading = &**crate::args::ARG_HELP_HEADING_MUST)]
+    packages: Vec<String>,
+    /// Also verify against md5sums from the cached package archives
+    #[arg(long, help = fl!("clap-verify-archive-help"))]
+    archive: bool,
+    /// Reinstall package(s) with modified or missing files
+    #[arg(long, conflicts_with = "json", help = fl!("clap-verify-reinstall-help"))]
+    reinstall: bool,
+    /// Bypass confirmation prompts
+    #[arg(short, long, requires = "reinstall", help = fl!("clap-yes-help"))]
+    yes: bool,
+    /// Install package(s) without fsync(2)
+    #[arg(
+        long,
+        requires = "reinstall",
+        help = &**crate::args::FORCE_UNSAFE_IO_TRANSLATE
+    )]
+    force_unsafe_io: bool,
+    /// Set output format as JSON
+    #[arg(long, help = fl!("clap-json-help"))]
+    json: bool,
+}
+
+/// An installed package to verify, collected before verifying in parallel
+struct Target {
+    fullname: String,
+    name: String,
+    arch: String,
+    version: String,
+    archive: Option<(PathBuf, String)>,
+}
+
+impl CliExecuter for Verify {
+    fn execute(self, config: OmaConfig) -> Result<ExitHandle, OutputError> {
+        let Verify {
+            packages,
+            archive,
+            reinstall,
+            yes,
+            force_unsafe_io,
+            json,
+        } = self;
+
+        let _lock_fd = if reinstall && !config.dry_run {
+            root()?;
+            Some(lock_oma(&config.sysroot)?)
+        } else {
+            None
+        };
+
+        let oma_apt_args = OmaAptArgs::builder()
+            .sysroot(config.sysroot.to_string_lossy().to_string())
+            .yes(yes)
+            .another_apt_options(&config.apt_options)
+            .dpkg_force_unsafe_io(force_unsafe_io)
+            .build();
+
+        let mut apt = OmaApt::new(vec![], oma_apt_args, config.dry_run)?;
+
+        let pkgs = if packages.is_empty() {
+            apt.cache
+                .packages(&PackageSort::default().installed())
+                .collect::<Vec<_>>()
+        } else {
+            packages
+                .iter()
+                .map(|name| {
+                    apt.cache
+                        .get(name)
+                        .filter(|pkg| pkg.is_installed())
+                        .ok_or_else(|| OutputError {
+                            description: fl!("verify-not-installed", name = name.as_str()),
+                            source: None,
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let mut targets = vec![];
+
+        for pkg in pkgs {
+            let Some(ver) = pkg.installed() else {
+                continue;
+            };
+
+            let name = pkg.name().to_string();
+            let version = ver.version().to_string();
+            let arch = ver.arch().to_string();
+
+            let archive = if archive {
+                let deb = apt.cached_archive(&name, &version, &arch);
+                let sha256 = ver.get_record(RecordField::SHA256);
+
+                if deb.is_none() || sha256.is_none() {
+                    warn!(
+                        "{}",
+                        fl!(
+                            "verify-no-archive",
+                            name = name.as_str(),
+                            version = version.as_str()
+                        )
+                    );
+                }
+
+                deb.zip(sha256)
+            } else {
+                None
+            };
+
+            targets.push(Target {
+                fullname: pkg.fullname(true),
+                name,
+                arch,
+                version,
+                archive,
+            });
+        }
+
+        let diversions = diversions(&config.sysroot)?;
+
+        let results = targets
+            .par_iter()
+            .map(|t| verify_target(&config.sysroot, t, &diversions).map(|issues| (t, issues)))
+            .collect::<Result<Vec<_>, IntegrityError>>()?;
+
+        let results = results
+            .into_iter()
+            .filter(|(_, issues)| !issues.is_empty())
+            .collect::<Vec<_>>();
+
+        let mut printer = PagerPrinter::new(stdout());
+        NOT_DISPLAY_ABORT.store(true, Ordering::Relaxed);
+
+        for (t, issues) in &results {
+            if json {
+                printer
+                    .println(serde_json::json!({
+                        "name": t.name,
+                        "version": t.version,
+                        "architecture": t.arch,
+                        "files": issues.iter().map(|i| serde_json::json!({
+                            "path": i.path,
+                            "status": i.problem.as_str(),
+                        })).collect::<Vec<_>>(),
+                    }))
+                    .ok();
+                continue;
+            }
+
+            printer
+                .println(format!(
+                    "{} {}",
+                    color_formatter()
+                        .color_str(&t.fullname, Action::Emphasis)
+                        .bold(),
+                    color_formatter().color_str(&t.version, Action::Secondary)
+                ))
+                .ok();
+
+            for i in issues {
+                let problem = match i.problem {
+                    FileProblem::Modified => fl!("verify-modified"),
+                    FileProblem::Missing => fl!("verify-missing"),
+                    FileProblem::Replaced => fl!("verify-replaced"),
+                };
+
+                printer
+                    .println(format!(
+                        "    {} {}",
+                        color_formatter().color_str(problem, Action::WARN),
+                        i.path
+                    ))
+                    .ok();
+            }
+        }
+
+        // 被其他软件包接管的文件不算损坏，重新安装也无法改变
+        let broken = results
+            .iter()
+            .filter(|(_, issues)| issues.iter().any(|i| i.problem != FileProblem::Replaced))
+            .map(|(t, _)| t.fullname.as_str())
+            .collect::<Vec<_>>();
+
+        if json {
+            return Ok(ExitHandle::default().status(if broken.is_empty() {
+                ExitStatus::Success
+            } else {
+                ExitStatus::Fail
+            }));
+        }
+
+        if broken.is_empty() {
+            success!("{}", fl!("verify-ok", count = targets.len()));
+            return Ok(ExitHandle::default());
+        }
+
+        warn!("{}", fl!("verify-broken", count = broken.len()));
+
+        if !reinstall {
+            return Ok(ExitHandle::default().status(ExitStatus::Fail));
+        }
+
+        let _fds = dbus_check(yes, &config)?;
+
+        let pkgs = broken
+            .iter()
+            .filter_map(|name| {
+                let pkg = apt.cache.get(name)?;
+                let ver = pkg.installed()?;
+                Some(OmaPackage::new(&ver, &pkg))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(oma_pm::apt::OmaAptError::from)?;
+
+        apt.install(&pkgs, true)?;
+
+        CommitChanges::builder()
+            .apt(apt)
+            .yes(yes)
+            .config(&config)
+            .build()
+            .run()
+    }
+}
+
+fn verify_target(
+    sysroot: &Path,
+    t: &Target,
+    diversions: &Diversions,
+) -> Result<Vec<FileIssue>, IntegrityError> {
+    let mut issues = match dpkg_md5sums(sysroot, &t.name, &t.arch)? {
+        Some(md5sums) => verify_files(sysroot, &t.name, &t.arch, &md5sums, diversions)?,
+        None => {
+            debug!("{} does not ship md5sums", t.fullname);
+            vec![]
+        }
+    };
+
+    // dpkg 数据库中的 md5sums 也可能被篡改，软件包文件的校验和则来自软件源
+    if let Some((deb, sha256)) = &t.archive {
+        let trusted =
+            Checksum::from_sha256_str(sha256).is_ok_and(|c| c.cmp_file(deb).is_ok_and(|b| b));
+
+        if !trusted {
+            warn!(
+                "{}",
+                fl!("verify-archive-mismatch", path = deb.display().to_string())
+            );
+        } else {
+            let md5sums = archive_md5sums(deb)?;
+
+            for i in verify_files(sysroot, &t.name, &t.arch, &md5sums, diversions)? {
+                if !issues.iter().any(|x| x.path == i.path) {
+                    issues.push(i);
+                }
+            }
+        }
+    }
+
+    issues.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(issues)
+}
@@ -1,10 +1,12 @@
 use std::time::Duration;
 
 use clap::Args;
-use oma_apt_pkg::search::{IndiciumSearch, OmaSearch, SearchResult, SearchType};
+use oma_apt_pkg::search::{IndiciumSearch, QuerySearch, SearchResult, SearchType};
+use oma_apt_pkg::{AptDb, DpkgState, OmaSearchResult, Query};
 use oma_console::pager::{exit_tui, prepare_create_tui};
 use oma_pm::apt::{OmaApt, OmaAptArgs, Upgrade};
 use oma_pm::oma_apt::raw::config as apt_config;
+use once_cell::unsync::OnceCell;
 use render::{Task, Tui as TuiInner};
 use spdlog::{debug, info};
 use zbus::Connection;
@@ -59,11 +61,26 @@ pub struct Tui {
     no_clean: bool,
 }
 
+/// Package data and search index on this system
+pub(crate) struct LocalSearcher {
+    apt_db: AptDb,
+    dpkg: DpkgState,
+    index: IndiciumSearch,
+}
+
+impl LocalSearcher {
+    fn search(&self, query: &Query) -> OmaSearchResult<Vec<SearchResult>> {
+        QuerySearch::new(&self.apt_db, &self.dpkg, &self.index).search_query(query)
+    }
+}
+
 pub(crate) enum Searcher {
-    Local(Box<IndiciumSearch>),
+    Local(Box<LocalSearcher>),
     Amo {
         _connection: Connection,
         proxy: AmoProxy<'static>,
+        /// Used for queries with filters, which amo does not support
+        local: OnceCell<Box<LocalSearcher>>,
     },
 }
 
@@ -87,23 +104,36 @@ impl Searcher {
         Ok(Searcher::Amo {
             _connection: connection,
             proxy,
+            local: OnceCell::new(),
         })
     }
 
-    pub(crate) fn search(&self, query: &str) -> anyhow::Result<Vec<SearchResult>> {
+    pub(crate) fn search(&self, s: &str) -> anyhow::Result<Vec<SearchResult>> {
+        let query = s.parse::<Query>()?;
+
         match self {
-            Searcher::Local(indicium_search) => Ok(indicium_search.search(query)?),
+            Searcher::Local(local) => Ok(local.search(&query)?),
+            Searcher::Amo { local, .. } if query.has_filters() => {
+                let local = local.get_or_try_init(|| {
+                    local_searcher(&None)
+                        .map(Box::new)
+                        .map_err(|e| anyhow::anyhow!("{e}"))
+                })?;
+                Ok(local.search(&query)?)
+            }
             Searcher::Amo { proxy, .. } => {
-                Ok(serde_json::from_str(&RT.block_on(proxy.search(query))?)?)
+                Ok(serde_json::from_str(&RT.block_on(proxy.search(s))?)?)
             }
         }
     }
 
     #[allow(dead_code)]
     /// Refresh status metadata from fresh dpkg status data.
-    pub(crate) fn refresh(&mut self, apt_db: &oma_apt_pkg::AptDb, dpkg: &oma_apt_pkg::DpkgState) {
-        if let Searcher::Local(indicium_search) = self {
-            indicium_search.refresh_from(apt_db, dpkg);
+    pub(crate) fn refresh(&mut self, apt_db: AptDb, dpkg: DpkgState) {
+        if let Searcher::Local(local) = self {
+            local.index.refresh_from(&apt_db, &dpkg);
+            local.apt_db = apt_db;
+            local.dpkg = dpkg;
         }
     }
 }
@@ -154,10 +184,10 @@ impl CliExecuter for Tui {
         let searcher = if config.amo && !config.no_check_dbus {
             match RT.block_on(Searcher::connect_amo()) {
                 Ok(searcher) => searcher,
-                Err(_) => local_searcher(&pb)?,
+                Err(_) => Searcher::Local(Box::new(local_searcher(&pb)?)),
             }
         } else {
-            local_searcher(&pb)?
+            Searcher::Local(Box::new(local_searcher(&pb)?))
         };
 
         if let Some(pb) = pb {
@@ -228,7 +258,7 @@ impl CliExecuter for Tui {
     }
 }
 
-fn local_searcher(pb: &Option<crate::pb::OmaProgressBar>) -> Result<Searcher, OutputError> {
+fn local_searcher(pb: &Option<crate::pb::OmaProgressBar>) -> Result<LocalSearcher, OutputError> {
     let lists_dir = apt_config::find_dir(
         "Dir::State::lists".to_string(),
         "var/lib/apt/lists".to_string(),
@@ -241,17 +271,16 @@ fn local_searcher(pb: &Option<crate::pb::OmaProgressBar>) -> Result<Searcher, Ou
     let search_cache =
         crate::utils::get_apt_cache_path("Dir::Cache::oma-search", "oma-search.bincode");
 
-    let dpkg = oma_apt_pkg::DpkgState::from_file(&dpkg_path).map_err(|e| OutputError {
+    let dpkg = DpkgState::from_file(&dpkg_path).map_err(|e| OutputError {
+        description: e.to_string(),
+        source: None,
+    })?;
+    let apt_db = AptDb::load_or_build(&apt_cache, &lists_dir).map_err(|e| OutputError {
         description: e.to_string(),
         source: None,
     })?;
-    let apt_db =
-        oma_apt_pkg::AptDb::load_or_build(&apt_cache, &lists_dir).map_err(|e| OutputError {
-            description: e.to_string(),
-            source: None,
-        })?;
 
-    let searcher = IndiciumSearch::new_with_cache(
+    let index = IndiciumSearch::new_with_cache(
         &apt_db,
         &dpkg,
         &lists_dir,
@@ -268,5 +297,10 @@ fn local_searcher(pb: &Option<crate::pb::OmaProgressBar>) -> Result<Searcher, Ou
         description: e.to_string(),
         source: None,
     })?;
-    Ok(Searcher::Local(Box::new(searcher)))
+
+    Ok(LocalSearcher {
+        apt_db,
+        dpkg,
+        index,
+    })
 }